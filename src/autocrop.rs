@@ -0,0 +1,58 @@
+use image::{DynamicImage, GenericImageView, RgbaImage};
+
+// Channel difference still counted as part of a uniform border, enough to
+// absorb scanner noise and compression artifacts
+const TOLERANCE: i16 = 8;
+
+fn matches(a: &[u8], b: &[u8]) -> bool {
+    a.iter().zip(b).all(|(&a, &b)| (a as i16 - b as i16).abs() <= TOLERANCE)
+}
+
+fn uniform_row(img: &RgbaImage, y: u32, x0: u32, x1: u32, color: &[u8]) -> bool {
+    (x0..x1).all(|x| matches(&img.get_pixel(x, y).0, color))
+}
+
+fn uniform_column(img: &RgbaImage, x: u32, y0: u32, y1: u32, color: &[u8]) -> bool {
+    (y0..y1).all(|y| matches(&img.get_pixel(x, y).0, color))
+}
+
+// Finds a border of one flat color (scanner margins, atlas padding) around
+// the image content. The color is taken from the top left corner; returns
+// the content rectangle as x, y, width, height when anything can be cropped.
+pub fn detect_border(img: &DynamicImage) -> Option<(u32, u32, u32, u32)> {
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let color = rgba.get_pixel(0, 0).0;
+
+    let (mut left, mut top, mut right, mut bottom) = (0, 0, width, height);
+    while top < bottom && uniform_row(&rgba, top, left, right, &color) {
+        top += 1;
+    }
+    while bottom > top && uniform_row(&rgba, bottom - 1, left, right, &color) {
+        bottom -= 1;
+    }
+    while left < right && uniform_column(&rgba, left, top, bottom, &color) {
+        left += 1;
+    }
+    while right > left && uniform_column(&rgba, right - 1, top, bottom, &color) {
+        right -= 1;
+    }
+
+    // A completely flat image is a valid constant map, not all border
+    if left >= right || top >= bottom {
+        return None;
+    }
+    let content = (left, top, right - left, bottom - top);
+    (content != (0, 0, width, height)).then_some(content)
+}
+
+// Returns the cropped image and the original size when a border was removed
+pub fn crop_border(img: DynamicImage) -> (DynamicImage, Option<(u32, u32)>) {
+    match detect_border(&img) {
+        Some((x, y, w, h)) => {
+            let original = img.dimensions();
+            (img.crop_imm(x, y, w, h), Some(original))
+        }
+        None => (img, None),
+    }
+}
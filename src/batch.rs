@@ -0,0 +1,247 @@
+use egui::{CollapsingHeader, Color32, Context, Ui};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use crate::budget::SetFiles;
+use crate::config;
+use crate::paths;
+use crate::project::Project;
+use crate::report::ExportReport;
+use crate::review::ReviewStatus;
+use crate::timing::{self, TimingStats};
+use crate::ProcessingState;
+
+// A batch file lists texture sets, each written like a project file:
+//
+// {
+//   "output_root": "packed",
+//   "defaults": { "output_format": "DDS", "two_channel_normals": true },
+//   "sets": [
+//     { "name": "rock", "albedo_map": "rock/albedo.png", "normal_map": "rock/normal.png" },
+//     { "name": "sand", "albedo_map": "sand/albedo.png", "normal_map": "sand/normal.png", "output_format": "PNG" }
+//   ]
+// }
+//
+// Set values override the defaults. Sets without an output_directory go to
+// output_root/name. Relative paths are relative to the batch file, and paths
+// can use ${PROJECT_ROOT} for the batch file's folder or ${NAME} for any
+// environment variable.
+pub struct BatchSet {
+    pub name: String,
+    pub project: Project,
+}
+
+// Shallow merge, so a set replaces whole option groups rather than single
+// fields inside them
+pub fn merge(defaults: &Value, set: &Value) -> Value {
+    let mut merged = defaults.as_object().cloned().unwrap_or_default();
+    if let Some(set) = set.as_object() {
+        merged.extend(set.clone());
+    }
+    Value::Object(merged)
+}
+
+pub fn load_batch(path: &Path) -> Result<Vec<BatchSet>, String> {
+    let text = fs::read_to_string(paths::long_path(path)).map_err(|e| format!("Failed to read batch file: {}", e))?;
+    let batch: Value = serde_json::from_str(&text).map_err(|e| format!("Invalid batch file: {}", e))?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    // The defaults file sits beneath the batch's own defaults, its output
+    // directory standing in for a missing output_root
+    let mut config_defaults = config::defaults().clone();
+    let config_root = config_defaults.as_object_mut()
+        .and_then(|defaults| defaults.remove("output_directory"))
+        .and_then(|dir| dir.as_str().map(PathBuf::from));
+    let output_root = batch.get("output_root").and_then(Value::as_str)
+        .map(|root| paths::expand_variables(Path::new(root), base).map(|root| base.join(root)))
+        .transpose()?
+        .or(config_root);
+    let defaults = merge(&config_defaults, batch.get("defaults").unwrap_or(&Value::Null));
+    let sets = batch.get("sets").and_then(Value::as_array).ok_or("Batch file has no sets list")?;
+
+    sets.iter().enumerate()
+        .map(|(i, set)| {
+            let merged = merge(&defaults, set);
+            let mut project: Project = serde_json::from_value(merged.clone())
+                .map_err(|e| format!("Set {}: {}", i + 1, e))?;
+            project.resolve(base).map_err(|e| format!("Set {}: {}", i + 1, e))?;
+            let name = merged.get("name").and_then(Value::as_str).map(str::to_string)
+                .or_else(|| {
+                    let folder = project.albedo_map.as_ref()?.parent()?.file_name()?;
+                    Some(folder.to_string_lossy().to_string())
+                })
+                .unwrap_or_else(|| format!("set_{}", i + 1));
+            if project.output_directory.is_none() {
+                project.output_directory = output_root.as_ref().map(|root| root.join(&name));
+            }
+            Ok(BatchSet { name, project })
+        })
+        .collect()
+}
+
+// Runs every set in order, one after the other, reporting each as it finishes.
+// A failed set doesn't stop the rest.
+pub fn run_batch(sets: Vec<BatchSet>, dry_run: bool, mut report: impl FnMut(usize, ExportReport)) {
+    for (i, set) in sets.into_iter().enumerate() {
+        let mut result = crate::TerrainApp::run_headless(set.project, dry_run);
+        result.name = Some(set.name);
+        report(i, result);
+    }
+}
+
+// A set as listed while its batch runs
+struct SetProgress {
+    name: String,
+    timing_key: Option<(u32, String)>,
+    review_status: ReviewStatus,
+    notes: String,
+    // None while it hasn't run yet
+    outcome: Option<Result<Vec<PathBuf>, String>>,
+}
+
+pub struct BatchExporter {
+    batch_file: Option<PathBuf>,
+    sets: Vec<SetProgress>,
+    state: ProcessingState,
+    // Set once a batch finishes, until its budget report is taken
+    finished: bool,
+    // Reloaded as sets finish, each one records its own duration
+    timing_stats: TimingStats,
+    receiver: Receiver<(usize, Result<Vec<PathBuf>, String>)>,
+    sender: Sender<(usize, Result<Vec<PathBuf>, String>)>,
+}
+
+impl Default for BatchExporter {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            batch_file: None,
+            sets: Vec::new(),
+            state: ProcessingState::NotStarted,
+            finished: false,
+            timing_stats: TimingStats::load(),
+            receiver: rx,
+            sender: tx,
+        }
+    }
+}
+
+impl BatchExporter {
+    fn start(&mut self, path: PathBuf) {
+        self.batch_file = Some(path.clone());
+        let sets = match load_batch(&path) {
+            Ok(sets) => sets,
+            Err(e) => {
+                self.sets.clear();
+                self.state = ProcessingState::Error(e);
+                return;
+            }
+        };
+        self.sets = sets.iter()
+            .map(|set| SetProgress {
+                name: set.name.clone(),
+                timing_key: timing::source_size(&set.project).map(|size| timing::export_key(&set.project, size)),
+                review_status: set.project.review_status,
+                notes: set.project.notes.clone(),
+                outcome: None,
+            })
+            .collect();
+        self.timing_stats = TimingStats::load();
+        self.state = ProcessingState::Processing;
+        let tx = self.sender.clone();
+        thread::spawn(move || {
+            run_batch(sets, false, |i, report| {
+                tx.send((i, report.into_result())).ok();
+            });
+        });
+    }
+
+    pub fn poll(&mut self, ctx: &Context) {
+        while let Ok((i, result)) = self.receiver.try_recv() {
+            if let Some(set) = self.sets.get_mut(i) {
+                set.outcome = Some(result);
+            }
+            self.timing_stats = TimingStats::load();
+            if self.sets.iter().all(|set| set.outcome.is_some()) {
+                self.state = ProcessingState::Done;
+                self.finished = true;
+            }
+            ctx.request_repaint();
+        }
+    }
+
+    // The batch file and the files each set ended up with, once after a
+    // batch finishes, for the texture budget
+    pub fn take_finished(&mut self) -> Option<(PathBuf, Vec<SetFiles>)> {
+        if !std::mem::take(&mut self.finished) {
+            return None;
+        }
+        let outputs = self.sets.iter()
+            .filter_map(|set| Some((set.name.clone(), set.outcome.as_ref()?.as_ref().ok()?.clone())))
+            .collect();
+        Some((self.batch_file.clone()?, outputs))
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) {
+        CollapsingHeader::new("Batch Export")
+            .default_open(false)
+            .show(ui, |ui| {
+                let busy = matches!(self.state, ProcessingState::Processing);
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!busy, egui::Button::new("Run Batch File")).clicked() {
+                        if let Some(path) = rfd::FileDialog::new().add_filter("Batch file", &["json"]).pick_file() {
+                            self.start(path);
+                        }
+                    }
+                    if let Some(path) = &self.batch_file {
+                        ui.label(path.to_string_lossy().to_string());
+                    }
+                });
+
+                match &self.state {
+                    ProcessingState::Processing => {
+                        let finished = self.sets.iter().filter(|set| set.outcome.is_some()).count();
+                        let remaining: Option<Vec<(u32, String)>> = self.sets.iter()
+                            .filter(|set| set.outcome.is_none())
+                            .map(|set| set.timing_key.clone())
+                            .collect();
+                        let estimate = remaining.and_then(|keys| self.timing_stats.describe(&keys));
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(format!("{} of {} sets", finished, self.sets.len()));
+                            if let Some(estimate) = estimate {
+                                ui.label(format!("Estimated: {} left", estimate));
+                            }
+                        });
+                    }
+                    ProcessingState::Done => {
+                        let failed = self.sets.iter().filter(|set| matches!(set.outcome, Some(Err(_)))).count();
+                        ui.label(format!("Batch complete, {} of {} sets failed. Texture Budget lists what it wrote.", failed, self.sets.len()));
+                    }
+                    ProcessingState::Error(e) => {
+                        ui.label(format!("Error: {}", e));
+                    }
+                    _ => {}
+                }
+
+                for set in &self.sets {
+                    ui.horizontal(|ui| {
+                        set.review_status.badge(ui, &set.notes);
+                        match &set.outcome {
+                            Some(Ok(files)) => {
+                                ui.colored_label(Color32::LIGHT_GREEN, format!("{}: {} files", set.name, files.len()));
+                            }
+                            Some(Err(e)) => {
+                                ui.colored_label(Color32::LIGHT_RED, format!("{}: {}", set.name, e));
+                            }
+                            None => {
+                                ui.label(format!("{}: waiting", set.name));
+                            }
+                        }
+                    });
+                }
+            });
+    }
+}
@@ -0,0 +1,206 @@
+use image::{DynamicImage, GrayImage, ImageFormat, RgbaImage};
+use serde::Serialize;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::dds_encode::{self, EncodeProgress};
+use crate::project::Project;
+use crate::{frames, height_alpha, pack8, paths, roughness};
+use crate::{AlbedoAlphaMode, NormalMapFormat, OutputFormat, RoughnessFormat};
+
+pub const STAGES: [&str; 6] = ["decode", "ao_multiply", "height_pack", "normal_pack", "encode", "write"];
+
+#[derive(Debug, Serialize)]
+pub struct StageTiming {
+    pub stage: &'static str,
+    pub mean_seconds: f64,
+    pub min_seconds: f64,
+    pub max_seconds: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BenchmarkReport {
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub runs: usize,
+    pub stages: Vec<StageTiming>,
+    pub mean_total_seconds: f64,
+    // Combined size of the packed albedo and normal textures
+    pub output_bytes: u64,
+}
+
+struct Sources {
+    albedo: RgbaImage,
+    ao: Option<GrayImage>,
+    height: Option<GrayImage>,
+    normal: RgbaImage,
+    roughness: Option<GrayImage>,
+}
+
+fn decode(path: &Path, frame: usize) -> Result<DynamicImage, String> {
+    frames::open_frame(&paths::long_path(path), frame)
+        .map(|(img, _)| img)
+        .map_err(|e| format!("Failed to decode {}: {}", path.display(), e))
+}
+
+fn decode_sources(project: &Project) -> Result<Sources, String> {
+    let frame = |path: &PathBuf| project.input_frames.get(path).copied().unwrap_or(0);
+    let optional = |path: &Option<PathBuf>| path.as_ref().map(|path| decode(path, frame(path)).map(|img| img.to_luma8())).transpose();
+    let albedo = project.albedo_map.as_ref().ok_or("The benchmark needs an albedo map")?;
+    let normal = project.normal_map.as_ref().ok_or("The benchmark needs a normal map")?;
+    let sources = Sources {
+        albedo: decode(albedo, frame(albedo))?.to_rgba8(),
+        ao: optional(&project.ambient_occlusion_map)?,
+        height: optional(&project.height_map)?,
+        normal: decode(normal, frame(normal))?.to_rgba8(),
+        roughness: optional(&project.roughness_map)?,
+    };
+    let size = sources.albedo.dimensions();
+    for (name, dimensions) in [
+        ("AO", sources.ao.as_ref().map(GrayImage::dimensions)),
+        ("Height", sources.height.as_ref().map(GrayImage::dimensions)),
+        ("Normal", Some(sources.normal.dimensions())),
+        ("Roughness", sources.roughness.as_ref().map(GrayImage::dimensions)),
+    ] {
+        if dimensions.is_some_and(|dimensions| dimensions != size) {
+            return Err(format!("{} map doesn't match the albedo size {}x{}", name, size.0, size.1));
+        }
+    }
+    Ok(sources)
+}
+
+fn encode(texture: &RgbaImage, project: &Project) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    match project.output_format {
+        OutputFormat::PNG => texture.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png).map_err(|e| e.to_string())?,
+        OutputFormat::DDS => {
+            // The same mip chain and chunked encoder an export uses
+            let dds = dds_encode::encode(
+                &DynamicImage::ImageRgba8(texture.clone()),
+                image_dds::ImageFormat::BC3RgbaUnorm,
+                project.dds_quality.encoder_quality(),
+                &EncodeProgress::default(),
+            )?;
+            dds.write(&mut bytes).map_err(|e| format!("Failed to write DDS: {}", e))?;
+        }
+    }
+    Ok(bytes)
+}
+
+// Processes the project's albedo and normal maps the given number of times,
+// timing each stage of the export on its own. Files are written to a scratch
+// folder in the output directory, or the system's temporary folder without
+// one, and removed afterwards. Both textures are encoded as a default export
+// writes them (BC3 for DDS); resizing and the optional extra maps aren't
+// part of it.
+pub fn run(project: &Project, runs: usize) -> Result<BenchmarkReport, String> {
+    let height_lut = height_alpha::height_lut(&project.height_alpha, &project.parallax);
+    let roughness_lut = roughness::combined_lut(&project.roughness_curve, &project.roughness_adjust);
+    let extension = match project.output_format {
+        OutputFormat::PNG => "png",
+        OutputFormat::DDS => "dds",
+    };
+    let scratch = project.output_directory.clone()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(format!(".benchmark_{}", std::process::id()));
+    fs::create_dir_all(paths::long_path(&scratch))
+        .map_err(|e| format!("Failed to create {}: {}", scratch.display(), e))?;
+
+    let mut samples = Vec::new();
+    let mut size = (0, 0);
+    let mut output_bytes = 0;
+    let result = (|| {
+        for _ in 0..runs {
+            let mut times = [0.0; STAGES.len()];
+            let mut timed = |stage: usize, started: Instant| times[stage] += started.elapsed().as_secs_f64();
+
+            let started = Instant::now();
+            let sources = decode_sources(project)?;
+            timed(0, started);
+            size = sources.albedo.dimensions();
+
+            let mut albedo = sources.albedo;
+            let started = Instant::now();
+            if project.albedo_alpha_mode == AlbedoAlphaMode::Unpremultiply {
+                pack8::unpremultiply(&mut albedo);
+            }
+            if let Some(ao) = &sources.ao {
+                pack8::multiply_ao(&mut albedo, ao);
+            }
+            timed(1, started);
+
+            let started = Instant::now();
+            pack8::pack_height(&mut albedo, sources.height.as_ref(), &height_lut, project.default_height, project.parallax.edge_padding);
+            timed(2, started);
+
+            let mut normal = sources.normal;
+            let started = Instant::now();
+            pack8::pack_normal_roughness(
+                &mut normal,
+                sources.roughness.as_ref(),
+                project.normal_map_format == NormalMapFormat::DirectX,
+                project.roughness_format == RoughnessFormat::Smoothness,
+                &roughness_lut,
+                project.default_roughness,
+            );
+            timed(3, started);
+
+            let started = Instant::now();
+            let encoded = [("albedo", encode(&albedo, project)?), ("normal", encode(&normal, project)?)];
+            timed(4, started);
+
+            let started = Instant::now();
+            for (name, bytes) in &encoded {
+                let path = scratch.join(format!("{}.{}", name, extension));
+                fs::write(paths::long_path(&path), bytes).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            }
+            timed(5, started);
+
+            output_bytes = encoded.iter().map(|(_, bytes)| bytes.len() as u64).sum();
+            samples.push(times);
+        }
+        Ok::<(), String>(())
+    })();
+    fs::remove_dir_all(paths::long_path(&scratch)).ok();
+    result?;
+
+    let stages = STAGES.iter().enumerate()
+        .map(|(i, stage)| {
+            let values = samples.iter().map(|times| times[i]);
+            StageTiming {
+                stage,
+                mean_seconds: values.clone().sum::<f64>() / runs as f64,
+                min_seconds: values.clone().fold(f64::INFINITY, f64::min),
+                max_seconds: values.fold(0.0, f64::max),
+            }
+        })
+        .collect::<Vec<_>>();
+    Ok(BenchmarkReport {
+        format: format!("{:?}", project.output_format),
+        width: size.0,
+        height: size.1,
+        runs,
+        mean_total_seconds: stages.iter().map(|stage| stage.mean_seconds).sum(),
+        stages,
+        output_bytes,
+    })
+}
+
+pub fn print_report(report: &BenchmarkReport) {
+    println!("{}x{} {}, {} runs", report.width, report.height, report.format, report.runs);
+    println!("{:<14} {:>10} {:>10} {:>10}", "stage", "mean ms", "min ms", "max ms");
+    for stage in &report.stages {
+        println!(
+            "{:<14} {:>10.1} {:>10.1} {:>10.1}",
+            stage.stage,
+            stage.mean_seconds * 1000.0,
+            stage.min_seconds * 1000.0,
+            stage.max_seconds * 1000.0,
+        );
+    }
+    println!("{:<14} {:>10.1}", "total", report.mean_total_seconds * 1000.0);
+    println!("Albedo and normal together: {}", crate::preflight::format_bytes(report.output_bytes));
+}
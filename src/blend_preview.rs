@@ -0,0 +1,364 @@
+use egui::{CollapsingHeader, ColorImage, Context, TextureHandle, Ui, Vec2, widgets::Image, load::SizedTexture};
+use image::{imageops::FilterType, RgbaImage};
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use crate::heightmap::{self, HeightBuffer};
+use crate::file_names::{self, ExportedSet, FileNames};
+use crate::{material_preview, normals, reorganize};
+
+// Terrain resolution of the rendered view
+const RENDER_SIZE: u32 = 512;
+// Set textures are sampled at this size, plenty for a few repeats on screen
+const TEXTURE_SIZE: u32 = 512;
+
+// Where the second set takes over from the first. Terrain3D blends two
+// textures by comparing their heights, so the transition follows the
+// material's own relief rather than a straight line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlendSettings {
+    // Slope in degrees from which the second set covers the ground
+    pub slope_start: f32,
+    // Terrain height, 0 at the lowest point and 1 at the highest, from which
+    // the second set covers the ground
+    pub height_start: f32,
+    // Width of the transition, as a fraction of the range
+    pub transition: f32,
+    // How far the heights reach into each other, small values give a sharp seam
+    pub blend_depth: f32,
+    // Texture repeats across the terrain
+    pub tiling: f32,
+    // Terrain height relative to its width
+    pub relief: f32,
+}
+
+impl Default for BlendSettings {
+    fn default() -> Self {
+        Self {
+            slope_start: 30.0,
+            height_start: 0.8,
+            transition: 0.1,
+            blend_depth: 0.2,
+            tiling: 8.0,
+            relief: 0.3,
+        }
+    }
+}
+
+// An exported set as packed: albedo with height in alpha and OpenGL normals
+// with roughness in alpha
+struct PackedSet {
+    albedo: RgbaImage,
+    normal: RgbaImage,
+}
+
+struct Sources {
+    sets: [PackedSet; 2],
+    heights: HeightBuffer,
+}
+
+enum Message {
+    Loaded(Result<Arc<Sources>, String>),
+    Rendered(RgbaImage),
+}
+
+fn load_set(set: &ExportedSet) -> Result<PackedSet, String> {
+    let texture = |map: &str| -> Result<Option<RgbaImage>, String> {
+        set.texture(map)
+            .map(|path| {
+                let img = reorganize::load_texture(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                Ok(image::imageops::resize(&img.to_rgba8(), TEXTURE_SIZE, TEXTURE_SIZE, FilterType::Triangle))
+            })
+            .transpose()
+    };
+    let missing = |map: &str| format!("No {} texture in set {} in {}", map, set.name, set.directory.display());
+    let albedo = texture("albedo")?.ok_or_else(|| missing("albedo"))?;
+    let mut normal = texture("normal")?.ok_or_else(|| missing("normal"))?;
+    // Two-channel exports keep roughness in its own texture
+    if let Some(roughness) = texture("roughness")? {
+        normals::reconstruct_z(&mut normal);
+        for (pixel, value) in normal.pixels_mut().zip(roughness.pixels()) {
+            pixel[3] = value[0];
+        }
+    }
+    Ok(PackedSet { albedo, normal })
+}
+
+// The exported set a file belongs to, found by the export's file names
+fn set_of(file: &Path, file_names: &FileNames) -> Result<ExportedSet, String> {
+    let dir = file.parent().unwrap_or(Path::new("."));
+    file_names::sets_in(dir, file_names)?
+        .into_iter()
+        .find(|set| set.files.values().flatten().any(|path| path == file))
+        .ok_or_else(|| format!("{} isn't named like a texture of an exported set", file.display()))
+}
+
+// A mountain with a few ridges, for trying sets out before there is a
+// heightmap to put them on
+fn test_terrain() -> HeightBuffer {
+    HeightBuffer::from_fn(RENDER_SIZE, RENDER_SIZE, |x, y| {
+        let u = x as f32 / RENDER_SIZE as f32 - 0.5;
+        let v = y as f32 / RENDER_SIZE as f32 - 0.5;
+        let mountain = (-(u * u + v * v) * 12.0).exp();
+        let ridges = (u * 19.0).sin() * (v * 13.0 + u * 5.0).cos() * 0.08;
+        image::Luma([(mountain + ridges).max(0.0)])
+    })
+}
+
+fn load_sources(sets: [ExportedSet; 2], terrain: Option<PathBuf>) -> Result<Sources, String> {
+    let [first, second] = sets;
+    let mut heights = match terrain {
+        Some(path) => {
+            let heights = heightmap::load_heightmap(&path)?;
+            image::imageops::resize(&heights, RENDER_SIZE, RENDER_SIZE, FilterType::Triangle)
+        }
+        None => test_terrain(),
+    };
+    heightmap::normalize_heights(&mut heights, None);
+    Ok(Sources {
+        sets: [load_set(&first)?, load_set(&second)?],
+        heights,
+    })
+}
+
+fn ramp(value: f32, start: f32, transition: f32) -> f32 {
+    ((value - start) / transition.max(0.001) + 0.5).clamp(0.0, 1.0)
+}
+
+// The terrain seen from above, each texel lit with the blended material on
+// top of the terrain's own slope
+fn render(sources: &Sources, settings: &BlendSettings) -> RgbaImage {
+    let heights = &sources.heights;
+    let size = heights.width();
+    let relief = settings.relief * size as f32;
+    let sample = |x: i64, y: i64| heights.get_pixel(x.clamp(0, size as i64 - 1) as u32, y.clamp(0, size as i64 - 1) as u32)[0];
+    let texel = |x: u32, y: u32| {
+        let scale = TEXTURE_SIZE as f32 * settings.tiling / size as f32;
+        let wrap = |v: u32| (v as f32 * scale) as u32 % TEXTURE_SIZE;
+        (wrap(x), wrap(y))
+    };
+
+    let mut view = RgbaImage::new(size, size);
+    view.par_chunks_mut(4).enumerate().for_each(|(i, pixel)| {
+        let (x, y) = ((i as u32) % size, (i as u32) / size);
+        let dx = (sample(x as i64 + 1, y as i64) - sample(x as i64 - 1, y as i64)) * 0.5 * relief;
+        let dy = (sample(x as i64, y as i64 + 1) - sample(x as i64, y as i64 - 1)) * 0.5 * relief;
+        let terrain_normal = material_preview::normalize([-dx, dy, 1.0]);
+        let slope = terrain_normal[2].acos().to_degrees();
+
+        let (tx, ty) = texel(x, y);
+        let [a, b] = &sources.sets;
+        let weight = ramp(slope / 90.0, settings.slope_start / 90.0, settings.transition)
+            .max(ramp(heights.get_pixel(x, y)[0], settings.height_start, settings.transition));
+        let height_a = a.albedo.get_pixel(tx, ty)[3] as f32 / 255.0 + (1.0 - weight);
+        let height_b = b.albedo.get_pixel(tx, ty)[3] as f32 / 255.0 + weight;
+        let top = height_a.max(height_b) - settings.blend_depth.max(0.001);
+        let (wa, wb) = ((height_a - top).max(0.0), (height_b - top).max(0.0));
+        let mix = |va: u8, vb: u8| ((va as f32 * wa + vb as f32 * wb) / (wa + wb)).round() as u8;
+
+        let (albedo_a, albedo_b) = (a.albedo.get_pixel(tx, ty), b.albedo.get_pixel(tx, ty));
+        let (normal_a, normal_b) = (a.normal.get_pixel(tx, ty), b.normal.get_pixel(tx, ty));
+        let albedo = [0, 1, 2].map(|c| mix(albedo_a[c], albedo_b[c]));
+        let detail = material_preview::decode_normal(&[0, 1, 2, 3].map(|c| mix(normal_a[c], normal_b[c])));
+        // Detail normal added on top of the terrain's
+        let normal = material_preview::normalize([
+            terrain_normal[0] + detail[0],
+            terrain_normal[1] + detail[1],
+            terrain_normal[2] * detail[2],
+        ]);
+        let color = material_preview::shade(albedo, normal, mix(normal_a[3], normal_b[3]));
+        pixel[..3].copy_from_slice(&color);
+        pixel[3] = 255;
+    });
+    view
+}
+
+// Two exported sets blended over a terrain by slope and height, to judge a
+// pair's transition before both go into the engine
+pub struct BlendPreview {
+    sets: [Option<ExportedSet>; 2],
+    // Heightmap to render on, a built-in test terrain without one
+    terrain: Option<PathBuf>,
+    settings: BlendSettings,
+    sources: Option<Arc<Sources>>,
+    loading: bool,
+    rendering: bool,
+    // Settings changed since the last render started
+    dirty: bool,
+    error: Option<String>,
+    texture: Option<TextureHandle>,
+    receiver: Receiver<Message>,
+    sender: Sender<Message>,
+}
+
+impl Default for BlendPreview {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            sets: [None, None],
+            terrain: None,
+            settings: Default::default(),
+            sources: None,
+            loading: false,
+            rendering: false,
+            dirty: false,
+            error: None,
+            texture: None,
+            receiver: rx,
+            sender: tx,
+        }
+    }
+}
+
+impl BlendPreview {
+    fn load(&mut self) {
+        let [Some(first), Some(second)] = self.sets.clone() else {
+            return;
+        };
+        let terrain = self.terrain.clone();
+        let tx = self.sender.clone();
+        self.loading = true;
+        self.error = None;
+        thread::spawn(move || {
+            tx.send(Message::Loaded(load_sources([first, second], terrain).map(Arc::new))).ok();
+        });
+    }
+
+    // One render at a time, settings dragged meanwhile are picked up by the next
+    fn render_if_needed(&mut self) {
+        let Some(sources) = self.sources.clone().filter(|_| self.dirty && !self.rendering) else {
+            return;
+        };
+        let settings = self.settings;
+        let tx = self.sender.clone();
+        self.dirty = false;
+        self.rendering = true;
+        thread::spawn(move || {
+            tx.send(Message::Rendered(render(&sources, &settings))).ok();
+        });
+    }
+
+    pub fn poll(&mut self, ctx: &Context) {
+        while let Ok(message) = self.receiver.try_recv() {
+            match message {
+                Message::Loaded(Ok(sources)) => {
+                    self.loading = false;
+                    self.sources = Some(sources);
+                    self.dirty = true;
+                }
+                Message::Loaded(Err(e)) => {
+                    self.loading = false;
+                    self.error = Some(e);
+                }
+                Message::Rendered(view) => {
+                    self.rendering = false;
+                    let size = [view.width() as _, view.height() as _];
+                    let color_image = ColorImage::from_rgba_unmultiplied(size, view.as_raw());
+                    self.texture = Some(ctx.load_texture("blend_preview", color_image, Default::default()));
+                }
+            }
+            ctx.request_repaint();
+        }
+        self.render_if_needed();
+    }
+
+    fn select(&mut self, index: usize, file: &Path, file_names: &FileNames) -> bool {
+        match set_of(file, file_names) {
+            Ok(set) => {
+                self.sets[index] = Some(set);
+                true
+            }
+            Err(e) => {
+                self.error = Some(e);
+                false
+            }
+        }
+    }
+
+    // current_set is a file of the set the editor exports, file_names how
+    // exported files are named
+    pub fn show(&mut self, ui: &mut Ui, current_set: Option<&Path>, file_names: &FileNames) {
+        CollapsingHeader::new("Blend Preview")
+            .default_open(false)
+            .show(ui, |ui| {
+                let mut changed = false;
+                for (i, label) in ["Low/Flat Set", "Steep/High Set"].into_iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.button(format!("Select {}", label)).on_hover_text("Pick any texture of an exported set").clicked() {
+                            if let Some(path) = rfd::FileDialog::new()
+                                .add_filter("Image files", &crate::TerrainApp::SUPPORTED_FORMATS)
+                                .pick_file() {
+                                changed |= self.select(i, &path, file_names);
+                            }
+                        }
+                        if let Some(file) = current_set.filter(|file| file.is_file()) {
+                            if ui.small_button("Use Current Set").clicked() {
+                                changed |= self.select(i, file, file_names);
+                            }
+                        }
+                        if let Some(set) = &self.sets[i] {
+                            ui.label(format!("{} in {}", set.name, set.directory.display()));
+                        }
+                    });
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Select Heightmap").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Image files", &crate::TerrainApp::SUPPORTED_FORMATS)
+                            .pick_file() {
+                            self.terrain = Some(path);
+                            changed = true;
+                        }
+                    }
+                    match &self.terrain {
+                        Some(path) => {
+                            ui.label(path.to_string_lossy().to_string());
+                            if ui.small_button("Use Test Terrain").clicked() {
+                                self.terrain = None;
+                                changed = true;
+                            }
+                        }
+                        None => {
+                            ui.label("Test terrain");
+                        }
+                    }
+                });
+                if changed {
+                    self.load();
+                }
+
+                let before = self.settings;
+                let settings = &mut self.settings;
+                ui.add(egui::Slider::new(&mut settings.slope_start, 0.0..=90.0).text("Slope Start").suffix("°"));
+                ui.add(egui::Slider::new(&mut settings.height_start, 0.0..=1.0).text("Height Start"));
+                ui.add(egui::Slider::new(&mut settings.transition, 0.01..=0.5).text("Transition"));
+                ui.add(egui::Slider::new(&mut settings.blend_depth, 0.01..=1.0).text("Blend Depth"))
+                    .on_hover_text("How far the material heights reach into each other at the seam");
+                ui.add(egui::Slider::new(&mut settings.tiling, 1.0..=32.0).text("Tiling"));
+                ui.add(egui::Slider::new(&mut settings.relief, 0.05..=1.0).text("Relief"));
+                if self.settings != before {
+                    self.dirty = true;
+                }
+
+                if self.loading || self.rendering {
+                    ui.spinner();
+                }
+                if let Some(e) = &self.error {
+                    ui.label(format!("Error: {}", e));
+                }
+                match &self.texture {
+                    Some(texture) => {
+                        let width = ui.available_width();
+                        ui.add(Image::from_texture(SizedTexture::from_handle(texture)).max_size(Vec2::splat(width)));
+                    }
+                    None if self.sets.iter().any(Option::is_none) => {
+                        ui.label("Select two exported sets to see them blended");
+                    }
+                    None => {}
+                }
+            });
+    }
+}
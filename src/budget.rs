@@ -0,0 +1,331 @@
+use egui::{CollapsingHeader, Context, Grid, Ui};
+use image::{ImageDecoder, ImageReader};
+use image_dds::ddsfile::Dds;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use crate::preflight::format_bytes;
+use crate::file_names::{self, FileNames};
+use crate::{paths, ProcessingState};
+
+#[derive(Debug, Clone)]
+pub struct BudgetEntry {
+    pub set: String,
+    pub file: String,
+    pub format: String,
+    pub disk_bytes: u64,
+    pub vram_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortColumn {
+    Set,
+    File,
+    Format,
+    Disk,
+    Vram,
+}
+
+// DDS data is uploaded as is, so its payload is the VRAM cost. Other
+// containers are uploaded uncompressed in their own pixel format, plus a
+// full mip chain.
+fn measure(path: &Path) -> Result<(String, u64), String> {
+    let long_path = paths::long_path(path);
+    let is_dds = path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("dds"));
+    if is_dds {
+        let file = File::open(&long_path).map_err(|e| e.to_string())?;
+        let dds = Dds::read(BufReader::new(file)).map_err(|e| e.to_string())?;
+        let format = dds.get_dxgi_format().map(|f| format!("{:?}", f))
+            .or_else(|| dds.get_d3d_format().map(|f| format!("{:?}", f)))
+            .unwrap_or_else(|| "unknown".to_string());
+        Ok((format, dds.data.len() as u64))
+    } else {
+        let decoder = ImageReader::open(&long_path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(|e| e.to_string())?
+            .into_decoder()
+            .map_err(|e| e.to_string())?;
+        let (width, height) = decoder.dimensions();
+        let color = decoder.color_type();
+        let ext = path.extension().unwrap_or_default().to_string_lossy().to_uppercase();
+        let bytes = width as u64 * height as u64 * color.bytes_per_pixel() as u64;
+        Ok((format!("{} ({:?})", ext, color), bytes * 4 / 3))
+    }
+}
+
+// A set's name and the files it was exported to
+pub type SetFiles = (String, Vec<PathBuf>);
+
+// The textures among each set's files, other files are left out
+pub fn measure_sets(sets: &[SetFiles]) -> Result<Vec<BudgetEntry>, String> {
+    sets.iter()
+        .flat_map(|(set, files)| files.iter().map(move |path| (set, path)))
+        .filter(|(_, path)| {
+            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+            crate::TerrainApp::SUPPORTED_FORMATS.contains(&ext.as_str())
+        })
+        .par_bridge()
+        .map(|(set, path)| {
+            let describe = |e: String| format!("{}: {}", path.display(), e);
+            let file = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let disk_bytes = fs::metadata(paths::long_path(path)).map_err(|e| describe(e.to_string()))?.len();
+            let (format, vram_bytes) = measure(path).map_err(describe)?;
+            Ok(BudgetEntry { set: set.clone(), file, format, disk_bytes, vram_bytes })
+        })
+        .collect()
+}
+
+// Every texture of the sets under root, found by how exports name their files
+pub fn scan_budget(root: &Path, file_names: &FileNames) -> Result<Vec<BudgetEntry>, String> {
+    let sets: Vec<_> = file_names::find_sets(root, file_names)?
+        .into_iter()
+        .map(|set| (set.name, set.files.into_values().flatten().collect()))
+        .collect();
+    measure_sets(&sets)
+}
+
+// Totals per format and per set, then overall, for the end of a batch run
+// on the command line
+pub fn print_summary(entries: &[BudgetEntry]) {
+    let by_format = totals_by(entries, |e| &e.format);
+    let by_set = totals_by(entries, |e| &e.set);
+    for (title, totals) in [("Format", by_format), ("Set", by_set)] {
+        println!("{:<32} {:>6} {:>10} {:>10}", title, "Files", "Disk", "VRAM");
+        for (name, count, disk, vram) in totals {
+            println!("{:<32} {:>6} {:>10} {:>10}", name, count, format_bytes(disk), format_bytes(vram));
+        }
+        println!();
+    }
+    let (disk, vram) = entries.iter().fold((0, 0), |t, e| (t.0 + e.disk_bytes, t.1 + e.vram_bytes));
+    println!("{:<32} {:>6} {:>10} {:>10}", "Total", entries.len(), format_bytes(disk), format_bytes(vram));
+}
+
+// Totals per group as (name, file count, disk bytes, vram bytes)
+pub fn totals_by(entries: &[BudgetEntry], key: fn(&BudgetEntry) -> &str) -> Vec<(String, usize, u64, u64)> {
+    let mut totals: BTreeMap<&str, (usize, u64, u64)> = BTreeMap::new();
+    for entry in entries {
+        let total = totals.entry(key(entry)).or_default();
+        total.0 += 1;
+        total.1 += entry.disk_bytes;
+        total.2 += entry.vram_bytes;
+    }
+    let mut totals: Vec<_> = totals.into_iter().map(|(name, (n, disk, vram))| (name.to_string(), n, disk, vram)).collect();
+    totals.sort_by_key(|t| std::cmp::Reverse(t.3));
+    totals
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn write_csv(entries: &[BudgetEntry], path: &Path) -> Result<(), String> {
+    let mut csv = String::from("set,file,format,disk_bytes,vram_bytes\n");
+    for entry in entries {
+        csv += &format!(
+            "{},{},{},{},{}\n",
+            csv_field(&entry.set),
+            csv_field(&entry.file),
+            csv_field(&entry.format),
+            entry.disk_bytes,
+            entry.vram_bytes
+        );
+    }
+    let (disk, vram) = entries.iter().fold((0, 0), |t, e| (t.0 + e.disk_bytes, t.1 + e.vram_bytes));
+    csv += &format!("total,,,{},{}\n", disk, vram);
+    fs::write(paths::long_path(path), csv).map_err(|e| e.to_string())
+}
+
+// Disk and VRAM cost of every texture under a folder of exported sets
+pub struct TextureBudget {
+    root: Option<PathBuf>,
+    entries: Vec<BudgetEntry>,
+    sort: SortColumn,
+    descending: bool,
+    state: ProcessingState,
+    receiver: Receiver<Result<Vec<BudgetEntry>, String>>,
+    sender: Sender<Result<Vec<BudgetEntry>, String>>,
+}
+
+impl Default for TextureBudget {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            root: None,
+            entries: Vec::new(),
+            sort: SortColumn::Vram,
+            descending: true,
+            state: ProcessingState::NotStarted,
+            receiver: rx,
+            sender: tx,
+        }
+    }
+}
+
+impl TextureBudget {
+    // Reports the files a batch wrote, labelled with the batch file
+    pub fn report_batch(&mut self, batch_file: PathBuf, sets: Vec<SetFiles>) {
+        self.root = Some(batch_file);
+        self.state = ProcessingState::Processing;
+        let tx = self.sender.clone();
+        thread::spawn(move || {
+            tx.send(measure_sets(&sets)).ok();
+        });
+    }
+
+    fn start(&mut self, root: PathBuf, file_names: &FileNames) {
+        self.root = Some(root.clone());
+        self.state = ProcessingState::Processing;
+        let tx = self.sender.clone();
+        let file_names = file_names.clone();
+        thread::spawn(move || {
+            tx.send(scan_budget(&root, &file_names)).ok();
+        });
+    }
+
+    fn sort_entries(&mut self) {
+        let sort = self.sort;
+        self.entries.sort_by(|a, b| {
+            let order = match sort {
+                SortColumn::Set => a.set.cmp(&b.set).then_with(|| a.file.cmp(&b.file)),
+                SortColumn::File => a.file.cmp(&b.file).then_with(|| a.set.cmp(&b.set)),
+                SortColumn::Format => a.format.cmp(&b.format),
+                SortColumn::Disk => a.disk_bytes.cmp(&b.disk_bytes),
+                SortColumn::Vram => a.vram_bytes.cmp(&b.vram_bytes),
+            };
+            if self.descending { order.reverse() } else { order }
+        });
+    }
+
+    pub fn poll(&mut self, ctx: &Context) {
+        if let Ok(result) = self.receiver.try_recv() {
+            self.state = match result {
+                Ok(entries) => {
+                    self.entries = entries;
+                    self.sort_entries();
+                    ProcessingState::Done
+                }
+                Err(e) => ProcessingState::Error(e),
+            };
+            ctx.request_repaint();
+        }
+    }
+
+    fn totals_table(ui: &mut Ui, id: &str, title: &str, totals: &[(String, usize, u64, u64)]) {
+        Grid::new(id).striped(true).show(ui, |ui| {
+            ui.strong(title);
+            ui.strong("Files");
+            ui.strong("Disk");
+            ui.strong("VRAM");
+            ui.end_row();
+            for (name, count, disk, vram) in totals {
+                ui.label(name);
+                ui.label(count.to_string());
+                ui.label(format_bytes(*disk));
+                ui.label(format_bytes(*vram));
+                ui.end_row();
+            }
+        });
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, output_directory: Option<&PathBuf>, file_names: &FileNames) {
+        CollapsingHeader::new("Texture Budget")
+            .default_open(false)
+            .show(ui, |ui| {
+                let busy = matches!(self.state, ProcessingState::Processing);
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!busy && output_directory.is_some(), egui::Button::new("Report Output Directory")).clicked() {
+                        if let Some(dir) = output_directory {
+                            self.start(dir.clone(), file_names);
+                        }
+                    }
+                    if ui.add_enabled(!busy, egui::Button::new("Report Folder")).clicked() {
+                        if let Some(root) = rfd::FileDialog::new().pick_folder() {
+                            self.start(root, file_names);
+                        }
+                    }
+                    if let Some(root) = &self.root {
+                        ui.label(root.to_string_lossy().to_string());
+                    }
+                });
+
+                match &self.state {
+                    ProcessingState::Processing => {
+                        ui.spinner();
+                    }
+                    ProcessingState::Error(e) => {
+                        ui.label(format!("Error: {}", e));
+                    }
+                    ProcessingState::Done => {
+                        let (disk, vram) = self.entries.iter().fold((0, 0), |t, e| (t.0 + e.disk_bytes, t.1 + e.vram_bytes));
+                        ui.horizontal(|ui| {
+                            ui.label(format!(
+                                "{} textures, {} on disk, {} VRAM",
+                                self.entries.len(),
+                                format_bytes(disk),
+                                format_bytes(vram)
+                            ));
+                            if ui.button("Export CSV").clicked() {
+                                if let Some(path) = rfd::FileDialog::new().add_filter("CSV", &["csv"]).set_file_name("texture_budget.csv").save_file() {
+                                    if let Err(e) = write_csv(&self.entries, &path) {
+                                        self.state = ProcessingState::Error(e);
+                                    }
+                                }
+                            }
+                        });
+
+                        Self::totals_table(ui, "budget_formats", "Format", &totals_by(&self.entries, |e| &e.format));
+                        ui.separator();
+                        Self::totals_table(ui, "budget_sets", "Set", &totals_by(&self.entries, |e| &e.set));
+                        ui.separator();
+
+                        let mut resort = false;
+                        egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                            Grid::new("budget_files").striped(true).show(ui, |ui| {
+                                for (column, title) in [
+                                    (SortColumn::Set, "Set"),
+                                    (SortColumn::File, "File"),
+                                    (SortColumn::Format, "Format"),
+                                    (SortColumn::Disk, "Disk"),
+                                    (SortColumn::Vram, "VRAM"),
+                                ] {
+                                    let arrow = match (self.sort == column, self.descending) {
+                                        (true, true) => " ⏷",
+                                        (true, false) => " ⏶",
+                                        _ => "",
+                                    };
+                                    if ui.button(format!("{}{}", title, arrow)).clicked() {
+                                        // Clicking the sorted column again flips the order
+                                        self.descending = if self.sort == column { !self.descending } else { matches!(column, SortColumn::Disk | SortColumn::Vram) };
+                                        self.sort = column;
+                                        resort = true;
+                                    }
+                                }
+                                ui.end_row();
+                                for entry in &self.entries {
+                                    ui.label(&entry.set);
+                                    ui.label(&entry.file);
+                                    ui.label(&entry.format);
+                                    ui.label(format_bytes(entry.disk_bytes));
+                                    ui.label(format_bytes(entry.vram_bytes));
+                                    ui.end_row();
+                                }
+                            });
+                        });
+                        if resort {
+                            self.sort_entries();
+                        }
+                    }
+                    _ => {}
+                }
+            });
+    }
+}
@@ -0,0 +1,91 @@
+use egui::{Color32, ComboBox, Ui};
+use image::RgbaImage;
+use moxcms::{ColorProfile, Layout, Transform8BitExecutor, TransformOptions};
+use std::path::PathBuf;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PreviewMode {
+    Raw,
+    Managed,
+}
+
+// Godot treats albedo as sRGB whatever profile the file carries, so the
+// managed preview converts from sRGB to the monitor's profile. Raw sends the
+// values to the display untouched, like egui and most tools do.
+pub struct PreviewColor {
+    pub mode: PreviewMode,
+    profile_path: Option<PathBuf>,
+    transform: Option<Box<Transform8BitExecutor>>,
+    error: Option<String>,
+}
+
+impl Default for PreviewColor {
+    fn default() -> Self {
+        Self {
+            mode: PreviewMode::Raw,
+            profile_path: None,
+            transform: None,
+            error: None,
+        }
+    }
+}
+
+fn load_transform(path: &PathBuf) -> Result<Box<Transform8BitExecutor>, String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    let display = ColorProfile::new_from_slice(&bytes).map_err(|e| format!("Invalid ICC profile: {:?}", e))?;
+    ColorProfile::new_srgb()
+        .create_transform_8bit(Layout::Rgba, &display, Layout::Rgba, TransformOptions::default())
+        .map_err(|e| format!("Unsupported display profile: {:?}", e))
+}
+
+impl PreviewColor {
+    // The preview as it should be uploaded, None when it can be used as is
+    pub fn apply(&self, preview: &RgbaImage) -> Option<RgbaImage> {
+        let transform = self.transform.as_ref().filter(|_| self.mode == PreviewMode::Managed)?;
+        let mut managed = preview.clone();
+        transform.transform(preview.as_raw(), &mut managed).ok()?;
+        Some(managed)
+    }
+
+    // Returns true when the preview needs rebuilding
+    pub fn show(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            let before = self.mode;
+            ComboBox::from_label("Preview")
+                .selected_text(match self.mode {
+                    PreviewMode::Raw => "Raw",
+                    PreviewMode::Managed => "Managed",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.mode, PreviewMode::Raw, "Raw");
+                    ui.add_enabled_ui(self.transform.is_some(), |ui| {
+                        ui.selectable_value(&mut self.mode, PreviewMode::Managed, "Managed");
+                    });
+                });
+            changed |= before != self.mode;
+
+            if ui.button("Display Profile").on_hover_text("ICC profile of the monitor, for the managed preview").clicked() {
+                if let Some(path) = rfd::FileDialog::new().add_filter("ICC profiles", &["icc", "icm"]).pick_file() {
+                    match load_transform(&path) {
+                        Ok(transform) => {
+                            self.transform = Some(transform);
+                            self.profile_path = Some(path);
+                            self.mode = PreviewMode::Managed;
+                            self.error = None;
+                        }
+                        Err(e) => self.error = Some(e),
+                    }
+                    changed = true;
+                }
+            }
+            if let Some(path) = &self.profile_path {
+                ui.label(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+            }
+        });
+        if let Some(e) = &self.error {
+            ui.colored_label(Color32::LIGHT_RED, e);
+        }
+        changed
+    }
+}
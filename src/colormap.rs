@@ -0,0 +1,78 @@
+use image::{imageops::FilterType, RgbaImage};
+use rayon::prelude::*;
+
+use crate::resize;
+
+pub const COLOR_MAP_RESOLUTIONS: [u32; 6] = [64, 128, 256, 512, 1024, 2048];
+
+// Terrain3D reads the color map alpha as a roughness modifier, 0.5 is neutral
+const NEUTRAL_ROUGHNESS: u8 = 128;
+
+// Three box blur passes approximate a Gaussian. Sampling wraps around the
+// edges since the albedo is expected to tile.
+pub fn box_blur_wrapped(img: &mut RgbaImage, radius: u32) {
+    if radius == 0 {
+        return;
+    }
+    let width = img.width() as i64;
+    let height = img.height() as i64;
+    let r = radius as i64;
+    let window = (2 * r + 1) as u32;
+
+    for _ in 0..3 {
+        for horizontal in [true, false] {
+            let source = img.clone();
+            let (lines, length) = if horizontal { (height, width) } else { (width, height) };
+            let mut rows: Vec<Vec<[u8; 4]>> = vec![Vec::new(); lines as usize];
+            rows.par_iter_mut().enumerate().for_each(|(line, row)| {
+                let line = line as i64;
+                let sample = |i: i64| {
+                    let i = i.rem_euclid(length) as u32;
+                    if horizontal {
+                        source.get_pixel(i, line as u32).0
+                    } else {
+                        source.get_pixel(line as u32, i).0
+                    }
+                };
+                let mut sum = [0u32; 4];
+                for i in -r..=r {
+                    let p = sample(i);
+                    for c in 0..4 {
+                        sum[c] += p[c] as u32;
+                    }
+                }
+                row.reserve(length as usize);
+                for i in 0..length {
+                    row.push([
+                        (sum[0] / window) as u8,
+                        (sum[1] / window) as u8,
+                        (sum[2] / window) as u8,
+                        (sum[3] / window) as u8,
+                    ]);
+                    let add = sample(i + r + 1);
+                    let sub = sample(i - r);
+                    for c in 0..4 {
+                        sum[c] = sum[c] + add[c] as u32 - sub[c] as u32;
+                    }
+                }
+            });
+            for (line, row) in rows.into_iter().enumerate() {
+                for (i, p) in row.into_iter().enumerate() {
+                    let (x, y) = if horizontal { (i as u32, line as u32) } else { (line as u32, i as u32) };
+                    img.put_pixel(x, y, image::Rgba(p));
+                }
+            }
+        }
+    }
+}
+
+pub fn generate_color_map(albedo: &RgbaImage, resolution: u32, blur_radius: u32) -> RgbaImage {
+    // Triangle filtering widens its support when minifying, so this averages
+    // every source texel into the low resolution result
+    let mut color_map = resize::resize_wrapped(albedo, resolution, resolution, FilterType::Triangle);
+    box_blur_wrapped(&mut color_map, blur_radius.min(resolution / 2));
+    for pixel in color_map.pixels_mut() {
+        pixel[3] = NEUTRAL_ROUGHNESS;
+    }
+    color_map
+}
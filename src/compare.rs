@@ -0,0 +1,259 @@
+use egui::{CollapsingHeader, Color32, Context, Ui};
+use image::{DynamicImage, Rgba32FImage};
+use image_dds::ddsfile::Dds;
+use rayon::prelude::*;
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use crate::frames;
+use crate::paths;
+use crate::ProcessingState;
+
+// Channel differences at or below half an 8-bit step count as unchanged so
+// re-encoding the same data doesn't show up as a change
+const PIXEL_TOLERANCE: f32 = 0.5 / 255.0;
+
+#[derive(Debug, Clone)]
+pub enum FileChange {
+    Unchanged,
+    Changed(String),
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileComparison {
+    pub name: String,
+    pub change: FileChange,
+}
+
+struct LoadedTexture {
+    pixels: Rgba32FImage,
+    // Container details that can change without the pixels changing
+    metadata: String,
+}
+
+fn is_image(path: &Path) -> bool {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    crate::TerrainApp::SUPPORTED_FORMATS.contains(&ext.as_str())
+}
+
+fn load_texture(path: &Path) -> Result<LoadedTexture, String> {
+    let is_dds = path.extension().and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("dds"));
+    if is_dds {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let dds = Dds::read(BufReader::new(file)).map_err(|e| e.to_string())?;
+        let format = dds.get_dxgi_format().map(|f| format!("{:?}", f))
+            .or_else(|| dds.get_d3d_format().map(|f| format!("{:?}", f)))
+            .unwrap_or_else(|| "unknown".to_string());
+        let image = image_dds::image_from_dds(&dds, 0).map_err(|e| e.to_string())?;
+        Ok(LoadedTexture {
+            metadata: format!("{}x{} {} with {} mips", image.width(), image.height(), format, dds.get_num_mipmap_levels()),
+            pixels: DynamicImage::ImageRgba8(image).to_rgba32f(),
+        })
+    } else {
+        let image = frames::open_single(path)?;
+        Ok(LoadedTexture {
+            metadata: format!("{}x{} {:?}", image.width(), image.height(), image.color()),
+            pixels: image.to_rgba32f(),
+        })
+    }
+}
+
+fn compare_textures(new: &Path, old: &Path) -> Result<FileChange, String> {
+    let new = load_texture(new)?;
+    let old = load_texture(old)?;
+    if new.metadata != old.metadata {
+        return Ok(FileChange::Changed(format!("{} -> {}", old.metadata, new.metadata)));
+    }
+
+    let (changed, max_diff) = new.pixels.as_raw().par_chunks(4)
+        .zip(old.pixels.as_raw().par_chunks(4))
+        .map(|(a, b)| {
+            let diff = a.iter().zip(b).map(|(a, b)| (a - b).abs()).fold(0.0f32, f32::max);
+            ((diff > PIXEL_TOLERANCE) as usize, diff)
+        })
+        .reduce(|| (0, 0.0), |a, b| (a.0 + b.0, a.1.max(b.1)));
+    if changed == 0 {
+        return Ok(FileChange::Unchanged);
+    }
+    let total = (new.pixels.width() * new.pixels.height()).max(1) as f32;
+    Ok(FileChange::Changed(format!(
+        "{:.2}% of pixels differ, max difference {:.1}/255",
+        changed as f32 / total * 100.0,
+        max_diff * 255.0
+    )))
+}
+
+fn compare_files(new: &Path, old: &Path) -> Result<FileChange, String> {
+    if is_image(new) {
+        return compare_textures(new, old);
+    }
+    let new_bytes = fs::read(new).map_err(|e| e.to_string())?;
+    let old_bytes = fs::read(old).map_err(|e| e.to_string())?;
+    let is_json = new.extension().and_then(|e| e.to_str()) == Some("json");
+    // Manifests are compared by value so key order and formatting don't count
+    let same = if is_json {
+        let parse = |bytes: &[u8]| serde_json::from_slice::<serde_json::Value>(bytes).ok();
+        match (parse(&new_bytes), parse(&old_bytes)) {
+            (Some(a), Some(b)) => a == b,
+            _ => new_bytes == old_bytes,
+        }
+    } else {
+        new_bytes == old_bytes
+    };
+    Ok(if same {
+        FileChange::Unchanged
+    } else {
+        FileChange::Changed(format!("{} -> {} bytes", old_bytes.len(), new_bytes.len()))
+    })
+}
+
+fn file_names(dir: &Path) -> Result<BTreeSet<String>, String> {
+    let mut names = BTreeSet::new();
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        if entry.file_type().map_err(|e| e.to_string())?.is_file() {
+            names.insert(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    Ok(names)
+}
+
+// Compares every file in a fresh export against a previous export folder
+pub fn compare_exports(new_dir: &Path, old_dir: &Path) -> Result<Vec<FileComparison>, String> {
+    let (new_dir, old_dir) = (paths::long_path(new_dir), paths::long_path(old_dir));
+    let new_names = file_names(&new_dir)?;
+    let old_names = file_names(&old_dir)?;
+
+    new_names.union(&old_names).cloned().collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|name| {
+            let change = match (new_names.contains(&name), old_names.contains(&name)) {
+                (true, false) => FileChange::Added,
+                (false, true) => FileChange::Removed,
+                _ => compare_files(&new_dir.join(&name), &old_dir.join(&name))
+                    .map_err(|e| format!("{}: {}", name, e))?,
+            };
+            Ok(FileComparison { name, change })
+        })
+        .collect()
+}
+
+pub fn summarize(results: &[FileComparison]) -> String {
+    let count = |f: fn(&FileChange) -> bool| results.iter().filter(|r| f(&r.change)).count();
+    format!(
+        "{} changed, {} added, {} removed, {} unchanged",
+        count(|c| matches!(c, FileChange::Changed(_))),
+        count(|c| matches!(c, FileChange::Added)),
+        count(|c| matches!(c, FileChange::Removed)),
+        count(|c| matches!(c, FileChange::Unchanged)),
+    )
+}
+
+pub struct ExportComparer {
+    previous_directory: Option<PathBuf>,
+    state: ProcessingState,
+    results: Vec<FileComparison>,
+    show_unchanged: bool,
+    receiver: Receiver<Result<Vec<FileComparison>, String>>,
+    sender: Sender<Result<Vec<FileComparison>, String>>,
+}
+
+impl Default for ExportComparer {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            previous_directory: None,
+            state: ProcessingState::NotStarted,
+            results: Vec::new(),
+            show_unchanged: false,
+            receiver: rx,
+            sender: tx,
+        }
+    }
+}
+
+impl ExportComparer {
+    fn start(&mut self, new_dir: PathBuf, old_dir: PathBuf) {
+        let tx = self.sender.clone();
+        self.state = ProcessingState::Processing;
+        thread::spawn(move || {
+            tx.send(compare_exports(&new_dir, &old_dir)).ok();
+        });
+    }
+
+    pub fn poll(&mut self, ctx: &Context) {
+        if let Ok(result) = self.receiver.try_recv() {
+            self.state = match result {
+                Ok(results) => {
+                    self.results = results;
+                    ProcessingState::Done
+                }
+                Err(e) => ProcessingState::Error(e),
+            };
+            ctx.request_repaint();
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, output_directory: Option<&PathBuf>) {
+        CollapsingHeader::new("Compare With Previous Export")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Select Previous Export").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                            self.previous_directory = Some(path);
+                        }
+                    }
+                    if let Some(path) = &self.previous_directory {
+                        ui.label(path.to_string_lossy().to_string());
+                    }
+                });
+
+                let busy = matches!(self.state, ProcessingState::Processing);
+                let ready = output_directory.is_some() && self.previous_directory.is_some() && !busy;
+                if ui.add_enabled(ready, egui::Button::new("Compare Output Directory")).clicked() {
+                    if let (Some(new_dir), Some(old_dir)) = (output_directory, self.previous_directory.clone()) {
+                        self.start(new_dir.clone(), old_dir);
+                    }
+                }
+
+                match &self.state {
+                    ProcessingState::Processing => {
+                        ui.spinner();
+                    }
+                    ProcessingState::Error(e) => {
+                        ui.label(format!("Error: {}", e));
+                    }
+                    ProcessingState::Done => {
+                        ui.label(summarize(&self.results));
+                        ui.checkbox(&mut self.show_unchanged, "Show unchanged files");
+                        for result in &self.results {
+                            match &result.change {
+                                FileChange::Unchanged if self.show_unchanged => {
+                                    ui.label(format!("{}: unchanged", result.name));
+                                }
+                                FileChange::Unchanged => {}
+                                FileChange::Changed(details) => {
+                                    ui.colored_label(Color32::YELLOW, format!("{}: {}", result.name, details));
+                                }
+                                FileChange::Added => {
+                                    ui.colored_label(Color32::LIGHT_GREEN, format!("{}: added", result.name));
+                                }
+                                FileChange::Removed => {
+                                    ui.colored_label(Color32::LIGHT_RED, format!("{}: removed", result.name));
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            });
+    }
+}
@@ -0,0 +1,70 @@
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::paths;
+use crate::project::Project;
+
+// Defaults for new projects, headless runs and batches, written with project
+// field names, e.g.
+//
+// output_directory = "exports"
+// output_format = "DDS"
+// dds_quality = "Slow"
+// normal_map_format = "DirectX"
+//
+// Relative paths are relative to the file. Project files and command line
+// values still take precedence.
+pub const CONFIG_FILE: &str = "terrain3d_prepare.toml";
+
+static DEFAULTS: OnceLock<Value> = OnceLock::new();
+
+// The nearest file from the working directory up, then the portable
+// install's or the per-user one
+pub fn find() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok();
+    cwd.iter()
+        .flat_map(|dir| dir.ancestors())
+        .map(|dir| dir.join(CONFIG_FILE))
+        .chain(paths::portable_config().map(Path::to_path_buf))
+        .chain(paths::config_dir().map(|dir| dir.join(CONFIG_FILE)))
+        .find(|path| path.is_file())
+}
+
+// Only the keys the file sets, with paths made absolute so they can sit
+// beneath a project or batch from anywhere
+pub fn load(path: &Path) -> Result<Value, String> {
+    let text = fs::read_to_string(paths::long_path(path))
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let table: toml::Table = toml::from_str(&text).map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+    // Projects ignore fields they don't know, but here that would hide a typo
+    let known = serde_json::to_value(Project::default()).map_err(|e| e.to_string())?;
+    if let Some(key) = table.keys().find(|key| known.get(key.as_str()).is_none()) {
+        return Err(format!("Unknown setting {} in {}", key, path.display()));
+    }
+    let json = serde_json::to_value(&table).map_err(|e| e.to_string())?;
+    let mut project: Project = serde_json::from_value(json)
+        .map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+    project.resolve(path.parent().unwrap_or(Path::new(".")))?;
+    let resolved = serde_json::to_value(&project).map_err(|e| e.to_string())?;
+    let defaults = table.keys()
+        .filter_map(|key| Some((key.clone(), resolved.get(key)?.clone())))
+        .collect();
+    Ok(Value::Object(defaults))
+}
+
+// Loads the given file or the one found, once at startup
+pub fn init(path: Option<&Path>) -> Result<(), String> {
+    let defaults = match path.map(Path::to_path_buf).or_else(find) {
+        Some(path) => load(&path)?,
+        None => Value::Object(Default::default()),
+    };
+    DEFAULTS.set(defaults).ok();
+    Ok(())
+}
+
+pub fn defaults() -> &'static Value {
+    static EMPTY: OnceLock<Value> = OnceLock::new();
+    DEFAULTS.get().unwrap_or_else(|| EMPTY.get_or_init(|| Value::Object(Default::default())))
+}
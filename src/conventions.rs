@@ -0,0 +1,447 @@
+use egui::{CollapsingHeader, Color32, ComboBox, Context, Ui};
+use image::{DynamicImage, ImageBuffer, Pixel, Primitive, RgbaImage};
+use rayon::prelude::*;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use crate::export_cache;
+use crate::frames;
+use crate::paths;
+use crate::{NormalMapFormat, ProcessingState, RoughnessFormat};
+
+// Normal maps are analysed at this size to keep scans of large libraries quick
+const ANALYSIS_SIZE: u32 = 256;
+// Below this the integrability test can't tell the conventions apart, e.g. for
+// nearly flat normal maps
+const MIN_CONFIDENCE: f32 = 0.1;
+// Terrain roughness rarely averages this low, a darker map is most likely gloss
+const SMOOTHNESS_MEAN: f32 = 0.35;
+// Formats a fixed map can be written back in as it was. DDS can only be read
+// here, and animations, icons and the rest would lose frames or quality.
+const WRITABLE_FORMATS: [&str; 11] = ["bmp", "exr", "jpg", "jpeg", "png", "pnm", "qoi", "tga", "tif", "tiff", "webp"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MapKind {
+    Normal,
+    Roughness,
+    Smoothness,
+}
+
+// Guesses the map type from common naming conventions
+pub fn classify_map(path: &Path) -> Option<MapKind> {
+    let stem = path.file_stem()?.to_string_lossy().to_lowercase();
+    let tokens: Vec<&str> = stem.split(|c: char| !c.is_ascii_alphanumeric()).collect();
+    let has = |names: &[&str]| tokens.iter().any(|t| names.contains(t));
+    if has(&["normal", "normalgl", "normaldx", "nrm", "nor", "norm", "n"]) {
+        Some(MapKind::Normal)
+    } else if has(&["gloss", "glossiness", "smoothness", "smooth"]) {
+        Some(MapKind::Smoothness)
+    } else if has(&["roughness", "rough", "rgh"]) {
+        Some(MapKind::Roughness)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Detection<T> {
+    pub value: T,
+    // 0 means a coin flip, 1 means certain
+    pub confidence: f32,
+}
+
+// A tangent space normal map is the gradient of some height field, so its
+// curl should vanish. With the green channel flipped it doesn't, which tells
+// OpenGL (Y+) and DirectX (Y-) maps apart without relying on file names.
+pub fn detect_normal_format(normal: &RgbaImage) -> Detection<NormalMapFormat> {
+    let small = image::imageops::resize(normal, ANALYSIS_SIZE, ANALYSIS_SIZE, image::imageops::FilterType::Triangle);
+    let size = small.width() as usize;
+    let decode = |x: usize, y: usize| {
+        let p = small.get_pixel((x % size) as u32, (y % size) as u32);
+        let nz = (p[2] as f32 / 127.5 - 1.0).max(0.1);
+        ((p[0] as f32 / 127.5 - 1.0) / nz, (p[1] as f32 / 127.5 - 1.0) / nz)
+    };
+    let (gl_error, dx_error) = (0..size * size).into_par_iter()
+        .map(|i| {
+            let (x, y) = (i % size, i / size);
+            let (nx, ny) = decode(x, y);
+            let dnx_dy = decode(x, y + 1).0 - nx;
+            let dny_dx = decode(x + 1, y).1 - ny;
+            ((-dnx_dy - dny_dx).abs(), (-dnx_dy + dny_dx).abs())
+        })
+        .reduce(|| (0.0, 0.0), |a, b| (a.0 + b.0, a.1 + b.1));
+    let total = (gl_error + dx_error).max(f32::EPSILON);
+    Detection {
+        value: if gl_error <= dx_error { NormalMapFormat::OpenGL } else { NormalMapFormat::DirectX },
+        confidence: (gl_error - dx_error).abs() / total,
+    }
+}
+
+pub fn detect_roughness_format(path: &Path, image: &DynamicImage) -> Detection<RoughnessFormat> {
+    match classify_map(path) {
+        Some(MapKind::Smoothness) => Detection { value: RoughnessFormat::Smoothness, confidence: 1.0 },
+        _ => {
+            let luma = image.resize(ANALYSIS_SIZE, ANALYSIS_SIZE, image::imageops::FilterType::Triangle).to_luma8();
+            let mean = luma.pixels().map(|p| p[0] as f32).sum::<f32>() / (luma.len() as f32 * 255.0);
+            let value = if mean < SMOOTHNESS_MEAN { RoughnessFormat::Smoothness } else { RoughnessFormat::Roughness };
+            Detection { value, confidence: ((mean - SMOOTHNESS_MEAN).abs() * 2.0).min(1.0) }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Finding {
+    Normal(Detection<NormalMapFormat>),
+    Roughness(Detection<RoughnessFormat>),
+}
+
+// What the last fix did to one map
+#[derive(Debug, Clone)]
+pub enum FixOutcome {
+    Fixed,
+    // A backup from an earlier fix is already there
+    Skipped,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ScannedMap {
+    pub path: PathBuf,
+    pub finding: Result<Finding, String>,
+    // Ticked for maps that disagree with the target convention
+    pub fix: bool,
+    // False for formats a fix couldn't write back
+    pub writable: bool,
+    pub outcome: Option<FixOutcome>,
+}
+
+fn collect_maps(dir: &Path, maps: &mut Vec<PathBuf>) -> Result<(), String> {
+    for entry in fs::read_dir(paths::long_path(dir)).map_err(|e| format!("{}: {}", dir.display(), e))? {
+        // Keep the path as given so it still strips against the scan root
+        let entry = entry.map_err(|e| e.to_string())?;
+        let path = dir.join(entry.file_name());
+        if entry.file_type().map_err(|e| e.to_string())?.is_dir() {
+            collect_maps(&path, maps)?;
+        } else if classify_map(&path).is_some() && !path.file_stem().unwrap_or_default().to_string_lossy().ends_with("_original") {
+            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+            if crate::TerrainApp::SUPPORTED_FORMATS.contains(&ext.as_str()) {
+                maps.push(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn scan_library(root: &Path) -> Result<Vec<ScannedMap>, String> {
+    let mut paths = Vec::new();
+    collect_maps(root, &mut paths)?;
+    paths.sort();
+    Ok(paths.into_par_iter()
+        .map(|path| {
+            let finding = frames::open_single(&paths::long_path(&path)).map(|image| {
+                match classify_map(&path) {
+                    Some(MapKind::Normal) => Finding::Normal(detect_normal_format(&image.to_rgba8())),
+                    _ => Finding::Roughness(detect_roughness_format(&path, &image)),
+                }
+            });
+            let writable = WRITABLE_FORMATS.contains(&extension(&path).as_str());
+            ScannedMap { path, finding, fix: false, writable, outcome: None }
+        })
+        .collect())
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase()
+}
+
+// Flips green in the map's own pixel format, so 16-bit and float maps keep
+// their precision and the file is written back in the format it came in
+fn flip_green(image: &mut DynamicImage) -> Result<(), String> {
+    fn flip<P>(buffer: &mut ImageBuffer<P, Vec<P::Subpixel>>)
+    where
+        P: Pixel + Send + Sync,
+        P::Subpixel: Send + Sync,
+    {
+        buffer.par_pixels_mut().for_each(|pixel| {
+            let green = &mut pixel.channels_mut()[1];
+            *green = P::Subpixel::DEFAULT_MAX_VALUE - *green;
+        });
+    }
+    match image {
+        DynamicImage::ImageRgb8(buffer) => flip(buffer),
+        DynamicImage::ImageRgba8(buffer) => flip(buffer),
+        DynamicImage::ImageRgb16(buffer) => flip(buffer),
+        DynamicImage::ImageRgba16(buffer) => flip(buffer),
+        DynamicImage::ImageRgb32F(buffer) => flip(buffer),
+        DynamicImage::ImageRgba32F(buffer) => flip(buffer),
+        _ => return Err("A grayscale normal map has no green channel to flip".to_string()),
+    }
+    Ok(())
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}_original.{}", stem, ext))
+}
+
+// Flips green for normals or inverts roughness/smoothness in place, keeping a
+// copy of the original next to it. A map that has a copy already was most
+// likely fixed before, and that copy may be the only original left.
+fn fix_map(map: &ScannedMap) -> Result<FixOutcome, String> {
+    if !map.writable {
+        return Err(format!("{} files can't be written back", extension(&map.path)));
+    }
+    let path = paths::long_path(&map.path);
+    let backup = backup_path(&path);
+    if backup.exists() {
+        return Ok(FixOutcome::Skipped);
+    }
+    let mut image = frames::open_single(&path)?;
+    match &map.finding {
+        Ok(Finding::Normal(_)) => flip_green(&mut image)?,
+        // Keeps the pixel format, like the flip
+        Ok(Finding::Roughness(_)) => image.invert(),
+        Err(e) => return Err(e.clone()),
+    }
+    // create_new, so a backup that turned up meanwhile isn't replaced either
+    let mut copy = match fs::OpenOptions::new().write(true).create_new(true).open(&backup) {
+        Ok(copy) => copy,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Ok(FixOutcome::Skipped),
+        Err(e) => return Err(e.to_string()),
+    };
+    let copied = fs::File::open(&path).and_then(|mut original| io::copy(&mut original, &mut copy));
+    drop(copy);
+    // Replaced rather than written in place, the map may be an export linked into the cache
+    let replaced = |_| export_cache::replace_file(&path, |partial| image.save(partial).map_err(|e| e.to_string()));
+    if let Err(e) = copied.map_err(|e| e.to_string()).and_then(replaced) {
+        // A backup left behind by a failed fix would make a retry skip the map
+        fs::remove_file(&backup).ok();
+        return Err(e);
+    }
+    Ok(FixOutcome::Fixed)
+}
+
+pub struct ConventionChecker {
+    root: Option<PathBuf>,
+    maps: Vec<ScannedMap>,
+    target_normal: NormalMapFormat,
+    target_roughness: RoughnessFormat,
+    state: ProcessingState,
+    confirming: bool,
+    scan_receiver: Receiver<Result<Vec<ScannedMap>, String>>,
+    scan_sender: Sender<Result<Vec<ScannedMap>, String>>,
+    fix_receiver: Receiver<Vec<(PathBuf, FixOutcome)>>,
+    fix_sender: Sender<Vec<(PathBuf, FixOutcome)>>,
+}
+
+impl Default for ConventionChecker {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        let (ftx, frx) = channel();
+        Self {
+            root: None,
+            maps: Vec::new(),
+            target_normal: NormalMapFormat::OpenGL,
+            target_roughness: RoughnessFormat::Roughness,
+            state: ProcessingState::NotStarted,
+            confirming: false,
+            scan_receiver: rx,
+            scan_sender: tx,
+            fix_receiver: frx,
+            fix_sender: ftx,
+        }
+    }
+}
+
+impl ConventionChecker {
+    fn start_scan(&mut self, root: PathBuf) {
+        self.root = Some(root.clone());
+        self.maps.clear();
+        self.state = ProcessingState::Processing;
+        self.confirming = false;
+        let tx = self.scan_sender.clone();
+        thread::spawn(move || {
+            tx.send(scan_library(&root)).ok();
+        });
+    }
+
+    fn start_fix(&mut self) {
+        let maps: Vec<ScannedMap> = self.maps.iter().filter(|m| m.fix).cloned().collect();
+        for map in &mut self.maps {
+            map.outcome = None;
+        }
+        self.state = ProcessingState::Processing;
+        self.confirming = false;
+        let tx = self.fix_sender.clone();
+        thread::spawn(move || {
+            let outcomes = maps.par_iter()
+                .map(|map| (map.path.clone(), fix_map(map).unwrap_or_else(FixOutcome::Failed)))
+                .collect();
+            tx.send(outcomes).ok();
+        });
+    }
+
+    // Ticks every confidently detected map that disagrees with the targets
+    fn select_mismatches(&mut self) {
+        for map in self.maps.iter_mut().filter(|map| map.writable) {
+            map.fix = match &map.finding {
+                Ok(Finding::Normal(d)) => d.value != self.target_normal && d.confidence >= MIN_CONFIDENCE,
+                Ok(Finding::Roughness(d)) => d.value != self.target_roughness && d.confidence >= MIN_CONFIDENCE,
+                Err(_) => false,
+            };
+        }
+    }
+
+    pub fn poll(&mut self, ctx: &Context) {
+        if let Ok(result) = self.scan_receiver.try_recv() {
+            self.state = match result {
+                Ok(maps) => {
+                    self.maps = maps;
+                    self.select_mismatches();
+                    ProcessingState::NotStarted
+                }
+                Err(e) => ProcessingState::Error(e),
+            };
+            ctx.request_repaint();
+        }
+        if let Ok(outcomes) = self.fix_receiver.try_recv() {
+            for (path, outcome) in outcomes {
+                let Some(map) = self.maps.iter_mut().find(|m| m.path == path) else {
+                    continue;
+                };
+                // Failed maps stay ticked for another try
+                map.fix = matches!(outcome, FixOutcome::Failed(_));
+                if let FixOutcome::Fixed = outcome {
+                    // The file now follows the other convention
+                    match &mut map.finding {
+                        Ok(Finding::Normal(d)) => d.value = match d.value {
+                            NormalMapFormat::OpenGL => NormalMapFormat::DirectX,
+                            NormalMapFormat::DirectX => NormalMapFormat::OpenGL,
+                        },
+                        Ok(Finding::Roughness(d)) => d.value = match d.value {
+                            RoughnessFormat::Roughness => RoughnessFormat::Smoothness,
+                            RoughnessFormat::Smoothness => RoughnessFormat::Roughness,
+                        },
+                        Err(_) => {}
+                    }
+                }
+                map.outcome = Some(outcome);
+            }
+            self.state = ProcessingState::Done;
+            ctx.request_repaint();
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) {
+        CollapsingHeader::new("Batch Convention Check")
+            .default_open(false)
+            .show(ui, |ui| {
+                let busy = matches!(self.state, ProcessingState::Processing);
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!busy, egui::Button::new("Scan Library Folder")).clicked() {
+                        if let Some(root) = rfd::FileDialog::new().pick_folder() {
+                            self.start_scan(root);
+                        }
+                    }
+                    if let Some(root) = &self.root {
+                        ui.label(root.to_string_lossy().to_string());
+                    }
+                });
+
+                let previous = (self.target_normal, self.target_roughness);
+                ui.horizontal(|ui| {
+                    ComboBox::from_label("Target Normals")
+                        .selected_text(format!("{:?}", self.target_normal))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.target_normal, NormalMapFormat::OpenGL, "OpenGL");
+                            ui.selectable_value(&mut self.target_normal, NormalMapFormat::DirectX, "DirectX");
+                        });
+                    ComboBox::from_label("Target Roughness")
+                        .selected_text(format!("{:?}", self.target_roughness))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.target_roughness, RoughnessFormat::Roughness, "Roughness");
+                            ui.selectable_value(&mut self.target_roughness, RoughnessFormat::Smoothness, "Smoothness");
+                        });
+                });
+                if previous != (self.target_normal, self.target_roughness) {
+                    self.select_mismatches();
+                }
+
+                match &self.state {
+                    ProcessingState::Processing => {
+                        ui.spinner();
+                    }
+                    ProcessingState::Done => {
+                        let count = |wanted: fn(&FixOutcome) -> bool| {
+                            self.maps.iter().filter(|m| m.outcome.as_ref().is_some_and(wanted)).count()
+                        };
+                        ui.label(format!(
+                            "Fixed {} maps, originals kept as *_original. {} skipped, {} failed",
+                            count(|o| matches!(o, FixOutcome::Fixed)),
+                            count(|o| matches!(o, FixOutcome::Skipped)),
+                            count(|o| matches!(o, FixOutcome::Failed(_))),
+                        ));
+                    }
+                    ProcessingState::Error(e) => {
+                        ui.label(format!("Error: {}", e));
+                    }
+                    _ => {}
+                }
+
+                let root = self.root.clone().unwrap_or_default();
+                for map in &mut self.maps {
+                    let name = map.path.strip_prefix(&root).unwrap_or(&map.path).to_string_lossy().to_string();
+                    ui.horizontal(|ui| {
+                        let (text, mismatch) = match &map.finding {
+                            Ok(Finding::Normal(d)) => (
+                                format!("{}: {:?} normals ({:.0}% confidence)", name, d.value, d.confidence * 100.0),
+                                d.value != self.target_normal,
+                            ),
+                            Ok(Finding::Roughness(d)) => (
+                                format!("{}: {:?} ({:.0}% confidence)", name, d.value, d.confidence * 100.0),
+                                d.value != self.target_roughness,
+                            ),
+                            Err(e) => (format!("{}: {}", name, e), false),
+                        };
+                        ui.add_enabled(map.finding.is_ok() && map.writable, egui::Checkbox::without_text(&mut map.fix));
+                        if mismatch {
+                            ui.colored_label(Color32::YELLOW, text);
+                        } else {
+                            ui.label(text);
+                        }
+                        if !map.writable {
+                            ui.colored_label(Color32::GRAY, "can't fix, this format can't be written back");
+                        }
+                        match &map.outcome {
+                            Some(FixOutcome::Skipped) => {
+                                ui.colored_label(Color32::GRAY, "skipped, a *_original backup is already there");
+                            }
+                            Some(FixOutcome::Failed(e)) => {
+                                ui.colored_label(Color32::RED, e);
+                            }
+                            _ => {}
+                        }
+                    });
+                }
+
+                let selected = self.maps.iter().filter(|m| m.fix).count();
+                if self.confirming {
+                    ui.horizontal(|ui| {
+                        ui.colored_label(Color32::YELLOW, format!("Overwrite {} files?", selected));
+                        if ui.button("Confirm").clicked() {
+                            self.start_fix();
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.confirming = false;
+                        }
+                    });
+                } else if ui.add_enabled(!busy && selected > 0, egui::Button::new(format!("Fix {} Selected", selected))).clicked() {
+                    self.confirming = true;
+                }
+            });
+    }
+}
@@ -0,0 +1,114 @@
+use image::{DynamicImage, RgbaImage};
+use image_dds::ddsfile::Dds;
+use image_dds::{ImageFormat, Mipmaps, Quality, Surface, SurfaceRgba8};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// Texel rows encoded between two progress updates and cancellation checks.
+// A multiple of the block height, so the strips join into whole block rows.
+const CHUNK_ROWS: u32 = 64;
+
+// Shared by an export thread and the editor: texels encoded so far out of
+// everything the export encodes, and a flag checked between chunks
+#[derive(Default)]
+pub struct EncodeProgress {
+    done: AtomicU64,
+    total: u64,
+    cancel: AtomicBool,
+}
+
+impl EncodeProgress {
+    pub fn new(total: u64) -> Self {
+        Self { total, ..Default::default() }
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        (self.done.load(Ordering::Relaxed) as f64 / self.total as f64).min(1.0) as f32
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+}
+
+pub fn mip_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).leading_zeros()
+}
+
+// Texels of a full mip chain
+pub fn mip_texels(width: u32, height: u32) -> u64 {
+    (0..mip_count(width, height))
+        .map(|level| (width >> level).max(1) as u64 * (height >> level).max(1) as u64)
+        .sum()
+}
+
+// Next mip level, averaging 2x2 texels. Odd edges repeat their last texel.
+fn half(level: &RgbaImage) -> RgbaImage {
+    let (width, height) = level.dimensions();
+    RgbaImage::from_fn((width / 2).max(1), (height / 2).max(1), |x, y| {
+        let (x0, y0) = (x * 2, y * 2);
+        let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+        let texels = [level.get_pixel(x0, y0), level.get_pixel(x1, y0), level.get_pixel(x0, y1), level.get_pixel(x1, y1)];
+        image::Rgba(std::array::from_fn(|c| {
+            let sum: u32 = texels.iter().map(|texel| texel[c] as u32).sum();
+            ((sum + 2) / 4) as u8
+        }))
+    })
+}
+
+// Encodes the image with a full mip chain, level by level in strips of
+// block rows, so progress shows during the longest stage of an export and a
+// cancelled export stops within one strip
+pub fn encode(img: &DynamicImage, format: ImageFormat, quality: Quality, progress: &EncodeProgress) -> Result<Dds, String> {
+    let mut level = img.to_rgba8();
+    let (width, height) = level.dimensions();
+    let mipmaps = mip_count(width, height);
+    let mut data = Vec::new();
+    for mip in 0..mipmaps {
+        if mip > 0 {
+            level = half(&level);
+        }
+        let (level_width, level_height) = level.dimensions();
+        let row_bytes = level_width as usize * 4;
+        for y in (0..level_height).step_by(CHUNK_ROWS as usize) {
+            if progress.is_cancelled() {
+                return Err("Export cancelled".to_string());
+            }
+            let rows = CHUNK_ROWS.min(level_height - y);
+            let strip = SurfaceRgba8 {
+                width: level_width,
+                height: rows,
+                depth: 1,
+                layers: 1,
+                mipmaps: 1,
+                data: &level.as_raw()[y as usize * row_bytes..(y + rows) as usize * row_bytes],
+            };
+            let encoded = strip.encode(format, quality, Mipmaps::Disabled)
+                .map_err(|e| format!("Failed to convert to DDS: {}", e))?;
+            data.extend_from_slice(&encoded.data);
+            progress.done.fetch_add(level_width as u64 * rows as u64, Ordering::Relaxed);
+        }
+    }
+
+    Surface { width, height, depth: 1, layers: 1, mipmaps, image_format: format, data }
+        .to_dds()
+        .map_err(|e| format!("Failed to convert to DDS: {}", e))
+}
+
+pub fn save(img: &DynamicImage, path: &Path, format: ImageFormat, quality: Quality, progress: &EncodeProgress) -> Result<(), String> {
+    let dds = encode(img, format, quality, progress)?;
+    let file = File::create(path)
+        .map_err(|e| format!("Failed to create file: {}", e))?;
+    let mut writer = BufWriter::new(file);
+    dds.write(&mut writer)
+        .map_err(|e| format!("Failed to write DDS: {}", e))
+}
@@ -0,0 +1,111 @@
+use egui::{ComboBox, Ui};
+use image::{DynamicImage, ImageBuffer, Pixel};
+use serde::{Deserialize, Serialize};
+
+// How a source file's values are encoded. Terrain3D reads albedo as sRGB and
+// every other map as linear data, so a map declared the other way is
+// converted on load, before the preview and the export see it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum MapEncoding {
+    // Use the values as they are, whatever the file's encoding
+    #[default]
+    Auto,
+    Srgb,
+    Linear,
+}
+
+impl MapEncoding {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Auto => "Auto",
+            Self::Srgb => "sRGB",
+            Self::Linear => "Linear",
+        }
+    }
+}
+
+// The encoding Terrain3D expects in a slot
+fn expected(image_type: &str) -> MapEncoding {
+    match image_type {
+        "albedo" => MapEncoding::Srgb,
+        _ => MapEncoding::Linear,
+    }
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Applies f to the color channels of every pixel, leaving alpha alone
+fn map_color<P: Pixel>(mut img: ImageBuffer<P, Vec<P::Subpixel>>, f: impl Fn(P::Subpixel) -> P::Subpixel) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let color_channels = P::CHANNEL_COUNT as usize - usize::from(P::COLOR_MODEL.ends_with('A'));
+    for pixel in img.pixels_mut() {
+        for value in pixel.channels_mut().iter_mut().take(color_channels) {
+            *value = f(*value);
+        }
+    }
+    img
+}
+
+// Converts a map declared in another encoding than its slot expects. 8 and
+// 16 bit sources keep their depth, float sources stay unclamped.
+pub fn convert(img: DynamicImage, image_type: &str, declared: MapEncoding) -> DynamicImage {
+    let target = expected(image_type);
+    if declared == MapEncoding::Auto || declared == target {
+        return img;
+    }
+    let transfer = match target {
+        MapEncoding::Srgb => linear_to_srgb,
+        _ => srgb_to_linear,
+    };
+    let lut8: Vec<u8> = (0..=255u8)
+        .map(|v| (transfer(v as f32 / 255.0) * 255.0).round().clamp(0.0, 255.0) as u8)
+        .collect();
+    let u8_fn = |v: u8| lut8[v as usize];
+    let u16_fn = |v: u16| (transfer(v as f32 / 65535.0) * 65535.0).round().clamp(0.0, 65535.0) as u16;
+    let f32_fn = |v: f32| if v > 0.0 { transfer(v) } else { v };
+    match img {
+        DynamicImage::ImageLuma8(img) => DynamicImage::ImageLuma8(map_color(img, u8_fn)),
+        DynamicImage::ImageLumaA8(img) => DynamicImage::ImageLumaA8(map_color(img, u8_fn)),
+        DynamicImage::ImageRgb8(img) => DynamicImage::ImageRgb8(map_color(img, u8_fn)),
+        DynamicImage::ImageRgba8(img) => DynamicImage::ImageRgba8(map_color(img, u8_fn)),
+        DynamicImage::ImageLuma16(img) => DynamicImage::ImageLuma16(map_color(img, u16_fn)),
+        DynamicImage::ImageLumaA16(img) => DynamicImage::ImageLumaA16(map_color(img, u16_fn)),
+        DynamicImage::ImageRgb16(img) => DynamicImage::ImageRgb16(map_color(img, u16_fn)),
+        DynamicImage::ImageRgba16(img) => DynamicImage::ImageRgba16(map_color(img, u16_fn)),
+        DynamicImage::ImageRgb32F(img) => DynamicImage::ImageRgb32F(map_color(img, f32_fn)),
+        DynamicImage::ImageRgba32F(img) => DynamicImage::ImageRgba32F(map_color(img, f32_fn)),
+        other => DynamicImage::ImageRgba32F(map_color(other.to_rgba32f(), f32_fn)),
+    }
+}
+
+// Returns true when the encoding changed and the map needs reloading
+pub fn show(ui: &mut Ui, image_type: &str, encoding: &mut MapEncoding) -> bool {
+    let before = *encoding;
+    ComboBox::new(("encoding", image_type), "Encoding")
+        .selected_text(encoding.label())
+        .show_ui(ui, |ui| {
+            for option in [MapEncoding::Auto, MapEncoding::Srgb, MapEncoding::Linear] {
+                ui.selectable_value(encoding, option, option.label());
+            }
+        })
+        .response
+        .on_hover_text(format!(
+            "How the file is encoded. Auto uses its values as they are, the export expects {} here \
+            and converts maps declared otherwise.",
+            expected(image_type).label()
+        ));
+    before != *encoding
+}
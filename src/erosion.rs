@@ -0,0 +1,200 @@
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+use crate::heightmap::{self, HeightBuffer, HeightStepMessage};
+
+// Neighbor offsets: left, right, up, down
+const NEIGHBORS: [(i64, i64); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+// How much sediment moving water can carry, and how fast it picks up/drops it
+const SEDIMENT_CAPACITY: f32 = 4.0;
+const DISSOLVE_RATE: f32 = 0.3;
+const DEPOSIT_RATE: f32 = 0.3;
+const EVAPORATION: f32 = 0.05;
+const THERMAL_RATE: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ErosionSettings {
+    pub iterations: u32,
+    // 0 disables hydraulic erosion
+    pub rain_amount: f32,
+    // Slopes steeper than this collapse, 90 disables thermal erosion
+    pub talus_angle: f32,
+}
+
+impl Default for ErosionSettings {
+    fn default() -> Self {
+        Self {
+            iterations: 100,
+            rain_amount: 0.5,
+            talus_angle: 40.0,
+        }
+    }
+}
+
+fn neighbor(i: usize, d: usize, width: usize, height: usize) -> Option<usize> {
+    let x = (i % width) as i64 + NEIGHBORS[d].0;
+    let y = (i / width) as i64 + NEIGHBORS[d].1;
+    if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+        None
+    } else {
+        Some(y as usize * width + x as usize)
+    }
+}
+
+// Direction index pointing back from a neighbor to the current cell
+fn opposite(d: usize) -> usize {
+    d ^ 1
+}
+
+fn thermal_step(heights: &mut [f32], width: usize, height: usize, talus: f32) {
+    let outflow: Vec<[f32; 4]> = (0..heights.len()).into_par_iter().map(|i| {
+        let mut diffs = [0.0f32; 4];
+        let mut total = 0.0;
+        let mut max_diff = 0.0f32;
+        for (d, diff) in diffs.iter_mut().enumerate() {
+            if let Some(n) = neighbor(i, d, width, height) {
+                let delta = heights[i] - heights[n];
+                if delta > talus {
+                    *diff = delta;
+                    total += delta;
+                    max_diff = max_diff.max(delta);
+                }
+            }
+        }
+        if total <= 0.0 {
+            return [0.0; 4];
+        }
+        let moved = THERMAL_RATE * (max_diff - talus) * 0.5;
+        diffs.map(|diff| moved * diff / total)
+    }).collect();
+
+    heights.par_iter_mut().enumerate().for_each(|(i, h)| {
+        let out: f32 = outflow[i].iter().sum();
+        let incoming: f32 = (0..4)
+            .filter_map(|d| neighbor(i, d, width, height).map(|n| outflow[n][opposite(d)]))
+            .sum();
+        *h += incoming - out;
+    });
+}
+
+fn hydraulic_step(
+    heights: &mut [f32],
+    water: &mut [f32],
+    sediment: &mut [f32],
+    width: usize,
+    height: usize,
+    rain: f32,
+) {
+    water.par_iter_mut().for_each(|w| *w += rain);
+
+    // Water flows towards lower neighbors proportionally to the drop in surface level
+    let outflow: Vec<[f32; 4]> = (0..heights.len()).into_par_iter().map(|i| {
+        let surface = heights[i] + water[i];
+        let mut diffs = [0.0f32; 4];
+        let mut total = 0.0;
+        for (d, diff) in diffs.iter_mut().enumerate() {
+            if let Some(n) = neighbor(i, d, width, height) {
+                let delta = surface - (heights[n] + water[n]);
+                if delta > 0.0 {
+                    *diff = delta;
+                    total += delta;
+                }
+            }
+        }
+        if total <= 0.0 {
+            return [0.0; 4];
+        }
+        let moved = water[i].min(total * 0.5);
+        diffs.map(|diff| moved * diff / total)
+    }).collect();
+
+    let results: Vec<(f32, f32, f32)> = (0..heights.len()).into_par_iter().map(|i| {
+        let out: f32 = outflow[i].iter().sum();
+        let carried_fraction = if water[i] > 0.0 { out / water[i] } else { 0.0 };
+        let mut w = water[i] - out;
+        let mut s = sediment[i] * (1.0 - carried_fraction);
+        for d in 0..4 {
+            if let Some(n) = neighbor(i, d, width, height) {
+                let incoming = outflow[n][opposite(d)];
+                if incoming > 0.0 && water[n] > 0.0 {
+                    w += incoming;
+                    s += sediment[n] * incoming / water[n];
+                }
+            }
+        }
+
+        // Flow volume stands in for velocity when estimating carry capacity
+        let mut h = heights[i];
+        let capacity = SEDIMENT_CAPACITY * (out + rain) * w.min(1.0);
+        if s > capacity {
+            let deposit = DEPOSIT_RATE * (s - capacity);
+            h += deposit;
+            s -= deposit;
+        } else {
+            let dissolve = (DISSOLVE_RATE * (capacity - s)).min(h.max(0.0));
+            h -= dissolve;
+            s += dissolve;
+        }
+        (h, w * (1.0 - EVAPORATION), s)
+    }).collect();
+
+    heights.par_iter_mut()
+        .zip(water.par_iter_mut())
+        .zip(sediment.par_iter_mut())
+        .zip(results.par_iter())
+        .for_each(|(((h, w), s), r)| {
+            *h = r.0;
+            *w = r.1;
+            *s = r.2;
+        });
+}
+
+// Heights are normalized, so the talus angle is converted using the vertical
+// scale with one texel per meter as Terrain3D's default vertex spacing
+pub fn erode(
+    mut heights: HeightBuffer,
+    settings: ErosionSettings,
+    height_scale: f32,
+    cancel: Arc<AtomicBool>,
+    tx: Sender<HeightStepMessage>,
+) {
+    let width = heights.width() as usize;
+    let height = heights.height() as usize;
+    let len = width * height;
+    let talus = settings.talus_angle.to_radians().tan() / height_scale.max(f32::EPSILON);
+    let rain = settings.rain_amount * 0.001;
+    let mut water = vec![0.0f32; len];
+    let mut sediment = vec![0.0f32; len];
+    let preview_interval = (settings.iterations / 20).max(1);
+
+    for iteration in 0..settings.iterations {
+        if cancel.load(Ordering::Relaxed) {
+            tx.send(HeightStepMessage::Cancelled).ok();
+            return;
+        }
+
+        let data: &mut [f32] = &mut heights;
+        if rain > 0.0 {
+            hydraulic_step(data, &mut water, &mut sediment, width, height, rain);
+        }
+        if settings.talus_angle < 90.0 {
+            thermal_step(data, width, height, talus);
+        }
+
+        if (iteration + 1) % preview_interval == 0 && iteration + 1 < settings.iterations {
+            let preview = heightmap::make_preview(&heights);
+            let progress = (iteration + 1) as f32 / settings.iterations as f32;
+            tx.send(HeightStepMessage::Progress(progress, preview)).ok();
+        }
+    }
+
+    // Drop any sediment still in suspension where it is
+    heights.par_iter_mut().zip(sediment.par_iter()).for_each(|(h, s)| {
+        *h = (*h + s).clamp(0.0, 1.0);
+    });
+    let preview = heightmap::make_preview(&heights);
+    tx.send(HeightStepMessage::Done(heights, preview)).ok();
+}
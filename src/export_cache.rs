@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::file_names::FileNames;
+use crate::paths;
+
+// Oldest entries are dropped beyond either limit once a new one is stored
+const MAX_CACHE_BYTES: u64 = 20 << 30;
+const MAX_AGE: Duration = Duration::from_secs(60 * 24 * 60 * 60);
+
+// How cached outputs end up in the output directory. Links are only possible
+// on the same filesystem as the cache, so they fall back to copying.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, Default)]
+pub enum LinkMode {
+    #[default]
+    Copy,
+    Hardlink,
+    Symlink,
+}
+
+fn cache_root() -> Option<PathBuf> {
+    paths::config_dir().map(|dir| paths::long_path(&dir.join("export_cache")))
+}
+
+// Identifies an export by its settings, the names its files are written
+// under and the contents of every input file, so renamed or moved sources
+// still hit the cache
+pub fn cache_key(settings: &str, file_names: &FileNames, inputs: &[Option<PathBuf>]) -> Result<String, String> {
+    let mut hasher = Xxh3::new();
+    hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+    hasher.update(settings.as_bytes());
+    // The set's name fills in {set} but isn't part of the settings
+    hasher.update(format!("{:?}", file_names).as_bytes());
+    for input in inputs {
+        // Which slots are filled matters as much as their contents
+        hasher.update(&[input.is_some() as u8]);
+        let Some(input) = input else {
+            continue;
+        };
+        let bytes = fs::read(input).map_err(|e| format!("{}: {}", input.display(), e))?;
+        hasher.update(&(bytes.len() as u64).to_le_bytes());
+        hasher.update(&bytes);
+    }
+    Ok(format!("{:032x}", hasher.digest128()))
+}
+
+// Marks an entry that outputs are symlinked to, so it's never evicted or
+// cleared from under them
+fn symlink_marker(root: &Path, key: &str) -> PathBuf {
+    root.join(format!("{}.symlinked", key))
+}
+
+// True when the file was linked rather than copied
+fn link_file(source: &Path, destination: &Path, mode: LinkMode) -> Result<bool, String> {
+    let linked = match mode {
+        LinkMode::Copy => false,
+        LinkMode::Hardlink => fs::hard_link(source, destination).is_ok(),
+        #[cfg(unix)]
+        LinkMode::Symlink => std::os::unix::fs::symlink(source, destination).is_ok(),
+        // Needs developer mode or elevation on Windows
+        #[cfg(windows)]
+        LinkMode::Symlink => std::os::windows::fs::symlink_file(source, destination).is_ok(),
+        #[cfg(not(any(unix, windows)))]
+        LinkMode::Symlink => false,
+    };
+    if !linked {
+        fs::copy(source, destination).map_err(|e| e.to_string())?;
+    }
+    Ok(linked)
+}
+
+// Restored outputs may be links into the cache, so writing one in place would
+// change the cache entry and every export linked to it. The new contents go
+// into a file of their own that's then renamed over the old one.
+pub fn replace_file(path: &Path, write: impl FnOnce(&Path) -> Result<(), String>) -> Result<(), String> {
+    let name = path.file_name().ok_or_else(|| format!("{} is not a file", path.display()))?;
+    // Keeps the extension, image encoders pick the format by it
+    let partial = path.with_file_name(format!(".partial-{}", name.to_string_lossy()));
+    let result = write(&partial).and_then(|()| fs::rename(&partial, path).map_err(|e| e.to_string()));
+    if result.is_err() {
+        fs::remove_file(&partial).ok();
+    }
+    result
+}
+
+// Places the cached outputs for key into dir. Returns false when nothing is
+// cached for it.
+pub fn restore(key: &str, dir: &Path, mode: LinkMode) -> Result<bool, String> {
+    let Some(root) = cache_root() else {
+        return Ok(false);
+    };
+    let entry = root.join(key);
+    if !entry.is_dir() {
+        return Ok(false);
+    }
+    let mut linked = false;
+    for file in fs::read_dir(&entry).map_err(|e| e.to_string())? {
+        let file = file.map_err(|e| e.to_string())?;
+        linked |= link_file(&file.path(), &dir.join(file.file_name()), mode)?;
+    }
+    if linked && mode == LinkMode::Symlink {
+        fs::write(symlink_marker(&root, key), "").map_err(|e| e.to_string())?;
+    }
+    Ok(true)
+}
+
+// Copies a finished export into the cache. Written under a temporary name
+// first so an interrupted copy is never mistaken for a complete entry.
+pub fn store(key: &str, dir: &Path) -> Result<(), String> {
+    let root = cache_root().ok_or("No configuration directory")?;
+    let entry = root.join(key);
+    if entry.is_dir() {
+        return Ok(());
+    }
+    let partial = root.join(format!("{}.partial", key));
+    fs::create_dir_all(&partial).map_err(|e| e.to_string())?;
+    let result = (|| {
+        for file in fs::read_dir(dir).map_err(|e| e.to_string())? {
+            let file = file.map_err(|e| e.to_string())?;
+            fs::copy(file.path(), partial.join(file.file_name())).map_err(|e| e.to_string())?;
+        }
+        fs::rename(&partial, &entry).map_err(|e| e.to_string())
+    })();
+    if result.is_err() {
+        fs::remove_dir_all(&partial).ok();
+    }
+    evict(&root);
+    result
+}
+
+fn directory_size(dir: &Path) -> u64 {
+    fs::read_dir(dir).into_iter().flatten().flatten()
+        .filter_map(|file| file.metadata().ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+// Drops entries older than MAX_AGE, then the oldest ones until the cache fits
+// in MAX_CACHE_BYTES. Hardlinked outputs keep their data when the entry goes,
+// symlinked ones would point nowhere, so their entries are kept.
+fn evict(root: &Path) {
+    let now = SystemTime::now();
+    let mut entries: Vec<(SystemTime, u64, PathBuf)> = fs::read_dir(root).into_iter().flatten().flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|entry| {
+            let stored = entry.metadata().and_then(|metadata| metadata.modified()).ok()?;
+            Some((stored, directory_size(&entry.path()), entry.path()))
+        })
+        .collect();
+    // Newest first, so whatever is over the limit comes last
+    entries.sort_by_key(|(stored, ..)| std::cmp::Reverse(*stored));
+    let mut total = 0;
+    for (stored, size, path) in entries {
+        let age = now.duration_since(stored).unwrap_or_default();
+        if path.extension().is_some_and(|extension| extension == "partial") {
+            // Another export may still be writing it, unless it's been a day
+            if age > Duration::from_secs(24 * 60 * 60) {
+                fs::remove_dir_all(&path).ok();
+            }
+            continue;
+        }
+        total += size;
+        if is_symlinked(root, &path) {
+            continue;
+        }
+        if age > MAX_AGE || total > MAX_CACHE_BYTES {
+            fs::remove_dir_all(&path).ok();
+        }
+    }
+}
+
+fn is_symlinked(root: &Path, entry: &Path) -> bool {
+    entry.file_name().is_some_and(|key| symlink_marker(root, &key.to_string_lossy()).exists())
+}
+
+// Removes every entry except those symlinked outputs still point into
+pub fn clear() -> Result<(), String> {
+    let Some(root) = cache_root().filter(|root| root.is_dir()) else {
+        return Ok(());
+    };
+    for entry in fs::read_dir(&root).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.is_dir() && !is_symlinked(&root, &path) {
+            fs::remove_dir_all(&path).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
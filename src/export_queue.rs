@@ -0,0 +1,267 @@
+use egui::{CollapsingHeader, Color32, Context, Ui};
+use std::fs;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use crate::paths;
+use crate::project::Project;
+use crate::report::{ExportReport, TriageDecision};
+use crate::timing::{self, TimingStats};
+
+struct QueuedJob {
+    id: u64,
+    name: String,
+    // Snapshot of the editor's settings taken when the job was added
+    project: Project,
+    // Resolution and format label for the estimate, None when the maps
+    // can't be read
+    timing_key: Option<(u32, String)>,
+    report: Option<ExportReport>,
+    // Decisions taken after earlier failed attempts, carried into the
+    // report of the next one
+    decisions: Vec<TriageDecision>,
+    // Left waiting when the queue was aborted, it won't run
+    aborted: bool,
+}
+
+impl QueuedJob {
+    fn is_waiting(&self) -> bool {
+        self.report.is_none() && !self.aborted
+    }
+}
+
+// Exports set up one after another in the editor, so the next set can be
+// configured while earlier ones are still being packed
+pub struct ExportQueue {
+    jobs: Vec<QueuedJob>,
+    next_id: u64,
+    running: Option<u64>,
+    // While active, each finished job starts the next waiting one
+    active: bool,
+    // A failed job the queue is paused on until it's decided what to do
+    triage: Option<u64>,
+    error: Option<String>,
+    // Reloaded as jobs finish, each one records its own duration
+    timing_stats: TimingStats,
+    receiver: Receiver<(u64, ExportReport)>,
+    sender: Sender<(u64, ExportReport)>,
+}
+
+impl Default for ExportQueue {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            jobs: Vec::new(),
+            next_id: 0,
+            running: None,
+            active: false,
+            triage: None,
+            error: None,
+            timing_stats: TimingStats::load(),
+            receiver: rx,
+            sender: tx,
+        }
+    }
+}
+
+impl ExportQueue {
+    pub fn add(&mut self, mut project: Project) {
+        project.overwrite_mode = project.overwrite_mode.unattended();
+        let name = project.export_directory()
+            .and_then(|dir| dir.file_name().map(|name| name.to_owned()))
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| format!("Job {}", self.next_id + 1));
+        let timing_key = timing::source_size(&project).map(|size| timing::export_key(&project, size));
+        self.jobs.push(QueuedJob {
+            id: self.next_id,
+            name,
+            timing_key,
+            project,
+            report: None,
+            decisions: Vec::new(),
+            aborted: false,
+        });
+        self.next_id += 1;
+    }
+
+    // One job at a time, each export already uses every core
+    fn run_next(&mut self) {
+        if !self.active || self.running.is_some() {
+            return;
+        }
+        let Some(job) = self.jobs.iter().find(|job| job.is_waiting()) else {
+            self.active = false;
+            return;
+        };
+        let (id, name, project) = (job.id, job.name.clone(), job.project.clone());
+        let tx = self.sender.clone();
+        self.running = Some(id);
+        thread::spawn(move || {
+            let mut report = crate::TerrainApp::run_headless(project, false);
+            report.name = Some(name);
+            tx.send((id, report)).ok();
+        });
+    }
+
+    pub fn poll(&mut self, ctx: &Context) {
+        while let Ok((id, mut report)) = self.receiver.try_recv() {
+            self.running = None;
+            if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+                report.triage = job.decisions.clone();
+                // Nothing more runs until the failure is dealt with
+                if report.error.is_some() {
+                    self.active = false;
+                    self.triage = Some(id);
+                }
+                job.report = Some(report);
+            }
+            self.timing_stats = TimingStats::load();
+            ctx.request_repaint();
+        }
+        self.run_next();
+    }
+
+    // Records the decision on the failed job and acts on it. Returns the
+    // job's settings when they should be opened in the editor.
+    fn decide(&mut self, id: u64, decision: TriageDecision) -> Option<Project> {
+        self.triage = None;
+        let job = self.jobs.iter_mut().find(|job| job.id == id)?;
+        job.decisions.push(decision);
+        if let Some(report) = &mut job.report {
+            report.triage.push(decision);
+        }
+        match decision {
+            TriageDecision::Skip => self.active = true,
+            TriageDecision::Retry => {
+                job.report = None;
+                self.active = true;
+            }
+            TriageDecision::EditSettings => return Some(job.project.clone()),
+            TriageDecision::AbortAll => {
+                for job in self.jobs.iter_mut().filter(|job| job.is_waiting()) {
+                    job.aborted = true;
+                }
+            }
+        }
+        None
+    }
+
+    fn save_report(&self) -> Result<(), String> {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_file_name("export_queue_report.json")
+            .save_file() else {
+            return Ok(());
+        };
+        let reports: Vec<&ExportReport> = self.jobs.iter().filter_map(|job| job.report.as_ref()).collect();
+        let json = serde_json::to_string_pretty(&reports).map_err(|e| e.to_string())?;
+        fs::write(paths::long_path(&path), json).map_err(|e| e.to_string())
+    }
+
+    // Shows the triage dialog when a job failed. Returns settings to load
+    // into the editor when a failed job is opened for editing.
+    fn show_triage(&mut self, ctx: &Context) -> Option<Project> {
+        let job = self.jobs.iter().find(|job| Some(job.id) == self.triage)?;
+        let id = job.id;
+        let error = job.report.as_ref().and_then(|report| report.error.clone()).unwrap_or_default();
+        let waiting = self.jobs.iter().filter(|job| job.is_waiting()).count();
+        let mut decision = None;
+        egui::Window::new(format!("{} Failed", job.name))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.colored_label(Color32::LIGHT_RED, &error);
+                ui.label(format!("The queue is paused with {} jobs waiting.", waiting));
+                ui.horizontal(|ui| {
+                    if ui.button("Skip").on_hover_text("Leaves this job failed and goes on with the next").clicked() {
+                        decision = Some(TriageDecision::Skip);
+                    }
+                    if ui.button("Retry").on_hover_text("Runs this job again, e.g. after freeing disk space or closing a locked file").clicked() {
+                        decision = Some(TriageDecision::Retry);
+                    }
+                    if ui.button("Edit Settings").on_hover_text("Opens the job's settings in the editor, add it to the queue again once fixed").clicked() {
+                        decision = Some(TriageDecision::EditSettings);
+                    }
+                    if ui.button("Abort All").on_hover_text("Stops the queue and drops every waiting job").clicked() {
+                        decision = Some(TriageDecision::AbortAll);
+                    }
+                });
+            });
+        decision.and_then(|decision| self.decide(id, decision))
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) -> Option<Project> {
+        let edit = self.show_triage(ui.ctx());
+        CollapsingHeader::new(format!("Export Queue ({})", self.jobs.len()))
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    let waiting = self.jobs.iter().any(QueuedJob::is_waiting);
+                    if self.active {
+                        if ui.button("Pause").on_hover_text("Stops after the job that is running").clicked() {
+                            self.active = false;
+                        }
+                    } else if ui.add_enabled(waiting && self.triage.is_none(), egui::Button::new("Run Queue")).clicked() {
+                        self.active = true;
+                    }
+                    let finished = self.jobs.iter().any(|job| !job.is_waiting());
+                    if ui.add_enabled(finished && self.triage.is_none(), egui::Button::new("Clear Finished")).clicked() {
+                        self.jobs.retain(QueuedJob::is_waiting);
+                    }
+                    let reported = self.jobs.iter().any(|job| job.report.is_some());
+                    if ui.add_enabled(reported, egui::Button::new("Save Report"))
+                        .on_hover_text("Writes every finished job's outcome, including what was decided about failures, as JSON")
+                        .clicked() {
+                        self.error = self.save_report().err();
+                    }
+                });
+                if let Some(e) = &self.error {
+                    ui.colored_label(Color32::LIGHT_RED, format!("Error: {}", e));
+                }
+                let waiting: Option<Vec<(u32, String)>> = self.jobs.iter()
+                    .filter(|job| job.is_waiting() || self.running == Some(job.id))
+                    .map(|job| job.timing_key.clone())
+                    .collect();
+                if let Some(estimate) = waiting.and_then(|keys| self.timing_stats.describe(&keys)) {
+                    ui.label(format!("Estimated: {}", estimate));
+                }
+
+                let mut remove = None;
+                for job in &self.jobs {
+                    ui.horizontal(|ui| {
+                        job.project.review_status.badge(ui, &job.project.notes);
+                        if self.running == Some(job.id) {
+                            ui.spinner();
+                            ui.label(format!("{}: exporting", job.name));
+                            return;
+                        }
+                        if ui.add_enabled(self.triage != Some(job.id), egui::Button::new("Remove").small()).clicked() {
+                            remove = Some(job.id);
+                        }
+                        let decision = job.decisions.last()
+                            .map(|decision| format!(" ({})", decision.label()))
+                            .unwrap_or_default();
+                        match &job.report {
+                            Some(ExportReport { error: Some(e), .. }) => {
+                                ui.colored_label(Color32::LIGHT_RED, format!("{}: {}{}", job.name, e, decision));
+                            }
+                            Some(report) => {
+                                ui.colored_label(Color32::LIGHT_GREEN, format!("{}: {} files{}", job.name, report.outputs.len(), decision));
+                            }
+                            None if job.aborted => {
+                                ui.label(format!("{}: aborted", job.name));
+                            }
+                            None => {
+                                ui.label(format!("{}: waiting{}", job.name, decision));
+                            }
+                        }
+                    });
+                }
+                if let Some(id) = remove {
+                    self.jobs.retain(|job| job.id != id);
+                }
+            });
+        edit
+    }
+}
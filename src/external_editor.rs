@@ -0,0 +1,98 @@
+use egui::{Color32, Ui};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+struct WatchedFile {
+    image_type: String,
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+// Opens source maps in another program and, with reload on save enabled,
+// notices when that program writes them back
+pub struct ExternalEditor {
+    // None uses the system's default application for the file type
+    program: Option<PathBuf>,
+    reload_on_save: bool,
+    watched: Vec<WatchedFile>,
+    last_check: Instant,
+    error: Option<String>,
+}
+
+impl Default for ExternalEditor {
+    fn default() -> Self {
+        Self {
+            program: None,
+            reload_on_save: true,
+            watched: Vec::new(),
+            last_check: Instant::now(),
+            error: None,
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl ExternalEditor {
+    pub fn open(&mut self, image_type: &str, path: &Path) {
+        let result = match &self.program {
+            Some(program) => Command::new(program).arg(path).spawn().map(|_| ()).map_err(|e| e.to_string()),
+            None => opener::open(path).map_err(|e| e.to_string()),
+        };
+        self.error = result.err();
+        self.watched.retain(|w| w.image_type != image_type);
+        self.watched.push(WatchedFile {
+            image_type: image_type.to_string(),
+            path: path.to_path_buf(),
+            modified: modified(path),
+        });
+    }
+
+    // Slots whose file was saved since the last check
+    pub fn poll(&mut self, ctx: &egui::Context) -> Vec<(String, PathBuf)> {
+        if !self.reload_on_save || self.watched.is_empty() {
+            return Vec::new();
+        }
+        ctx.request_repaint_after(CHECK_INTERVAL);
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return Vec::new();
+        }
+        self.last_check = Instant::now();
+        let mut saved = Vec::new();
+        for watched in &mut self.watched {
+            let current = modified(&watched.path);
+            if current.is_some() && current != watched.modified {
+                watched.modified = current;
+                saved.push((watched.image_type.clone(), watched.path.clone()));
+            }
+        }
+        saved
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("External Editor:");
+            match &self.program {
+                Some(program) => ui.label(program.file_name().unwrap_or_default().to_string_lossy().to_string()),
+                None => ui.label("System Default"),
+            };
+            if ui.button("Choose Program").clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_file() {
+                    self.program = Some(path);
+                }
+            }
+            if self.program.is_some() && ui.button("Use Default").clicked() {
+                self.program = None;
+            }
+        });
+        ui.checkbox(&mut self.reload_on_save, "Reload Maps When Saved Externally");
+        if let Some(e) = &self.error {
+            ui.colored_label(Color32::LIGHT_RED, format!("Failed to open editor: {}", e));
+        }
+    }
+}
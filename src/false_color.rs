@@ -0,0 +1,125 @@
+use egui::{Color32, ColorImage, ComboBox, Ui};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+
+const PREFERENCE_FILE: &str = "preview_palette.json";
+
+// Samples of the matplotlib colormaps at eighths, both stay readable with
+// the common forms of color blindness and keep a steady lightness ramp
+const VIRIDIS: [[u8; 3]; 9] = [
+    [68, 1, 84],
+    [72, 40, 120],
+    [62, 73, 137],
+    [49, 104, 142],
+    [38, 130, 142],
+    [31, 158, 137],
+    [53, 183, 121],
+    [110, 206, 88],
+    [253, 231, 37],
+];
+const MAGMA: [[u8; 3]; 9] = [
+    [0, 0, 4],
+    [28, 16, 68],
+    [79, 18, 123],
+    [129, 37, 129],
+    [181, 54, 122],
+    [229, 80, 100],
+    [251, 136, 97],
+    [254, 194, 135],
+    [252, 253, 191],
+];
+
+// How single channel maps like height, AO and roughness are shown. Only
+// affects previews, exports are never colored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum GrayscalePalette {
+    #[default]
+    Grayscale,
+    Viridis,
+    Magma,
+}
+
+impl GrayscalePalette {
+    const ALL: [GrayscalePalette; 3] = [Self::Grayscale, Self::Viridis, Self::Magma];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Grayscale => "Grayscale",
+            Self::Viridis => "Viridis",
+            Self::Magma => "Magma",
+        }
+    }
+
+    pub fn lut(self) -> [[u8; 3]; 256] {
+        let stops = match self {
+            Self::Grayscale => return std::array::from_fn(|i| [i as u8; 3]),
+            Self::Viridis => &VIRIDIS,
+            Self::Magma => &MAGMA,
+        };
+        let segments = (stops.len() - 1) as f32;
+        std::array::from_fn(|i| {
+            let t = i as f32 / 255.0 * segments;
+            let index = (t as usize).min(stops.len() - 2);
+            let f = t - index as f32;
+            let (a, b) = (stops[index], stops[index + 1]);
+            std::array::from_fn(|c| (a[c] as f32 + (b[c] as f32 - a[c] as f32) * f).round() as u8)
+        })
+    }
+
+    pub fn color(lut: &[[u8; 3]; 256], value: u8) -> Color32 {
+        let [r, g, b] = lut[value as usize];
+        Color32::from_rgb(r, g, b)
+    }
+
+    // The first channel goes through the palette, so packed maps show the
+    // channel the slot reads. Grayscale keeps the image as loaded.
+    pub fn color_image(self, preview: &RgbaImage) -> ColorImage {
+        let size = [preview.width() as _, preview.height() as _];
+        if self == Self::Grayscale {
+            return ColorImage::from_rgba_unmultiplied(size, preview.as_raw());
+        }
+        let lut = self.lut();
+        let pixels = preview.pixels().map(|p| Self::color(&lut, p[0])).collect();
+        ColorImage { size, pixels }
+    }
+
+    // Returns true when the palette changed
+    pub fn show(&mut self, ui: &mut Ui) -> bool {
+        let before = *self;
+        ComboBox::from_label("Grayscale Preview")
+            .selected_text(self.label())
+            .show_ui(ui, |ui| {
+                for palette in Self::ALL {
+                    ui.selectable_value(self, palette, palette.label());
+                }
+            })
+            .response
+            .on_hover_text("Colors for height, AO, roughness and other single channel previews");
+        if before != *self {
+            self.save();
+            return true;
+        }
+        false
+    }
+
+    // The palette is a per-user preference rather than part of a project
+    pub fn load() -> Self {
+        paths::config_dir()
+            .and_then(|dir| std::fs::read_to_string(dir.join(PREFERENCE_FILE)).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(self) {
+        let Some(dir) = paths::config_dir() else {
+            return;
+        };
+        if std::fs::create_dir_all(&dir).is_ok() {
+            if let Ok(text) = serde_json::to_string(&self) {
+                std::fs::write(dir.join(PREFERENCE_FILE), text).ok();
+            }
+        }
+    }
+}
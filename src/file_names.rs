@@ -0,0 +1,398 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{map_names, paths, project};
+
+// Matches the names exports have always had, e.g. albedo.png
+pub const DEFAULT_TEMPLATE: &str = "{map}.{ext}";
+
+pub const TEMPLATE_HELP: &str = "Names of the written textures. \
+    {set} is the set name from the albedo file, e.g. Rock023_2K for Rock023_2K_Color.png, or its folder name, \
+    {map} the output, e.g. albedo or normal, {res} its resolution, e.g. 2K or 512, and {ext} png or dds. \
+    Use {set} to export several sets into one folder. \
+    The manifest, palette, preview and project file are named the same way without {res}.";
+
+// Outputs an export can write, by the name {map} takes and what they hold
+pub const OUTPUTS: [(&str, &str); 11] = [
+    ("albedo", "Albedo + height"),
+    ("normal", "Normal + roughness"),
+    ("roughness", "Roughness"),
+    ("opacity", "Opacity"),
+    ("translucency", "Translucency"),
+    ("albedo_mask", "Albedo mask"),
+    ("color_map", "Color map"),
+    ("macro_variation", "Macro variation"),
+    ("average_color", "Average color"),
+    ("palette", "Palette"),
+    ("preview", "Preview"),
+];
+// Written with every export besides the outputs above
+pub const MANIFEST: &str = "manifest";
+pub const PROJECT: &str = "project";
+
+pub fn validate(template: &str) -> Result<(), String> {
+    if !template.contains("{map}") {
+        return Err("The file name template needs {map} or every output gets the same name".to_string());
+    }
+    if !template.contains("{ext}") {
+        return Err("The file name template needs {ext}".to_string());
+    }
+    if template.contains(['/', '\\']) {
+        return Err("The file name template can't contain folders".to_string());
+    }
+    if !template.ends_with(".{ext}") {
+        return Err("The file name template has to end in .{ext}".to_string());
+    }
+    Ok(())
+}
+
+// Named the way texture libraries name their downloads, 2K for 2048, with
+// sizes that aren't whole multiples of 1024 in pixels
+pub fn resolution_label((width, height): (u32, u32)) -> String {
+    let side = width.max(height);
+    if side >= 1024 && side % 1024 == 0 {
+        format!("{}K", side / 1024)
+    } else {
+        side.to_string()
+    }
+}
+
+// The prefix the set's maps share, or the folder they're in for plain
+// names like albedo.png
+pub fn set_name(albedo: Option<&Path>) -> String {
+    let Some(albedo) = albedo else {
+        return "set".to_string();
+    };
+    map_names::set_prefix(albedo)
+        .filter(|prefix| !prefix.is_empty())
+        .or_else(|| {
+            let albedo = std::path::absolute(albedo).ok()?;
+            albedo.parent()?.file_name().map(|name| name.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| "set".to_string())
+}
+
+pub fn validate_subfolder(name: &str) -> Result<(), String> {
+    let name = name.trim();
+    if name.contains(['/', '\\']) || name == "." || name == ".." {
+        return Err("The set folder name can't contain folders".to_string());
+    }
+    Ok(())
+}
+
+// Where a set's files go: the output directory itself, or with subfolder on
+// a folder inside it named as entered or after the set
+pub fn set_directory(output: &Path, subfolder: bool, name: &str, albedo: Option<&Path>) -> PathBuf {
+    if !subfolder {
+        return output.to_path_buf();
+    }
+    match name.trim() {
+        "" => output.join(set_name(albedo)),
+        name => output.join(name),
+    }
+}
+
+// albedo.{ext} as albedo_v2.{ext}
+pub fn versioned_name(name: &str, version: u32) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) => format!("{}_v{}.{}", stem, version, ext),
+        None => format!("{}_v{}", name, version),
+    }
+}
+
+// The first version from 2 up that none of the names it gives are taken
+// for, so the files of one export share their version
+pub fn next_free_version(dir: &Path, names: impl Fn(u32) -> Vec<String>) -> u32 {
+    (2..)
+        .find(|version| names(*version).iter().all(|name| !dir.join(name).exists()))
+        .unwrap_or(2)
+}
+
+// The template for files that go with the whole set, {res} dropped along
+// with a separator next to it, e.g. {set}_{map}.{ext} for {set}_{map}_{res}.{ext}
+fn without_resolution(template: &str) -> String {
+    const SEPARATORS: [char; 4] = ['_', '-', ' ', '.'];
+    let mut template = template.to_string();
+    while let Some(start) = template.find("{res}") {
+        let (before, after) = (&template[..start], &template[start + "{res}".len()..]);
+        template = match before.strip_suffix(SEPARATORS) {
+            Some(before) => format!("{}{}", before, after),
+            None => format!("{}{}", before, after.strip_prefix(SEPARATORS).unwrap_or(after)),
+        };
+    }
+    template
+}
+
+// Checks the names {map} takes in place of the outputs' own, e.g. _AH for
+// albedo, as no two outputs may end up with the same one
+fn validate_map_names(map_names: &BTreeMap<String, String>) -> Result<(), String> {
+    let mut taken: BTreeMap<&str, &str> = BTreeMap::new();
+    for map in known_maps() {
+        let name = map_name(map_names, map);
+        if name.contains(['/', '\\']) {
+            return Err(format!("The name for {} can't contain folders", map));
+        }
+        // Another output's own name would make its earlier exports look like this one
+        if name != map && known_maps().contains(&name) {
+            return Err(format!("{} can't be named {}, that's the name of another output", map, name));
+        }
+        if let Some(other) = taken.insert(name, map) {
+            return Err(format!("{} and {} are both named {}", other, map, name));
+        }
+    }
+    Ok(())
+}
+
+fn map_name<'a>(map_names: &'a BTreeMap<String, String>, map: &'a str) -> &'a str {
+    map_names.get(map).map(|name| name.trim()).filter(|name| !name.is_empty()).unwrap_or(map)
+}
+
+// Output file names of one export, also used to tell which set and output
+// an existing file is
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileNames {
+    template: String,
+    // What {map} becomes for each output, outputs without one keep their own name
+    map_names: BTreeMap<String, String>,
+    set: String,
+    version: Option<u32>,
+}
+
+impl FileNames {
+    pub fn new(template: &str, map_names: &BTreeMap<String, String>, set: String) -> Self {
+        Self { template: template.to_string(), map_names: map_names.clone(), set, version: None }
+    }
+
+    pub fn versioned(self, version: u32) -> Self {
+        Self { version: Some(version), ..self }
+    }
+
+    pub fn name(&self, map: &str, size: (u32, u32), ext: &str) -> String {
+        self.render(&self.template.replace("{res}", &resolution_label(size)), map, ext)
+    }
+
+    // The manifest, palette, preview and project sidecar, which go with the
+    // set rather than one resolution
+    pub fn file(&self, map: &str, ext: &str) -> String {
+        self.render(&without_resolution(&self.template), map, ext)
+    }
+
+    pub fn manifest(&self) -> String {
+        self.file(MANIFEST, "json")
+    }
+
+    pub fn sidecar(&self) -> String {
+        self.file(PROJECT, project::SIDECAR_EXTENSION)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        validate(&self.template)?;
+        validate_map_names(&self.map_names)
+    }
+
+    // What {map} becomes for the output
+    pub fn map_name<'a>(&'a self, map: &'a str) -> &'a str {
+        map_name(&self.map_names, map)
+    }
+
+    fn render(&self, template: &str, map: &str, ext: &str) -> String {
+        let name = template.replace("{set}", &self.set).replace("{map}", self.map_name(map));
+        // Versioned ahead of the extension, which can have dots of its own
+        let name = match self.version {
+            Some(version) => versioned_name(&name, version),
+            None => name,
+        };
+        name.replace("{ext}", ext)
+    }
+}
+
+enum Part<'a> {
+    Text(&'a str),
+    Set,
+    Map,
+    Res,
+    Ext,
+}
+
+fn parts(template: &str) -> Vec<Part<'_>> {
+    const TOKENS: [&str; 4] = ["{set}", "{map}", "{res}", "{ext}"];
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while !rest.is_empty() {
+        let next = TOKENS.iter()
+            .filter_map(|token| rest.find(token).map(|start| (start, *token)))
+            .min_by_key(|(start, _)| *start);
+        let Some((start, token)) = next else {
+            parts.push(Part::Text(rest));
+            break;
+        };
+        if start > 0 {
+            parts.push(Part::Text(&rest[..start]));
+        }
+        parts.push(match token {
+            "{set}" => Part::Set,
+            "{map}" => Part::Map,
+            "{res}" => Part::Res,
+            _ => Part::Ext,
+        });
+        rest = &rest[start + token.len()..];
+    }
+    parts
+}
+
+// Extensions exported files can have, so files that only start like one,
+// e.g. the albedo.png.import Godot puts next to it, aren't taken for it
+fn is_output_extension(ext: &str) -> bool {
+    ext == "json" || ext == project::SIDECAR_EXTENSION || crate::TerrainApp::SUPPORTED_FORMATS.contains(&ext)
+}
+
+// What {set} and {map} may be while matching a name, anything for None
+#[derive(Clone, Copy)]
+struct Allowed<'a> {
+    sets: Option<&'a [&'a str]>,
+    maps: Option<&'a [&'a str]>,
+}
+
+// The set and map of a name matching the template from the start, with
+// whatever {set} and {map} already took
+fn match_parts<'a>(parts: &[Part], name: &'a str, allowed: Allowed, set: Option<&'a str>, map: Option<&'a str>) -> Option<(Option<&'a str>, Option<&'a str>)> {
+    let Some((part, rest)) = parts.split_first() else {
+        return name.is_empty().then_some((set, map));
+    };
+    if let Part::Text(text) = part {
+        return match_parts(rest, name.strip_prefix(text)?, allowed, set, map);
+    }
+    (1..=name.len()).filter(|end| name.is_char_boundary(*end)).find_map(|end| {
+        let (value, remainder) = name.split_at(end);
+        match part {
+            Part::Set if set.is_none_or(|set| set == value) && allowed.sets.is_none_or(|sets| sets.contains(&value)) => {
+                match_parts(rest, remainder, allowed, Some(value), map)
+            }
+            Part::Map if map.is_none_or(|map| map == value) && allowed.maps.is_none_or(|maps| maps.contains(&value)) => {
+                match_parts(rest, remainder, allowed, set, Some(value))
+            }
+            Part::Res if value.strip_suffix('K').unwrap_or(value).bytes().all(|b| b.is_ascii_digit()) && value != "K" => {
+                match_parts(rest, remainder, allowed, set, map)
+            }
+            Part::Ext if is_output_extension(&value.to_lowercase()) => match_parts(rest, remainder, allowed, set, map),
+            _ => None,
+        }
+    })
+}
+
+fn known_maps() -> Vec<&'static str> {
+    OUTPUTS.iter().map(|(map, _)| *map).chain([MANIFEST, PROJECT]).collect()
+}
+
+impl FileNames {
+    // Names the outputs are written with, their own and any given in map_names
+    fn known_names(&self) -> Vec<&str> {
+        known_maps().into_iter().flat_map(|map| [map, self.map_name(map)]).collect()
+    }
+
+    // The output a name {map} took stands for
+    fn map_of(&self, name: &str) -> String {
+        known_maps().into_iter()
+            .find(|map| self.map_name(map) == name)
+            .unwrap_or(name)
+            .to_string()
+    }
+
+    fn parse(&self, name: &str, allowed: Allowed) -> Option<(Option<String>, String)> {
+        let (set, map) = [self.template.clone(), without_resolution(&self.template)].iter()
+            .find_map(|template| match_parts(&parts(template), name, allowed, None, None))?;
+        Some((set.map(str::to_string), self.map_of(map?)))
+    }
+
+    // The set and output a file was written for under the template, e.g.
+    // ("Rock023", "albedo") for Rock023_albedo_2K.png with {set}_{map}_{res}.{ext}.
+    // Names the tool writes are tried first, so a set name with underscores
+    // isn't split up at them, then the sets given, then anything else, e.g.
+    // for the textures of a custom layout. The set is None for templates
+    // without it.
+    pub fn parse_name(&self, name: &str, sets: &[&str]) -> Option<(Option<String>, String)> {
+        let known = self.known_names();
+        let passes = [
+            Allowed { sets: None, maps: Some(&known) },
+            Allowed { sets: Some(sets), maps: None },
+            Allowed { sets: None, maps: None },
+        ];
+        for allowed in passes {
+            if let Some(parsed) = self.parse(name, allowed) {
+                return Some(parsed);
+            }
+        }
+        None
+    }
+}
+
+// The files of one exported set
+#[derive(Debug, Clone)]
+pub struct ExportedSet {
+    pub directory: PathBuf,
+    pub name: String,
+    // By output, with one file per format it was written in
+    pub files: BTreeMap<String, Vec<PathBuf>>,
+}
+
+impl ExportedSet {
+    // The output as a texture, there may also be a .json with the same map
+    pub fn texture(&self, map: &str) -> Option<&PathBuf> {
+        self.files.get(map)?.iter().find(|path| {
+            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+            crate::TerrainApp::SUPPORTED_FORMATS.contains(&ext.as_str())
+        })
+    }
+}
+
+// The sets exported into a folder, whatever set file_names was made for
+pub fn sets_in(dir: &Path, file_names: &FileNames) -> Result<Vec<ExportedSet>, String> {
+    let folder_name = dir.file_name().map_or_else(String::new, |name| name.to_string_lossy().to_string());
+    let mut names = Vec::new();
+    for entry in fs::read_dir(paths::long_path(dir)).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        if entry.file_type().is_ok_and(|t| t.is_file()) {
+            names.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    // Sets named by files the tool knows, so other files can be matched to them
+    let known = file_names.known_names();
+    let known_sets: BTreeSet<String> = names.iter()
+        .filter_map(|name| file_names.parse(name, Allowed { sets: None, maps: Some(&known) })?.0)
+        .collect();
+    let known_sets: Vec<&str> = known_sets.iter().map(String::as_str).collect();
+    let mut sets: BTreeMap<String, ExportedSet> = BTreeMap::new();
+    for file_name in names {
+        let Some((set, map)) = file_names.parse_name(&file_name, &known_sets) else {
+            continue;
+        };
+        let name = set.unwrap_or_else(|| folder_name.clone());
+        sets.entry(name.clone())
+            .or_insert_with(|| ExportedSet { directory: dir.to_path_buf(), name, files: BTreeMap::new() })
+            .files.entry(map).or_default()
+            .push(dir.join(file_name));
+    }
+    Ok(sets.into_values()
+        .filter(|set| set.texture("albedo").is_some())
+        .map(|mut set| {
+            set.files.values_mut().for_each(|files| files.sort());
+            set
+        })
+        .collect())
+}
+
+// Every set with an albedo in the root itself or one of its direct subfolders
+pub fn find_sets(root: &Path, file_names: &FileNames) -> Result<Vec<ExportedSet>, String> {
+    let mut sets = sets_in(root, file_names)?;
+    for entry in fs::read_dir(paths::long_path(root)).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        // Hidden folders hold exports still being staged
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if entry.file_type().is_ok_and(|t| t.is_dir()) && !hidden {
+            sets.extend(sets_in(&root.join(entry.file_name()), file_names)?);
+        }
+    }
+    sets.sort_by(|a, b| a.directory.cmp(&b.directory).then_with(|| a.name.cmp(&b.name)));
+    Ok(sets)
+}
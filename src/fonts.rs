@@ -0,0 +1,27 @@
+use egui::{Context, FontData, FontDefinitions, FontFamily};
+use std::sync::Arc;
+
+// The bundled egui fonts have no CJK glyphs, so Japanese set and file names
+// render as boxes. The first of these that exists is added as a fallback.
+const CJK_FONTS: &[&str] = &[
+    r"C:\Windows\Fonts\YuGothM.ttc",
+    r"C:\Windows\Fonts\meiryo.ttc",
+    r"C:\Windows\Fonts\msgothic.ttc",
+    "/System/Library/Fonts/ヒラギノ角ゴシック W3.ttc",
+    "/System/Library/Fonts/Hiragino Sans GB.ttc",
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+];
+
+pub fn install_fallback_fonts(ctx: &Context) {
+    let Some(data) = CJK_FONTS.iter().find_map(|path| std::fs::read(path).ok()) else {
+        return;
+    };
+    let mut fonts = FontDefinitions::default();
+    fonts.font_data.insert("cjk".to_owned(), Arc::new(FontData::from_owned(data)));
+    for family in [FontFamily::Proportional, FontFamily::Monospace] {
+        fonts.families.entry(family).or_default().push("cjk".to_owned());
+    }
+    ctx.set_fonts(fonts);
+}
@@ -0,0 +1,241 @@
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::error::{ImageError, LimitErrorKind};
+use image::{AnimationDecoder, DynamicImage, GrayImage, ImageBuffer, ImageDecoder, ImageFormat, ImageReader, Limits, Luma, Rgb, Rgba};
+use std::any::Any;
+use std::fs::File;
+use std::io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tiff::decoder::{Decoder as TiffDecoder, DecodingResult, Limits as TiffLimits};
+use tiff::{ColorType, TiffError};
+
+// Downloads and scans arrive broken often enough that decoding is treated as
+// untrusted: it runs on its own thread with limits on size and time, and a
+// decoder that panics only fails the one file.
+
+// Larger than any texture an engine takes, but keeps a corrupt header from
+// asking for terabytes
+const MAX_DIMENSION: u32 = 65536;
+// Memory one decode may allocate, enough for a 16k map with 16-bit channels
+const MAX_ALLOC: u64 = 4 << 30;
+// A decode still running after this is stopped, its next read of the file
+// fails so the thread ends and frees what it allocated
+const DECODE_TIMEOUT: Duration = Duration::from_secs(60);
+
+// The file as the decoders see it, reads fail once the decode is stopped
+struct Stoppable {
+    file: File,
+    stop: Arc<AtomicBool>,
+}
+
+impl Stoppable {
+    fn check(&self) -> io::Result<()> {
+        if self.stop.load(Ordering::Relaxed) {
+            return Err(io::Error::other("Decoding was stopped"));
+        }
+        Ok(())
+    }
+}
+
+impl Read for Stoppable {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.check()?;
+        self.file.read(buf)
+    }
+}
+
+impl Seek for Stoppable {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.check()?;
+        self.file.seek(pos)
+    }
+}
+
+fn limits() -> Limits {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_ALLOC);
+    limits
+}
+
+fn tiff_limits() -> TiffLimits {
+    let mut limits = TiffLimits::default();
+    limits.decoding_buffer_size = MAX_ALLOC as usize;
+    limits.intermediate_buffer_size = MAX_ALLOC as usize;
+    limits
+}
+
+// Decoder errors in terms of what's wrong with the file
+fn describe(error: ImageError) -> String {
+    match error {
+        ImageError::Limits(e) if matches!(e.kind(), LimitErrorKind::DimensionError) => {
+            format!("The image is larger than {} pixels on a side, or its header is damaged", MAX_DIMENSION)
+        }
+        ImageError::Limits(_) => "The image needs more memory than a texture should, its header may be damaged".to_string(),
+        ImageError::Decoding(e) => format!("The file looks damaged or incomplete ({})", e),
+        ImageError::IoError(e) if e.kind() == ErrorKind::UnexpectedEof => {
+            "The file ends early, the download or copy may be incomplete".to_string()
+        }
+        e => e.to_string(),
+    }
+}
+
+fn describe_tiff(error: TiffError) -> String {
+    match error {
+        TiffError::LimitsExceeded => "The image needs more memory than a texture should, its header may be damaged".to_string(),
+        TiffError::FormatError(e) => format!("The file looks damaged or incomplete ({})", e),
+        TiffError::IoError(e) if e.kind() == ErrorKind::UnexpectedEof => {
+            "The file ends early, the download or copy may be incomplete".to_string()
+        }
+        e => e.to_string(),
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload.downcast_ref::<&str>().copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown error")
+}
+
+// Single frame formats, detected by content so misnamed files still open
+fn open_image(path: &Path, stop: &Arc<AtomicBool>) -> Result<DynamicImage, String> {
+    let mut reader = ImageReader::new(open(path, stop)?);
+    // The extension is the first guess, like ImageReader::open makes it
+    if let Ok(format) = ImageFormat::from_path(path) {
+        reader.set_format(format);
+    }
+    let mut reader = reader.with_guessed_format().map_err(|e| e.to_string())?;
+    reader.limits(limits());
+    reader.decode().map_err(describe)
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase()
+}
+
+fn open(path: &Path, stop: &Arc<AtomicBool>) -> Result<BufReader<Stoppable>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    Ok(BufReader::new(Stoppable { file, stop: stop.clone() }))
+}
+
+// Frames are composited by the decoders, so each one is the full image as it
+// would be displayed at that point of the animation
+fn animation_frame<'a>(decoder: impl AnimationDecoder<'a>, index: usize) -> Result<(DynamicImage, usize), String> {
+    // Only the wanted frame is kept, so long animations don't pile up
+    let mut count = 0;
+    let mut frame = None;
+    for decoded in decoder.into_frames() {
+        let decoded = decoded.map_err(describe)?;
+        if count == index {
+            frame = Some(decoded);
+        }
+        count += 1;
+    }
+    let frame = frame.ok_or_else(|| format!("Frame {} out of range, the file has {}", index, count))?;
+    Ok((DynamicImage::ImageRgba8(frame.into_buffer()), count))
+}
+
+fn tiff_page(path: &Path, index: usize, stop: &Arc<AtomicBool>) -> Result<(DynamicImage, usize), String> {
+    let mut decoder = TiffDecoder::new(open(path, stop)?).map_err(describe_tiff)?.with_limits(tiff_limits());
+    let mut count = 1;
+    while decoder.more_images() {
+        decoder.next_image().map_err(describe_tiff)?;
+        count += 1;
+    }
+    if count == 1 {
+        // Single page files go through the regular loader with its wider
+        // format support
+        return open_image(path, stop).map(|img| (img, 1));
+    }
+    if index >= count {
+        return Err(format!("Page {} out of range, the file has {}", index, count));
+    }
+    decoder.seek_to_image(index).map_err(describe_tiff)?;
+    let (width, height) = decoder.dimensions().map_err(describe_tiff)?;
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(format!("The image is larger than {} pixels on a side, or its header is damaged", MAX_DIMENSION));
+    }
+    let color = decoder.colortype().map_err(describe_tiff)?;
+    let data = decoder.read_image().map_err(describe_tiff)?;
+    let unsupported = || format!("Unsupported TIFF page format {:?}", color);
+    let image = match (color, data) {
+        (ColorType::Gray(8), DecodingResult::U8(data)) => GrayImage::from_raw(width, height, data).map(DynamicImage::ImageLuma8),
+        (ColorType::Gray(16), DecodingResult::U16(data)) => ImageBuffer::<Luma<u16>, _>::from_raw(width, height, data).map(DynamicImage::ImageLuma16),
+        (ColorType::Gray(32), DecodingResult::F32(data)) => {
+            // Float height stacks keep their precision as gray in RGB float
+            let rgb = data.iter().flat_map(|&v| [v, v, v]).collect();
+            ImageBuffer::<Rgb<f32>, _>::from_raw(width, height, rgb).map(DynamicImage::ImageRgb32F)
+        }
+        (ColorType::RGB(8), DecodingResult::U8(data)) => ImageBuffer::<Rgb<u8>, _>::from_raw(width, height, data).map(DynamicImage::ImageRgb8),
+        (ColorType::RGB(16), DecodingResult::U16(data)) => ImageBuffer::<Rgb<u16>, _>::from_raw(width, height, data).map(DynamicImage::ImageRgb16),
+        (ColorType::RGBA(8), DecodingResult::U8(data)) => ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, data).map(DynamicImage::ImageRgba8),
+        (ColorType::RGBA(16), DecodingResult::U16(data)) => ImageBuffer::<Rgba<u16>, _>::from_raw(width, height, data).map(DynamicImage::ImageRgba16),
+        _ => return Err(unsupported()),
+    };
+    image.map(|img| (img, count)).ok_or_else(unsupported)
+}
+
+fn decode_frame(path: &Path, index: usize, stop: &Arc<AtomicBool>) -> Result<(DynamicImage, usize), String> {
+    match extension(path).as_str() {
+        "gif" => {
+            let mut decoder = GifDecoder::new(open(path, stop)?).map_err(describe)?;
+            decoder.set_limits(limits()).map_err(describe)?;
+            animation_frame(decoder, index)
+        }
+        "webp" => {
+            let mut decoder = WebPDecoder::new(open(path, stop)?).map_err(describe)?;
+            if decoder.has_animation() {
+                decoder.set_limits(limits()).map_err(describe)?;
+                return animation_frame(decoder, index);
+            }
+            open_image(path, stop).map(|img| (img, 1))
+        }
+        "png" => {
+            let decoder = PngDecoder::with_limits(open(path, stop)?, limits()).map_err(describe)?;
+            if decoder.is_apng().map_err(describe)? {
+                return animation_frame(decoder.apng().map_err(describe)?, index);
+            }
+            open_image(path, stop).map(|img| (img, 1))
+        }
+        "tif" | "tiff" => tiff_page(path, index, stop),
+        _ => open_image(path, stop).map(|img| (img, 1)),
+    }
+}
+
+// Opens one frame of an animated GIF/WebP/PNG or one page of a multi-page
+// TIFF, along with how many there are. Everything else has a single frame.
+pub fn open_frame(path: &Path, index: usize) -> Result<(DynamicImage, usize), String> {
+    if std::fs::metadata(path).is_ok_and(|metadata| metadata.len() == 0) {
+        return Err("The file is empty, the download or copy may have failed".to_string());
+    }
+    let (tx, rx) = channel();
+    let owned = path.to_path_buf();
+    let stop = Arc::new(AtomicBool::new(false));
+    let decode_stop = stop.clone();
+    thread::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| decode_frame(&owned, index, &decode_stop)))
+            .unwrap_or_else(|payload| Err(format!("The decoder failed on this file, it's probably damaged ({})", panic_message(&*payload))));
+        tx.send(result).ok();
+    });
+    match rx.recv_timeout(DECODE_TIMEOUT) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => {
+            stop.store(true, Ordering::Relaxed);
+            Err(format!("Decoding took longer than {} seconds and was stopped", DECODE_TIMEOUT.as_secs()))
+        }
+        Err(RecvTimeoutError::Disconnected) => Err("The decoder failed on this file, it's probably damaged".to_string()),
+    }
+}
+
+// A single image through the same safeguards, for tools that take one file
+pub fn open_single(path: &Path) -> Result<DynamicImage, String> {
+    open_frame(path, 0).map(|(img, _)| img)
+}
@@ -0,0 +1,417 @@
+// Minimal reader for Godot 4 binary resources (.res), enough to pull
+// properties and embedded images out of saved Terrain3D data
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+const FORMAT_FLAG_NAMED_SCENE_IDS: u32 = 1;
+const FORMAT_FLAG_UIDS: u32 = 2;
+const FORMAT_FLAG_REAL_T_IS_DOUBLE: u32 = 4;
+const FORMAT_FLAG_HAS_SCRIPT_CLASS: u32 = 8;
+const RESERVED_FIELDS: usize = 11;
+
+#[derive(Debug, Clone)]
+pub enum ObjectRef {
+    Empty,
+    Internal(usize),
+    External,
+}
+
+#[derive(Debug, Clone)]
+pub enum Variant {
+    Nil,
+    Int(i64),
+    Float(f64),
+    String(String),
+    VectorI(Vec<i32>),
+    Object(ObjectRef),
+    Dictionary(Vec<(Variant, Variant)>),
+    Bytes(Vec<u8>),
+    // Values we can skip over but have no use for
+    Unsupported,
+}
+
+impl Variant {
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(v) => Some(*v),
+            Self::Float(v) => Some(*v as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Variant> {
+        match self {
+            Self::Dictionary(entries) => entries.iter()
+                .find(|(k, _)| k.as_str() == Some(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Resource {
+    pub type_name: String,
+    pub path: String,
+    pub properties: Vec<(String, Variant)>,
+}
+
+impl Resource {
+    pub fn property(&self, name: &str) -> Option<&Variant> {
+        self.properties.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+}
+
+#[derive(Debug)]
+pub struct ResourceFile {
+    pub main_type: String,
+    pub resources: Vec<Resource>,
+    named_scene_ids: bool,
+}
+
+impl ResourceFile {
+    // The main resource is always stored last
+    pub fn main(&self) -> Option<&Resource> {
+        self.resources.last()
+    }
+
+    pub fn resolve(&self, object: &Variant) -> Option<&Resource> {
+        match object {
+            Variant::Object(ObjectRef::Internal(index)) => {
+                if self.named_scene_ids {
+                    self.resources.get(*index)
+                } else {
+                    let path = format!("local://{}", index);
+                    self.resources.iter().find(|r| r.path == path)
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    real64: bool,
+    strings: Vec<String>,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, len: usize) -> Result<&[u8], String> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.data.len())
+            .ok_or("Unexpected end of resource file")?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn real(&mut self) -> Result<f64, String> {
+        if self.real64 { self.f64() } else { Ok(self.f32()? as f64) }
+    }
+
+    fn reals(&mut self, count: usize) -> Result<Vec<f64>, String> {
+        (0..count).map(|_| self.real()).collect()
+    }
+
+    fn ints(&mut self, count: usize) -> Result<Vec<i32>, String> {
+        (0..count).map(|_| self.u32().map(|v| v as i32)).collect()
+    }
+
+    fn unicode_string(&mut self) -> Result<String, String> {
+        let len = self.u32()? as usize;
+        let bytes = self.bytes(len)?;
+        let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+
+    fn string_ref(&mut self) -> Result<String, String> {
+        let id = self.u32()?;
+        if id & 0x8000_0000 != 0 {
+            let bytes = self.bytes((id & 0x7FFF_FFFF) as usize)?;
+            Ok(String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string())
+        } else {
+            self.strings.get(id as usize).cloned().ok_or_else(|| "Invalid string table index".to_string())
+        }
+    }
+
+    fn padding(&mut self, len: usize) -> Result<(), String> {
+        let extra = (4 - len % 4) % 4;
+        self.bytes(extra).map(|_| ())
+    }
+
+    fn variant(&mut self) -> Result<Variant, String> {
+        let kind = self.u32()?;
+        Ok(match kind {
+            1 => Variant::Nil,
+            2 => Variant::Int((self.u32()? != 0) as i64),
+            3 => Variant::Int(self.u32()? as i32 as i64),
+            40 => Variant::Int(self.u64()? as i64),
+            4 => Variant::Float(self.f32()? as f64),
+            41 => Variant::Float(self.f64()?),
+            5 | 44 => Variant::String(self.unicode_string()?),
+            // Vector2, Rect2, Vector3, Plane, Quaternion, AABB, Basis, Transform3D,
+            // Transform2D, Vector4, Projection
+            10 => { self.reals(2)?; Variant::Unsupported },
+            11 | 13 | 14 | 50 => { self.reals(4)?; Variant::Unsupported },
+            12 => { self.reals(3)?; Variant::Unsupported },
+            15 | 18 => { self.reals(6)?; Variant::Unsupported },
+            16 => { self.reals(9)?; Variant::Unsupported },
+            17 => { self.reals(12)?; Variant::Unsupported },
+            52 => { self.reals(16)?; Variant::Unsupported },
+            // Vector2i, Rect2i, Vector3i, Vector4i
+            45 => Variant::VectorI(self.ints(2)?),
+            46 | 51 => Variant::VectorI(self.ints(4)?),
+            47 => Variant::VectorI(self.ints(3)?),
+            20 => {
+                self.bytes(16)?;
+                Variant::Unsupported
+            }
+            22 => {
+                // Name count and subname count (with the absolute flag) as two u16s
+                let header = self.u32()?;
+                let names = (header & 0xFFFF) as usize;
+                let subnames = ((header >> 16) & 0x7FFF) as usize;
+                for _ in 0..names + subnames {
+                    self.string_ref()?;
+                }
+                Variant::Unsupported
+            }
+            23 => {
+                self.u32()?;
+                Variant::Unsupported
+            }
+            24 => match self.u32()? {
+                0 => Variant::Object(ObjectRef::Empty),
+                1 => {
+                    self.unicode_string()?;
+                    self.unicode_string()?;
+                    Variant::Object(ObjectRef::External)
+                }
+                2 => Variant::Object(ObjectRef::Internal(self.u32()? as usize)),
+                3 => {
+                    self.u32()?;
+                    Variant::Object(ObjectRef::External)
+                }
+                other => return Err(format!("Unknown object reference type {}", other)),
+            },
+            42 | 43 => Variant::Unsupported,
+            26 => {
+                let len = (self.u32()? & 0x7FFF_FFFF) as usize;
+                let mut entries = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let key = self.variant()?;
+                    let value = self.variant()?;
+                    entries.push((key, value));
+                }
+                Variant::Dictionary(entries)
+            }
+            30 => {
+                let len = (self.u32()? & 0x7FFF_FFFF) as usize;
+                for _ in 0..len {
+                    self.variant()?;
+                }
+                Variant::Unsupported
+            }
+            31 => {
+                let len = self.u32()? as usize;
+                let bytes = self.bytes(len)?.to_vec();
+                self.padding(len)?;
+                Variant::Bytes(bytes)
+            }
+            // Packed int32, float32, int64, float64 arrays
+            32 | 33 | 48 | 49 => {
+                let len = self.u32()? as usize;
+                self.bytes(len * if kind >= 48 { 8 } else { 4 })?;
+                Variant::Unsupported
+            }
+            34 => {
+                let len = self.u32()? as usize;
+                for _ in 0..len {
+                    self.unicode_string()?;
+                }
+                Variant::Unsupported
+            }
+            // Packed Vector2/3/4 arrays follow real_t, colors are always float
+            35 | 37 | 53 => {
+                let len = self.u32()? as usize;
+                let components = match kind { 35 => 3, 37 => 2, _ => 4 };
+                self.reals(len * components)?;
+                Variant::Unsupported
+            }
+            36 => {
+                let len = self.u32()? as usize;
+                self.bytes(len * 16)?;
+                Variant::Unsupported
+            }
+            other => return Err(format!("Unsupported variant type {}", other)),
+        })
+    }
+}
+
+// Godot's FileAccessCompressed container ("RSCC"): independently compressed blocks
+fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = Reader { data, pos: 4, real64: false, strings: Vec::new() };
+    let mode = reader.u32()?;
+    let block_size = reader.u32()? as usize;
+    let total = reader.u32()? as usize;
+    if block_size == 0 {
+        return Err("Invalid compressed block size".to_string());
+    }
+    let block_count = total / block_size + 1;
+    let sizes: Vec<usize> = (0..block_count).map(|_| reader.u32().map(|v| v as usize)).collect::<Result<_, _>>()?;
+
+    let mut out = Vec::with_capacity(total);
+    for (i, size) in sizes.into_iter().enumerate() {
+        let block = reader.bytes(size)?;
+        let expected = if i + 1 == block_count { total - out.len() } else { block_size };
+        if expected == 0 {
+            continue;
+        }
+        let mut chunk = Vec::with_capacity(expected);
+        match mode {
+            1 => {
+                flate2::read::ZlibDecoder::new(block).read_to_end(&mut chunk)
+                    .map_err(|e| format!("Failed to inflate resource: {}", e))?;
+            }
+            2 => {
+                ruzstd::StreamingDecoder::new(block)
+                    .map_err(|e| format!("Failed to decompress resource: {}", e))?
+                    .read_to_end(&mut chunk)
+                    .map_err(|e| format!("Failed to decompress resource: {}", e))?;
+            }
+            3 => {
+                flate2::read::GzDecoder::new(block).read_to_end(&mut chunk)
+                    .map_err(|e| format!("Failed to inflate resource: {}", e))?;
+            }
+            other => return Err(format!("Unsupported resource compression mode {}", other)),
+        }
+        out.extend_from_slice(&chunk);
+    }
+    Ok(out)
+}
+
+pub fn read_resource(path: &Path) -> Result<ResourceFile, String> {
+    let raw = fs::read(path).map_err(|e| e.to_string())?;
+    let data = match raw.get(0..4) {
+        Some(b"RSRC") => raw,
+        Some(b"RSCC") => {
+            let mut data = b"RSRC".to_vec();
+            data.extend(decompress(&raw)?);
+            data
+        }
+        _ => return Err("Not a Godot binary resource".to_string()),
+    };
+
+    let mut r = Reader { data: &data, pos: 4, real64: false, strings: Vec::new() };
+    if r.u32()? != 0 {
+        return Err("Big endian resources are not supported".to_string());
+    }
+    r.real64 = r.u32()? != 0;
+    let _major = r.u32()?;
+    let _minor = r.u32()?;
+    let _format = r.u32()?;
+    let main_type = r.unicode_string()?;
+    let _import_metadata = r.u64()?;
+    let flags = r.u32()?;
+    let _uid = r.u64()?;
+    if flags & FORMAT_FLAG_REAL_T_IS_DOUBLE != 0 {
+        r.real64 = true;
+    }
+    if flags & FORMAT_FLAG_HAS_SCRIPT_CLASS != 0 {
+        r.unicode_string()?;
+    }
+    for _ in 0..RESERVED_FIELDS {
+        r.u32()?;
+    }
+
+    let string_count = r.u32()?;
+    for _ in 0..string_count {
+        let s = r.unicode_string()?;
+        r.strings.push(s);
+    }
+
+    let external_count = r.u32()?;
+    for _ in 0..external_count {
+        r.unicode_string()?;
+        r.unicode_string()?;
+        if flags & FORMAT_FLAG_UIDS != 0 {
+            r.u64()?;
+        }
+    }
+
+    let internal_count = r.u32()?;
+    let mut internal = Vec::with_capacity(internal_count as usize);
+    for _ in 0..internal_count {
+        let path = r.unicode_string()?;
+        let offset = r.u64()? as usize;
+        internal.push((path, offset));
+    }
+
+    let mut resources = Vec::with_capacity(internal.len());
+    for (path, offset) in internal {
+        r.pos = offset;
+        let type_name = r.unicode_string()?;
+        let property_count = r.u32()?;
+        let mut properties = Vec::with_capacity(property_count as usize);
+        for _ in 0..property_count {
+            let name = r.string_ref()?;
+            let value = r.variant()?;
+            properties.push((name, value));
+        }
+        resources.push(Resource { type_name, path, properties });
+    }
+
+    Ok(ResourceFile {
+        main_type,
+        resources,
+        named_scene_ids: flags & FORMAT_FLAG_NAMED_SCENE_IDS != 0,
+    })
+}
+
+// Godot serializes Image as a "data" dictionary holding the format name and raw bytes
+pub struct GodotImage {
+    pub width: u32,
+    pub height: u32,
+    pub format: String,
+    pub data: Vec<u8>,
+}
+
+pub fn image_from_resource(resource: &Resource) -> Result<GodotImage, String> {
+    if resource.type_name != "Image" {
+        return Err(format!("Expected an Image, found {}", resource.type_name));
+    }
+    let data = resource.property("data").ok_or("Image resource has no data")?;
+    let width = data.get("width").and_then(Variant::as_int).ok_or("Image is missing width")? as u32;
+    let height = data.get("height").and_then(Variant::as_int).ok_or("Image is missing height")? as u32;
+    let format = data.get("format").and_then(Variant::as_str).ok_or("Image is missing format")?.to_string();
+    let bytes = match data.get("data") {
+        Some(Variant::Bytes(bytes)) => bytes.clone(),
+        _ => return Err("Image is missing pixel data".to_string()),
+    };
+    Ok(GodotImage { width, height, format, data: bytes })
+}
@@ -0,0 +1,325 @@
+// Golden image and snapshot tests for the packing pipeline. Each case packs
+// small synthetic maps with its settings and compares the result against the
+// reference files in tests/golden and tests/snapshots. After an intended
+// change to the output, run the tests with UPDATE_GOLDEN=1 to rewrite the
+// references and review them like any other change.
+
+use image::{DynamicImage, GenericImageView, GrayImage, ImageBuffer, Luma, Rgba, RgbaImage};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::height_alpha::{self, HeightAlphaSettings, ParallaxSettings};
+use crate::roughness::{self, RoughnessAdjust, RoughnessCurve};
+use crate::{pack16, pack8};
+
+const SIZE: u32 = 32;
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+fn snapshot_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("snapshots")
+}
+
+fn updating() -> bool {
+    std::env::var_os("UPDATE_GOLDEN").is_some()
+}
+
+// Where mismatching output is written for a look next to the reference
+fn failure_dir() -> PathBuf {
+    std::env::temp_dir().join("terrain_3d_prepare_golden")
+}
+
+struct Case {
+    name: &'static str,
+    directx: bool,
+    smoothness: bool,
+    unpremultiply: bool,
+    // Without them the defaults below fill the channels
+    optional_maps: bool,
+    default_height: u8,
+    default_roughness: u8,
+    curve: RoughnessCurve,
+    adjust: RoughnessAdjust,
+    height_alpha: HeightAlphaSettings,
+    parallax: ParallaxSettings,
+}
+
+impl Default for Case {
+    fn default() -> Self {
+        Self {
+            name: "default",
+            directx: false,
+            smoothness: false,
+            unpremultiply: false,
+            optional_maps: true,
+            default_height: 255,
+            default_roughness: 128,
+            curve: Default::default(),
+            adjust: Default::default(),
+            height_alpha: Default::default(),
+            parallax: Default::default(),
+        }
+    }
+}
+
+fn cases() -> Vec<Case> {
+    vec![
+        Case::default(),
+        // A smoothness source has to come out inverted, DirectX normals with
+        // green flipped
+        Case {
+            name: "directx_smoothness",
+            directx: true,
+            smoothness: true,
+            ..Default::default()
+        },
+        Case {
+            name: "no_optional_maps",
+            optional_maps: false,
+            default_height: 200,
+            default_roughness: 90,
+            ..Default::default()
+        },
+        // The default roughness is written as set, the curve and remap only
+        // shape roughness read from a map
+        Case {
+            name: "no_optional_maps_shaped",
+            optional_maps: false,
+            default_roughness: 90,
+            curve: RoughnessCurve { points: vec![[0.0, 0.0], [0.5, 0.3], [1.0, 1.0]] },
+            adjust: RoughnessAdjust { output_min: 0.1, output_max: 0.9, clamp_min: 0.5, clamp_max: 0.8 },
+            ..Default::default()
+        },
+        Case {
+            name: "shaped",
+            unpremultiply: true,
+            curve: RoughnessCurve { points: vec![[0.0, 0.0], [0.5, 0.3], [1.0, 1.0]] },
+            adjust: RoughnessAdjust { output_min: 0.1, output_max: 0.9, clamp_min: 0.2, clamp_max: 0.8 },
+            height_alpha: HeightAlphaSettings { contrast: 1.5, bias: 0.1 },
+            parallax: ParallaxSettings { invert: true, mid_level: 0.4, edge_padding: 4 },
+            ..Default::default()
+        },
+    ]
+}
+
+// Smooth unit values over the test square, so every map covers a range
+fn wave(x: u32, y: u32, fx: f32, fy: f32) -> f32 {
+    let (u, v) = (x as f32 / SIZE as f32, y as f32 / SIZE as f32);
+    ((u * fx * std::f32::consts::TAU).sin() * (v * fy * std::f32::consts::TAU).cos()) * 0.5 + 0.5
+}
+
+fn albedo_value(x: u32, y: u32) -> [f32; 4] {
+    [x as f32 / (SIZE - 1) as f32, wave(x, y, 1.0, 1.0), y as f32 / (SIZE - 1) as f32, 0.25 + 0.75 * wave(x, y, 2.0, 1.0)]
+}
+
+fn normal_value(x: u32, y: u32) -> [f32; 4] {
+    let (nx, ny) = (wave(x, y, 1.0, 2.0) - 0.5, wave(y, x, 2.0, 1.0) - 0.5);
+    let nz = (1.0 - nx * nx - ny * ny).sqrt();
+    [nx * 0.5 + 0.5, ny * 0.5 + 0.5, nz * 0.5 + 0.5, 1.0]
+}
+
+fn gray_values() -> [fn(u32, u32) -> f32; 3] {
+    [
+        // ao
+        |x, y| 0.5 + 0.5 * wave(x, y, 3.0, 3.0),
+        // height
+        |x, y| wave(x, y, 1.0, 1.0),
+        // roughness
+        |x, _| x as f32 / (SIZE - 1) as f32,
+    ]
+}
+
+fn rgba8(value: fn(u32, u32) -> [f32; 4]) -> RgbaImage {
+    RgbaImage::from_fn(SIZE, SIZE, |x, y| Rgba(value(x, y).map(|v| (v * 255.0).round() as u8)))
+}
+
+fn rgba16(value: fn(u32, u32) -> [f32; 4]) -> DynamicImage {
+    DynamicImage::ImageRgba16(ImageBuffer::from_fn(SIZE, SIZE, |x, y| Rgba(value(x, y).map(|v| (v * 65535.0).round() as u16))))
+}
+
+fn gray8(value: fn(u32, u32) -> f32) -> GrayImage {
+    GrayImage::from_fn(SIZE, SIZE, |x, y| Luma([(value(x, y) * 255.0).round() as u8]))
+}
+
+fn gray16(value: fn(u32, u32) -> f32) -> DynamicImage {
+    DynamicImage::ImageLuma16(ImageBuffer::from_fn(SIZE, SIZE, |x, y| Luma([(value(x, y) * 65535.0).round() as u16])))
+}
+
+// The 8-bit steps in the order an export runs them
+fn pack_8bit(case: &Case) -> (RgbaImage, RgbaImage) {
+    let [ao, height, roughness] = gray_values().map(gray8);
+    let optional = |map| Some(map).filter(|_| case.optional_maps);
+    let (ao, height, roughness) = (optional(ao), optional(height), optional(roughness));
+
+    let mut albedo = rgba8(albedo_value);
+    if case.unpremultiply {
+        pack8::unpremultiply(&mut albedo);
+    }
+    if let Some(ao) = &ao {
+        pack8::multiply_ao(&mut albedo, ao);
+    }
+    let height_lut = height_alpha::height_lut(&case.height_alpha, &case.parallax);
+    pack8::pack_height(&mut albedo, height.as_ref(), &height_lut, case.default_height, case.parallax.edge_padding);
+
+    let mut normal = rgba8(normal_value);
+    let roughness_lut = roughness::combined_lut(&case.curve, &case.adjust);
+    pack8::pack_normal_roughness(
+        &mut normal,
+        roughness.as_ref(),
+        case.directx,
+        case.smoothness,
+        &roughness_lut,
+        case.default_roughness,
+    );
+    (albedo, normal)
+}
+
+// The 16-bit pipeline, also downsampled to cover the resize
+fn pack_16bit(case: &Case, max_size: Option<u32>) -> (pack16::Rgba16Image, pack16::Rgba16Image) {
+    let [ao, height, roughness] = gray_values().map(gray16);
+    let optional = |map| Some(map).filter(|_| case.optional_maps);
+    let (ao, height, roughness) = (optional(ao), optional(height), optional(roughness));
+    let albedo = pack16::pack_albedo_height(
+        &rgba16(albedo_value),
+        ao.as_ref(),
+        height.as_ref(),
+        case.unpremultiply,
+        &case.height_alpha,
+        &case.parallax,
+        case.default_height,
+        max_size,
+        true,
+    );
+    let normal = pack16::pack_normal_roughness(
+        &rgba16(normal_value),
+        roughness.as_ref(),
+        false,
+        case.directx,
+        case.smoothness,
+        &case.curve,
+        &case.adjust,
+        case.default_roughness,
+        max_size,
+        true,
+    );
+    (albedo, normal)
+}
+
+// Compares with the reference image, tolerance in steps of the image's own
+// bit depth to allow for floating point differences between platforms
+fn check_image(name: &str, actual: DynamicImage, tolerance: u16) {
+    let path = golden_dir().join(format!("{}.png", name));
+    if updating() {
+        fs::create_dir_all(golden_dir()).unwrap();
+        actual.save(&path).unwrap();
+        return;
+    }
+    let expected = image::open(&path)
+        .unwrap_or_else(|e| panic!("No golden image {} ({}), run with UPDATE_GOLDEN=1 to create it", path.display(), e));
+    assert_eq!(expected.color(), actual.color(), "{}: pixel format changed", name);
+    assert_eq!(expected.dimensions(), actual.dimensions(), "{}: size changed", name);
+
+    let scale = if actual.color().bytes_per_pixel() > 4 { 1 } else { 257 };
+    let (expected16, actual16) = (expected.to_rgba16(), actual.to_rgba16());
+    let mut worst: Option<(u32, u32, usize, u16)> = None;
+    let mut mismatches = 0;
+    for (x, y, pixel) in actual16.enumerate_pixels() {
+        let reference = expected16.get_pixel(x, y);
+        for c in 0..4 {
+            let difference = pixel[c].abs_diff(reference[c]) / scale;
+            if difference > tolerance {
+                mismatches += 1;
+                if worst.is_none_or(|(.., worst)| difference > worst) {
+                    worst = Some((x, y, c, difference));
+                }
+            }
+        }
+    }
+    if let Some((x, y, channel, difference)) = worst {
+        fs::create_dir_all(failure_dir()).unwrap();
+        let output = failure_dir().join(format!("{}.png", name));
+        actual.save(&output).unwrap();
+        panic!(
+            "{}: {} channel values differ from the golden image, worst by {} in channel {} at {},{}. Output written to {}",
+            name, mismatches, difference, channel, x, y, output.display(),
+        );
+    }
+}
+
+fn check_snapshot(name: &str, actual: &str) {
+    let path = snapshot_dir().join(format!("{}.txt", name));
+    if updating() {
+        fs::create_dir_all(snapshot_dir()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("No snapshot {} ({}), run with UPDATE_GOLDEN=1 to create it", path.display(), e));
+    // Checkouts may have converted the line endings
+    if expected.replace("\r\n", "\n") != actual {
+        let (line, (expected, actual)) = expected.lines().zip(actual.lines()).enumerate()
+            .find(|(_, (expected, actual))| expected != actual)
+            .unwrap_or((expected.lines().count().min(actual.lines().count()), ("<end>", "<end>")));
+        panic!("{}: snapshot differs at line {}\nexpected: {}\nactual:   {}", name, line + 1, expected, actual);
+    }
+}
+
+#[test]
+fn packed_8bit_matches_golden() {
+    for case in cases() {
+        let (albedo, normal) = pack_8bit(&case);
+        check_image(&format!("{}_albedo_height", case.name), DynamicImage::ImageRgba8(albedo), 1);
+        check_image(&format!("{}_normal_roughness", case.name), DynamicImage::ImageRgba8(normal), 1);
+    }
+}
+
+#[test]
+fn packed_16bit_matches_golden() {
+    for case in cases() {
+        for (suffix, max_size) in [("", None), ("_half", Some(SIZE / 2))] {
+            let (albedo, normal) = pack_16bit(&case, max_size);
+            check_image(&format!("{}_albedo_height_16{}", case.name, suffix), DynamicImage::ImageRgba16(albedo), 16);
+            check_image(&format!("{}_normal_roughness_16{}", case.name, suffix), DynamicImage::ImageRgba16(normal), 16);
+        }
+    }
+}
+
+// The lookup tables behind the 8-bit packing, readable in a diff
+#[test]
+fn lookup_tables_match_snapshot() {
+    for case in cases() {
+        let mut text = String::new();
+        let tables = [
+            ("roughness", roughness::combined_lut(&case.curve, &case.adjust)),
+            ("height", height_alpha::height_lut(&case.height_alpha, &case.parallax)),
+        ];
+        for (table, lut) in tables {
+            writeln!(text, "{}:", table).unwrap();
+            for row in lut.chunks(16) {
+                let values: Vec<String> = row.iter().map(|v| format!("{:3}", v)).collect();
+                writeln!(text, "{}", values.join(" ")).unwrap();
+            }
+        }
+        check_snapshot(&format!("{}_luts", case.name), &text);
+    }
+}
+
+// The two pipelines are meant to agree, apart from the 8-bit rounding
+#[test]
+fn packed_16bit_follows_8bit() {
+    for case in cases() {
+        let (albedo8, normal8) = pack_8bit(&case);
+        let (albedo16, normal16) = pack_16bit(&case, None);
+        for (name, eight, sixteen) in [("albedo", albedo8, albedo16), ("normal", normal8, normal16)] {
+            for (a, b) in eight.pixels().zip(sixteen.pixels()) {
+                for c in 0..4 {
+                    let difference = (a[c] as i32 - (b[c] as i32 / 257)).abs();
+                    assert!(difference <= 3, "{} {}: 8 and 16-bit differ by {} in channel {}", case.name, name, difference, c);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use image::{GrayImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::colormap;
+
+// Shaping for the height stored in the albedo alpha. Terrain3D blends
+// textures by comparing these heights, so flat mid-gray data gives soft,
+// smeared transitions while more contrast gives crisp ones.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeightAlphaSettings {
+    // Scales distance from mid gray
+    pub contrast: f32,
+    // Added after the contrast, shifts the whole texture up or down in blends
+    pub bias: f32,
+}
+
+impl Default for HeightAlphaSettings {
+    fn default() -> Self {
+        Self {
+            contrast: 1.0,
+            bias: 0.0,
+        }
+    }
+}
+
+impl HeightAlphaSettings {
+    pub fn apply(&self, height: f32) -> f32 {
+        ((height - 0.5) * self.contrast + 0.5 + self.bias).clamp(0.0, 1.0)
+    }
+}
+
+// Preparation for parallax occlusion mapping shaders
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParallaxSettings {
+    // Store depth (white is deepest) instead of height
+    pub invert: bool,
+    // Input level that should land on the shader's reference plane at 0.5,
+    // everything above and below is stretched to keep the full range
+    pub mid_level: f32,
+    // Pixels along the texture border faded towards the reference plane so
+    // non-tileable sources don't step at the seams
+    pub edge_padding: u32,
+}
+
+impl Default for ParallaxSettings {
+    fn default() -> Self {
+        Self {
+            invert: false,
+            mid_level: 0.5,
+            edge_padding: 0,
+        }
+    }
+}
+
+impl ParallaxSettings {
+    pub fn apply(&self, height: f32) -> f32 {
+        let mid = self.mid_level.clamp(0.001, 0.999);
+        let centered = if height <= mid {
+            height / mid * 0.5
+        } else {
+            0.5 + (height - mid) / (1.0 - mid) * 0.5
+        };
+        if self.invert { 1.0 - centered } else { centered }
+    }
+}
+
+pub fn height_lut(settings: &HeightAlphaSettings, parallax: &ParallaxSettings) -> [u8; 256] {
+    std::array::from_fn(|i| {
+        let height = parallax.apply(settings.apply(i as f32 / 255.0));
+        (height.clamp(0.0, 1.0) * 255.0).round() as u8
+    })
+}
+
+// Fades alpha towards mid gray over the outermost pixels with a smoothstep
+pub fn pad_edges(image: &mut RgbaImage, padding: u32) {
+    if padding == 0 {
+        return;
+    }
+    let width = image.width();
+    let height = image.height();
+    image.enumerate_pixels_mut().for_each(|(x, y, pixel)| {
+        let distance = x.min(y).min(width - 1 - x).min(height - 1 - y);
+        if distance < padding {
+            let t = distance as f32 / padding as f32;
+            let t = t * t * (3.0 - 2.0 * t);
+            pixel[3] = (128.0 + (pixel[3] as f32 - 128.0) * t).round() as u8;
+        }
+    });
+}
+
+// Fallback height guessed from albedo brightness for sets without a height
+// map. Crevices tend to be darker than exposed surfaces, so luminance is a
+// rough but useful stand-in for blending.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeightEstimateSettings {
+    pub enabled: bool,
+    pub blur_radius: u32,
+    // Levels applied after stretching luminance to the full range
+    pub black_level: f32,
+    pub white_level: f32,
+    pub invert: bool,
+}
+
+impl Default for HeightEstimateSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            blur_radius: 2,
+            black_level: 0.0,
+            white_level: 1.0,
+            invert: false,
+        }
+    }
+}
+
+pub fn estimate_height(albedo: &RgbaImage, settings: &HeightEstimateSettings) -> GrayImage {
+    let mut luminance = RgbaImage::from_fn(albedo.width(), albedo.height(), |x, y| {
+        let p = albedo.get_pixel(x, y);
+        let l = (0.2126 * p[0] as f32 + 0.7152 * p[1] as f32 + 0.0722 * p[2] as f32).round() as u8;
+        image::Rgba([l, l, l, 255])
+    });
+    colormap::box_blur_wrapped(&mut luminance, settings.blur_radius);
+
+    let (min, max) = luminance.pixels()
+        .fold((255u8, 0u8), |(min, max), p| (min.min(p[0]), max.max(p[0])));
+    let range = (max.saturating_sub(min)).max(1) as f32;
+    // Black stays below 1 so white has room above it
+    let black = settings.black_level.clamp(0.0, 0.999);
+    let white = settings.white_level.clamp(black + 0.001, 1.0);
+    GrayImage::from_fn(luminance.width(), luminance.height(), |x, y| {
+        let stretched = (luminance.get_pixel(x, y)[0] - min) as f32 / range;
+        let leveled = ((stretched - black) / (white - black)).clamp(0.0, 1.0);
+        let height = if settings.invert { 1.0 - leveled } else { leveled };
+        image::Luma([(height * 255.0).round() as u8])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_height_with_full_black_level() {
+        let albedo = RgbaImage::from_fn(4, 4, |x, _| image::Rgba([x as u8 * 60, x as u8 * 60, x as u8 * 60, 255]));
+        let settings = HeightEstimateSettings { enabled: true, blur_radius: 0, black_level: 1.0, ..Default::default() };
+        let height = estimate_height(&albedo, &settings);
+        assert_eq!(height.dimensions(), (4, 4));
+        assert_eq!(height.get_pixel(0, 0)[0], 0);
+        assert_eq!(height.get_pixel(3, 0)[0], 255);
+    }
+}
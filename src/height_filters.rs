@@ -0,0 +1,224 @@
+use image::GrayImage;
+use rayon::prelude::*;
+
+use crate::heightmap::HeightBuffer;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TerraceSettings {
+    pub steps: u32,
+    // Fraction of each step given to the sloped riser, 0 gives hard quantization
+    pub slope_blend: f32,
+}
+
+impl Default for TerraceSettings {
+    fn default() -> Self {
+        Self {
+            steps: 8,
+            slope_blend: 0.3,
+        }
+    }
+}
+
+pub fn terrace(heights: &mut HeightBuffer, settings: TerraceSettings) {
+    let steps = settings.steps.max(1) as f32;
+    let blend = settings.slope_blend.clamp(0.0, 1.0);
+    heights.par_iter_mut().for_each(|h| {
+        let t = *h * steps;
+        let step = t.floor();
+        let f = t - step;
+        // Flat tread for most of the step, then a smooth riser up to the next one
+        let riser = if blend > 0.0 {
+            let r = ((f - (1.0 - blend)) / blend).clamp(0.0, 1.0);
+            r * r * (3.0 - 2.0 * r)
+        } else {
+            0.0
+        };
+        *h = ((step + riser) / steps).clamp(0.0, 1.0);
+    });
+}
+
+fn sample_clamped(heights: &[f32], width: usize, height: usize, x: i64, y: i64) -> f32 {
+    let x = x.clamp(0, width as i64 - 1) as usize;
+    let y = y.clamp(0, height as i64 - 1) as usize;
+    heights[y * width + x]
+}
+
+// Separable Gaussian with sigma at half the radius
+pub fn gaussian_smooth(heights: &mut HeightBuffer, radius: u32) {
+    if radius == 0 {
+        return;
+    }
+    let width = heights.width() as usize;
+    let height = heights.height() as usize;
+    let r = radius as i64;
+    let sigma = (radius as f32 * 0.5).max(0.5);
+    let kernel: Vec<f32> = (-r..=r)
+        .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = kernel.iter().sum();
+    let kernel: Vec<f32> = kernel.into_iter().map(|k| k / total).collect();
+
+    for horizontal in [true, false] {
+        let source = heights.as_raw().clone();
+        heights.par_iter_mut().enumerate().for_each(|(i, h)| {
+            let x = (i % width) as i64;
+            let y = (i / width) as i64;
+            *h = kernel.iter().enumerate().map(|(k, weight)| {
+                let offset = k as i64 - r;
+                let (sx, sy) = if horizontal { (x + offset, y) } else { (x, y + offset) };
+                weight * sample_clamped(&source, width, height, sx, sy)
+            }).sum();
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DespeckleSettings {
+    pub radius: u32,
+    // Only pixels further than this from the local median are replaced,
+    // 0 applies a plain median filter
+    pub threshold: f32,
+}
+
+impl Default for DespeckleSettings {
+    fn default() -> Self {
+        Self {
+            radius: 1,
+            threshold: 0.0,
+        }
+    }
+}
+
+pub fn despeckle(heights: &mut HeightBuffer, settings: DespeckleSettings) {
+    if settings.radius == 0 {
+        return;
+    }
+    let width = heights.width() as usize;
+    let height = heights.height() as usize;
+    let r = settings.radius as i64;
+    let source = heights.as_raw().clone();
+
+    heights.par_iter_mut().enumerate().for_each_init(
+        || Vec::with_capacity(((2 * r + 1) * (2 * r + 1)) as usize),
+        |window, (i, h)| {
+            let x = (i % width) as i64;
+            let y = (i / width) as i64;
+            window.clear();
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    window.push(sample_clamped(&source, width, height, x + dx, y + dy));
+                }
+            }
+            let mid = window.len() / 2;
+            let median = *window.select_nth_unstable_by(mid, |a, b| a.total_cmp(b)).1;
+            if (*h - median).abs() > settings.threshold {
+                *h = median;
+            }
+        },
+    );
+}
+
+// Marks non-finite samples and anything below the nodata cutoff as holes,
+// zeroing them so later math stays finite
+pub fn detect_nodata(heights: &mut HeightBuffer, nodata_below: f32) -> Vec<bool> {
+    heights.par_iter_mut().map(|h| {
+        if !h.is_finite() || *h <= nodata_below {
+            *h = 0.0;
+            true
+        } else {
+            false
+        }
+    }).collect()
+}
+
+const RELAX_ITERATIONS: usize = 40;
+const COARSEST_ITERATIONS: usize = 400;
+
+// Diffusion fill: solve holes on a half resolution copy first, use that as the
+// starting guess, then relax hole pixels towards the average of their neighbors
+fn fill_level(values: &mut [f32], holes: &[bool], width: usize, height: usize) {
+    let hole_indices: Vec<usize> = (0..values.len()).filter(|i| holes[*i]).collect();
+    if hole_indices.is_empty() {
+        return;
+    }
+
+    let iterations = if width > 16 && height > 16 {
+        let cw = width.div_ceil(2);
+        let ch = height.div_ceil(2);
+        let mut coarse = vec![0.0f32; cw * ch];
+        let mut coarse_holes = vec![true; cw * ch];
+        for cy in 0..ch {
+            for cx in 0..cw {
+                let mut sum = 0.0;
+                let mut count = 0;
+                for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| (cx * 2 + dx, cy * 2 + dy)) {
+                    if x < width && y < height && !holes[y * width + x] {
+                        sum += values[y * width + x];
+                        count += 1;
+                    }
+                }
+                if count > 0 {
+                    coarse[cy * cw + cx] = sum / count as f32;
+                    coarse_holes[cy * cw + cx] = false;
+                }
+            }
+        }
+        fill_level(&mut coarse, &coarse_holes, cw, ch);
+        for &i in &hole_indices {
+            values[i] = coarse[(i / width / 2) * cw + (i % width) / 2];
+        }
+        RELAX_ITERATIONS
+    } else {
+        let valid: Vec<f32> = (0..values.len()).filter(|i| !holes[*i]).map(|i| values[i]).collect();
+        let mean = if valid.is_empty() { 0.0 } else { valid.iter().sum::<f32>() / valid.len() as f32 };
+        for &i in &hole_indices {
+            values[i] = mean;
+        }
+        COARSEST_ITERATIONS
+    };
+
+    for _ in 0..iterations {
+        let updated: Vec<f32> = hole_indices.par_iter().map(|&i| {
+            let x = (i % width) as i64;
+            let y = (i / width) as i64;
+            [(-1, 0), (1, 0), (0, -1), (0, 1)].iter()
+                .map(|(dx, dy)| sample_clamped(values, width, height, x + dx, y + dy))
+                .sum::<f32>() * 0.25
+        }).collect();
+        for (&i, v) in hole_indices.iter().zip(updated) {
+            values[i] = v;
+        }
+    }
+}
+
+pub fn fill_holes(heights: &mut HeightBuffer, holes: &[bool]) {
+    let width = heights.width() as usize;
+    let height = heights.height() as usize;
+    fill_level(heights, holes, width, height);
+}
+
+// White where the terrain sits at or below the water level
+pub fn water_mask(heights: &HeightBuffer, level: f32) -> GrayImage {
+    GrayImage::from_fn(heights.width(), heights.height(), |x, y| {
+        image::Luma([if heights.get_pixel(x, y)[0] <= level { 255 } else { 0 }])
+    })
+}
+
+// Raises everything below the level up to it, leaving a flat sea floor
+pub fn flatten_below(heights: &mut HeightBuffer, level: f32) {
+    heights.par_iter_mut().for_each(|h| *h = h.max(level));
+}
+
+// White where a contour line crosses, found by comparing each pixel's
+// elevation band with its right and lower neighbors
+pub fn contour_lines(heights: &HeightBuffer, height_scale: f32, height_offset: f32, interval: f32) -> GrayImage {
+    let width = heights.width();
+    let height = heights.height();
+    let interval = interval.max(f32::EPSILON);
+    let band = |x: u32, y: u32| ((heights.get_pixel(x, y)[0] * height_scale + height_offset) / interval).floor();
+    GrayImage::from_fn(width, height, |x, y| {
+        let b = band(x, y);
+        let crosses = (x + 1 < width && band(x + 1, y) != b) || (y + 1 < height && band(x, y + 1) != b);
+        image::Luma([if crosses { 255 } else { 0 }])
+    })
+}
@@ -0,0 +1,863 @@
+use egui::{Align2, CollapsingHeader, Color32, ColorImage, ComboBox, Context, FontId, Rect, Sense, Stroke, TextureHandle, Ui, Vec2, widgets::Image, load::SizedTexture};
+use image::{GrayImage, ImageBuffer, Luma, RgbaImage, imageops::FilterType};
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use crate::erosion::{self, ErosionSettings};
+use crate::false_color::GrayscalePalette;
+use crate::frames;
+use crate::paths;
+use crate::height_filters::{self, DespeckleSettings, TerraceSettings};
+use crate::regions::{self, ControlBuffer, RegionData, RegionLayout, REGION_SIZES};
+use crate::staging::StagedOutput;
+use crate::{ImageLoadState, ProcessingState};
+
+pub type HeightBuffer = ImageBuffer<Luma<f32>, Vec<f32>>;
+
+const PREVIEW_SIZE: u32 = 1024;
+
+pub fn load_heightmap(path: &Path) -> Result<HeightBuffer, String> {
+    let img = frames::open_single(&paths::long_path(path))?;
+    Ok(img.to_luma32f())
+}
+
+// Lambertian shading lit from the north west, heights are treated as
+// normalized so relief reads the same at any resolution
+pub fn hillshade(heights: &HeightBuffer) -> RgbaImage {
+    let width = heights.width();
+    let height = heights.height();
+    let relief = width.max(height) as f32 * 0.5;
+    let light = {
+        let l = [-1.0f32, -1.0, 1.0];
+        let len = (l[0] * l[0] + l[1] * l[1] + l[2] * l[2]).sqrt();
+        [l[0] / len, l[1] / len, l[2] / len]
+    };
+    let sample = |x: i64, y: i64| {
+        let x = x.clamp(0, width as i64 - 1) as u32;
+        let y = y.clamp(0, height as i64 - 1) as u32;
+        heights.get_pixel(x, y)[0]
+    };
+
+    let mut pixels = vec![0u8; (width * height) as usize];
+    pixels.par_iter_mut().enumerate().for_each(|(i, value)| {
+        let x = (i % width as usize) as i64;
+        let y = (i / width as usize) as i64;
+        let dx = (sample(x + 1, y) - sample(x - 1, y)) * 0.5 * relief;
+        let dy = (sample(x, y + 1) - sample(x, y - 1)) * 0.5 * relief;
+        let len = (dx * dx + dy * dy + 1.0).sqrt();
+        let shade = (-dx * light[0] - dy * light[1] + light[2]) / len;
+        *value = (shade.clamp(0.0, 1.0) * 255.0) as u8;
+    });
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let v = pixels[(y * width + x) as usize];
+        image::Rgba([v, v, v, 255])
+    })
+}
+
+// Rescales world unit heights into 0..1, returning the scale and offset that
+// restore the original values. Holes are ignored and end up at the bottom.
+pub fn normalize_heights(heights: &mut HeightBuffer, holes: Option<&[bool]>) -> (f32, f32) {
+    let is_hole = |i: usize| holes.is_some_and(|holes| holes[i]);
+    let (min, max) = heights.iter().enumerate()
+        .filter(|(i, _)| !is_hole(*i))
+        .fold((f32::MAX, f32::MIN), |(min, max), (_, h)| (min.min(*h), max.max(*h)));
+    if min > max {
+        return (1.0, 0.0);
+    }
+    let range = (max - min).max(f32::EPSILON);
+    heights.par_iter_mut().enumerate().for_each(|(i, h)| {
+        *h = if is_hole(i) { 0.0 } else { (*h - min) / range };
+    });
+    (range, min)
+}
+
+pub fn preview_heights(heights: &HeightBuffer) -> HeightBuffer {
+    let scale = (PREVIEW_SIZE as f32 / heights.width().max(heights.height()) as f32).min(1.0);
+    let width = ((heights.width() as f32 * scale) as u32).max(1);
+    let height = ((heights.height() as f32 * scale) as u32).max(1);
+    image::imageops::resize(heights, width, height, FilterType::Triangle)
+}
+
+// Downsampled heights kept next to their hillshade so overlays can be
+// recomputed without touching the full resolution data
+pub struct HeightPreview {
+    pub heights: HeightBuffer,
+    pub shaded: RgbaImage,
+}
+
+pub fn make_preview(heights: &HeightBuffer) -> HeightPreview {
+    let heights = preview_heights(heights);
+    let shaded = hillshade(&heights);
+    HeightPreview { heights, shaded }
+}
+
+// Hillshade tinted by elevation through the palette, plain shading for
+// grayscale
+fn relief_image(preview: &HeightPreview, palette: GrayscalePalette) -> ColorImage {
+    let shaded = &preview.shaded;
+    let size = [shaded.width() as _, shaded.height() as _];
+    if palette == GrayscalePalette::Grayscale {
+        return ColorImage::from_rgba_unmultiplied(size, shaded.as_raw());
+    }
+    let (low, high) = preview.heights.iter().fold((f32::MAX, f32::MIN), |(low, high), h| (low.min(*h), high.max(*h)));
+    let range = (high - low).max(f32::EPSILON);
+    let lut = palette.lut();
+    let pixels = preview.heights.pixels().zip(shaded.pixels())
+        .map(|(h, shade)| {
+            let value = ((h[0] - low) / range * 255.0).round() as u8;
+            let [r, g, b] = lut[value as usize];
+            let light = 0.4 + 0.6 * shade[0] as f32 / 255.0;
+            Color32::from_rgb((r as f32 * light) as u8, (g as f32 * light) as u8, (b as f32 * light) as u8)
+        })
+        .collect();
+    ColorImage { size, pixels }
+}
+
+// Sent by processing steps running on a worker thread
+pub enum HeightStepMessage {
+    Progress(f32, HeightPreview),
+    Done(HeightBuffer, HeightPreview),
+    Cancelled,
+}
+
+// Tints white mask pixels with the given color and leaves the rest transparent
+fn mask_texture(ctx: &Context, name: &str, mask: &GrayImage, color: [u8; 4]) -> TextureHandle {
+    let pixels: Vec<Color32> = mask.pixels()
+        .map(|p| {
+            if p[0] > 127 {
+                Color32::from_rgba_unmultiplied(color[0], color[1], color[2], color[3])
+            } else {
+                Color32::TRANSPARENT
+            }
+        })
+        .collect();
+    let color_image = ColorImage {
+        size: [mask.width() as _, mask.height() as _],
+        pixels,
+    };
+    ctx.load_texture(name, color_image, Default::default())
+}
+
+pub struct LoadedTerrain {
+    heights: HeightBuffer,
+    preview: HeightPreview,
+    control: Option<ControlBuffer>,
+    color: Option<RgbaImage>,
+    // Set when importing existing Terrain3D data
+    region_layout: Option<RegionLayout>,
+    height_scale: Option<(f32, f32)>,
+    holes: Option<Vec<bool>>,
+}
+
+pub struct HeightmapTool {
+    path: Option<PathBuf>,
+    load_state: ImageLoadState,
+    heights: Option<HeightBuffer>,
+    // Heights as loaded, before any processing steps
+    original_heights: Option<HeightBuffer>,
+    control: Option<ControlBuffer>,
+    color: Option<RgbaImage>,
+    load_receiver: Receiver<Result<LoadedTerrain, String>>,
+    load_sender: Sender<Result<LoadedTerrain, String>>,
+    preview_texture: Option<TextureHandle>,
+    palette: GrayscalePalette,
+    region_layout: RegionLayout,
+    height_scale: f32,
+    height_offset: f32,
+    export_state: ProcessingState,
+    export_receiver: Receiver<Result<usize, String>>,
+    export_sender: Sender<Result<usize, String>>,
+    exported_regions: usize,
+    erosion_settings: ErosionSettings,
+    terrace_settings: TerraceSettings,
+    smooth_radius: u32,
+    despeckle_settings: DespeckleSettings,
+    step_receiver: Receiver<HeightStepMessage>,
+    step_sender: Sender<HeightStepMessage>,
+    step_cancel: Arc<AtomicBool>,
+    step_progress: Option<f32>,
+    // Nodata pixels still waiting to be filled
+    holes: Option<Vec<bool>>,
+    original_holes: Option<Vec<bool>>,
+    hole_mask: Option<PathBuf>,
+    // Loaded when selected, so a mask that can't be read shows up right away
+    hole_mask_image: Option<GrayImage>,
+    hole_mask_state: ImageLoadState,
+    hole_mask_receiver: Receiver<(PathBuf, Result<GrayImage, String>)>,
+    hole_mask_sender: Sender<(PathBuf, Result<GrayImage, String>)>,
+    nodata_below: f32,
+    preview: Option<HeightPreview>,
+    water_enabled: bool,
+    // World units, like the height offset
+    sea_level: f32,
+    water_texture: Option<TextureHandle>,
+    water_texture_level: Option<f32>,
+    contours_enabled: bool,
+    // Meters between contour lines
+    contour_interval: f32,
+    export_contours: bool,
+    contour_texture: Option<TextureHandle>,
+    contour_texture_key: Option<(f32, f32, f32)>,
+}
+
+impl Default for HeightmapTool {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        let (etx, erx) = channel();
+        let (stx, srx) = channel();
+        let (mtx, mrx) = channel();
+        Self {
+            path: None,
+            load_state: ImageLoadState::NotLoaded,
+            heights: None,
+            original_heights: None,
+            control: None,
+            color: None,
+            load_receiver: rx,
+            load_sender: tx,
+            preview_texture: None,
+            palette: GrayscalePalette::load(),
+            region_layout: Default::default(),
+            height_scale: 512.0,
+            height_offset: 0.0,
+            export_state: ProcessingState::NotStarted,
+            export_receiver: erx,
+            export_sender: etx,
+            exported_regions: 0,
+            erosion_settings: Default::default(),
+            terrace_settings: Default::default(),
+            smooth_radius: 2,
+            despeckle_settings: Default::default(),
+            step_receiver: srx,
+            step_sender: stx,
+            step_cancel: Arc::new(AtomicBool::new(false)),
+            step_progress: None,
+            holes: None,
+            original_holes: None,
+            hole_mask: None,
+            hole_mask_image: None,
+            hole_mask_state: ImageLoadState::NotLoaded,
+            hole_mask_receiver: mrx,
+            hole_mask_sender: mtx,
+            nodata_below: -1000.0,
+            preview: None,
+            water_enabled: false,
+            sea_level: 0.0,
+            water_texture: None,
+            water_texture_level: None,
+            contours_enabled: false,
+            contour_interval: 50.0,
+            export_contours: false,
+            contour_texture: None,
+            contour_texture_key: None,
+        }
+    }
+}
+
+impl HeightmapTool {
+    fn load(&mut self, path: PathBuf) {
+        self.path = Some(path.clone());
+        self.load_state = ImageLoadState::Loading;
+        self.region_layout.excluded.clear();
+
+        let nodata_below = self.nodata_below;
+        let tx = self.load_sender.clone();
+        thread::spawn(move || {
+            let result = load_heightmap(&path).map(|mut heights| {
+                let holes = height_filters::detect_nodata(&mut heights, nodata_below);
+                let has_holes = holes.iter().any(|h| *h);
+
+                // Float DEMs arrive in world units, integer formats are already 0..1
+                let out_of_range = heights.iter().enumerate()
+                    .any(|(i, h)| !holes[i] && !(0.0..=1.0).contains(h));
+                let height_scale = out_of_range
+                    .then(|| normalize_heights(&mut heights, Some(&holes)));
+
+                let preview = make_preview(&heights);
+                LoadedTerrain {
+                    heights,
+                    preview,
+                    control: None,
+                    color: None,
+                    region_layout: None,
+                    height_scale,
+                    holes: has_holes.then_some(holes),
+                }
+            });
+            tx.send(result).ok();
+        });
+    }
+
+    fn import_terrain3d(&mut self, dir: PathBuf) {
+        self.path = Some(dir.clone());
+        self.load_state = ImageLoadState::Loading;
+
+        let tx = self.load_sender.clone();
+        thread::spawn(move || {
+            let result = regions::import_terrain3d_data(&dir).map(|imported| {
+                let mut heights = imported.heights;
+                let height_scale = normalize_heights(&mut heights, None);
+                let preview = make_preview(&heights);
+                LoadedTerrain {
+                    heights,
+                    preview,
+                    control: imported.control,
+                    color: imported.color,
+                    region_layout: Some(RegionLayout {
+                        region_size: imported.region_size,
+                        excluded: imported.missing,
+                        origin: Some(imported.origin),
+                    }),
+                    height_scale: Some(height_scale),
+                    holes: None,
+                }
+            });
+            tx.send(result).ok();
+        });
+    }
+
+    fn export_regions(&mut self, output_dir: PathBuf) {
+        let Some(heights) = self.heights.clone() else {
+            return;
+        };
+        let control = self.control.clone();
+        let color = self.color.clone();
+        let layout = self.region_layout.clone();
+        let height_scale = self.height_scale;
+        let height_offset = self.height_offset;
+        let sea_level = self.water_enabled.then(|| self.normalized_sea_level());
+        let contour_interval = self.export_contours.then_some(self.contour_interval);
+        let tx = self.export_sender.clone();
+
+        self.export_state = ProcessingState::Processing;
+
+        thread::spawn(move || {
+            let water = sea_level.map(|level| height_filters::water_mask(&heights, level));
+            let data = RegionData {
+                heights: &heights,
+                control: control.as_ref(),
+                color: color.as_ref(),
+                water: water.as_ref(),
+            };
+            let result = StagedOutput::new(&output_dir).and_then(|staged| {
+                let count = regions::export_regions(&data, &layout, height_scale, height_offset, staged.path())?;
+                // Contours cover the whole map so they line up with reference maps
+                if let Some(interval) = contour_interval {
+                    height_filters::contour_lines(&heights, height_scale, height_offset, interval)
+                        .save(staged.path().join("contours.png"))
+                        .map_err(|e| e.to_string())?;
+                }
+                staged.commit()?;
+                Ok(count)
+            });
+            tx.send(result).ok();
+        });
+    }
+
+    pub fn set_palette(&mut self, ctx: &Context, palette: GrayscalePalette) {
+        self.palette = palette;
+        if let Some(preview) = &self.preview {
+            let color_image = relief_image(preview, palette);
+            self.preview_texture = Some(ctx.load_texture("heightmap_hillshade", color_image, Default::default()));
+        }
+    }
+
+    fn set_preview(&mut self, ctx: &Context, preview: HeightPreview) {
+        let color_image = relief_image(&preview, self.palette);
+        self.preview_texture = Some(ctx.load_texture("heightmap_hillshade", color_image, Default::default()));
+        self.preview = Some(preview);
+        self.water_texture_level = None;
+        self.contour_texture_key = None;
+    }
+
+    // Sea level in normalized height units
+    fn normalized_sea_level(&self) -> f32 {
+        (self.sea_level - self.height_offset) / self.height_scale.max(f32::EPSILON)
+    }
+
+    fn update_water_overlay(&mut self, ctx: &Context) {
+        let level = self.normalized_sea_level();
+        if !self.water_enabled {
+            self.water_texture = None;
+            self.water_texture_level = None;
+            return;
+        }
+        if self.water_texture_level == Some(level) {
+            return;
+        }
+        let Some(preview) = &self.preview else {
+            return;
+        };
+        let mask = height_filters::water_mask(&preview.heights, level);
+        self.water_texture = Some(mask_texture(ctx, "heightmap_water", &mask, [40, 110, 220, 140]));
+        self.water_texture_level = Some(level);
+    }
+
+    fn update_contour_overlay(&mut self, ctx: &Context) {
+        let key = (self.height_scale, self.height_offset, self.contour_interval);
+        if !self.contours_enabled {
+            self.contour_texture = None;
+            self.contour_texture_key = None;
+            return;
+        }
+        if self.contour_texture_key == Some(key) {
+            return;
+        }
+        let Some(preview) = &self.preview else {
+            return;
+        };
+        let lines = height_filters::contour_lines(&preview.heights, key.0, key.1, key.2);
+        self.contour_texture = Some(mask_texture(ctx, "heightmap_contours", &lines, [255, 140, 0, 220]));
+        self.contour_texture_key = Some(key);
+    }
+
+    fn is_busy(&self) -> bool {
+        self.step_progress.is_some()
+            || matches!(self.load_state, ImageLoadState::Loading)
+            || matches!(self.export_state, ProcessingState::Processing)
+    }
+
+    // Runs a processing step on a copy of the current heights in the background
+    fn run_step<F>(&mut self, step: F)
+    where
+        F: FnOnce(HeightBuffer, Arc<AtomicBool>, Sender<HeightStepMessage>) + Send + 'static,
+    {
+        let Some(heights) = self.heights.clone() else {
+            return;
+        };
+        let tx = self.step_sender.clone();
+        self.step_cancel = Arc::new(AtomicBool::new(false));
+        let cancel = self.step_cancel.clone();
+
+        self.step_progress = Some(0.0);
+
+        thread::spawn(move || step(heights, cancel, tx));
+    }
+
+    fn start_erosion(&mut self) {
+        let settings = self.erosion_settings;
+        let height_scale = self.height_scale;
+        self.run_step(move |heights, cancel, tx| {
+            erosion::erode(heights, settings, height_scale, cancel, tx);
+        });
+    }
+
+    // Runs a filter that finishes in one pass without progress updates
+    fn run_filter<F>(&mut self, filter: F)
+    where
+        F: FnOnce(&mut HeightBuffer) + Send + 'static,
+    {
+        self.run_step(move |mut heights, _, tx| {
+            filter(&mut heights);
+            let preview = make_preview(&heights);
+            tx.send(HeightStepMessage::Done(heights, preview)).ok();
+        });
+    }
+
+    fn revert(&mut self, ctx: &Context) {
+        if let Some(original) = self.original_heights.clone() {
+            self.set_preview(ctx, make_preview(&original));
+            self.heights = Some(original);
+            self.holes = self.original_holes.clone();
+        }
+    }
+
+    fn load_hole_mask(&mut self, path: PathBuf) {
+        self.hole_mask = Some(path.clone());
+        self.hole_mask_image = None;
+        self.hole_mask_state = ImageLoadState::Loading;
+        let tx = self.hole_mask_sender.clone();
+        thread::spawn(move || {
+            let result = frames::open_single(&paths::long_path(&path)).map(|img| img.to_luma8());
+            tx.send((path, result)).ok();
+        });
+    }
+
+    fn start_hole_fill(&mut self) {
+        let holes = self.holes.take();
+        let mask = self.hole_mask_image.clone();
+        self.run_filter(move |heights| {
+            let mut holes = holes.unwrap_or_else(|| vec![false; heights.len()]);
+            // White pixels in the mask mark additional holes
+            if let Some(mask) = mask {
+                let mask = image::imageops::resize(&mask, heights.width(), heights.height(), FilterType::Nearest);
+                for (hole, m) in holes.iter_mut().zip(mask.pixels()) {
+                    *hole |= m[0] > 127;
+                }
+            }
+            height_filters::fill_holes(heights, &holes);
+        });
+    }
+
+    pub fn poll(&mut self, ctx: &Context) {
+        while let Ok((path, result)) = self.hole_mask_receiver.try_recv() {
+            // A mask replaced or cleared while loading is dropped
+            if self.hole_mask.as_ref() != Some(&path) {
+                continue;
+            }
+            match result {
+                Ok(mask) => {
+                    self.hole_mask_image = Some(mask);
+                    self.hole_mask_state = ImageLoadState::Loaded;
+                }
+                Err(e) => self.hole_mask_state = ImageLoadState::Error(format!("Failed to load the hole mask: {}", e)),
+            }
+            ctx.request_repaint();
+        }
+        if let Ok(result) = self.load_receiver.try_recv() {
+            match result {
+                Ok(loaded) => {
+                    self.set_preview(ctx, loaded.preview);
+                    self.original_heights = Some(loaded.heights.clone());
+                    self.heights = Some(loaded.heights);
+                    self.original_holes = loaded.holes.clone();
+                    self.holes = loaded.holes;
+                    self.control = loaded.control;
+                    self.color = loaded.color;
+                    if let Some(layout) = loaded.region_layout {
+                        self.region_layout = layout;
+                    } else {
+                        self.region_layout.origin = None;
+                    }
+                    if let Some((scale, offset)) = loaded.height_scale {
+                        self.height_scale = scale;
+                        self.height_offset = offset;
+                    }
+                    self.load_state = ImageLoadState::Loaded;
+                }
+                Err(e) => self.load_state = ImageLoadState::Error(e),
+            }
+            ctx.request_repaint();
+        }
+
+        if let Ok(result) = self.export_receiver.try_recv() {
+            self.export_state = match result {
+                Ok(count) => {
+                    self.exported_regions = count;
+                    ProcessingState::Done
+                }
+                Err(e) => ProcessingState::Error(e),
+            };
+            ctx.request_repaint();
+        }
+
+        while let Ok(message) = self.step_receiver.try_recv() {
+            match message {
+                HeightStepMessage::Progress(progress, preview) => {
+                    self.set_preview(ctx, preview);
+                    self.step_progress = Some(progress);
+                }
+                HeightStepMessage::Done(heights, preview) => {
+                    self.set_preview(ctx, preview);
+                    self.heights = Some(heights);
+                    self.step_progress = None;
+                }
+                HeightStepMessage::Cancelled => {
+                    self.step_progress = None;
+                    self.revert_preview(ctx);
+                }
+            }
+            ctx.request_repaint();
+        }
+    }
+
+    // Restores the preview of the current heights after a cancelled step
+    fn revert_preview(&mut self, ctx: &Context) {
+        if let Some(heights) = &self.heights {
+            let preview = make_preview(heights);
+            self.set_preview(ctx, preview);
+        }
+    }
+
+    fn show_processing(&mut self, ui: &mut Ui) {
+        CollapsingHeader::new("Height Processing")
+            .default_open(false)
+            .show(ui, |ui| {
+                let busy = self.is_busy();
+
+                CollapsingHeader::new("Hole Filling")
+                    .default_open(self.holes.is_some())
+                    .show(ui, |ui| {
+                        match &self.holes {
+                            Some(holes) => {
+                                let count = holes.iter().filter(|h| **h).count();
+                                ui.colored_label(Color32::YELLOW, format!("{} nodata pixels, fill them before other steps", count));
+                            }
+                            None => {
+                                ui.label("No nodata pixels detected");
+                            }
+                        }
+                        ui.add(egui::DragValue::new(&mut self.nodata_below).prefix("Nodata at or below: "))
+                            .on_hover_text("Applied when the heightmap is loaded, NaN is always nodata");
+                        ui.horizontal(|ui| {
+                            if ui.button("Select Hole Mask").clicked() {
+                                if let Some(path) = rfd::FileDialog::new()
+                                    .add_filter("Image files", &crate::TerrainApp::SUPPORTED_FORMATS)
+                                    .pick_file() {
+                                    self.load_hole_mask(path);
+                                }
+                            }
+                            if let Some(path) = &self.hole_mask {
+                                ui.label(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+                                if ui.button("Clear").clicked() {
+                                    self.hole_mask = None;
+                                    self.hole_mask_image = None;
+                                    self.hole_mask_state = ImageLoadState::NotLoaded;
+                                }
+                            }
+                            match &self.hole_mask_state {
+                                ImageLoadState::Loading => { ui.spinner(); }
+                                ImageLoadState::Error(e) => { ui.label(format!("Error: {}", e)); }
+                                _ => {}
+                            }
+                        });
+                        // A selected mask has to be loaded, filling without it would leave its holes
+                        let mask_ready = self.hole_mask.is_none() || self.hole_mask_image.is_some();
+                        let can_fill = mask_ready && (self.holes.is_some() || self.hole_mask_image.is_some());
+                        if ui.add_enabled(!busy && can_fill, egui::Button::new("Fill Holes")).clicked() {
+                            self.start_hole_fill();
+                        }
+                    });
+
+                CollapsingHeader::new("Erosion")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        let settings = &mut self.erosion_settings;
+                        ui.add_enabled_ui(!busy, |ui| {
+                            ui.add(egui::Slider::new(&mut settings.iterations, 1..=2000).text("Iterations"));
+                            ui.add(egui::Slider::new(&mut settings.rain_amount, 0.0..=2.0).text("Rain Amount"));
+                            ui.add(egui::Slider::new(&mut settings.talus_angle, 5.0..=90.0).text("Talus Angle").suffix("°"));
+                        });
+                        if ui.add_enabled(!busy, egui::Button::new("Run Erosion")).clicked() {
+                            self.start_erosion();
+                        }
+                    });
+
+                CollapsingHeader::new("Terracing")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        let settings = &mut self.terrace_settings;
+                        ui.add_enabled_ui(!busy, |ui| {
+                            ui.add(egui::Slider::new(&mut settings.steps, 1..=64).text("Terraces"));
+                            ui.add(egui::Slider::new(&mut settings.slope_blend, 0.0..=1.0).text("Slope Blend"));
+                        });
+                        if ui.add_enabled(!busy, egui::Button::new("Apply Terracing")).clicked() {
+                            let settings = self.terrace_settings;
+                            self.run_filter(move |heights| height_filters::terrace(heights, settings));
+                        }
+                    });
+
+                CollapsingHeader::new("Smoothing")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.add_enabled(!busy, egui::Slider::new(&mut self.smooth_radius, 1..=32).text("Radius"));
+                            if ui.add_enabled(!busy, egui::Button::new("Apply Gaussian")).clicked() {
+                                let radius = self.smooth_radius;
+                                self.run_filter(move |heights| height_filters::gaussian_smooth(heights, radius));
+                            }
+                        });
+
+                        // Threshold is edited in meters so it lines up with spike heights in-engine
+                        let mut threshold_m = self.despeckle_settings.threshold * self.height_scale;
+                        ui.add_enabled_ui(!busy, |ui| {
+                            ui.add(egui::Slider::new(&mut self.despeckle_settings.radius, 1..=5).text("Median Radius"));
+                            ui.add(egui::Slider::new(&mut threshold_m, 0.0..=100.0).text("Spike Threshold").suffix(" m"));
+                        });
+                        self.despeckle_settings.threshold = threshold_m / self.height_scale.max(f32::EPSILON);
+                        if ui.add_enabled(!busy, egui::Button::new("Apply Despeckle")).clicked() {
+                            let settings = self.despeckle_settings;
+                            self.run_filter(move |heights| height_filters::despeckle(heights, settings));
+                        }
+                    });
+
+                CollapsingHeader::new("Sea Level")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        ui.checkbox(&mut self.water_enabled, "Export Water Mask")
+                            .on_hover_text("Writes a mask per region, white where terrain is at or below sea level");
+                        ui.add(egui::DragValue::new(&mut self.sea_level).range(-10000.0..=10000.0).prefix("Sea Level: ").suffix(" m"));
+                        if ui.add_enabled(!busy, egui::Button::new("Flatten Below Sea Level")).clicked() {
+                            let level = self.normalized_sea_level();
+                            self.run_filter(move |heights| height_filters::flatten_below(heights, level));
+                        }
+                    });
+
+                if let Some(progress) = self.step_progress {
+                    ui.horizontal(|ui| {
+                        if ui.button("Cancel").clicked() {
+                            self.step_cancel.store(true, Ordering::Relaxed);
+                        }
+                        ui.add(egui::ProgressBar::new(progress).show_percentage());
+                    });
+                }
+
+                ui.add_space(4.0);
+                if ui.add_enabled(!busy && self.original_heights.is_some(), egui::Button::new("Revert to Loaded Heights")).clicked() {
+                    self.revert(ui.ctx());
+                }
+            });
+    }
+
+    fn show_region_overlay(&mut self, ui: &mut Ui, texture: &TextureHandle) {
+        self.update_water_overlay(ui.ctx());
+        self.update_contour_overlay(ui.ctx());
+        let Some(heights) = &self.heights else {
+            return;
+        };
+        let (width, height) = heights.dimensions();
+        let size = texture.size_vec2();
+        let display_size = size * (ui.available_width() / size.x);
+        let response = ui.add(
+            Image::from_texture(SizedTexture::from_handle(texture))
+                .fit_to_exact_size(display_size)
+                .sense(Sense::click()),
+        );
+        let rect = response.rect;
+        let scale = rect.width() / width as f32;
+        let region_px = self.region_layout.region_size as f32 * scale;
+        let (cols, rows) = self.region_layout.grid(width, height);
+        let painter = ui.painter_at(rect);
+        if let Some(water) = &self.water_texture {
+            let uv = Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+            painter.image(water.id(), rect, uv, Color32::WHITE);
+        }
+        if let Some(contours) = &self.contour_texture {
+            let uv = Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+            painter.image(contours.id(), rect, uv, Color32::WHITE);
+        }
+
+        for row in 0..rows {
+            for col in 0..cols {
+                let min = rect.min + Vec2::new(col as f32 * region_px, row as f32 * region_px);
+                let cell = Rect::from_min_size(min, Vec2::splat(region_px)).intersect(rect);
+                let location = self.region_layout.location(col, row, cols, rows);
+                if self.region_layout.is_excluded(location) {
+                    painter.rect_filled(cell, 0.0, Color32::from_rgba_unmultiplied(200, 30, 30, 110));
+                }
+                painter.rect_stroke(cell, 0.0, Stroke::new(1.0, Color32::YELLOW));
+                if region_px >= 28.0 {
+                    painter.text(
+                        cell.center(),
+                        Align2::CENTER_CENTER,
+                        format!("{},{}", location.0, location.1),
+                        FontId::proportional(10.0),
+                        Color32::WHITE,
+                    );
+                }
+            }
+        }
+
+        if response.clicked() {
+            if let Some(pos) = response.interact_pointer_pos() {
+                let local = pos - rect.min;
+                let col = (local.x / region_px) as u32;
+                let row = (local.y / region_px) as u32;
+                if col < cols && row < rows {
+                    let location = self.region_layout.location(col, row, cols, rows);
+                    self.region_layout.toggle(location);
+                }
+            }
+        }
+
+        let included = (cols * rows).saturating_sub(self.region_layout.excluded.len() as u32);
+        ui.label(format!(
+            "{}x{} px, {}x{} regions ({} included). Click a region to exclude it from export.",
+            width, height, cols, rows, included
+        ));
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, output_directory: Option<&PathBuf>) {
+        CollapsingHeader::new("Terrain Heightmap")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Select Terrain Heightmap").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Image files", &crate::TerrainApp::SUPPORTED_FORMATS)
+                            .pick_file() {
+                            self.load(path);
+                        }
+                    }
+                    if ui.button("Import Terrain3D Data").clicked() {
+                        if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                            self.import_terrain3d(dir);
+                        }
+                    }
+                    if let Some(path) = &self.path {
+                        ui.label(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+                    }
+                    match &self.load_state {
+                        ImageLoadState::Loading => { ui.spinner(); }
+                        ImageLoadState::Error(e) => { ui.label(format!("Error: {}", e)); }
+                        _ => {}
+                    }
+                });
+
+                let previous_size = self.region_layout.region_size;
+                ComboBox::from_label("Region Size")
+                    .selected_text(self.region_layout.region_size.to_string())
+                    .show_ui(ui, |ui| {
+                        for size in REGION_SIZES {
+                            ui.selectable_value(&mut self.region_layout.region_size, size, size.to_string());
+                        }
+                    });
+                if previous_size != self.region_layout.region_size {
+                    self.region_layout.excluded.clear();
+                    self.region_layout.origin = None;
+                }
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut self.height_scale).range(0.0..=10000.0).prefix("Height Scale: ").suffix(" m"));
+                    ui.add(egui::DragValue::new(&mut self.height_offset).range(-10000.0..=10000.0).prefix("Offset: ").suffix(" m"));
+                });
+                if self.control.is_some() || self.color.is_some() {
+                    ui.label("Control and color maps from the imported data are re-exported per region.");
+                }
+
+                if self.heights.is_some() {
+                    self.show_processing(ui);
+                }
+
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.contours_enabled, "Show Contours");
+                    ui.add(egui::DragValue::new(&mut self.contour_interval).range(1.0..=5000.0).prefix("Interval: ").suffix(" m"));
+                    ui.checkbox(&mut self.export_contours, "Export Contour Overlay")
+                        .on_hover_text("Writes contours.png at full resolution with the regions");
+                });
+
+                if let Some(texture) = self.preview_texture.clone() {
+                    self.show_region_overlay(ui, &texture);
+                }
+
+                let can_export = self.heights.is_some()
+                    && output_directory.is_some()
+                    && !self.is_busy();
+                let export_button = ui.add_enabled_ui(can_export, |ui| {
+                    ui.button("Export Regions")
+                }).inner;
+                if export_button.clicked() {
+                    if let Some(dir) = output_directory {
+                        self.export_regions(dir.clone());
+                    }
+                }
+
+                match &self.export_state {
+                    ProcessingState::Processing => {
+                        ui.spinner();
+                    }
+                    ProcessingState::Done => {
+                        ui.label(format!("Exported {} regions", self.exported_regions));
+                    }
+                    ProcessingState::Error(e) => {
+                        ui.label(format!("Error: {}", e));
+                    }
+                    _ => {}
+                }
+            });
+    }
+}
@@ -0,0 +1,205 @@
+use egui::{CollapsingHeader, Color32, ComboBox, Ui};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::manifest::ExportManifest;
+use crate::paths;
+
+pub const HISTORY_FILE: &str = "export_history.json";
+// Oldest snapshots are dropped beyond this, they're only a few KB each
+const MAX_SNAPSHOTS: usize = 200;
+
+// What one export was made from and what it wrote, kept per output directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    // Seconds since the Unix epoch
+    pub time: u64,
+    pub tool_version: String,
+    pub settings: Value,
+    pub inputs: BTreeMap<String, PathBuf>,
+    // Content hash of every file the export left, as in the manifest
+    pub files: BTreeMap<String, String>,
+}
+
+impl Snapshot {
+    pub fn new(settings: &str, inputs: BTreeMap<String, PathBuf>) -> Self {
+        Self {
+            time: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default(),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            settings: serde_json::from_str(settings).unwrap_or(Value::Null),
+            inputs,
+            files: BTreeMap::new(),
+        }
+    }
+}
+
+pub fn load(output_dir: &Path) -> Vec<Snapshot> {
+    fs::read_to_string(paths::long_path(&output_dir.join(HISTORY_FILE))).ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+// Appends a snapshot once an export is in place, taking the file hashes from
+// the manifest it wrote
+pub fn record(output_dir: &Path, manifest: &str, mut snapshot: Snapshot) -> Result<(), String> {
+    if let Some(manifest) = ExportManifest::load(output_dir, manifest) {
+        snapshot.files = manifest.files;
+    }
+    let mut history = load(output_dir);
+    history.push(snapshot);
+    let excess = history.len().saturating_sub(MAX_SNAPSHOTS);
+    history.drain(..excess);
+    let json = serde_json::to_string_pretty(&history).map_err(|e| e.to_string())?;
+    fs::write(paths::long_path(&output_dir.join(HISTORY_FILE)), json).map_err(|e| format!("Failed to write export history: {}", e))
+}
+
+// Every leaf value under its dotted path, e.g. roughness_adjust.output_min
+fn flatten(value: &Value, prefix: &str, out: &mut BTreeMap<String, String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(value, &path, out);
+            }
+        }
+        other => {
+            out.insert(prefix.to_string(), other.to_string());
+        }
+    }
+}
+
+fn changed<'a>(old: &'a BTreeMap<String, String>, new: &'a BTreeMap<String, String>) -> Vec<(&'a str, Option<&'a str>, Option<&'a str>)> {
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    keys.into_iter()
+        .map(|key| (key.as_str(), old.get(key).map(String::as_str), new.get(key).map(String::as_str)))
+        .filter(|(_, old, new)| old != new)
+        .collect()
+}
+
+// What changed from one export to the next, settings first, then sources,
+// then the files written
+pub fn diff(old: &Snapshot, new: &Snapshot) -> Vec<String> {
+    let mut lines = Vec::new();
+    let (mut old_settings, mut new_settings) = (BTreeMap::new(), BTreeMap::new());
+    flatten(&old.settings, "", &mut old_settings);
+    flatten(&new.settings, "", &mut new_settings);
+    for (key, old, new) in changed(&old_settings, &new_settings) {
+        lines.push(format!("Setting {}: {} -> {}", key, old.unwrap_or("unset"), new.unwrap_or("unset")));
+    }
+
+    let text = |inputs: &BTreeMap<String, PathBuf>| -> BTreeMap<String, String> {
+        inputs.iter().map(|(slot, path)| (slot.clone(), path.display().to_string())).collect()
+    };
+    let (old_inputs, new_inputs) = (text(&old.inputs), text(&new.inputs));
+    for (slot, old, new) in changed(&old_inputs, &new_inputs) {
+        lines.push(match (old, new) {
+            (Some(old), Some(new)) => format!("{} map: {} -> {}", slot, old, new),
+            (None, Some(new)) => format!("{} map added: {}", slot, new),
+            _ => format!("{} map removed", slot),
+        });
+    }
+    if old.tool_version != new.tool_version {
+        lines.push(format!("Tool version: {} -> {}", old.tool_version, new.tool_version));
+    }
+
+    for (file, old, new) in changed(&old.files, &new.files) {
+        lines.push(match (old, new) {
+            (Some(_), Some(_)) => format!("{} changed", file),
+            (None, Some(_)) => format!("{} added", file),
+            _ => format!("{} removed", file),
+        });
+    }
+    lines
+}
+
+// UTC date and time, e.g. 2024-05-01 13:45
+pub fn format_time(time: u64) -> String {
+    let days = (time / 86400) as i64;
+    let minutes = time % 86400 / 60;
+    // Days to civil date, from Howard Hinnant's date algorithms
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, minutes / 60, minutes % 60)
+}
+
+// Lists the exports recorded in the output directory and what changed
+// between any two of them
+#[derive(Default)]
+pub struct ExportHistory {
+    loaded_from: Option<(PathBuf, Option<SystemTime>)>,
+    snapshots: Vec<Snapshot>,
+    // Indices of the compared versions, None for the latest and the one before
+    selected: Option<(usize, usize)>,
+}
+
+impl ExportHistory {
+    // Reloads when the directory changes or another export was recorded
+    fn refresh(&mut self, output_dir: &Path) {
+        let modified = fs::metadata(paths::long_path(&output_dir.join(HISTORY_FILE))).and_then(|m| m.modified()).ok();
+        let key = (output_dir.to_path_buf(), modified);
+        if self.loaded_from.as_ref() == Some(&key) {
+            return;
+        }
+        self.snapshots = load(output_dir);
+        self.selected = None;
+        self.loaded_from = Some(key);
+    }
+
+    fn version_label(&self, index: usize) -> String {
+        let snapshot = &self.snapshots[index];
+        format!("Version {} ({})", index + 1, format_time(snapshot.time))
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, output_directory: Option<&PathBuf>) {
+        CollapsingHeader::new("Export History")
+            .default_open(false)
+            .show(ui, |ui| {
+                let Some(output_dir) = output_directory else {
+                    ui.label("Select an output directory to see its exports");
+                    return;
+                };
+                self.refresh(output_dir);
+                if self.snapshots.len() < 2 {
+                    ui.label(format!("{} exports recorded, changes show from the second one", self.snapshots.len()));
+                    return;
+                }
+
+                let latest = self.snapshots.len() - 1;
+                let before = self.selected.unwrap_or((latest - 1, latest));
+                let (mut old, mut new) = before;
+                ui.horizontal(|ui| {
+                    for (label, index) in [("Compare", &mut old), ("With", &mut new)] {
+                        ComboBox::from_label(label)
+                            .selected_text(self.version_label(*index))
+                            .show_ui(ui, |ui| {
+                                for i in (0..self.snapshots.len()).rev() {
+                                    ui.selectable_value(index, i, self.version_label(i));
+                                }
+                            });
+                    }
+                });
+                if (old, new) != before {
+                    self.selected = Some((old, new));
+                }
+
+                let lines = diff(&self.snapshots[old], &self.snapshots[new]);
+                if lines.is_empty() {
+                    ui.colored_label(Color32::LIGHT_GREEN, "No differences");
+                }
+                for line in lines {
+                    ui.label(line);
+                }
+            });
+    }
+}
@@ -0,0 +1,259 @@
+use egui::{CollapsingHeader, Color32, Context, Ui};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::project::Project;
+use crate::map_names::{map_files, set_project};
+use crate::paths;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+// How many checks in a row a folder has to stay the same before it's packed,
+// so a set still being written isn't picked up halfway
+const SETTLE_CHECKS: u32 = 2;
+
+// File names, sizes and modification times of a folder's maps
+type Signature = Vec<(String, u64, Option<SystemTime>)>;
+
+fn signature(files: &[PathBuf]) -> Signature {
+    files.iter()
+        .map(|path| {
+            let metadata = fs::metadata(paths::long_path(path)).ok();
+            (
+                path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                metadata.as_ref().map_or(0, |m| m.len()),
+                metadata.and_then(|m| m.modified().ok()),
+            )
+        })
+        .collect()
+}
+
+// Whether path is dir or inside it. Both are resolved as far as they exist,
+// so links and relative parts compare equal.
+fn is_within(path: &Path, dir: &Path) -> bool {
+    let resolve = |path: &Path| path.ancestors()
+        .find_map(|ancestor| {
+            let resolved = fs::canonicalize(paths::long_path(ancestor)).ok()?;
+            Some(resolved.join(path.strip_prefix(ancestor).ok()?))
+        })
+        .unwrap_or_else(|| path.to_path_buf());
+    resolve(path).starts_with(resolve(dir))
+}
+
+struct WatchedFolder {
+    signature: Signature,
+    unchanged_checks: u32,
+    // Signature of the last export, so a set is packed again once it changes
+    exported: Option<Signature>,
+    result: Option<Result<usize, String>>,
+}
+
+// Packs every set folder dropped into a watched directory into an output
+// root, and packs it again whenever its maps change
+pub struct HotFolder {
+    watch_directory: Option<PathBuf>,
+    output_root: Option<PathBuf>,
+    // Snapshot of the editor's settings taken when watching starts
+    settings: Option<Project>,
+    folders: HashMap<PathBuf, WatchedFolder>,
+    running: Option<PathBuf>,
+    // Why watching didn't start
+    error: Option<String>,
+    last_check: Instant,
+    receiver: Receiver<(PathBuf, Signature, Result<Vec<PathBuf>, String>)>,
+    sender: Sender<(PathBuf, Signature, Result<Vec<PathBuf>, String>)>,
+}
+
+impl Default for HotFolder {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            watch_directory: None,
+            output_root: None,
+            settings: None,
+            folders: HashMap::new(),
+            running: None,
+            error: None,
+            last_check: Instant::now(),
+            receiver: rx,
+            sender: tx,
+        }
+    }
+}
+
+impl HotFolder {
+    pub fn start(&mut self, mut settings: Project) {
+        settings.overwrite_mode = settings.overwrite_mode.unattended();
+        // Every export would show up as a new set and be packed in turn
+        if let (Some(watch_directory), Some(output_root)) = (&self.watch_directory, &self.output_root) {
+            if is_within(output_root, watch_directory) {
+                self.error = Some("The output root can't be the watch folder or inside it".to_string());
+                return;
+            }
+        }
+        self.error = None;
+        self.settings = Some(settings);
+        self.folders.clear();
+    }
+
+    fn stop(&mut self) {
+        self.settings = None;
+    }
+
+    fn scan(&mut self) {
+        let Some(watch_directory) = &self.watch_directory else {
+            return;
+        };
+        let Ok(entries) = fs::read_dir(paths::long_path(watch_directory)) else {
+            return;
+        };
+        for entry in entries.flatten().filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir())) {
+            let dir = watch_directory.join(entry.file_name());
+            let current = signature(&map_files(&dir));
+            let folder = self.folders.entry(dir).or_insert_with(|| WatchedFolder {
+                signature: Vec::new(),
+                unchanged_checks: 0,
+                exported: None,
+                result: None,
+            });
+            if current == folder.signature {
+                folder.unchanged_checks += 1;
+            } else {
+                folder.signature = current;
+                folder.unchanged_checks = 0;
+            }
+        }
+    }
+
+    // One set at a time, each export already uses every core
+    fn export_next(&mut self) {
+        let (Some(settings), Some(output_root), Some(watch_directory)) = (&self.settings, &self.output_root, &self.watch_directory) else {
+            return;
+        };
+        if self.running.is_some() {
+            return;
+        }
+        let ready = self.folders.iter()
+            .filter(|(_, folder)| {
+                !folder.signature.is_empty()
+                    && folder.unchanged_checks >= SETTLE_CHECKS
+                    && folder.exported.as_ref() != Some(&folder.signature)
+            })
+            .map(|(dir, _)| dir.clone())
+            .min();
+        let Some(dir) = ready else {
+            return;
+        };
+        let folder = &self.folders[&dir];
+        let signature = folder.signature.clone();
+        let output = output_root.join(dir.file_name().unwrap_or_default());
+        let project = set_project(settings, &map_files(&dir), output);
+        // With the watch folder inside the output root, a set named like it
+        // would still be packed into it
+        let inside = project.as_ref().ok()
+            .and_then(Project::export_directory)
+            .filter(|output| is_within(output, watch_directory));
+        if let Some(output) = inside {
+            let folder = self.folders.get_mut(&dir).unwrap();
+            folder.exported = Some(signature);
+            folder.result = Some(Err(format!("Not packed, {} is inside the watch folder", output.display())));
+            return;
+        }
+        let tx = self.sender.clone();
+        self.running = Some(dir.clone());
+        thread::spawn(move || {
+            tx.send((dir, signature, project.and_then(|project| crate::TerrainApp::run_headless(project, false).into_result()))).ok();
+        });
+    }
+
+    pub fn poll(&mut self, ctx: &Context) {
+        while let Ok((dir, signature, result)) = self.receiver.try_recv() {
+            self.running = None;
+            if let Some(folder) = self.folders.get_mut(&dir) {
+                // Failed sets are retried once their maps change
+                folder.exported = Some(signature);
+                folder.result = Some(result.map(|files| files.len()));
+            }
+            ctx.request_repaint();
+        }
+        if self.settings.is_none() {
+            return;
+        }
+        ctx.request_repaint_after(CHECK_INTERVAL);
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return;
+        }
+        self.last_check = Instant::now();
+        self.scan();
+        self.export_next();
+    }
+
+    // True when watching should start with the editor's current settings
+    pub fn show(&mut self, ui: &mut Ui) -> bool {
+        let mut start = false;
+        CollapsingHeader::new("Hot Folder")
+            .default_open(false)
+            .show(ui, |ui| {
+                let watching = self.settings.is_some();
+                for (label, folder) in [
+                    ("Select Watch Folder", &mut self.watch_directory),
+                    ("Select Output Root", &mut self.output_root),
+                ] {
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(!watching, egui::Button::new(label)).clicked() {
+                            if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                                *folder = Some(path);
+                            }
+                        }
+                        if let Some(path) = folder {
+                            ui.label(path.to_string_lossy().to_string());
+                        }
+                    });
+                }
+
+                if watching {
+                    if ui.button("Stop Watching").clicked() {
+                        self.stop();
+                    }
+                } else {
+                    let ready = self.watch_directory.is_some() && self.output_root.is_some();
+                    start = ui.add_enabled(ready, egui::Button::new("Start Watching"))
+                        .on_hover_text("Every set folder in it is packed with the current settings, and packed \
+                            again when its maps change. Maps are assigned by their file names.")
+                        .clicked();
+                }
+                if let Some(e) = &self.error {
+                    ui.colored_label(Color32::LIGHT_RED, e);
+                }
+
+                let mut folders: Vec<_> = self.folders.iter().collect();
+                folders.sort_by_key(|(dir, _)| *dir);
+                for (dir, folder) in folders {
+                    let name = dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+                    if self.running.as_ref() == Some(dir) {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(format!("{}: packing", name));
+                        });
+                        continue;
+                    }
+                    match &folder.result {
+                        Some(Ok(files)) => {
+                            ui.colored_label(Color32::LIGHT_GREEN, format!("{}: {} files", name, files));
+                        }
+                        Some(Err(e)) => {
+                            ui.colored_label(Color32::LIGHT_RED, format!("{}: {}", name, e));
+                        }
+                        None if folder.signature.is_empty() => {}
+                        None => {
+                            ui.label(format!("{}: waiting for files to settle", name));
+                        }
+                    }
+                }
+            });
+        start
+    }
+}
@@ -0,0 +1,311 @@
+use egui::{CollapsingHeader, Color32, Context, TextureFilter, TextureOptions, Ui};
+use image::{imageops::FilterType, DynamicImage, RgbaImage};
+use image_dds::ddsfile::Dds;
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use crate::frames;
+use crate::paths;
+use crate::tiled_preview::TiledPreview;
+use crate::provenance::{self, Provenance};
+use crate::ImageLoadState;
+
+const CHANNEL_NAMES: [&str; 4] = ["R", "G", "B", "A"];
+const PREVIEW_SIZE: u32 = 512;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelStats {
+    pub min: u8,
+    pub max: u8,
+    pub mean: f32,
+}
+
+pub struct InspectedTexture {
+    pub format: String,
+    // Stored levels for DDS, for other formats a chain generated the way an
+    // engine would on import
+    pub mips: Vec<RgbaImage>,
+    pub mips_generated: bool,
+    pub stats: Vec<[ChannelStats; 4]>,
+    pub findings: Vec<String>,
+    // Embedded by this tool when it wrote the texture
+    pub provenance: Option<Provenance>,
+}
+
+pub fn channel_stats(image: &RgbaImage) -> [ChannelStats; 4] {
+    let (min, max, sum) = image.as_raw().par_chunks(4)
+        .fold(
+            || ([255u8; 4], [0u8; 4], [0u64; 4]),
+            |(mut min, mut max, mut sum), p| {
+                for c in 0..4 {
+                    min[c] = min[c].min(p[c]);
+                    max[c] = max[c].max(p[c]);
+                    sum[c] += p[c] as u64;
+                }
+                (min, max, sum)
+            },
+        )
+        .reduce(
+            || ([255u8; 4], [0u8; 4], [0u64; 4]),
+            |a, b| (
+                std::array::from_fn(|c| a.0[c].min(b.0[c])),
+                std::array::from_fn(|c| a.1[c].max(b.1[c])),
+                std::array::from_fn(|c| a.2[c] + b.2[c]),
+            ),
+        );
+    let count = (image.width() as u64 * image.height() as u64).max(1) as f32;
+    std::array::from_fn(|c| ChannelStats {
+        min: min[c],
+        max: max[c],
+        mean: sum[c] as f32 / count,
+    })
+}
+
+// Heuristics for packed Terrain3D textures, guessed from the file name
+fn find_problems(name: &str, image: &RgbaImage, stats: &[ChannelStats; 4]) -> Vec<String> {
+    let name = name.to_lowercase();
+    let is_normal = name.contains("normal") || name.contains("nrm");
+    let mut findings = Vec::new();
+    for (c, stat) in stats.iter().enumerate() {
+        if stat.min == stat.max {
+            let hint = match (c, is_normal) {
+                (3, false) => ", no height packed in alpha",
+                (3, true) => ", no roughness packed in alpha",
+                (2, true) if stat.max == 0 => ", two-channel normal map, the shader has to rebuild Z",
+                _ => "",
+            };
+            findings.push(format!("{} is constant at {}{}", CHANNEL_NAMES[c], stat.max, hint));
+        }
+    }
+    if is_normal && stats[2].max > 0 {
+        // Unit vectors, allowing for 8-bit quantization and filtering
+        let mean_length = image.as_raw().par_chunks(4)
+            .map(|p| {
+                let v = [0, 1, 2].map(|c| p[c] as f32 / 127.5 - 1.0);
+                (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+            })
+            .sum::<f32>() / (image.width() * image.height()).max(1) as f32;
+        if (mean_length - 1.0).abs() > 0.1 {
+            findings.push(format!("Average normal length is {:.2}, expected 1.0", mean_length));
+        }
+    }
+    findings
+}
+
+fn generate_mips(image: RgbaImage) -> Vec<RgbaImage> {
+    let mut mips = vec![image];
+    while let Some(last) = mips.last().filter(|m| m.width() > 1 || m.height() > 1) {
+        let next = DynamicImage::ImageRgba8(last.clone())
+            .resize_exact((last.width() / 2).max(1), (last.height() / 2).max(1), FilterType::Triangle)
+            .to_rgba8();
+        mips.push(next);
+    }
+    mips
+}
+
+// Alpha carries height or roughness, so a mean that wanders across the mip
+// chain shows up as blending or shininess changing with distance
+fn find_mip_drift(stats: &[[ChannelStats; 4]]) -> Option<String> {
+    let base = stats.first()?[3].mean;
+    let (level, drifted) = stats.iter().enumerate()
+        .map(|(level, s)| (level, s[3].mean))
+        .max_by(|a, b| (a.1 - base).abs().total_cmp(&(b.1 - base).abs()))?;
+    ((drifted - base).abs() > 12.0).then(|| {
+        format!("Alpha mean drifts from {:.1} at mip 0 to {:.1} at mip {}", base, drifted, level)
+    })
+}
+
+fn load(path: &Path) -> Result<InspectedTexture, String> {
+    let long_path = paths::long_path(path);
+    let is_dds = path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("dds"));
+    let (format, mips, mips_generated) = if is_dds {
+        let file = File::open(&long_path).map_err(|e| e.to_string())?;
+        let dds = Dds::read(BufReader::new(file)).map_err(|e| e.to_string())?;
+        let format = dds.get_dxgi_format().map(|f| format!("{:?}", f))
+            .or_else(|| dds.get_d3d_format().map(|f| format!("{:?}", f)))
+            .unwrap_or_else(|| "unknown".to_string());
+        let mips = (0..dds.get_num_mipmap_levels().max(1))
+            .map(|level| image_dds::image_from_dds(&dds, level).map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        (format, mips, false)
+    } else {
+        let image = frames::open_single(&long_path)?;
+        (format!("{:?}", image.color()), generate_mips(image.to_rgba8()), true)
+    };
+    let stats: Vec<_> = mips.iter().map(channel_stats).collect();
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let mut findings = find_problems(&name, &mips[0], &stats[0]);
+    findings.extend(find_mip_drift(&stats));
+    Ok(InspectedTexture {
+        format,
+        mips,
+        mips_generated,
+        findings,
+        stats,
+        provenance: provenance::read(path),
+    })
+}
+
+// Which part of the texture the preview shows
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ChannelView {
+    Color,
+    Channel(usize),
+}
+
+// Decodes an existing packed texture to check it without setting up a job
+pub struct TextureInspector {
+    path: Option<PathBuf>,
+    load_state: ImageLoadState,
+    texture: Option<InspectedTexture>,
+    view: ChannelView,
+    mip: usize,
+    preview: Option<TiledPreview>,
+    preview_key: Option<(ChannelView, usize)>,
+    receiver: Receiver<Result<InspectedTexture, String>>,
+    sender: Sender<Result<InspectedTexture, String>>,
+}
+
+impl Default for TextureInspector {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            path: None,
+            load_state: ImageLoadState::NotLoaded,
+            texture: None,
+            view: ChannelView::Color,
+            mip: 0,
+            preview: None,
+            preview_key: None,
+            receiver: rx,
+            sender: tx,
+        }
+    }
+}
+
+impl TextureInspector {
+    pub fn open(&mut self, path: PathBuf) {
+        let tx = self.sender.clone();
+        self.load_state = ImageLoadState::Loading;
+        self.texture = None;
+        self.mip = 0;
+        self.preview = None;
+        self.preview_key = None;
+        self.path = Some(path.clone());
+        thread::spawn(move || {
+            tx.send(load(&path)).ok();
+        });
+    }
+
+    pub fn poll(&mut self, ctx: &Context) {
+        if let Ok(result) = self.receiver.try_recv() {
+            match result {
+                Ok(texture) => {
+                    self.texture = Some(texture);
+                    self.load_state = ImageLoadState::Loaded;
+                }
+                Err(e) => self.load_state = ImageLoadState::Error(e),
+            }
+            ctx.request_repaint();
+        }
+    }
+
+    fn update_preview(&mut self, ctx: &Context) {
+        let Some(texture) = &self.texture else {
+            return;
+        };
+        if self.preview_key != Some((self.view, self.mip)) {
+            let image = texture.mips[self.mip].clone();
+            // Small mips are blown up with hard texel edges so each texel is
+            // visible, large ones are filtered down to the preview width
+            let options = if image.width() < PREVIEW_SIZE {
+                TextureOptions::NEAREST
+            } else {
+                TextureOptions { mipmap_mode: Some(TextureFilter::Linear), ..TextureOptions::LINEAR }
+            };
+            let view = self.view;
+            self.preview = Some(TiledPreview::build("inspector_preview", image, options, move |p| match view {
+                // Alpha is shown as opaque so packed data doesn't hide the color
+                ChannelView::Color => p[3] = 255,
+                ChannelView::Channel(c) => {
+                    let v = p[c];
+                    p.copy_from_slice(&[v, v, v, 255]);
+                }
+            }));
+            self.preview_key = Some((self.view, self.mip));
+        }
+        if let Some(preview) = &mut self.preview {
+            preview.poll(ctx);
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) {
+        CollapsingHeader::new("Inspect Packed Texture")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Open Texture").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Image files", &crate::TerrainApp::SUPPORTED_FORMATS)
+                            .pick_file() {
+                            self.open(path);
+                        }
+                    }
+                    if let Some(path) = &self.path {
+                        ui.label(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+                    }
+                    match &self.load_state {
+                        ImageLoadState::Loading => { ui.spinner(); }
+                        ImageLoadState::Error(e) => { ui.label(format!("Error: {}", e)); }
+                        _ => {}
+                    }
+                });
+
+                let Some(texture) = &self.texture else {
+                    return;
+                };
+                let base = &texture.mips[0];
+                ui.label(format!(
+                    "{}x{} {}, {} mip level{}{}",
+                    base.width(),
+                    base.height(),
+                    texture.format,
+                    texture.mips.len(),
+                    if texture.mips.len() == 1 { "" } else { "s" },
+                    if texture.mips_generated { " (generated for preview)" } else { "" }
+                ));
+                if texture.mips.len() > 1 {
+                    let mip = &texture.mips[self.mip.min(texture.mips.len() - 1)];
+                    ui.add(egui::Slider::new(&mut self.mip, 0..=texture.mips.len() - 1)
+                        .text(format!("Mip ({}x{})", mip.width(), mip.height())));
+                }
+                for (c, stat) in texture.stats[self.mip].iter().enumerate() {
+                    ui.label(format!("{}: min {} max {} mean {:.1}", CHANNEL_NAMES[c], stat.min, stat.max, stat.mean));
+                }
+                if let Some(provenance) = &texture.provenance {
+                    for line in provenance.describe() {
+                        ui.label(line);
+                    }
+                }
+                for finding in &texture.findings {
+                    ui.colored_label(Color32::YELLOW, finding);
+                }
+
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.view, ChannelView::Color, "RGB");
+                    for (c, name) in CHANNEL_NAMES.iter().enumerate() {
+                        ui.selectable_value(&mut self.view, ChannelView::Channel(c), *name);
+                    }
+                });
+                self.update_preview(ui.ctx());
+                if let Some(preview) = &self.preview {
+                    // Every level is shown at the size of the first
+                    preview.show(ui, ui.available_width().min(PREVIEW_SIZE as f32));
+                }
+            });
+    }
+}
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::paths;
+use crate::project::Project;
+use crate::{NormalMapFormat, OutputFormat, RoughnessFormat};
+
+const LAST_USED_FILE: &str = "last_used.json";
+
+// Output settings of the previous session, so a launch without a project
+// picks up where the last one left off instead of starting from the defaults
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastUsed {
+    pub output_directory: Option<PathBuf>,
+    pub output_format: OutputFormat,
+    pub normal_map_format: NormalMapFormat,
+    pub roughness_format: RoughnessFormat,
+}
+
+impl LastUsed {
+    // None before the first session, so the defaults file still applies
+    pub fn load() -> Option<Self> {
+        paths::config_dir()
+            .and_then(|dir| std::fs::read_to_string(dir.join(LAST_USED_FILE)).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+    }
+
+    fn save(&self) {
+        let Some(dir) = paths::config_dir() else {
+            return;
+        };
+        if std::fs::create_dir_all(&dir).is_ok() {
+            if let Ok(text) = serde_json::to_string_pretty(self) {
+                std::fs::write(dir.join(LAST_USED_FILE), text).ok();
+            }
+        }
+    }
+
+    // Writes the settings when they differ from the ones remembered
+    pub fn remember(self, remembered: &mut Option<Self>) {
+        if remembered.as_ref() != Some(&self) {
+            self.save();
+            *remembered = Some(self);
+        }
+    }
+
+    pub fn apply(&self, project: &mut Project) {
+        // A directory that has gone away since isn't worth restoring
+        if let Some(dir) = self.output_directory.as_ref().filter(|dir| dir.is_dir()) {
+            project.output_directory = Some(dir.clone());
+        }
+        project.output_format = self.output_format;
+        project.normal_map_format = self.normal_map_format;
+        project.roughness_format = self.roughness_format;
+    }
+}
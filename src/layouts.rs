@@ -0,0 +1,256 @@
+use egui::{ComboBox, Ui};
+use image::{imageops::FilterType, DynamicImage, GrayImage, RgbaImage};
+
+use ChannelSource::*;
+
+// What fills one channel of an output texture. Albedo and height come out
+// of the albedo pipeline (AO applied when the layout has no AO channel),
+// normal and roughness out of the normal pipeline, with every convention
+// conversion already done.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelSource {
+    AlbedoRed,
+    AlbedoGreen,
+    AlbedoBlue,
+    Height,
+    NormalX,
+    // OpenGL, green pointing up as Godot expects
+    NormalY,
+    // Green flipped for DirectX engines
+    NormalYDirectX,
+    NormalZ,
+    Roughness,
+    Smoothness,
+    AmbientOcclusion,
+    Opacity,
+    Translucency,
+    Constant(u8),
+}
+
+// Which of the two output size settings a texture follows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeGroup {
+    Albedo,
+    Normal,
+}
+
+// One written texture, single channel textures are grayscale
+pub struct OutputTexture {
+    pub name: &'static str,
+    pub channels: &'static [ChannelSource],
+    pub size: SizeGroup,
+}
+
+pub struct PackingLayout {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub textures: &'static [OutputTexture],
+}
+
+pub const TERRAIN3D: &str = "Terrain3D";
+
+const OPAQUE: ChannelSource = Constant(255);
+
+const HEIGHT: OutputTexture = OutputTexture { name: "height", channels: &[Height], size: SizeGroup::Albedo };
+const ALBEDO_OPAQUE: OutputTexture = OutputTexture {
+    name: "albedo",
+    channels: &[AlbedoRed, AlbedoGreen, AlbedoBlue, OPAQUE],
+    size: SizeGroup::Albedo,
+};
+
+// Every layout is assembled channel by channel from the same processed maps.
+// Terrain3D's albedo and normal can also be written as two-channel normals,
+// 16-bit PNGs or with punch-through alpha, which the export swaps in for them.
+pub const LAYOUTS: [PackingLayout; 5] = [
+    PackingLayout {
+        name: TERRAIN3D,
+        description: "Albedo RGB + height A, normal RGB + roughness A",
+        textures: &[
+            OutputTexture { name: "albedo", channels: &[AlbedoRed, AlbedoGreen, AlbedoBlue, Height], size: SizeGroup::Albedo },
+            OutputTexture { name: "normal", channels: &[NormalX, NormalY, NormalZ, Roughness], size: SizeGroup::Normal },
+        ],
+    },
+    PackingLayout {
+        name: "Separate Maps",
+        description: "Every map in its own texture, AO kept out of the albedo",
+        textures: &[
+            ALBEDO_OPAQUE,
+            HEIGHT,
+            OutputTexture { name: "normal", channels: &[NormalX, NormalY, NormalZ, OPAQUE], size: SizeGroup::Normal },
+            OutputTexture { name: "roughness", channels: &[Roughness], size: SizeGroup::Normal },
+            OutputTexture { name: "ao", channels: &[AmbientOcclusion], size: SizeGroup::Albedo },
+        ],
+    },
+    PackingLayout {
+        name: "Unreal ORM",
+        description: "Albedo RGB, DirectX normal RGB, AO/roughness/metallic in one texture",
+        textures: &[
+            ALBEDO_OPAQUE,
+            OutputTexture { name: "normal", channels: &[NormalX, NormalYDirectX, NormalZ, OPAQUE], size: SizeGroup::Normal },
+            OutputTexture { name: "orm", channels: &[AmbientOcclusion, Roughness, Constant(0), OPAQUE], size: SizeGroup::Normal },
+            HEIGHT,
+        ],
+    },
+    // glTF reads occlusion from red and roughness/metallic from green/blue,
+    // so one texture serves both, and base color alpha is opacity
+    PackingLayout {
+        name: "glTF ORM",
+        description: "Base color RGB + opacity A, OpenGL normal RGB, occlusion/roughness/metallic in one texture",
+        textures: &[
+            OutputTexture {
+                name: "base_color",
+                channels: &[AlbedoRed, AlbedoGreen, AlbedoBlue, Opacity],
+                size: SizeGroup::Albedo,
+            },
+            OutputTexture { name: "normal", channels: &[NormalX, NormalY, NormalZ, OPAQUE], size: SizeGroup::Normal },
+            OutputTexture { name: "orm", channels: &[AmbientOcclusion, Roughness, Constant(0), OPAQUE], size: SizeGroup::Normal },
+            HEIGHT,
+        ],
+    },
+    PackingLayout {
+        name: "Unity HDRP Mask Map",
+        description: "Albedo RGB, normal RGB, metallic/AO/detail mask/smoothness mask map",
+        textures: &[
+            ALBEDO_OPAQUE,
+            OutputTexture { name: "normal", channels: &[NormalX, NormalY, NormalZ, OPAQUE], size: SizeGroup::Normal },
+            OutputTexture { name: "mask", channels: &[Constant(0), AmbientOcclusion, Constant(0), Smoothness], size: SizeGroup::Normal },
+            HEIGHT,
+        ],
+    },
+];
+
+pub fn find(name: &str) -> Result<&'static PackingLayout, String> {
+    LAYOUTS.iter().find(|layout| layout.name == name).ok_or_else(|| format!(
+        "Unknown packing layout {}, expected one of {}",
+        name,
+        LAYOUTS.iter().map(|layout| layout.name).collect::<Vec<_>>().join(", "),
+    ))
+}
+
+impl PackingLayout {
+    pub fn is_terrain3d(&self) -> bool {
+        self.name == TERRAIN3D
+    }
+
+    pub fn uses(&self, source: ChannelSource) -> bool {
+        self.textures.iter().any(|texture| texture.channels.contains(&source))
+    }
+
+    // Without a channel of its own, AO darkens the albedo
+    pub fn bakes_ao(&self) -> bool {
+        !self.uses(AmbientOcclusion)
+    }
+
+    // Textures follow the size of their group's source, and are left out
+    // when that group isn't exported
+    pub fn pack(&'static self, sources: &PackSources) -> Vec<(&'static OutputTexture, DynamicImage)> {
+        self.textures.iter()
+            .filter_map(|texture| {
+                let source = match texture.size {
+                    SizeGroup::Albedo => sources.albedo?,
+                    SizeGroup::Normal => sources.normal?,
+                };
+                Some((texture, texture.pack(sources, source.dimensions())))
+            })
+            .collect()
+    }
+}
+
+impl OutputTexture {
+    // Texture has an alpha channel that isn't constant white
+    pub fn has_alpha(&self) -> bool {
+        self.channels.len() == 4 && self.channels[3] != OPAQUE
+    }
+
+    // BC4 for grayscale, BC1 when alpha is unused and BC3 otherwise
+    pub fn dds_format(&self) -> image_dds::ImageFormat {
+        if self.channels.len() == 1 {
+            image_dds::ImageFormat::BC4RUnorm
+        } else if self.has_alpha() {
+            image_dds::ImageFormat::BC3RgbaUnorm
+        } else {
+            image_dds::ImageFormat::BC1RgbaUnorm
+        }
+    }
+
+    fn pack(&self, sources: &PackSources, (width, height): (u32, u32)) -> DynamicImage {
+        // A processed map taken as it is, like Terrain3D's, is copied whole
+        let whole = match self.channels {
+            [AlbedoRed, AlbedoGreen, AlbedoBlue, Height] => sources.albedo,
+            [NormalX, NormalY, NormalZ, Roughness] => sources.normal,
+            _ => None,
+        };
+        if let Some(image) = whole.filter(|image| image.dimensions() == (width, height)) {
+            return DynamicImage::ImageRgba8(image.clone());
+        }
+        let channels: Vec<GrayImage> = self.channels.iter().map(|source| sources.channel(*source, width, height)).collect();
+        if let [gray] = channels.as_slice() {
+            return DynamicImage::ImageLuma8(gray.clone());
+        }
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba(std::array::from_fn(|c| channels.get(c).map_or(255, |channel| channel.get_pixel(x, y)[0])))
+        }))
+    }
+}
+
+// The processed maps a layout's channels are taken from
+pub struct PackSources<'a> {
+    // Albedo RGB with height in alpha, at the albedo output size
+    pub albedo: Option<&'a RgbaImage>,
+    // Normal XYZ with roughness in alpha, at the normal output size
+    pub normal: Option<&'a RgbaImage>,
+    pub ambient_occlusion: Option<&'a GrayImage>,
+    pub opacity: Option<&'a GrayImage>,
+    pub translucency: Option<&'a GrayImage>,
+}
+
+impl PackSources<'_> {
+    fn channel(&self, source: ChannelSource, width: u32, height: u32) -> GrayImage {
+        // A group that isn't exported has no source, none of the textures
+        // reading it get packed
+        let from_rgba = |image: Option<&RgbaImage>, c: usize, invert: bool| match image {
+            Some(image) => GrayImage::from_fn(image.width(), image.height(), |x, y| {
+                let value = image.get_pixel(x, y)[c];
+                image::Luma([if invert { 255 - value } else { value }])
+            }),
+            None => GrayImage::new(width, height),
+        };
+        let gray = match source {
+            AlbedoRed => from_rgba(self.albedo, 0, false),
+            AlbedoGreen => from_rgba(self.albedo, 1, false),
+            AlbedoBlue => from_rgba(self.albedo, 2, false),
+            Height => from_rgba(self.albedo, 3, false),
+            NormalX => from_rgba(self.normal, 0, false),
+            NormalY => from_rgba(self.normal, 1, false),
+            NormalYDirectX => from_rgba(self.normal, 1, true),
+            NormalZ => from_rgba(self.normal, 2, false),
+            Roughness => from_rgba(self.normal, 3, false),
+            Smoothness => from_rgba(self.normal, 3, true),
+            // Missing maps fall back to what leaves the surface unchanged
+            AmbientOcclusion => self.ambient_occlusion.cloned().unwrap_or_else(|| GrayImage::from_pixel(width, height, image::Luma([255]))),
+            Opacity => self.opacity.cloned().unwrap_or_else(|| GrayImage::from_pixel(width, height, image::Luma([255]))),
+            Translucency => self.translucency.cloned().unwrap_or_else(|| GrayImage::from_pixel(width, height, image::Luma([0]))),
+            Constant(value) => GrayImage::from_pixel(width, height, image::Luma([value])),
+        };
+        // Albedo and normal may be exported at different sizes
+        if gray.dimensions() == (width, height) {
+            gray
+        } else {
+            image::imageops::resize(&gray, width, height, FilterType::Triangle)
+        }
+    }
+}
+
+pub fn show_selector(ui: &mut Ui, selected: &mut String) {
+    let description = find(selected).map(|layout| layout.description).unwrap_or_default();
+    ComboBox::from_label("Packing Layout")
+        .selected_text(selected.as_str())
+        .show_ui(ui, |ui| {
+            for layout in &LAYOUTS {
+                ui.selectable_value(selected, layout.name.to_string(), layout.name)
+                    .on_hover_text(layout.description);
+            }
+        })
+        .response
+        .on_hover_text(description);
+}
@@ -0,0 +1,154 @@
+use egui::{CollapsingHeader, Key, ScrollArea, Ui};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::paths;
+use crate::review::{self, ReviewStatus};
+
+// Keys that assign the highlighted file while browsing, in display order
+const SLOT_KEYS: [(Key, &str, &str); 5] = [
+    (Key::A, "albedo", "Albedo"),
+    (Key::N, "normal", "Normal"),
+    (Key::H, "height", "Height"),
+    (Key::O, "ao", "AO"),
+    (Key::R, "roughness", "Roughness"),
+];
+
+#[derive(Default)]
+pub struct LibraryBrowser {
+    directory: Option<PathBuf>,
+    // With the review of the set project saved in each, if any
+    folders: Vec<(PathBuf, Option<(ReviewStatus, String)>)>,
+    files: Vec<PathBuf>,
+    highlighted: Option<usize>,
+    // Set when the arrow keys move the highlight, so the list follows it on
+    // the next frame and scrolls freely otherwise
+    scroll_to_highlighted: bool,
+    error: Option<String>,
+}
+
+fn is_image(path: &Path) -> bool {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    crate::TerrainApp::SUPPORTED_FORMATS.contains(&ext.as_str())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().to_string()
+}
+
+impl LibraryBrowser {
+    fn open(&mut self, directory: PathBuf) {
+        self.folders.clear();
+        self.files.clear();
+        self.highlighted = None;
+        self.error = None;
+        match fs::read_dir(paths::long_path(&directory)) {
+            Ok(entries) => {
+                for entry in entries.flatten() {
+                    let path = directory.join(entry.file_name());
+                    if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                        let review = review::folder_review(&path);
+                        self.folders.push((path, review));
+                    } else if is_image(&path) {
+                        self.files.push(path);
+                    }
+                }
+                self.folders.sort_by(|(a, _), (b, _)| a.cmp(b));
+                self.files.sort();
+                self.highlighted = (!self.files.is_empty()).then_some(0);
+            }
+            Err(e) => self.error = Some(e.to_string()),
+        }
+        self.directory = Some(directory);
+    }
+
+    // Returns the slot and file to assign when one of the slot keys is pressed
+    pub fn show(&mut self, ui: &mut Ui) -> Option<(&'static str, PathBuf)> {
+        let mut assignment = None;
+        CollapsingHeader::new("Library")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Browse Folder").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                            self.open(path);
+                        }
+                    }
+                    let parent = self.directory.as_ref().and_then(|d| d.parent()).map(Path::to_path_buf);
+                    if ui.add_enabled(parent.is_some(), egui::Button::new("Up")).clicked() {
+                        if let Some(parent) = parent {
+                            self.open(parent);
+                        }
+                    }
+                    if let Some(directory) = &self.directory {
+                        ui.label(directory.to_string_lossy().to_string());
+                    }
+                });
+                if let Some(e) = &self.error {
+                    ui.label(format!("Error: {}", e));
+                }
+                if self.directory.is_none() {
+                    return;
+                }
+                ui.label(SLOT_KEYS.iter()
+                    .map(|(key, _, name)| format!("{} {}", key.name(), name))
+                    .collect::<Vec<_>>()
+                    .join(", ") + " assign the highlighted file, arrow keys move");
+
+                let mut enter = None;
+                ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                    for (folder, review) in &self.folders {
+                        ui.horizontal(|ui| {
+                            if ui.selectable_label(false, format!("[{}]", file_name(folder))).double_clicked() {
+                                enter = Some(folder.clone());
+                            }
+                            if let Some((status, notes)) = review {
+                                status.badge(ui, notes);
+                            }
+                        });
+                    }
+                    for (i, file) in self.files.iter().enumerate() {
+                        let highlighted = self.highlighted == Some(i);
+                        let response = ui.selectable_label(highlighted, file_name(file));
+                        if response.clicked() {
+                            self.highlighted = Some(i);
+                        }
+                        if highlighted && self.scroll_to_highlighted {
+                            response.scroll_to_me(None);
+                        }
+                    }
+                });
+                self.scroll_to_highlighted = false;
+                if let Some(folder) = enter {
+                    self.open(folder);
+                    return;
+                }
+
+                // Text fields elsewhere keep their keys
+                if ui.ctx().wants_keyboard_input() || self.files.is_empty() {
+                    return;
+                }
+                let last = self.files.len() - 1;
+                let before = self.highlighted;
+                ui.input(|input| {
+                    if input.key_pressed(Key::ArrowDown) {
+                        self.highlighted = Some(self.highlighted.map_or(0, |i| (i + 1).min(last)));
+                    }
+                    if input.key_pressed(Key::ArrowUp) {
+                        self.highlighted = Some(self.highlighted.map_or(0, |i| i.saturating_sub(1)));
+                    }
+                    let file = self.highlighted.and_then(|i| self.files.get(i));
+                    for (key, slot, _) in SLOT_KEYS {
+                        if input.key_pressed(key) {
+                            assignment = file.map(|file| (slot, file.clone()));
+                        }
+                    }
+                });
+                if self.highlighted != before {
+                    self.scroll_to_highlighted = true;
+                    ui.ctx().request_repaint();
+                }
+            });
+        assignment
+    }
+}
@@ -0,0 +1,99 @@
+use image::{imageops::FilterType, RgbaImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::colormap;
+use crate::resize;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum MacroSource {
+    AlbedoLuminance,
+    Noise,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MacroVariationSettings {
+    pub source: MacroSource,
+    pub resolution: u32,
+    // Size of the variation features as a fraction of the texture
+    pub scale: f32,
+    pub contrast: f32,
+    pub seed: u32,
+}
+
+impl Default for MacroVariationSettings {
+    fn default() -> Self {
+        Self {
+            source: MacroSource::AlbedoLuminance,
+            resolution: 512,
+            scale: 0.25,
+            contrast: 1.0,
+            seed: 0,
+        }
+    }
+}
+
+fn hash(x: u32, y: u32, seed: u32) -> f32 {
+    let mut h = x.wrapping_mul(0x8da6_b343) ^ y.wrapping_mul(0xd816_3841) ^ seed.wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5bd1_e995);
+    h ^= h >> 15;
+    (h & 0xffff) as f32 / 65535.0
+}
+
+// Smoothly interpolated lattice noise that wraps every `cells` cells
+fn value_noise(u: f32, v: f32, cells: u32, seed: u32) -> f32 {
+    let x = u * cells as f32;
+    let y = v * cells as f32;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (sx, sy) = (fx * fx * (3.0 - 2.0 * fx), fy * fy * (3.0 - 2.0 * fy));
+    let corner = |dx: u32, dy: u32| {
+        hash((x0 as u32 + dx) % cells, (y0 as u32 + dy) % cells, seed)
+    };
+    let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * sx;
+    let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * sx;
+    top + (bottom - top) * sy
+}
+
+fn noise_field(resolution: u32, scale: f32, seed: u32) -> Vec<f32> {
+    let base_cells = ((1.0 / scale.max(0.01)).round() as u32).clamp(1, resolution / 2);
+    (0..resolution * resolution).into_par_iter().map(|i| {
+        let u = (i % resolution) as f32 / resolution as f32;
+        let v = (i / resolution) as f32 / resolution as f32;
+        let mut value = 0.0;
+        let mut amplitude = 0.5;
+        let mut total = 0.0;
+        for octave in 0..4 {
+            value += value_noise(u, v, base_cells << octave, seed.wrapping_add(octave)) * amplitude;
+            total += amplitude;
+            amplitude *= 0.5;
+        }
+        value / total
+    }).collect()
+}
+
+fn luminance_field(albedo: &RgbaImage, resolution: u32, scale: f32) -> Vec<f32> {
+    let mut small = resize::resize_wrapped(albedo, resolution, resolution, FilterType::Triangle);
+    let radius = (resolution as f32 * scale * 0.25) as u32;
+    colormap::box_blur_wrapped(&mut small, radius.min(resolution / 2));
+    small.pixels()
+        .map(|p| (0.2126 * p[0] as f32 + 0.7152 * p[1] as f32 + 0.0722 * p[2] as f32) / 255.0)
+        .collect()
+}
+
+// Low frequency grayscale variation centered on mid gray, tileable like the albedo
+pub fn generate_macro_variation(albedo: &RgbaImage, settings: &MacroVariationSettings) -> RgbaImage {
+    let resolution = settings.resolution.max(2);
+    let values = match settings.source {
+        MacroSource::AlbedoLuminance => luminance_field(albedo, resolution, settings.scale),
+        MacroSource::Noise => noise_field(resolution, settings.scale, settings.seed),
+    };
+    let mean = values.iter().sum::<f32>() / values.len() as f32;
+    RgbaImage::from_fn(resolution, resolution, |x, y| {
+        let v = values[(y * resolution + x) as usize];
+        let v = ((0.5 + (v - mean) * settings.contrast).clamp(0.0, 1.0) * 255.0).round() as u8;
+        image::Rgba([v, v, v, 255])
+    })
+}
@@ -1,15 +1,131 @@
 use eframe::{run_native, App, Frame, NativeOptions};
 use egui::{CentralPanel, Context, ComboBox, ColorImage, TextureHandle, Vec2, widgets::Image, load::SizedTexture, CollapsingHeader};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::thread;
+use std::time::Instant;
 use image::{DynamicImage, ImageBuffer, GenericImageView};
 use rayon::prelude::*;
-use image_dds::{dds_from_image, Quality, Mipmaps};
-use std::fs::File;
-use std::io::BufWriter;
+use image_dds::Quality;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+mod autocrop;
+mod batch;
+mod benchmark;
+mod blend_preview;
+mod budget;
+mod color_management;
+mod colormap;
+mod compare;
+mod config;
+mod conventions;
+mod dds_encode;
+mod encoding;
+mod erosion;
+mod export_cache;
+mod export_queue;
+mod external_editor;
+mod false_color;
+mod file_names;
+mod fonts;
+mod frames;
+mod godot_resource;
+#[cfg(test)]
+mod golden_tests;
+mod height_alpha;
+mod height_filters;
+mod history;
+mod heightmap;
+mod hot_folder;
+mod inspector;
+mod last_used;
+mod layouts;
+mod library;
+mod macro_variation;
+mod manifest;
+mod material_preview;
+mod map_names;
+mod normals;
+mod overwrite;
+mod pack16;
+mod pack8;
+mod packed_input;
+mod palette;
+mod paths;
+mod plugins;
+mod preflight;
+mod preview_resolution;
+mod post_export;
+mod project;
+mod provenance;
+mod recent;
+mod regions;
+mod reorganize;
+mod report;
+mod review;
+mod resize;
+mod scripting;
+mod source_info;
+mod roughness;
+mod server;
+mod set_scan;
+mod single_instance;
+mod slope_tint;
+mod splatmap;
+mod staging;
+mod texel_density;
+mod tiled_preview;
+mod tiling_preview;
+mod timing;
+mod undo;
+mod validate;
+
+use height_alpha::{HeightAlphaSettings, HeightEstimateSettings, ParallaxSettings};
+use encoding::MapEncoding;
+use color_management::PreviewColor;
+use false_color::GrayscalePalette;
+use preview_resolution::PreviewResolution;
+use last_used::LastUsed;
+use file_names::FileNames;
+use layouts::{ChannelSource, PackSources};
+use recent::{RecentChoice, RecentItems};
+use compare::ExportComparer;
+use history::ExportHistory;
+use conventions::ConventionChecker;
+use export_cache::LinkMode;
+use external_editor::ExternalEditor;
+use heightmap::HeightmapTool;
+use tiling_preview::TilingPreview;
+use slope_tint::SlopeTint;
+use blend_preview::BlendPreview;
+use plugins::Plugins;
+use inspector::TextureInspector;
+use library::LibraryBrowser;
+use macro_variation::{MacroSource, MacroVariationSettings};
+use texel_density::{TexelDensity, TexelDensitySettings};
+use undo::UndoStack;
+use dds_encode::EncodeProgress;
+use overwrite::OverwriteMode;
+use manifest::ExportManifest;
+use packed_input::{Channel, ChannelMapping};
+use project::{ExportTarget, LaunchOptions, Project};
+use provenance::Provenance;
+use reorganize::ExportReorganizer;
+use report::{ExportReport, ExportStatus, PlannedOutput};
+use review::ReviewStatus;
+use budget::TextureBudget;
+use batch::BatchExporter;
+use export_queue::ExportQueue;
+use set_scan::SetScanner;
+use hot_folder::HotFolder;
+use roughness::{RoughnessAdjust, RoughnessCurve};
+use splatmap::SplatmapConverter;
+use staging::StagedOutput;
+use timing::TimingStats;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 enum NormalMapFormat {
     OpenGL,
     DirectX,
@@ -21,7 +137,7 @@ impl Default for NormalMapFormat {
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 enum OutputFormat {
     PNG,
     DDS,
@@ -33,8 +149,31 @@ impl Default for OutputFormat {
     }
 }
 
+impl OutputFormat {
+    const ALL: [OutputFormat; 2] = [OutputFormat::PNG, OutputFormat::DDS];
+}
+
+// Block compression effort, slower finds closer block colors
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, Default)]
+enum DdsQuality {
+    Fast,
+    #[default]
+    Normal,
+    Slow,
+}
+
+impl DdsQuality {
+    fn encoder_quality(self) -> Quality {
+        match self {
+            DdsQuality::Fast => Quality::Fast,
+            DdsQuality::Normal => Quality::Normal,
+            DdsQuality::Slow => Quality::Slow,
+        }
+    }
+}
+
 // Add new enum for roughness format
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 enum RoughnessFormat {
     Roughness,
     Smoothness,
@@ -46,6 +185,16 @@ impl Default for RoughnessFormat {
     }
 }
 
+// What happens to alpha already present in the albedo source before height
+// replaces it
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize, Default)]
+enum AlbedoAlphaMode {
+    #[default]
+    Discard,
+    ExportMask,
+    Unpremultiply,
+}
+
 #[derive(Debug)]
 enum ImageLoadState {
     NotLoaded,
@@ -58,6 +207,11 @@ enum ImageLoadState {
 struct ProcessedImage {
     original: DynamicImage,
     downscaled: ImageBuffer<image::Rgba<u8>, Vec<u8>>,
+    info: source_info::SourceInfo,
+    // Size before a uniform border was cropped away
+    cropped_from: Option<(u32, u32)>,
+    // Frames or pages in the source file, only one of them is used
+    frame_count: usize,
 }
 
 #[derive(Debug)]
@@ -65,6 +219,7 @@ enum ImageValidationError {
     NotSquare,
     NotPowerOfTwo,
     TooSmall,
+    NotBlockAligned,
 }
 
 impl std::fmt::Display for ImageValidationError {
@@ -73,6 +228,7 @@ impl std::fmt::Display for ImageValidationError {
             Self::NotSquare => write!(f, "Image must be square"),
             Self::NotPowerOfTwo => write!(f, "Image dimensions must be power of 2"),
             Self::TooSmall => write!(f, "Image must be at least 512x512"),
+            Self::NotBlockAligned => write!(f, "Image dimensions must be multiples of 4"),
         }
     }
 }
@@ -91,66 +247,300 @@ struct TerrainApp {
     ambient_occlusion_map: Option<PathBuf>,
     normal_map: Option<PathBuf>,
     normal_map_format: NormalMapFormat,
+    library_conventions: bool,
+    // Library the last maps assigned by name came from
+    recognized_library: Option<map_names::SourceLibrary>,
+    // Allows non-square maps as long as every map shares the same size
+    rectangular_mode: bool,
+    // Crop flat borders such as scanner margins before validation
+    auto_crop: bool,
+    // Chosen frame of animated or multi-page sources, keyed by file
+    input_frames: BTreeMap<PathBuf, usize>,
+    input_encodings: BTreeMap<String, MapEncoding>,
+    // Rhai script run on every map as it loads
+    script: Option<PathBuf>,
+    albedo_alpha_mode: AlbedoAlphaMode,
+    albedo_preview_color: PreviewColor,
+    grayscale_palette: GrayscalePalette,
+    preview_resolution: PreviewResolution,
+    // Output settings as last written to the preferences
+    last_used: Option<LastUsed>,
+    undo_stack: UndoStack,
+    recent: RecentItems,
     albedo_load_state: ImageLoadState,
     height_load_state: ImageLoadState,
     normal_load_state: ImageLoadState,
     ao_load_state: ImageLoadState,
     image_receiver: Receiver<(String, Result<ProcessedImage, String>)>,
     image_sender: Sender<(String, Result<ProcessedImage, String>)>,
+    // Files handed over by later launches
+    forwarded_receiver: Receiver<single_instance::Forwarded>,
+    forwarded_sender: Sender<single_instance::Forwarded>,
     albedo_image: Option<ProcessedImage>,
     height_image: Option<ProcessedImage>,
     normal_image: Option<ProcessedImage>,
     ao_image: Option<ProcessedImage>,
     albedo_texture: Option<TextureHandle>,
+    tiling_preview: TilingPreview,
+    slope_tint: SlopeTint,
     height_texture: Option<TextureHandle>,
     normal_texture: Option<TextureHandle>,
     ao_texture: Option<TextureHandle>,
     output_directory: Option<PathBuf>,
+    set_subfolder: bool,
+    set_folder_name: String,
+    // Named output directories saved with the project
+    export_targets: BTreeMap<String, PathBuf>,
+    new_target_name: String,
     output_format: OutputFormat,
+    additional_formats: Vec<OutputFormat>,
+    dds_quality: DdsQuality,
     processing_state: ProcessingState,
-    processing_receiver: Receiver<Result<(), String>>,
-    processing_sender: Sender<Result<(), String>>,
+    // DDS encoding of the running export, None for PNG exports
+    encode_progress: Option<Arc<EncodeProgress>>,
+    processing_receiver: Receiver<Result<Vec<PathBuf>, String>>,
+    processing_sender: Sender<Result<Vec<PathBuf>, String>>,
+    // Files written by the last successful export
+    exported_files: Vec<PathBuf>,
+    height_alpha: HeightAlphaSettings,
+    parallax: ParallaxSettings,
+    // Neutral values packed in place of a missing height or roughness map
+    default_height: u8,
+    default_roughness: u8,
+    height_estimate: HeightEstimateSettings,
     roughness_map: Option<PathBuf>,
     roughness_load_state: ImageLoadState,
     roughness_image: Option<ProcessedImage>,
     roughness_texture: Option<TextureHandle>,
+    translucency_map: Option<PathBuf>,
+    translucency_load_state: ImageLoadState,
+    translucency_image: Option<ProcessedImage>,
+    translucency_texture: Option<TextureHandle>,
+    opacity_map: Option<PathBuf>,
+    opacity_load_state: ImageLoadState,
+    opacity_image: Option<ProcessedImage>,
+    opacity_texture: Option<TextureHandle>,
     roughness_format: RoughnessFormat,
+    roughness_adjust: RoughnessAdjust,
+    roughness_curve: RoughnessCurve,
+    // Roughness preview with the curve and adjustments applied, keyed by the LUT it was built from
+    roughness_preview: Option<TextureHandle>,
+    roughness_preview_key: Option<([u8; 256], RoughnessFormat)>,
+    // Input values spread over the roughness map, shown before and after conversion
+    roughness_swatches: Option<[u8; 5]>,
+    splatmap_converter: SplatmapConverter,
+    heightmap_tool: HeightmapTool,
+    blend_preview: BlendPreview,
+    plugins: Plugins,
+    export_comparer: ExportComparer,
+    export_history: ExportHistory,
+    convention_checker: ConventionChecker,
+    texture_inspector: TextureInspector,
+    export_reorganizer: ExportReorganizer,
+    texture_budget: TextureBudget,
+    batch_exporter: BatchExporter,
+    export_queue: ExportQueue,
+    set_scanner: SetScanner,
+    hot_folder: HotFolder,
+    library: LibraryBrowser,
+    external_editor: ExternalEditor,
+    project_path: Option<PathBuf>,
+    project_error: Option<String>,
+    // Normal map whose alpha data the user chose to have overwritten
+    discarded_normal_alpha: Option<PathBuf>,
+    // Files handed over together whose names matched no slot
+    unassigned_inputs: Vec<PathBuf>,
+    // Set from the command line to export once the project's maps load
+    pending_export: bool,
+    exit_after_export: bool,
+    timing_stats: TimingStats,
+    // Start time, resolution and format label of the running export
+    run_started: Option<(Instant, u32, String)>,
+    // Shown next to the export button, updated along with the undo stack
+    export_estimate: Option<String>,
+    export_color_map: bool,
+    color_map_resolution: u32,
+    color_map_blur: u32,
+    export_macro_variation: bool,
+    export_average_color: bool,
+    export_palette: bool,
+    export_preview: bool,
+    palette_size: usize,
+    macro_variation: MacroVariationSettings,
+    texel_density: TexelDensitySettings,
+    review_status: ReviewStatus,
+    notes: String,
+    // Input normal maps only carry X/Y
+    reconstruct_normal_z: bool,
+    packing_layout: String,
+    file_name_template: String,
+    map_names: BTreeMap<String, String>,
+    overwrite_mode: OverwriteMode,
+    // Existing textures the editor is asking about before an export
+    overwrite_prompt: Option<Vec<String>>,
+    export_albedo: bool,
+    export_normal: bool,
+    two_channel_normals: bool,
+    pack_translucency: bool,
+    // Channels of packed sources currently feeding the grayscale slots, and
+    // the mapping picked for the next packed texture
+    packed_channels: ChannelMapping,
+    packed_mapping: ChannelMapping,
+    // Longest side of each packed output, None keeps the source size
+    albedo_output_size: Option<u32>,
+    normal_output_size: Option<u32>,
+    seamless_resize: bool,
+    // BC1 with 1-bit alpha for albedo when alpha is a mask
+    punch_through_alpha: bool,
+    sixteen_bit_png: bool,
+    // Run after every successful export, with placeholders for the outputs
+    post_export_command: String,
+    // Reuse outputs of an identical earlier export instead of reprocessing
+    use_export_cache: bool,
+    // Skip exports whose output is already up to date
+    skip_up_to_date: bool,
+    link_mode: LinkMode,
+    alpha_threshold: u8,
 }
 
 impl Default for TerrainApp {
     fn default() -> Self {
         let (tx, rx) = channel();
         let (ptx, prx) = channel();
+        let (ftx, frx) = channel();
         Self {
             albedo_map: None,
             height_map: None,
             ambient_occlusion_map: None,
             normal_map: None,
             normal_map_format: Default::default(),
+            library_conventions: true,
+            recognized_library: None,
+            rectangular_mode: false,
+            auto_crop: false,
+            input_frames: BTreeMap::new(),
+            input_encodings: BTreeMap::new(),
+            script: None,
+            albedo_alpha_mode: Default::default(),
+            albedo_preview_color: Default::default(),
+            grayscale_palette: GrayscalePalette::load(),
+            preview_resolution: PreviewResolution::load(),
+            last_used: LastUsed::load(),
+            undo_stack: UndoStack::default(),
+            recent: RecentItems::load(),
             albedo_load_state: ImageLoadState::NotLoaded,
             height_load_state: ImageLoadState::NotLoaded,
             normal_load_state: ImageLoadState::NotLoaded,
             ao_load_state: ImageLoadState::NotLoaded,
             image_receiver: rx,
             image_sender: tx,
+            forwarded_receiver: frx,
+            forwarded_sender: ftx,
             albedo_image: None,
             height_image: None,
             normal_image: None,
             ao_image: None,
             albedo_texture: None,
+            tiling_preview: Default::default(),
+            slope_tint: Default::default(),
             height_texture: None,
             normal_texture: None,
             ao_texture: None,
             output_directory: None,
+            set_subfolder: false,
+            set_folder_name: String::new(),
+            export_targets: BTreeMap::new(),
+            new_target_name: String::new(),
             output_format: Default::default(),
+            additional_formats: Vec::new(),
+            dds_quality: Default::default(),
             processing_state: ProcessingState::NotStarted,
+            encode_progress: None,
             processing_receiver: prx,
             processing_sender: ptx,
+            exported_files: Vec::new(),
+            height_alpha: Default::default(),
+            parallax: Default::default(),
+            default_height: 255,
+            default_roughness: 128,
+            height_estimate: Default::default(),
             roughness_map: None,
             roughness_load_state: ImageLoadState::NotLoaded,
             roughness_image: None,
             roughness_texture: None,
+            translucency_map: None,
+            translucency_load_state: ImageLoadState::NotLoaded,
+            translucency_image: None,
+            translucency_texture: None,
+            opacity_map: None,
+            opacity_load_state: ImageLoadState::NotLoaded,
+            opacity_image: None,
+            opacity_texture: None,
             roughness_format: Default::default(),
+            roughness_adjust: Default::default(),
+            roughness_curve: Default::default(),
+            roughness_preview: None,
+            roughness_preview_key: None,
+            roughness_swatches: None,
+            splatmap_converter: Default::default(),
+            heightmap_tool: Default::default(),
+            blend_preview: Default::default(),
+            plugins: Default::default(),
+            export_comparer: Default::default(),
+            export_history: Default::default(),
+            convention_checker: Default::default(),
+            texture_inspector: Default::default(),
+            export_reorganizer: Default::default(),
+            texture_budget: Default::default(),
+            batch_exporter: Default::default(),
+            export_queue: Default::default(),
+            set_scanner: Default::default(),
+            hot_folder: Default::default(),
+            library: Default::default(),
+            external_editor: Default::default(),
+            project_path: None,
+            project_error: None,
+            discarded_normal_alpha: None,
+            unassigned_inputs: Vec::new(),
+            pending_export: false,
+            exit_after_export: false,
+            timing_stats: TimingStats::load(),
+            run_started: None,
+            export_estimate: None,
+            export_color_map: false,
+            color_map_resolution: 256,
+            color_map_blur: 8,
+            export_macro_variation: false,
+            export_average_color: false,
+            export_palette: false,
+            export_preview: false,
+            palette_size: 6,
+            macro_variation: Default::default(),
+            texel_density: Default::default(),
+            review_status: Default::default(),
+            notes: String::new(),
+            reconstruct_normal_z: false,
+            packing_layout: layouts::TERRAIN3D.to_string(),
+            file_name_template: file_names::DEFAULT_TEMPLATE.to_string(),
+            map_names: BTreeMap::new(),
+            overwrite_mode: Default::default(),
+            overwrite_prompt: None,
+            export_albedo: true,
+            export_normal: true,
+            two_channel_normals: false,
+            pack_translucency: false,
+            packed_channels: ChannelMapping::NONE,
+            packed_mapping: ChannelMapping::arm(),
+            albedo_output_size: None,
+            normal_output_size: None,
+            seamless_resize: true,
+            punch_through_alpha: false,
+            sixteen_bit_png: false,
+            post_export_command: String::new(),
+            use_export_cache: false,
+            skip_up_to_date: false,
+            link_mode: LinkMode::Copy,
+            alpha_threshold: 128,
         }
     }
 }
@@ -161,8 +551,16 @@ impl TerrainApp {
         "png", "pnm", "qoi", "tga", "tiff", "tif", "webp"
     ];
 
-    fn validate_image(img: &DynamicImage) -> Result<(), ImageValidationError> {
+    fn validate_image(img: &DynamicImage, rectangular: bool) -> Result<(), ImageValidationError> {
         let (width, height) = img.dimensions();
+
+        // Trim sheets and decals only need whole compression blocks
+        if rectangular {
+            if width % 4 != 0 || height % 4 != 0 {
+                return Err(ImageValidationError::NotBlockAligned);
+            }
+            return Ok(());
+        }
         
         if width != height {
             return Err(ImageValidationError::NotSquare);
@@ -179,35 +577,120 @@ impl TerrainApp {
         Ok(())
     }
 
-    fn process_image(img: DynamicImage) -> Result<ProcessedImage, String> {
-        Self::validate_image(&img).map_err(|e| e.to_string())?;
+    fn process_image(img: DynamicImage, rectangular: bool, auto_crop: bool, preview: PreviewResolution) -> Result<ProcessedImage, String> {
+        let (img, cropped_from) = if auto_crop {
+            autocrop::crop_border(img)
+        } else {
+            (img, None)
+        };
+        Self::validate_image(&img, rectangular).map_err(|e| e.to_string())?;
         
-        let downscaled = img.resize_exact(512, 512, image::imageops::FilterType::Nearest)
-            .to_rgba8();
+        let downscaled = preview.downscale(&img);
             
         Ok(ProcessedImage {
+            info: source_info::inspect(&img),
             original: img,
             downscaled,
+            cropped_from,
+            frame_count: 1,
         })
     }
 
     fn load_image(&self, path: PathBuf, image_type: String) {
         let tx = self.image_sender.clone();
+        let rectangular = self.rectangular_mode;
+        let auto_crop = self.auto_crop;
+        let preview = self.preview_resolution;
+        let channel = self.packed_channels.channel_for(&image_type);
+        let frame = self.input_frames.get(&path).copied().unwrap_or(0);
+        let encoding = self.input_encodings.get(&image_type).copied().unwrap_or_default();
+        let script = self.script.clone();
         thread::spawn(move || {
-            let result = image::open(&path)
-                .map_err(|e| e.to_string())
-                .and_then(TerrainApp::process_image);
+            let result = frames::open_frame(&paths::long_path(&path), frame)
+                .and_then(|(img, frame_count)| {
+                    let img = match channel {
+                        Some(channel) => packed_input::extract_channel(&img, channel),
+                        None => img,
+                    };
+                    let img = encoding::convert(img, &image_type, encoding);
+                    let float = matches!(img, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_));
+                    let (img, scripted) = match &script {
+                        Some(script) => {
+                            let script = scripting::Script::load(script)?;
+                            let scripted = script.transforms(&image_type);
+                            (script.apply(img, &image_type)?, scripted)
+                        }
+                        None => (img, false),
+                    };
+                    let mut processed = TerrainApp::process_image(img, rectangular, auto_crop, preview)?;
+                    processed.frame_count = frame_count;
+                    processed.info.scripted_float = float && scripted;
+                    Ok(processed)
+                });
             tx.send((image_type, result)).ok();
         });
     }
 
-    fn process_image_to_texture(&mut self, processed: &ProcessedImage, ctx: &Context) -> TextureHandle {
+    fn process_image_to_texture(&self, processed: &ProcessedImage, ctx: &Context) -> TextureHandle {
         let size = [processed.downscaled.width() as _, processed.downscaled.height() as _];
         let pixels = processed.downscaled.as_flat_samples();
         let color_image = ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
         ctx.load_texture("image", color_image, Default::default())
     }
 
+    // Single channel maps are shown through the chosen preview palette
+    fn grayscale_texture(&self, processed: &ProcessedImage, ctx: &Context) -> TextureHandle {
+        let color_image = self.grayscale_palette.color_image(&processed.downscaled);
+        ctx.load_texture("image", color_image, Default::default())
+    }
+
+    fn update_grayscale_textures(&mut self, ctx: &Context) {
+        self.height_texture = self.height_image.as_ref().map(|processed| self.grayscale_texture(processed, ctx));
+        self.ao_texture = self.ao_image.as_ref().map(|processed| self.grayscale_texture(processed, ctx));
+        self.roughness_texture = self.roughness_image.as_ref().map(|processed| self.grayscale_texture(processed, ctx));
+        self.opacity_texture = self.opacity_image.as_ref().map(|processed| self.grayscale_texture(processed, ctx));
+        self.translucency_texture = self.translucency_image.as_ref().map(|processed| self.grayscale_texture(processed, ctx));
+        self.roughness_preview_key = None;
+        self.heightmap_tool.set_palette(ctx, self.grayscale_palette);
+    }
+
+    // Previews of the loaded maps at a new resolution, made the same way as
+    // when the maps load but from the sources already in memory
+    fn regenerate_previews(&mut self, ctx: &Context) {
+        let resolution = self.preview_resolution;
+        for processed in [
+            &mut self.albedo_image,
+            &mut self.height_image,
+            &mut self.normal_image,
+            &mut self.ao_image,
+            &mut self.roughness_image,
+            &mut self.translucency_image,
+            &mut self.opacity_image,
+        ].into_iter().flatten() {
+            processed.downscaled = resolution.downscale(&processed.original);
+        }
+        self.slope_tint.invalidate();
+        self.update_albedo_texture(ctx);
+        self.normal_texture = self.normal_image.as_ref().map(|processed| self.process_image_to_texture(processed, ctx));
+        self.update_grayscale_textures(ctx);
+    }
+
+    // Albedo goes through the display profile when the managed preview is on
+    fn update_albedo_texture(&mut self, ctx: &Context) {
+        self.tiling_preview.invalidate();
+        let Some(processed) = &self.albedo_image else {
+            self.albedo_texture = None;
+            return;
+        };
+        let preview = self.albedo_preview_color.apply(&processed.downscaled);
+        let preview = preview.as_ref().unwrap_or(&processed.downscaled);
+        let tinted = self.slope_tint.apply(preview, self.height_image.as_ref().map(|img| &img.downscaled));
+        let preview = tinted.as_ref().unwrap_or(preview);
+        let size = [preview.width() as _, preview.height() as _];
+        let color_image = ColorImage::from_rgba_unmultiplied(size, preview.as_raw());
+        self.albedo_texture = Some(ctx.load_texture("albedo", color_image, Default::default()));
+    }
+
     fn display_image(&self, ui: &mut egui::Ui, texture: &TextureHandle) {
         let available_width = ui.available_width();
         let size = texture.size_vec2();
@@ -220,168 +703,781 @@ impl TerrainApp {
         ui.add(Image::from_texture(SizedTexture::from_handle(texture)).max_size(display_size));
     }
 
+    // Only the sources of the textures being exported are needed, updating
+    // just the normal texture works without an albedo map and the other way round
     fn are_required_images_loaded(&self) -> bool {
-        matches!(
-            (&self.albedo_load_state, &self.normal_load_state),
-            (
-                ImageLoadState::Loaded,
-                ImageLoadState::Loaded
-            )
-        ) && self.output_directory.is_some()
-    }
-
-    fn save_as_dds(img: &DynamicImage, path: PathBuf) -> Result<(), String> {
-        let rgba = img.to_rgba8();
-        let dds = dds_from_image(
-            &rgba,
-            image_dds::ImageFormat::BC3RgbaUnorm,
-            Quality::Normal,
-            Mipmaps::GeneratedAutomatic,
-        ).map_err(|e| format!("Failed to convert to DDS: {}", e))?;
-
-        let file = File::create(path)
-            .map_err(|e| format!("Failed to create file: {}", e))?;
-        let mut writer = BufWriter::new(file);
-        
-        dds.write(&mut writer)
-            .map_err(|e| format!("Failed to write DDS: {}", e))
+        let loaded = |state: &ImageLoadState| matches!(state, ImageLoadState::Loaded);
+        (!self.export_albedo || loaded(&self.albedo_load_state))
+            && (!self.export_normal || loaded(&self.normal_load_state))
+            && self.output_directory.is_some()
+    }
+
+    // The map the output sizes are taken from, the albedo unless there's only a normal map
+    fn reference_image(&self) -> Option<&ProcessedImage> {
+        self.albedo_image.as_ref().or(self.normal_image.as_ref())
     }
 
-    fn process_and_save_images(&mut self) -> Result<(), String> {
-        let output_dir = self.output_directory.as_ref().unwrap().clone();
-        let albedo = self.albedo_image.as_ref().unwrap().original.clone();
+    fn save_as_dds_format(img: &DynamicImage, path: PathBuf, format: image_dds::ImageFormat, quality: DdsQuality) -> Result<(), String> {
+        dds_encode::save(img, &path, format, quality.encoder_quality(), &EncodeProgress::default())
+    }
+
+    // Every map is sampled with the albedo's coordinates
+    fn check_matching_sizes(&self) -> Result<(), String> {
+        let (reference, size) = match (&self.albedo_image, &self.normal_image) {
+            (Some(albedo), _) => ("albedo", albedo.original.dimensions()),
+            (None, Some(normal)) => ("normal map", normal.original.dimensions()),
+            (None, None) => return Err("Albedo map is not loaded".to_string()),
+        };
+        for (name, image) in [
+            ("Height", &self.height_image),
+            ("Normal", &self.normal_image),
+            ("AO", &self.ao_image),
+            ("Roughness", &self.roughness_image),
+            ("Translucency", &self.translucency_image),
+            ("Opacity", &self.opacity_image),
+        ] {
+            if let Some(image) = image {
+                let other = image.original.dimensions();
+                if other != size {
+                    return Err(format!(
+                        "{} map is {}x{} but the {} is {}x{}",
+                        name, other.0, other.1, reference, size.0, size.1
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Resolution and format label used for timing statistics
+    fn timing_key(&self, project: &Project) -> Option<(u32, String)> {
+        let size = self.reference_image()?.original.dimensions();
+        Some(timing::export_key(project, size))
+    }
+
+    // Width of the albedo as exported, after any resize
+    fn albedo_output_width(&self) -> Option<u32> {
+        let (width, height) = self.albedo_image.as_ref()?.original.dimensions();
+        Some(resize::output_size(width, height, self.albedo_output_size).0)
+    }
+
+    fn texel_density(&self) -> Option<TexelDensity> {
+        self.albedo_output_width().map(|width| self.texel_density.compute(width))
+    }
+
+    // The output directory, or the set's folder inside it
+    fn export_directory(&self) -> Option<PathBuf> {
+        let dir = self.output_directory.as_ref()?;
+        Some(file_names::set_directory(dir, self.set_subfolder, &self.set_folder_name, self.albedo_map.as_deref()))
+    }
+
+    // The output format first, then any others written in the same run
+    fn output_formats(&self) -> Vec<OutputFormat> {
+        std::iter::once(self.output_format)
+            .chain(OutputFormat::ALL.into_iter()
+                .filter(|format| *format != self.output_format && self.additional_formats.contains(format)))
+            .collect()
+    }
+
+    fn file_names(&self) -> FileNames {
+        FileNames::new(&self.file_name_template, &self.map_names, file_names::set_name(self.albedo_map.as_deref()))
+    }
+
+    // Every texture an export writes with its size and format, following the
+    // same choices as process_and_save_images. PNGs are counted uncompressed
+    // as an upper bound, DDS sizes are exact including mips.
+    fn planned_textures(&self, file_names: &FileNames) -> Vec<PlannedOutput> {
+        let Some(reference) = self.reference_image() else {
+            return Vec::new();
+        };
+        let (width, height) = reference.original.dimensions();
+        let albedo_size = resize::output_size(width, height, self.albedo_output_size);
+        let normal_size = resize::output_size(width, height, self.normal_output_size);
+        let pack_translucency = self.two_channel_normals && self.pack_translucency && self.packing_layout == layouts::TERRAIN3D;
+        let formats = self.output_formats();
+
+        let mut planned = Vec::new();
+        let mut add = |name: &str, (width, height): (u32, u32), png_channels: u64, bc: image_dds::ImageFormat| {
+            // One file per format
+            for output_format in &formats {
+                let (file, format, estimated_bytes) = if *output_format == OutputFormat::DDS {
+                    // 8 bytes per 4x4 block for BC1/BC4, 16 for the rest
+                    let block_bytes = if matches!(bc, image_dds::ImageFormat::BC1RgbaUnorm | image_dds::ImageFormat::BC4RUnorm) { 8 } else { 16 };
+                    let mips = 32 - width.max(height).leading_zeros();
+                    let data: u64 = (0..mips)
+                        .map(|level| (width >> level).max(1).div_ceil(4) as u64 * (height >> level).max(1).div_ceil(4) as u64 * block_bytes)
+                        .sum();
+                    (file_names.name(name, (width, height), "dds"), format!("{:?}", bc), data + 148)
+                } else {
+                    let format = match png_channels {
+                        1 => "L8",
+                        8 => "RGBA16",
+                        _ => "RGBA8",
+                    };
+                    (file_names.name(name, (width, height), "png"), format.to_string(), width as u64 * height as u64 * png_channels)
+                };
+                planned.push(PlannedOutput { file, dimensions: Some([width, height]), format, estimated_bytes });
+            }
+        };
+
+        let layout = layouts::find(&self.packing_layout).ok();
+        let terrain3d = layout.is_some_and(|layout| layout.is_terrain3d());
+        for texture in layout.map_or(&[][..], |layout| layout.textures) {
+            let size = match texture.size {
+                layouts::SizeGroup::Albedo if self.export_albedo => albedo_size,
+                layouts::SizeGroup::Normal if self.export_normal => normal_size,
+                _ => continue,
+            };
+            let channels = if terrain3d && self.sixteen_bit_png { 8 } else { texture.channels.len() as u64 };
+            match texture.name {
+                "albedo" if terrain3d && self.punch_through_alpha => add("albedo", size, channels, image_dds::ImageFormat::BC1RgbaUnorm),
+                "normal" if terrain3d && self.two_channel_normals => {
+                    let translucency_in_blue = pack_translucency && self.translucency_image.is_some();
+                    add("normal", size, 4, normals::two_channel_dds_format(translucency_in_blue));
+                    add("roughness", size, 1, image_dds::ImageFormat::BC4RUnorm);
+                }
+                name => add(name, size, channels, texture.dds_format()),
+            }
+        }
+        let in_layout = |source| layout.is_some_and(|layout| layout.uses(source));
+        // The extra maps are all made from the albedo
+        let albedo_extras = self.export_albedo;
+        if albedo_extras && self.export_color_map {
+            let size = (self.color_map_resolution, self.color_map_resolution);
+            add("color_map", size, 4, image_dds::ImageFormat::BC3RgbaUnorm);
+        }
+        if albedo_extras && self.export_macro_variation {
+            let resolution = self.macro_variation.resolution.max(2);
+            add("macro_variation", (resolution, resolution), 1, image_dds::ImageFormat::BC4RUnorm);
+        }
+        if albedo_extras && self.export_average_color {
+            add("average_color", (4, 4), 4, image_dds::ImageFormat::BC3RgbaUnorm);
+        }
+        if albedo_extras && self.albedo_alpha_mode == AlbedoAlphaMode::ExportMask {
+            add("albedo_mask", albedo_size, 1, image_dds::ImageFormat::BC4RUnorm);
+        }
+        if albedo_extras && self.opacity_image.is_some() && !in_layout(ChannelSource::Opacity) {
+            add("opacity", albedo_size, 1, image_dds::ImageFormat::BC4RUnorm);
+        }
+        if albedo_extras && self.translucency_image.is_some() && !pack_translucency && !in_layout(ChannelSource::Translucency) {
+            add("translucency", albedo_size, 1, image_dds::ImageFormat::BC4RUnorm);
+        }
+        planned
+    }
+
+    // The textures plus the small files written along with them
+    fn planned_outputs(&self, file_names: &FileNames) -> Vec<PlannedOutput> {
+        if self.reference_image().is_none() {
+            return Vec::new();
+        }
+        let mut planned = self.planned_textures(file_names);
+        let albedo_extras = self.export_albedo;
+        let small_file = |file: &str, dimensions: Option<[u32; 2]>, format: &str| PlannedOutput {
+            file: file.to_string(),
+            dimensions,
+            format: format.to_string(),
+            estimated_bytes: 4096,
+        };
+        if albedo_extras && self.export_palette {
+            planned.push(small_file(&file_names.file("palette", "json"), None, "JSON"));
+            let width = palette::SWATCH_SIZE * self.palette_size.max(1) as u32;
+            planned.push(small_file(&file_names.file("palette", "png"), Some([width, palette::SWATCH_SIZE]), "RGBA8"));
+        }
+        planned.push(small_file(&file_names.sidecar(), None, "JSON"));
+        // The preview needs both textures
+        if self.export_preview && self.export_albedo && self.export_normal {
+            let size = material_preview::PREVIEW_SIZE;
+            planned.push(small_file(&file_names.file("preview", "png"), Some([size, size]), "RGBA8"));
+        }
+        planned.push(small_file(&file_names.manifest(), None, "JSON"));
+        planned
+    }
+
+    // Textures of this export already in the output directory, matched by
+    // name whatever their extension, so PNGs of an earlier export count for a
+    // DDS one. The manifest and other small files are always replaced.
+    fn existing_outputs(&self) -> Vec<String> {
+        let Some(dir) = self.export_directory() else {
+            return Vec::new();
+        };
+        let Ok(entries) = std::fs::read_dir(paths::long_path(&dir)) else {
+            return Vec::new();
+        };
+        let stems: Vec<String> = self.planned_textures(&self.file_names()).into_iter()
+            .filter_map(|output| Some(Path::new(&output.file).file_stem()?.to_string_lossy().to_string()))
+            .collect();
+        let mut existing: Vec<String> = entries.flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|file| Path::new(file).file_stem().is_some_and(|stem| stems.iter().any(|name| stem == name.as_str())))
+            .collect();
+        existing.sort();
+        existing
+    }
+
+    fn estimated_output_bytes(&self) -> u64 {
+        self.planned_outputs(&self.file_names()).iter().map(|output| output.estimated_bytes).sum()
+    }
+
+    // Checks that don't need any processing, done before an export starts
+    fn validate_export(&self) -> Result<(), String> {
+        if self.export_albedo && self.albedo_image.is_none() {
+            return Err("Albedo map is not loaded, the albedo texture is made from it".to_string());
+        }
+        if self.export_normal && self.normal_image.is_none() {
+            return Err("Normal map is not loaded, the normal texture is made from it".to_string());
+        }
+        self.check_matching_sizes()?;
+        layouts::find(&self.packing_layout)?;
+        self.file_names().validate()?;
+        if self.set_subfolder {
+            file_names::validate_subfolder(&self.set_folder_name)?;
+        }
+        if !self.export_albedo && !self.export_normal {
+            return Err("Nothing to export, enable the albedo or the normal textures".to_string());
+        }
+        if let Some(dir) = &self.output_directory {
+            preflight::check_output_directory(dir, self.estimated_output_bytes())?;
+        }
+        Ok(())
+    }
+
+    // Starts an export from the editor, asking first about existing textures
+    // when set to
+    fn start_export(&mut self) {
+        if self.overwrite_mode == OverwriteMode::Ask {
+            let existing = self.existing_outputs();
+            if !existing.is_empty() {
+                self.overwrite_prompt = Some(existing);
+                return;
+            }
+        }
+        if let Err(e) = self.process_and_save_images(self.overwrite_mode) {
+            self.processing_state = ProcessingState::Error(e);
+        }
+    }
+
+    fn process_and_save_images(&mut self, overwrite: OverwriteMode) -> Result<(), String> {
+        self.validate_export()?;
+        let mut file_names = self.file_names();
+        let existing = self.existing_outputs();
+        let mut versioned = false;
+        if !existing.is_empty() {
+            match overwrite {
+                OverwriteMode::Overwrite => {}
+                OverwriteMode::Ask => return Err(overwrite::cannot_ask(&existing)),
+                OverwriteMode::Skip => {
+                    // Not a failure, the set keeps the textures it has
+                    let dir = self.export_directory().unwrap();
+                    self.processing_sender.send(Ok(existing.iter().map(|file| dir.join(file)).collect())).ok();
+                    self.processing_state = ProcessingState::Processing;
+                    return Ok(());
+                }
+                OverwriteMode::Version => {
+                    let dir = self.export_directory().unwrap();
+                    let version = file_names::next_free_version(&dir, |version| {
+                        self.planned_outputs(&file_names.clone().versioned(version)).into_iter().map(|output| output.file).collect()
+                    });
+                    file_names = file_names.versioned(version);
+                    versioned = true;
+                }
+            }
+        }
+        let layout = layouts::find(&self.packing_layout)?;
+        // Only the Terrain3D layout has the packing options below
+        let terrain3d = layout.is_terrain3d();
+        self.run_started = self.timing_key(&self.to_project()).map(|(resolution, format)| (Instant::now(), resolution, format));
+        let output_dir = self.export_directory().unwrap();
+        std::fs::create_dir_all(paths::long_path(&output_dir))
+            .map_err(|e| format!("Failed to create {}: {}", output_dir.display(), e))?;
+        // Sources of the textures that aren't exported are left out, they may not be loaded
+        let albedo = self.albedo_image.as_ref().filter(|_| self.export_albedo).map(|img| img.original.clone());
         let height = self.height_image.as_ref().map(|img| img.original.clone());
-        let normal = self.normal_image.as_ref().unwrap().original.clone();
+        let height_lut = height_alpha::height_lut(&self.height_alpha, &self.parallax);
+        let edge_padding = self.parallax.edge_padding;
+        let (default_height, default_roughness) = (self.default_height, self.default_roughness);
+        let height_estimate = self.height_estimate;
+        let normal = self.normal_image.as_ref().filter(|_| self.export_normal).map(|img| img.original.clone());
         let ao = self.ao_image.clone();
         let roughness = self.roughness_image.clone();
+        let opacity = self.opacity_image.as_ref()
+            .filter(|_| self.export_albedo)
+            .map(|img| img.original.clone());
+        let pack_translucency = self.pack_translucency && terrain3d;
+        // Translucency goes with the normal texture when packed into it
+        let translucency_exported = if pack_translucency && self.two_channel_normals { self.export_normal } else { self.export_albedo };
+        let translucency = self.translucency_image.as_ref()
+            .filter(|_| translucency_exported)
+            .map(|img| img.original.clone());
+        let albedo_size = self.albedo_output_size;
+        let normal_size = self.normal_output_size;
+        let seamless_resize = self.seamless_resize;
         let roughness_format = self.roughness_format;
+        let albedo_alpha_mode = self.albedo_alpha_mode;
+        let roughness_lut = roughness::combined_lut(&self.roughness_curve, &self.roughness_adjust);
         let normal_format = self.normal_map_format;
-        let output_format = self.output_format;
+        let output_formats = self.output_formats();
+        let dds_quality = self.dds_quality;
+        let reconstruct_normal_z = self.reconstruct_normal_z;
+        let two_channel_normals = self.two_channel_normals && terrain3d;
+        let punch_through = (self.punch_through_alpha && terrain3d).then_some(self.alpha_threshold);
+        let sixteen_bit = (self.sixteen_bit_png && output_formats.contains(&OutputFormat::PNG) && terrain3d).then(|| {
+            (self.height_alpha, self.parallax, self.roughness_curve.clone(), self.roughness_adjust)
+        });
+        let (export_albedo, export_normal) = (self.export_albedo, self.export_normal);
+        // The extra maps are made from the albedo, the preview from both
+        let color_map_settings = (self.export_color_map && export_albedo)
+            .then_some((self.color_map_resolution, self.color_map_blur));
+        let macro_settings = (self.export_macro_variation && export_albedo).then_some(self.macro_variation);
+        let export_average_color = self.export_average_color && export_albedo;
+        let palette_size = (self.export_palette && export_albedo).then_some(self.palette_size);
+        let export_preview = self.export_preview && export_albedo && export_normal;
+        let texel_density = self.texel_density();
+        let encode_progress = Arc::new(EncodeProgress::new(
+            self.planned_textures(&file_names).iter()
+                .filter(|output| output.file.ends_with(".dds"))
+                .filter_map(|output| output.dimensions)
+                .map(|[width, height]| dds_encode::mip_texels(width, height))
+                .sum(),
+        ));
+        self.encode_progress = output_formats.contains(&OutputFormat::DDS).then(|| encode_progress.clone());
+        let project = self.to_project();
+        let key_request = (self.use_export_cache || self.skip_up_to_date)
+            .then(|| (project.settings_json(), project.file_names(), project.input_paths()));
+        // Cached files carry the unversioned names
+        let (use_export_cache, skip_up_to_date) = (self.use_export_cache && !versioned, self.skip_up_to_date);
+        let conventions = [
+            (normal_format == NormalMapFormat::DirectX, provenance::DIRECTX_SOURCE_NORMALS),
+            (roughness_format == RoughnessFormat::Smoothness, provenance::SMOOTHNESS_SOURCE),
+            (two_channel_normals, provenance::TWO_CHANNEL_NORMALS),
+            (two_channel_normals && pack_translucency && translucency.is_some(), provenance::TRANSLUCENCY_IN_NORMAL),
+            (terrain3d && (height.is_some() || height_estimate.enabled), provenance::HEIGHT_IN_ALBEDO_ALPHA),
+            (reconstruct_normal_z, provenance::RECONSTRUCTED_NORMAL_Z),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |flags, (_, flag)| flags | flag);
+        let provenance_request = (project.settings_json(), project.input_paths(), conventions);
+        let sidecar = (file_names.sidecar(), project.sidecar_json(&output_dir)?);
+        let manifest_name = file_names.manifest();
+        let snapshot = history::Snapshot::new(&project.settings_json(), project.named_inputs());
+        let link_mode = self.link_mode;
+        let post_export_command = Some(self.post_export_command.trim().to_string()).filter(|c| !c.is_empty());
+        let final_dir = output_dir.clone();
+        let existing_dir = output_dir.clone();
+        let up_to_date_tx = self.processing_sender.clone();
         let tx = self.processing_sender.clone();
+        let history_dir = output_dir.clone();
+        let history_manifest = manifest_name.clone();
+        // Every export that writes, from the editor or without a window, puts
+        // its files in place through here. Up to date and skipped sets leave
+        // the files and their history as they were.
+        let commit = move |staged: StagedOutput| {
+            let files = staged.commit()?;
+            // History is only for auditing, an export doesn't fail over it
+            history::record(&history_dir, &history_manifest, snapshot).ok();
+            Ok(files)
+        };
+        let send = move |result: Result<Vec<PathBuf>, String>| {
+            let result = result.and_then(|files| match &post_export_command {
+                Some(command) => post_export::run(command, &final_dir, &files).map(|()| files),
+                None => Ok(files),
+            });
+            tx.send(result).ok();
+        };
 
         self.processing_state = ProcessingState::Processing;
         
         thread::spawn(move || {
-            let result = (move || {
-                // Process albedo + AO
-                let mut final_texture = albedo.to_rgba8();
-                let width = final_texture.width();
-                
-                // Convert to vec for parallel processing
-                let mut pixels: Vec<_> = final_texture.pixels_mut().collect();
-                
-                // If AO map exists, multiply it with albedo
-                if let Some(ao_image) = ao {
-                    let ao = ao_image.original.to_luma8();
-                    pixels.par_iter_mut().enumerate().for_each(|(i, pixel)| {
-                        let x = (i % width as usize) as u32;
-                        let y = (i / width as usize) as u32;
-                        let ao_val = ao.get_pixel(x, y)[0] as f32 / 255.0;
-                        pixel[0] = (pixel[0] as f32 * ao_val) as u8;
-                        pixel[1] = (pixel[1] as f32 * ao_val) as u8;
-                        pixel[2] = (pixel[2] as f32 * ao_val) as u8;
-                    });
+            let export_key = key_request
+                .and_then(|(settings, names, inputs)| export_cache::cache_key(&settings, &names, &inputs).ok());
+            if let Some(files) = export_key.as_ref().filter(|_| skip_up_to_date)
+                .and_then(|key| ExportManifest::up_to_date_files(&existing_dir, &manifest_name, key)) {
+                // Nothing changed, so there's nothing for the post-export command to pick up
+                up_to_date_tx.send(Ok(files)).ok();
+                return;
+            }
+            let staged = match StagedOutput::new(&output_dir) {
+                Ok(staged) => staged,
+                Err(e) => {
+                    send(Err(e));
+                    return;
+                }
+            };
+            let cache_key = export_key.clone().filter(|_| use_export_cache);
+            if let Some(key) = &cache_key {
+                if let Ok(true) = export_cache::restore(key, staged.path(), link_mode) {
+                    // The entry's sidecar points at the sources and folder of
+                    // whichever export stored it, this one gets its own. Both
+                    // files are removed first, they may be links into the cache.
+                    let (sidecar_name, sidecar_json) = &sidecar;
+                    let dir = staged.path();
+                    let refreshed = (|| {
+                        let mut manifest = ExportManifest::for_partial_update(dir, &manifest_name);
+                        for name in [sidecar_name, &manifest_name] {
+                            std::fs::remove_file(dir.join(name)).ok();
+                        }
+                        std::fs::write(dir.join(sidecar_name), sidecar_json).map_err(|e| e.to_string())?;
+                        manifest.record_files(dir, &manifest_name)?;
+                        manifest.save(dir, &manifest_name)
+                    })();
+                    send(refreshed.and_then(|()| commit(staged)));
+                    return;
                 }
+            }
+            let output_dir = staged.path().to_path_buf();
+            let result = (move || {
+                let file_name = |map: &str, size: (u32, u32), ext: &str| output_dir.join(file_names.name(map, size, ext));
+                let save_dds = |img: &DynamicImage, path: PathBuf, format| {
+                    dds_encode::save(img, &path, format, dds_quality.encoder_quality(), &encode_progress)
+                };
 
-                // Add height as alpha channel if it exists
-                if let Some(height_img) = height {
-                    let height = height_img.to_luma8();
-                    pixels.par_iter_mut().enumerate().for_each(|(i, pixel)| {
-                        let x = (i % width as usize) as u32;
-                        let y = (i / width as usize) as u32;
-                        pixel[3] = height.get_pixel(x, y)[0];
-                    });
-                } else {
-                    // Set alpha to full opacity if no height map
-                    pixels.par_iter_mut().for_each(|pixel| {
-                        pixel[3] = 255;
+                // Packed textures at full precision, computed from the untouched sources
+                let (albedo16, normal16) = sixteen_bit.map_or((None, None), |(height_alpha, parallax, curve, adjust)| {
+                    let albedo_height = albedo.as_ref().map(|albedo| {
+                        let estimated = (height.is_none() && height_estimate.enabled).then(|| {
+                            DynamicImage::ImageLuma8(height_alpha::estimate_height(&albedo.to_rgba8(), &height_estimate))
+                        });
+                        pack16::pack_albedo_height(
+                            albedo,
+                            ao.as_ref().map(|img| &img.original),
+                            height.as_ref().or(estimated.as_ref()),
+                            albedo_alpha_mode == AlbedoAlphaMode::Unpremultiply,
+                            &height_alpha,
+                            &parallax,
+                            default_height,
+                            albedo_size,
+                            seamless_resize,
+                        )
                     });
-                }
+                    let normal_roughness = normal.as_ref().map(|normal| pack16::pack_normal_roughness(
+                        normal,
+                        roughness.as_ref().map(|img| &img.original),
+                        reconstruct_normal_z,
+                        normal_format == NormalMapFormat::DirectX,
+                        roughness_format == RoughnessFormat::Smoothness,
+                        &curve,
+                        &adjust,
+                        default_roughness,
+                        normal_size,
+                        seamless_resize,
+                    ));
+                    (albedo_height, normal_roughness)
+                });
 
-                // Process normal map with roughness
-                let mut normal_image = normal.to_rgba8();
-                let width = normal_image.width();
-                let height = normal_image.height();  // Get height before mutable borrow
-                let mut pixels: Vec<_> = normal_image.pixels_mut().collect();
+                // Process albedo + AO, skipped when only the normal texture is updated
+                let albedo_textures = albedo.as_ref().map(|albedo| {
+                    let mut final_texture = albedo.to_rgba8();
 
-                // Process DirectX normal map if needed
-                if normal_format == NormalMapFormat::DirectX {
-                    pixels.par_iter_mut().for_each(|p| {
-                        p[1] = 255 - p[1]; // Invert green channel
+                    // Keep the source alpha as its own mask before height overwrites it
+                    let albedo_mask = (albedo_alpha_mode == AlbedoAlphaMode::ExportMask).then(|| {
+                        image::GrayImage::from_fn(final_texture.width(), final_texture.height(), |x, y| {
+                            image::Luma([final_texture.get_pixel(x, y)[3]])
+                        })
                     });
-                }
 
-                // Add roughness as alpha channel
-                if let Some(roughness_img) = roughness {
-                    let roughness = roughness_img.original.to_luma8();
-                    pixels.par_iter_mut().enumerate().for_each(|(i, pixel)| {
-                        let x = (i % width as usize) as u32;
-                        let y = (i / width as usize) as u32;
-                        let value = roughness.get_pixel(x, y)[0];
-                        // Store as smoothness - invert if it's a roughness map
-                        pixel[3] = match roughness_format {
-                            RoughnessFormat::Roughness => value, // Invert roughness to smoothness
-                            RoughnessFormat::Smoothness => 255 - value, // Keep smoothness as-is
-                        };
+                    if albedo_alpha_mode == AlbedoAlphaMode::Unpremultiply {
+                        pack8::unpremultiply(&mut final_texture);
+                    }
+
+                    // If AO map exists, multiply it with albedo, unless the layout keeps it apart
+                    if let Some(ao_image) = ao.as_ref().filter(|_| layout.bakes_ao()) {
+                        pack8::multiply_ao(&mut final_texture, &ao_image.original.to_luma8());
+                    }
+
+                    // Without a height map, optionally estimate one from the untouched albedo
+                    let height = height.map(|img| img.to_luma8()).or_else(|| {
+                        height_estimate.enabled
+                            .then(|| height_alpha::estimate_height(&albedo.to_rgba8(), &height_estimate))
                     });
+                    pack8::pack_height(&mut final_texture, height.as_ref(), &height_lut, default_height, edge_padding);
+                    (final_texture, albedo_mask)
+                });
+
+                // Low frequency color map from the AO-applied albedo
+                let color_map = albedo_textures.as_ref().zip(color_map_settings).map(|((final_texture, _), (resolution, blur))| {
+                    colormap::generate_color_map(final_texture, resolution, blur)
+                });
+                let macro_map = albedo_textures.as_ref().zip(macro_settings).map(|((final_texture, _), settings)| {
+                    macro_variation::generate_macro_variation(final_texture, &settings)
+                });
+
+                // A partial export keeps what the last one recorded about the other texture
+                let mut manifest = if export_albedo && export_normal {
+                    ExportManifest::new()
                 } else {
-                    // Set default smoothness if no map provided (0.5)
-                    pixels.par_iter_mut().for_each(|pixel| {
-                        pixel[3] = 128;
-                    });
+                    ExportManifest::for_partial_update(&existing_dir, &manifest_name)
+                };
+                manifest.export_key = export_key;
+                if let Some((final_texture, _)) = &albedo_textures {
+                    manifest.average_color = Some(manifest::average_color(final_texture));
+                }
+                manifest.texel_density = texel_density;
+                let average_texture = manifest.average_color.as_ref()
+                    .filter(|_| export_average_color)
+                    .map(manifest::average_color_texture);
+
+                // Palette for matching vegetation and props to the terrain
+                if let Some(((final_texture, _), size)) = albedo_textures.as_ref().zip(palette_size) {
+                    palette::save_palette(
+                        &palette::extract_palette(final_texture, size),
+                        &output_dir.join(file_names.file("palette", "json")),
+                        &output_dir.join(file_names.file("palette", "png")),
+                    )?;
                 }
 
-                // Save images based on format
-                match output_format {
-                    OutputFormat::PNG => {
-                        final_texture.save(output_dir.join("albedo.png"))
-                            .map_err(|e| e.to_string())?;
-                        
-                        // Create RGBA image buffer with explicit type
-                        let normal_buffer = ImageBuffer::<image::Rgba<u8>, Vec<u8>>::from_vec(
-                            width,
-                            height,  // Use stored height value
-                            pixels.into_iter().flat_map(|p| p.0.to_vec()).collect()
-                        ).unwrap();
-                        
-                        normal_buffer.save(output_dir.join("normal.png"))
-                            .map_err(|e| e.to_string())?;
+                // Everything sampled with albedo coordinates follows its size
+                let (final_texture, albedo_mask) = albedo_textures
+                    .map(|(final_texture, albedo_mask)| (
+                        resize::downsample(&final_texture, albedo_size, seamless_resize),
+                        albedo_mask.map(|mask| resize::downsample(&mask, albedo_size, seamless_resize)),
+                    ))
+                    .unzip();
+                let albedo_mask = albedo_mask.flatten();
+                let opacity = opacity.map(|img| resize::downsample(&img.to_luma8(), albedo_size, seamless_resize));
+
+                // Process normal map with roughness, skipped when only the albedo is updated
+                let normal_buffer = normal.as_ref().map(|normal| {
+                    let mut normal_buffer = normal.to_rgba8();
+                    if reconstruct_normal_z {
+                        normals::reconstruct_z(&mut normal_buffer);
                     }
-                    OutputFormat::DDS => {
-                        Self::save_as_dds(&final_texture.into(), output_dir.join("albedo.dds"))?;
-                        
-                        // Create RGBA image buffer with explicit type
-                        let normal_buffer = ImageBuffer::<image::Rgba<u8>, Vec<u8>>::from_vec(
-                            width,
-                            height,  // Use stored height value
-                            pixels.into_iter().flat_map(|p| p.0.to_vec()).collect()
-                        ).unwrap();
-                        
-                        Self::save_as_dds(
-                            &DynamicImage::ImageRgba8(normal_buffer),
-                            output_dir.join("normal.dds")
-                        )?;
+                    pack8::pack_normal_roughness(
+                        &mut normal_buffer,
+                        roughness.map(|img| img.original.to_luma8()).as_ref(),
+                        normal_format == NormalMapFormat::DirectX,
+                        roughness_format == RoughnessFormat::Smoothness,
+                        &roughness_lut,
+                        default_roughness,
+                    );
+                    resize::downsample_normal(&normal_buffer, normal_size, seamless_resize)
+                });
+                if let Some((final_texture, normal_buffer)) = final_texture.as_ref().zip(normal_buffer.as_ref()).filter(|_| export_preview) {
+                    material_preview::render(final_texture, normal_buffer, material_preview::PREVIEW_SIZE)
+                        .save(output_dir.join(file_names.file("preview", "png")))
+                        .map_err(|e| e.to_string())?;
+                }
+                let mut two_channel = normal_buffer.as_ref()
+                    .filter(|_| two_channel_normals)
+                    .map(normals::split_two_channel);
+
+                // Two-channel normals leave blue free for translucency
+                let translucency_in_normal = two_channel.is_some() && pack_translucency && translucency.is_some();
+                let translucency = translucency.map(|img| img.to_luma8());
+                let translucency = match (&mut two_channel, translucency) {
+                    (Some((normal_xy, _)), Some(translucency)) if pack_translucency => {
+                        let translucency = resize::downsample(&translucency, normal_size, seamless_resize);
+                        for (pixel, value) in normal_xy.pixels_mut().zip(translucency.pixels()) {
+                            pixel[2] = value[0];
+                        }
+                        None
+                    }
+                    (_, translucency) => translucency.map(|img| resize::downsample(&img, albedo_size, seamless_resize)),
+                };
+
+                // The layout's textures are assembled channel by channel from the packed maps
+                let layout_textures = {
+                    let ambient_occlusion = ao.as_ref().map(|img| img.original.to_luma8());
+                    let sources = PackSources {
+                        albedo: final_texture.as_ref(),
+                        normal: normal_buffer.as_ref(),
+                        ambient_occlusion: ambient_occlusion.as_ref(),
+                        opacity: opacity.as_ref(),
+                        translucency: translucency.as_ref(),
+                    };
+                    layout.pack(&sources)
+                };
+                let opacity = opacity.filter(|_| !layout.uses(ChannelSource::Opacity));
+                let translucency = translucency.filter(|_| !layout.uses(ChannelSource::Translucency));
+
+                // Every format is written from the same packed buffers
+                for format in &output_formats {
+                    match format {
+                        OutputFormat::PNG => {
+                            // The Terrain3D options only ever apply to its albedo and normal
+                            for (texture, image) in &layout_textures {
+                                match (texture.name, &albedo16, &normal16, &two_channel) {
+                                    ("albedo", Some(albedo16), _, _) => {
+                                        albedo16.save(file_name("albedo", albedo16.dimensions(), "png"))
+                                            .map_err(|e| e.to_string())?;
+                                    }
+                                    ("normal", _, _, Some((normal_xy, roughness))) => {
+                                        normal_xy.save(file_name("normal", normal_xy.dimensions(), "png"))
+                                            .map_err(|e| e.to_string())?;
+                                        roughness.save(file_name("roughness", roughness.dimensions(), "png"))
+                                            .map_err(|e| e.to_string())?;
+                                    }
+                                    ("normal", _, Some(normal16), _) => {
+                                        normal16.save(file_name("normal", normal16.dimensions(), "png"))
+                                            .map_err(|e| e.to_string())?;
+                                    }
+                                    (name, ..) => {
+                                        image.save(file_name(name, image.dimensions(), "png"))
+                                            .map_err(|e| e.to_string())?;
+                                    }
+                                }
+                            }
+
+                            if let Some(color_map) = &color_map {
+                                color_map.save(file_name("color_map", color_map.dimensions(), "png"))
+                                    .map_err(|e| e.to_string())?;
+                            }
+
+                            if let Some(macro_map) = &macro_map {
+                                let path = file_name("macro_variation", macro_map.dimensions(), "png");
+                                DynamicImage::ImageRgba8(macro_map.clone()).to_luma8()
+                                    .save(path)
+                                    .map_err(|e| e.to_string())?;
+                            }
+
+                            if let Some(texture) = &average_texture {
+                                texture.save(file_name("average_color", texture.dimensions(), "png"))
+                                    .map_err(|e| e.to_string())?;
+                            }
+
+                            if let Some(mask) = &albedo_mask {
+                                mask.save(file_name("albedo_mask", mask.dimensions(), "png"))
+                                    .map_err(|e| e.to_string())?;
+                            }
+
+                            if let Some(opacity) = &opacity {
+                                opacity.save(file_name("opacity", opacity.dimensions(), "png"))
+                                    .map_err(|e| e.to_string())?;
+                            }
+
+                            if let Some(translucency) = &translucency {
+                                translucency.save(file_name("translucency", translucency.dimensions(), "png"))
+                                    .map_err(|e| e.to_string())?;
+                            }
+                        }
+                        OutputFormat::DDS => {
+                            for (texture, image) in &layout_textures {
+                                match (texture.name, punch_through, &two_channel) {
+                                    ("albedo", Some(threshold), _) => {
+                                        // BC1 only keeps fully opaque or fully transparent texels
+                                        let mut masked = image.to_rgba8();
+                                        masked.par_chunks_mut(4).for_each(|p| {
+                                            p[3] = if p[3] >= threshold { 255 } else { 0 };
+                                        });
+                                        let path = file_name("albedo", masked.dimensions(), "dds");
+                                        save_dds(&DynamicImage::ImageRgba8(masked), path, image_dds::ImageFormat::BC1RgbaUnorm)?;
+                                    }
+                                    ("normal", _, Some((normal_xy, roughness))) => {
+                                        let path = file_name("normal", normal_xy.dimensions(), "dds");
+                                        save_dds(&DynamicImage::ImageRgba8(normal_xy.clone()), path, normals::two_channel_dds_format(translucency_in_normal))?;
+                                        let path = file_name("roughness", roughness.dimensions(), "dds");
+                                        save_dds(&DynamicImage::ImageLuma8(roughness.clone()), path, image_dds::ImageFormat::BC4RUnorm)?;
+                                    }
+                                    (name, ..) => {
+                                        save_dds(image, file_name(name, image.dimensions(), "dds"), texture.dds_format())?;
+                                    }
+                                }
+                            }
+
+                            if let Some(color_map) = &color_map {
+                                let path = file_name("color_map", color_map.dimensions(), "dds");
+                                save_dds(&DynamicImage::ImageRgba8(color_map.clone()), path, image_dds::ImageFormat::BC3RgbaUnorm)?;
+                            }
+
+                            if let Some(macro_map) = &macro_map {
+                                let path = file_name("macro_variation", macro_map.dimensions(), "dds");
+                                save_dds(&DynamicImage::ImageRgba8(macro_map.clone()), path, image_dds::ImageFormat::BC4RUnorm)?;
+                            }
+
+                            if let Some(texture) = &average_texture {
+                                let path = file_name("average_color", texture.dimensions(), "dds");
+                                save_dds(&DynamicImage::ImageRgba8(texture.clone()), path, image_dds::ImageFormat::BC3RgbaUnorm)?;
+                            }
+
+                            if let Some(mask) = &albedo_mask {
+                                let path = file_name("albedo_mask", mask.dimensions(), "dds");
+                                save_dds(&DynamicImage::ImageLuma8(mask.clone()), path, image_dds::ImageFormat::BC4RUnorm)?;
+                            }
+
+                            if let Some(opacity) = &opacity {
+                                let path = file_name("opacity", opacity.dimensions(), "dds");
+                                save_dds(&DynamicImage::ImageLuma8(opacity.clone()), path, image_dds::ImageFormat::BC4RUnorm)?;
+                            }
+
+                            if let Some(translucency) = &translucency {
+                                let path = file_name("translucency", translucency.dimensions(), "dds");
+                                save_dds(&DynamicImage::ImageLuma8(translucency.clone()), path, image_dds::ImageFormat::BC4RUnorm)?;
+                            }
+                        }
                     }
                 }
 
+                if output_formats.contains(&OutputFormat::DDS) {
+                    let (settings, inputs, conventions) = provenance_request;
+                    let provenance = Provenance::new(&settings, &inputs, conventions)?;
+                    provenance::stamp_outputs(&output_dir, &provenance)?;
+                }
+
+                let (sidecar_name, sidecar_json) = sidecar;
+                std::fs::write(output_dir.join(sidecar_name), sidecar_json).map_err(|e| e.to_string())?;
+                manifest.record_files(&output_dir, &manifest_name)?;
+                manifest.save(&output_dir, &manifest_name)?;
+
                 Ok(())
-            })();
+            })().and_then(|()| {
+                if let Some(key) = &cache_key {
+                    // A failed cache write only costs a future reprocess
+                    export_cache::store(key, staged.path()).ok();
+                }
+                commit(staged)
+            });
 
-            tx.send(result).ok();
+            send(result);
         });
 
         Ok(())
     }
 
+    // Roughness as it will be packed, rebuilt only when the LUT or format changes
+    fn update_roughness_preview(&mut self, ctx: &Context) {
+        let Some(roughness) = &self.roughness_image else {
+            self.roughness_preview = None;
+            self.roughness_preview_key = None;
+            self.roughness_swatches = None;
+            return;
+        };
+        let lut = roughness::combined_lut(&self.roughness_curve, &self.roughness_adjust);
+        let key = (lut, self.roughness_format);
+        if self.roughness_preview_key == Some(key) {
+            return;
+        }
+        let source = DynamicImage::ImageRgba8(roughness.downscaled.clone()).to_luma8();
+        let palette = self.grayscale_palette.lut();
+        let pixels = source.pixels()
+            .map(|p| {
+                let value = match self.roughness_format {
+                    RoughnessFormat::Roughness => p[0],
+                    RoughnessFormat::Smoothness => 255 - p[0],
+                };
+                GrayscalePalette::color(&palette, lut[value as usize])
+            })
+            .collect();
+        let color_image = ColorImage {
+            size: [source.width() as _, source.height() as _],
+            pixels,
+        };
+        self.roughness_preview = Some(ctx.load_texture("roughness_preview", color_image, Default::default()));
+        self.roughness_preview_key = Some(key);
+        self.roughness_swatches = Some(roughness::sample_swatches(&source));
+    }
+
     // Add new methods to clear image states
     fn clear_height_map(&mut self) {
+        self.packed_channels.height = None;
         self.height_map = None;
         self.height_image = None;
         self.height_texture = None;
         self.height_load_state = ImageLoadState::NotLoaded;
+        self.slope_tint.invalidate();
     }
 
     fn clear_ao_map(&mut self) {
+        self.packed_channels.ambient_occlusion = None;
         self.ambient_occlusion_map = None;
         self.ao_image = None;
         self.ao_texture = None;
@@ -389,27 +1485,627 @@ impl TerrainApp {
     }
 
     fn clear_roughness_map(&mut self) {
+        self.packed_channels.roughness = None;
         self.roughness_map = None;
         self.roughness_image = None;
         self.roughness_texture = None;
         self.roughness_load_state = ImageLoadState::NotLoaded;
     }
+
+    fn clear_opacity_map(&mut self) {
+        self.opacity_map = None;
+        self.opacity_image = None;
+        self.opacity_texture = None;
+        self.opacity_load_state = ImageLoadState::NotLoaded;
+    }
+
+    fn clear_translucency_map(&mut self) {
+        self.translucency_map = None;
+        self.translucency_image = None;
+        self.translucency_texture = None;
+        self.translucency_load_state = ImageLoadState::NotLoaded;
+    }
+
+    fn clear_albedo_map(&mut self) {
+        self.albedo_map = None;
+        self.albedo_image = None;
+        self.albedo_texture = None;
+        self.albedo_load_state = ImageLoadState::NotLoaded;
+    }
+
+    fn clear_normal_map(&mut self) {
+        self.normal_map = None;
+        self.normal_image = None;
+        self.normal_texture = None;
+        self.normal_load_state = ImageLoadState::NotLoaded;
+    }
+
+    // Loads a map into the given slot, or clears the slot for None
+    fn set_input_map(&mut self, image_type: &str, path: Option<PathBuf>) {
+        let Some(path) = path else {
+            match image_type {
+                "albedo" => self.clear_albedo_map(),
+                "height" => self.clear_height_map(),
+                "normal" => self.clear_normal_map(),
+                "ao" => self.clear_ao_map(),
+                "roughness" => self.clear_roughness_map(),
+                "translucency" => self.clear_translucency_map(),
+                "opacity" => self.clear_opacity_map(),
+                _ => {}
+            }
+            return;
+        };
+        let (slot, state) = match image_type {
+            "albedo" => (&mut self.albedo_map, &mut self.albedo_load_state),
+            "height" => (&mut self.height_map, &mut self.height_load_state),
+            "normal" => (&mut self.normal_map, &mut self.normal_load_state),
+            "ao" => (&mut self.ambient_occlusion_map, &mut self.ao_load_state),
+            "roughness" => (&mut self.roughness_map, &mut self.roughness_load_state),
+            "translucency" => (&mut self.translucency_map, &mut self.translucency_load_state),
+            "opacity" => (&mut self.opacity_map, &mut self.opacity_load_state),
+            _ => return,
+        };
+        *slot = Some(path.clone());
+        *state = ImageLoadState::Loading;
+        self.load_image(path, image_type.to_string());
+    }
+
+    fn input_slot(&self, image_type: &str) -> (Option<&PathBuf>, Option<&ProcessedImage>) {
+        match image_type {
+            "albedo" => (self.albedo_map.as_ref(), self.albedo_image.as_ref()),
+            "height" => (self.height_map.as_ref(), self.height_image.as_ref()),
+            "normal" => (self.normal_map.as_ref(), self.normal_image.as_ref()),
+            "ao" => (self.ambient_occlusion_map.as_ref(), self.ao_image.as_ref()),
+            "roughness" => (self.roughness_map.as_ref(), self.roughness_image.as_ref()),
+            "translucency" => (self.translucency_map.as_ref(), self.translucency_image.as_ref()),
+            "opacity" => (self.opacity_map.as_ref(), self.opacity_image.as_ref()),
+            _ => (None, None),
+        }
+    }
+
+    // Source of every filled slot, read the way load_image reads it
+    fn plugin_sources(&self) -> BTreeMap<String, plugins::Source> {
+        project::SLOTS.into_iter()
+            .filter_map(|image_type| {
+                let path = self.input_slot(image_type).0?.clone();
+                let source = plugins::Source {
+                    frame: self.input_frames.get(&path).copied().unwrap_or(0),
+                    channel: self.packed_channels.channel_for(image_type),
+                    path,
+                };
+                Some((image_type.to_string(), source))
+            })
+            .collect()
+    }
+
+    // Format, crop and frame details under a slot. Returns a newly picked
+    // frame for multi-frame sources and whether to load it yet.
+    fn show_source_details(&mut self, ui: &mut egui::Ui, image_type: &str) -> Option<(usize, bool)> {
+        self.show_encoding(ui, image_type);
+        let (Some(path), Some(image)) = self.input_slot(image_type) else {
+            return None;
+        };
+        source_info::show(ui, &image.info, image_type);
+        if let Some((width, height)) = image.cropped_from {
+            ui.label(format!("Cropped from {}x{}", width, height));
+        }
+        if image.frame_count <= 1 {
+            return None;
+        }
+        let mut frame = self.input_frames.get(path).copied().unwrap_or(0);
+        let response = ui.add(egui::Slider::new(&mut frame, 0..=image.frame_count - 1).text(format!("Frame of {}", image.frame_count)))
+            .on_hover_text("Animated and multi-page files only use the selected frame");
+        // Decoding a frame is slow, so wait until the slider is let go
+        let load = response.drag_stopped() || (response.changed() && !response.dragged());
+        (response.changed() || load).then_some((frame, load))
+    }
+
+    // Declared encoding of a slot's file, reloading it when that changes
+    fn show_encoding(&mut self, ui: &mut egui::Ui, image_type: &str) {
+        let Some(path) = self.input_slot(image_type).0.cloned() else {
+            return;
+        };
+        let mut encoding = self.input_encodings.get(image_type).copied().unwrap_or_default();
+        if encoding::show(ui, image_type, &mut encoding) {
+            if encoding == MapEncoding::Auto {
+                self.input_encodings.remove(image_type);
+            } else {
+                self.input_encodings.insert(image_type.to_string(), encoding);
+            }
+            self.set_input_map(image_type, Some(path));
+        }
+    }
+
+    // True while the normal map's alpha holds data that nothing reads and the
+    // export would overwrite with roughness
+    fn normal_alpha_unused(&self) -> bool {
+        let Some(path) = &self.normal_map else {
+            return false;
+        };
+        let from_alpha = |slot: &Option<PathBuf>, channel: Option<Channel>| {
+            slot.as_ref() == Some(path) && channel == Some(Channel::Alpha)
+        };
+        self.normal_image.as_ref().is_some_and(|image| image.info.alpha_data)
+            && self.discarded_normal_alpha.as_ref() != Some(path)
+            && !from_alpha(&self.roughness_map, self.packed_channels.roughness)
+            && !from_alpha(&self.height_map, self.packed_channels.height)
+    }
+
+    fn show_normal_alpha_options(&mut self, ui: &mut egui::Ui) {
+        if !self.normal_alpha_unused() {
+            return;
+        }
+        let Some(path) = self.normal_map.clone() else {
+            return;
+        };
+        ui.colored_label(egui::Color32::YELLOW, "Alpha holds data, e.g. gloss or height, that the export replaces with roughness");
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Use it as:");
+            for (label, image_type, format) in [
+                ("Roughness", "roughness", Some(RoughnessFormat::Roughness)),
+                ("Smoothness", "roughness", Some(RoughnessFormat::Smoothness)),
+                ("Height", "height", None),
+            ] {
+                if ui.button(label).clicked() {
+                    if let Some(format) = format {
+                        self.roughness_format = format;
+                    }
+                    self.packed_channels.set(image_type, Some(Channel::Alpha));
+                    self.set_input_map(image_type, Some(path.clone()));
+                }
+            }
+            if ui.button("Discard").on_hover_text("Overwrite it with roughness as usual").clicked() {
+                self.discarded_normal_alpha = Some(path.clone());
+            }
+        });
+    }
+
+    fn select_frame(&mut self, image_type: &str, (frame, load): (usize, bool)) {
+        let Some(path) = self.input_slot(image_type).0.cloned() else {
+            return;
+        };
+        self.input_frames.insert(path.clone(), frame);
+        if load {
+            self.set_input_map(image_type, Some(path));
+        }
+    }
+
+    // Points every mapped slot at the packed texture, each loading only its
+    // own channel. Unmapped slots keep whatever they had.
+    fn load_packed_input(&mut self, path: PathBuf) {
+        for (image_type, channel) in self.packed_mapping.slots() {
+            if channel.is_some() {
+                self.packed_channels.set(image_type, channel);
+                self.set_input_map(image_type, Some(path.clone()));
+            }
+        }
+    }
+
+    fn to_project(&self) -> Project {
+        Project {
+            albedo_map: self.albedo_map.clone(),
+            height_map: self.height_map.clone(),
+            ambient_occlusion_map: self.ambient_occlusion_map.clone(),
+            normal_map: self.normal_map.clone(),
+            roughness_map: self.roughness_map.clone(),
+            translucency_map: self.translucency_map.clone(),
+            opacity_map: self.opacity_map.clone(),
+            normal_map_format: self.normal_map_format,
+            library_conventions: self.library_conventions,
+            rectangular_mode: self.rectangular_mode,
+            auto_crop: self.auto_crop,
+            input_frames: self.input_frames.clone(),
+            input_encodings: self.input_encodings.clone(),
+            script: self.script.clone(),
+            albedo_alpha_mode: self.albedo_alpha_mode,
+            roughness_format: self.roughness_format,
+            reconstruct_normal_z: self.reconstruct_normal_z,
+            roughness_adjust: self.roughness_adjust,
+            roughness_curve: self.roughness_curve.clone(),
+            height_alpha: self.height_alpha,
+            parallax: self.parallax,
+            default_height: self.default_height,
+            default_roughness: self.default_roughness,
+            height_estimate: self.height_estimate,
+            output_directory: self.output_directory.clone(),
+            set_subfolder: self.set_subfolder,
+            set_folder_name: self.set_folder_name.clone(),
+            output_directory_absolute: None,
+            export_targets: self.export_targets.iter()
+                .map(|(name, dir)| (name.clone(), ExportTarget { directory: dir.clone(), absolute: None }))
+                .collect(),
+            output_format: self.output_format,
+            additional_formats: self.additional_formats.clone(),
+            dds_quality: self.dds_quality,
+            packing_layout: self.packing_layout.clone(),
+            file_name_template: self.file_name_template.clone(),
+            map_names: self.map_names.clone(),
+            overwrite_mode: self.overwrite_mode,
+            export_albedo: self.export_albedo,
+            export_normal: self.export_normal,
+            two_channel_normals: self.two_channel_normals,
+            pack_translucency: self.pack_translucency,
+            packed_channels: self.packed_channels,
+            albedo_output_size: self.albedo_output_size,
+            normal_output_size: self.normal_output_size,
+            seamless_resize: self.seamless_resize,
+            punch_through_alpha: self.punch_through_alpha,
+            sixteen_bit_png: self.sixteen_bit_png,
+            post_export_command: self.post_export_command.clone(),
+            use_export_cache: self.use_export_cache,
+            skip_up_to_date: self.skip_up_to_date,
+            link_mode: self.link_mode,
+            alpha_threshold: self.alpha_threshold,
+            export_color_map: self.export_color_map,
+            color_map_resolution: self.color_map_resolution,
+            color_map_blur: self.color_map_blur,
+            export_average_color: self.export_average_color,
+            export_palette: self.export_palette,
+            export_preview: self.export_preview,
+            palette_size: self.palette_size,
+            export_macro_variation: self.export_macro_variation,
+            macro_variation: self.macro_variation,
+            texel_density: self.texel_density,
+            review_status: self.review_status,
+            notes: self.notes.clone(),
+        }
+    }
+
+    // Reloads every slot, so files edited since they were loaded are picked up
+    fn apply_project(&mut self, project: Project) {
+        self.apply_project_with(project, true);
+    }
+
+    // Everything that decides how a slot's file is decoded
+    fn slot_load_key(&self, image_type: &str, path: Option<&PathBuf>) -> String {
+        format!(
+            "{:?}",
+            (
+                path,
+                self.packed_channels.channel_for(image_type),
+                path.and_then(|path| self.input_frames.get(path)),
+                self.input_encodings.get(image_type),
+                self.rectangular_mode,
+                self.auto_crop,
+                &self.script,
+            ),
+        )
+    }
+
+    fn apply_project_with(&mut self, project: Project, reload_unchanged: bool) {
+        let slots = [
+            ("albedo", project.albedo_map),
+            ("height", project.height_map),
+            ("ao", project.ambient_occlusion_map),
+            ("normal", project.normal_map),
+            ("roughness", project.roughness_map),
+            ("translucency", project.translucency_map),
+            ("opacity", project.opacity_map),
+        ];
+        let loaded_keys = slots.each_ref().map(|(image_type, _)| self.slot_load_key(image_type, self.input_slot(image_type).0));
+        self.rectangular_mode = project.rectangular_mode;
+        self.auto_crop = project.auto_crop;
+        self.input_frames = project.input_frames;
+        self.input_encodings = project.input_encodings;
+        self.script = project.script;
+        self.albedo_alpha_mode = project.albedo_alpha_mode;
+        self.packed_channels = project.packed_channels;
+        for ((image_type, path), loaded_key) in slots.into_iter().zip(loaded_keys) {
+            if reload_unchanged || self.slot_load_key(image_type, path.as_ref()) != loaded_key {
+                self.set_input_map(image_type, path);
+            }
+        }
+        self.normal_map_format = project.normal_map_format;
+        self.library_conventions = project.library_conventions;
+        self.roughness_format = project.roughness_format;
+        self.reconstruct_normal_z = project.reconstruct_normal_z;
+        self.roughness_adjust = project.roughness_adjust;
+        self.roughness_curve = project.roughness_curve;
+        self.height_alpha = project.height_alpha;
+        self.parallax = project.parallax;
+        self.default_height = project.default_height;
+        self.default_roughness = project.default_roughness;
+        self.height_estimate = project.height_estimate;
+        self.output_directory = project.output_directory;
+        self.set_subfolder = project.set_subfolder;
+        self.set_folder_name = project.set_folder_name;
+        self.export_targets = project.export_targets.into_iter()
+            .map(|(name, target)| (name, target.directory))
+            .collect();
+        self.output_format = project.output_format;
+        self.additional_formats = project.additional_formats;
+        self.dds_quality = project.dds_quality;
+        self.packing_layout = project.packing_layout;
+        self.file_name_template = project.file_name_template;
+        self.map_names = project.map_names;
+        self.overwrite_mode = project.overwrite_mode;
+        self.export_albedo = project.export_albedo;
+        self.export_normal = project.export_normal;
+        self.two_channel_normals = project.two_channel_normals;
+        self.pack_translucency = project.pack_translucency;
+        self.albedo_output_size = project.albedo_output_size;
+        self.normal_output_size = project.normal_output_size;
+        self.seamless_resize = project.seamless_resize;
+        self.punch_through_alpha = project.punch_through_alpha;
+        self.sixteen_bit_png = project.sixteen_bit_png;
+        self.post_export_command = project.post_export_command;
+        self.use_export_cache = project.use_export_cache;
+        self.skip_up_to_date = project.skip_up_to_date;
+        self.link_mode = project.link_mode;
+        self.alpha_threshold = project.alpha_threshold;
+        self.export_color_map = project.export_color_map;
+        self.color_map_resolution = project.color_map_resolution;
+        self.color_map_blur = project.color_map_blur;
+        self.export_average_color = project.export_average_color;
+        self.export_palette = project.export_palette;
+        self.export_preview = project.export_preview;
+        self.palette_size = project.palette_size;
+        self.export_macro_variation = project.export_macro_variation;
+        self.macro_variation = project.macro_variation;
+        self.texel_density = project.texel_density;
+        self.review_status = project.review_status;
+        self.notes = project.notes;
+        self.processing_state = ProcessingState::NotStarted;
+    }
+
+    // Settings from the undo history, only slots whose file or decoding
+    // changed are loaded again
+    fn restore_settings(&mut self, project: Project) {
+        self.apply_project_with(project, false);
+        self.undo_stack.sync(&self.to_project());
+    }
+
+    fn open_project(&mut self, path: PathBuf) {
+        match Project::load(&path) {
+            Ok(project) => {
+                self.apply_project(project);
+                self.recent.add_project(path.clone());
+                // Saving a restored export setup makes a new project rather
+                // than overwriting the sidecar
+                self.project_path = Some(path).filter(|path| !project::is_sidecar(path));
+                self.project_error = None;
+            }
+            Err(e) => self.project_error = Some(e),
+        }
+    }
+
+    // Loads several maps at once into the slots their names suggest
+    fn assign_input_files(&mut self, files: Vec<PathBuf>) {
+        let (maps, unrecognized) = map_names::assign(&map_names::expand_folders(&files));
+        self.recognized_library = None;
+        if self.library_conventions {
+            self.recognized_library = map_names::conventions(&maps, &mut self.normal_map_format, &mut self.roughness_format);
+        }
+        for (slot, path) in maps {
+            match slot {
+                map_names::Slot::Roughness => self.roughness_format = RoughnessFormat::Roughness,
+                map_names::Slot::Smoothness => self.roughness_format = RoughnessFormat::Smoothness,
+                _ => {}
+            }
+            // A file of its own, not a channel of whatever was packed there before
+            self.packed_channels.set(slot.input_name(), None);
+            self.set_input_map(slot.input_name(), Some(path));
+        }
+        self.unassigned_inputs = unrecognized;
+    }
+
+    fn save_project(&mut self, path: PathBuf) {
+        match self.to_project().save(&path) {
+            Ok(()) => {
+                self.recent.add_project(path.clone());
+                self.project_path = Some(path);
+                self.project_error = None;
+            }
+            Err(e) => self.project_error = Some(e),
+        }
+    }
+
+    // Validation happens on load, so maps are reloaded when the mode changes
+    fn reload_input_maps(&mut self) {
+        self.set_input_map("albedo", self.albedo_map.clone());
+        self.set_input_map("height", self.height_map.clone());
+        self.set_input_map("ao", self.ambient_occlusion_map.clone());
+        self.set_input_map("normal", self.normal_map.clone());
+        self.set_input_map("roughness", self.roughness_map.clone());
+        self.set_input_map("translucency", self.translucency_map.clone());
+        self.set_input_map("opacity", self.opacity_map.clone());
+    }
+
+    fn is_loading_inputs(&self) -> bool {
+        [
+            &self.albedo_load_state,
+            &self.height_load_state,
+            &self.normal_load_state,
+            &self.ao_load_state,
+            &self.roughness_load_state,
+            &self.opacity_load_state,
+            &self.translucency_load_state,
+        ].iter().any(|state| matches!(state, ImageLoadState::Loading))
+    }
+
+    // Drives exports requested on the command line
+    fn run_pending_export(&mut self, ctx: &Context) {
+        if self.pending_export && !self.is_loading_inputs() {
+            self.pending_export = false;
+            if let Some(e) = &self.project_error {
+                self.processing_state = ProcessingState::Error(e.clone());
+            } else if self.are_required_images_loaded() {
+                self.start_export();
+            } else {
+                self.processing_state = ProcessingState::Error("Project is missing required maps or an output directory".to_string());
+            }
+        }
+
+        if self.exit_after_export && !self.pending_export {
+            match &self.processing_state {
+                ProcessingState::Done => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+                ProcessingState::Error(e) => {
+                    eprintln!("Export failed: {}", e);
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Same pipeline as the Process button, waiting on the loader and export
+    // threads instead of polling them every frame. A dry run stops after
+    // validation and lists what would have been written.
+    fn run_headless(project: Project, dry_run: bool) -> ExportReport {
+        let mut report = ExportReport::new(project.named_inputs());
+        let output_missing = project.output_directory.as_ref().is_some_and(|dir| !dir.is_dir());
+        // Checked before loading, so unchanged sets don't even get decoded
+        let up_to_date = project.export_directory()
+            .filter(|_| project.skip_up_to_date)
+            .and_then(|dir| {
+                let key = export_cache::cache_key(&project.settings_json(), &project.file_names(), &project.input_paths()).ok()?;
+                ExportManifest::up_to_date_files(&dir, &project.file_names().manifest(), &key)
+            });
+        if let Some(files) = up_to_date {
+            report.up_to_date = true;
+            if dry_run {
+                report.warnings.push("Output is up to date, an export would leave it alone".to_string());
+            } else {
+                report.outputs = files;
+                return report;
+            }
+        }
+        let started = Instant::now();
+        let mut app = match Self::load_headless(project, !dry_run) {
+            Ok(app) => app,
+            Err(e) => return report.fail(ExportStatus::ValidationFailed, e),
+        };
+        report.load_seconds = started.elapsed().as_secs_f64();
+        for image_type in project::SLOTS {
+            if let (_, Some(image)) = app.input_slot(image_type) {
+                report.warnings.extend(image.info.warnings(image_type).into_iter().map(|w| format!("{}: {}", image_type, w)));
+                if let Some((width, height)) = image.cropped_from {
+                    report.warnings.push(format!("{}: border cropped from {}x{}", image_type, width, height));
+                }
+            }
+        }
+        if app.normal_alpha_unused() {
+            report.warnings.push("normal: alpha holds data that is replaced with roughness".to_string());
+        }
+        if let Some(density) = app.texel_density().filter(|density| density.below_threshold) {
+            report.warnings.push(format!(
+                "albedo: {:.1} texels per meter is below the {} minimum",
+                density.texels_per_meter, app.texel_density.min_texels_per_meter,
+            ));
+        }
+
+        if dry_run {
+            // Nothing to check free space on until the directory exists
+            let validation = if output_missing {
+                report.warnings.push("Output directory doesn't exist yet and will be created".to_string());
+                app.check_matching_sizes()
+            } else {
+                app.validate_export()
+            };
+            if let Err(e) = validation {
+                return report.fail(ExportStatus::ValidationFailed, e);
+            }
+            report.planned = app.planned_outputs(&app.file_names());
+            let existing = app.existing_outputs();
+            if !existing.is_empty() {
+                match app.overwrite_mode {
+                    OverwriteMode::Ask => return report.fail(ExportStatus::ValidationFailed, overwrite::cannot_ask(&existing)),
+                    OverwriteMode::Skip => {
+                        report.skipped = true;
+                        report.warnings.push("Textures are already in the output directory, an export would skip the set".to_string());
+                    }
+                    OverwriteMode::Overwrite | OverwriteMode::Version => {}
+                }
+            }
+            return report;
+        }
+
+        // Like an up to date set, a skipped one succeeds without writing
+        let existing = app.existing_outputs();
+        if app.overwrite_mode == OverwriteMode::Skip && !existing.is_empty() {
+            let dir = app.export_directory().unwrap();
+            report.skipped = true;
+            report.outputs = existing.iter().map(|file| dir.join(file)).collect();
+            return report;
+        }
+        let started = Instant::now();
+        let overwrite = app.overwrite_mode;
+        if let Err(e) = app.process_and_save_images(overwrite) {
+            return report.fail(ExportStatus::ValidationFailed, e);
+        }
+        // Leave the export thread with the only sender, so a panic in it ends
+        // the wait below instead of blocking forever
+        app.processing_sender = channel().0;
+        let result = app.processing_receiver.recv()
+            .unwrap_or_else(|_| Err("Export stopped unexpectedly".to_string()));
+        report.export_seconds = started.elapsed().as_secs_f64();
+        match result {
+            Ok(files) => {
+                if let Some((started, resolution, format)) = app.run_started.take() {
+                    app.timing_stats.record(resolution, &format, started.elapsed().as_secs_f64());
+                }
+                report.outputs = files;
+                report
+            }
+            Err(e) => report.fail(ExportStatus::EncodeFailed, e),
+        }
+    }
+
+    // Loads every map of the project, failing on the first that can't be used
+    fn load_headless(project: Project, create_output: bool) -> Result<Self, String> {
+        if let Some(dir) = project.output_directory.as_ref().filter(|_| create_output) {
+            std::fs::create_dir_all(paths::long_path(dir))
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let mut app = TerrainApp::default();
+        app.apply_project(project);
+        while app.is_loading_inputs() {
+            let (image_type, result) = app.image_receiver.recv().map_err(|e| e.to_string())?;
+            let (image, state) = match image_type.as_str() {
+                "albedo" => (&mut app.albedo_image, &mut app.albedo_load_state),
+                "height" => (&mut app.height_image, &mut app.height_load_state),
+                "normal" => (&mut app.normal_image, &mut app.normal_load_state),
+                "ao" => (&mut app.ao_image, &mut app.ao_load_state),
+                "roughness" => (&mut app.roughness_image, &mut app.roughness_load_state),
+                "translucency" => (&mut app.translucency_image, &mut app.translucency_load_state),
+                "opacity" => (&mut app.opacity_image, &mut app.opacity_load_state),
+                _ => continue,
+            };
+            match result {
+                Ok(processed) => {
+                    *image = Some(processed);
+                    *state = ImageLoadState::Loaded;
+                }
+                Err(e) => return Err(format!("Failed to load {} map: {}", image_type, e)),
+            }
+        }
+        if !app.are_required_images_loaded() {
+            return Err("The maps of the exported textures and an output directory are required".to_string());
+        }
+        Ok(app)
+    }
 }
 
 impl App for TerrainApp {
     fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
+        // Results that change the settings or the export estimate without
+        // any input, taken into the undo stack below
+        let mut received = false;
         // Handle image loading results
         while let Ok((image_type, result)) = self.image_receiver.try_recv() {
+            received = true;
+            let loaded = result.is_ok();
             match (image_type.as_str(), result) {
                 ("albedo", Ok(processed)) => {
-                    self.albedo_texture = Some(self.process_image_to_texture(&processed, ctx));
                     self.albedo_image = Some(processed);
+                    self.update_albedo_texture(ctx);
                     self.albedo_load_state = ImageLoadState::Loaded;
                 }
                 ("height", Ok(processed)) => {
-                    self.height_texture = Some(self.process_image_to_texture(&processed, ctx));
+                    self.height_texture = Some(self.grayscale_texture(&processed, ctx));
                     self.height_image = Some(processed);
                     self.height_load_state = ImageLoadState::Loaded;
+                    self.slope_tint.invalidate();
                 }
                 ("normal", Ok(processed)) => {
                     self.normal_texture = Some(self.process_image_to_texture(&processed, ctx));
@@ -417,15 +2113,26 @@ impl App for TerrainApp {
                     self.normal_load_state = ImageLoadState::Loaded;
                 }
                 ("ao", Ok(processed)) => {
-                    self.ao_texture = Some(self.process_image_to_texture(&processed, ctx));
+                    self.ao_texture = Some(self.grayscale_texture(&processed, ctx));
                     self.ao_image = Some(processed);
                     self.ao_load_state = ImageLoadState::Loaded;
                 }
                 ("roughness", Ok(processed)) => {
-                    self.roughness_texture = Some(self.process_image_to_texture(&processed, ctx));
+                    self.roughness_preview_key = None;
+                    self.roughness_texture = Some(self.grayscale_texture(&processed, ctx));
                     self.roughness_image = Some(processed);
                     self.roughness_load_state = ImageLoadState::Loaded;
                 }
+                ("opacity", Ok(processed)) => {
+                    self.opacity_texture = Some(self.grayscale_texture(&processed, ctx));
+                    self.opacity_image = Some(processed);
+                    self.opacity_load_state = ImageLoadState::Loaded;
+                }
+                ("translucency", Ok(processed)) => {
+                    self.translucency_texture = Some(self.grayscale_texture(&processed, ctx));
+                    self.translucency_image = Some(processed);
+                    self.translucency_load_state = ImageLoadState::Loaded;
+                }
                 (type_name, Err(e)) => {
                     match type_name {
                         "albedo" => self.albedo_load_state = ImageLoadState::Error(e),
@@ -433,32 +2140,233 @@ impl App for TerrainApp {
                         "normal" => self.normal_load_state = ImageLoadState::Error(e),
                         "ao" => self.ao_load_state = ImageLoadState::Error(e),
                         "roughness" => self.roughness_load_state = ImageLoadState::Error(e),
+                        "translucency" => self.translucency_load_state = ImageLoadState::Error(e),
+                        "opacity" => self.opacity_load_state = ImageLoadState::Error(e),
                         _ => {}
                     }
                 }
                 _ => {}
             }
+            if let Some(path) = self.input_slot(&image_type).0.filter(|_| loaded) {
+                self.recent.add_map(path.clone());
+            }
             ctx.request_repaint();
         }
 
+        // A later launch opened files here instead of in a window of its own
+        while let Ok(forwarded) = self.forwarded_receiver.try_recv() {
+            received = true;
+            if let Some(path) = forwarded.project {
+                self.open_project(path);
+            }
+            if !forwarded.inputs.is_empty() {
+                self.assign_input_files(forwarded.inputs);
+            }
+        }
+
         // Handle processing results
         if let Ok(result) = self.processing_receiver.try_recv() {
+            received = true;
+            if let Some((started, resolution, format)) = self.run_started.take() {
+                if result.is_ok() {
+                    self.timing_stats.record(resolution, &format, started.elapsed().as_secs_f64());
+                }
+            }
             self.processing_state = match result {
-                Ok(()) => ProcessingState::Done,
+                Ok(files) => {
+                    self.exported_files = files;
+                    ProcessingState::Done
+                }
                 Err(e) => ProcessingState::Error(e),
             };
             ctx.request_repaint();
         }
 
+        // Maps edited externally are reloaded unless the slot moved on
+        for (image_type, path) in self.external_editor.poll(ctx) {
+            received = true;
+            if self.input_slot(&image_type).0 == Some(&path) {
+                self.set_input_map(&image_type, Some(path));
+            }
+        }
+
+        if self.slope_tint.is_stale() && self.albedo_image.is_some() {
+            self.update_albedo_texture(ctx);
+        }
+
+        self.run_pending_export(ctx);
+        self.splatmap_converter.poll(ctx);
+        self.heightmap_tool.poll(ctx);
+        self.blend_preview.poll(ctx);
+        for (image_type, path) in self.plugins.poll(ctx) {
+            received = true;
+            // The output is a whole file, even where the source was packed
+            self.packed_channels.set(&image_type, None);
+            self.set_input_map(&image_type, Some(path));
+        }
+        self.export_comparer.poll(ctx);
+        self.convention_checker.poll(ctx);
+        self.texture_inspector.poll(ctx);
+        self.export_reorganizer.poll(ctx);
+        self.texture_budget.poll(ctx);
+        self.batch_exporter.poll(ctx);
+        if let Some((batch_file, outputs)) = self.batch_exporter.take_finished() {
+            self.texture_budget.report_batch(batch_file, outputs);
+        }
+        self.export_queue.poll(ctx);
+        self.set_scanner.poll(ctx);
+        self.hot_folder.poll(ctx);
+
+        if let Some(existing) = &self.overwrite_prompt {
+            match overwrite::show_prompt(ctx, "in the output directory", existing) {
+                Some(OverwriteMode::Skip) => self.overwrite_prompt = None,
+                Some(choice) => {
+                    self.overwrite_prompt = None;
+                    if let Err(e) = self.process_and_save_images(choice) {
+                        self.processing_state = ProcessingState::Error(e);
+                    }
+                }
+                None => {}
+            }
+        }
+
         CentralPanel::default().show(ctx, |ui| {
             egui::ScrollArea::vertical().show(ui, |ui| {
                 ui.vertical_centered(|ui| {
                     ui.heading("Terrain 3D Prepare");
+
+                    ui.horizontal(|ui| {
+                        if ui.button("Open Project").clicked() {
+                            if let Some(path) = rfd::FileDialog::new()
+                                .add_filter("Terrain 3D Prepare project", &[project::PROJECT_EXTENSION])
+                                .add_filter("Export settings", &["json"])
+                                .pick_file() {
+                                self.open_project(path);
+                            }
+                        }
+                        if ui.button("Save Project").clicked() {
+                            let mut dialog = rfd::FileDialog::new()
+                                .add_filter("Terrain 3D Prepare project", &[project::PROJECT_EXTENSION]);
+                            if let Some(path) = &self.project_path {
+                                dialog = dialog.set_file_name(path.file_name().unwrap_or_default().to_string_lossy());
+                            }
+                            if let Some(path) = dialog.save_file() {
+                                self.save_project(path.with_extension(project::PROJECT_EXTENSION));
+                            }
+                        }
+                        match self.recent.show(ui) {
+                            Some(RecentChoice::Project(path)) => self.open_project(path),
+                            Some(RecentChoice::Maps(paths)) => self.assign_input_files(paths),
+                            None => {}
+                        }
+                        if let Some(project) = self.undo_stack.show(ui) {
+                            self.restore_settings(project);
+                        }
+                        if let Some(path) = &self.project_path {
+                            ui.label(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+                        }
+                    });
+                    if let Some(e) = &self.project_error {
+                        ui.label(format!("Error: {}", e));
+                    }
+                    review::show(ui, &mut self.review_status, &mut self.notes);
                     
                     // Input Section
                     CollapsingHeader::new("Input")
                         .default_open(true)
                         .show(ui, |ui| {
+                            if ui.checkbox(&mut self.rectangular_mode, "Allow Rectangular Maps")
+                                .on_hover_text("For trim sheets and decals: any size in multiples of 4, all maps must match")
+                                .changed() {
+                                self.reload_input_maps();
+                            }
+                            if ui.button("Load Maps...")
+                                .on_hover_text("Pick a whole set at once, each file goes into the slot its name suggests, \
+                                    e.g. rock_BaseColor.png or rock_Normal.png")
+                                .clicked() {
+                                if let Some(files) = rfd::FileDialog::new()
+                                    .add_filter("Image files", &Self::SUPPORTED_FORMATS)
+                                    .pick_files() {
+                                    self.assign_input_files(files);
+                                }
+                            }
+                            if !self.unassigned_inputs.is_empty() {
+                                let names: Vec<String> = self.unassigned_inputs.iter()
+                                    .map(|path| path.file_name().unwrap_or_default().to_string_lossy().to_string())
+                                    .collect();
+                                ui.horizontal(|ui| {
+                                    ui.colored_label(egui::Color32::YELLOW, format!("Not matched to a map: {}", names.join(", ")));
+                                    if ui.small_button("Dismiss").clicked() {
+                                        self.unassigned_inputs.clear();
+                                    }
+                                });
+                            }
+                            ui.checkbox(&mut self.library_conventions, "Library Conventions")
+                                .on_hover_text("When maps are loaded by name, set the normal and roughness conventions from names \
+                                    like NormalDX and from the library a set comes from (Megascans, AmbientCG, Poliigon)");
+                            if let Some(library) = self.recognized_library {
+                                let profile = library.profile();
+                                ui.label(format!(
+                                    "Recognized {} maps: {:?} normals, {:?}",
+                                    library.name(),
+                                    self.normal_map_format,
+                                    self.roughness_format,
+                                )).on_hover_text(format!(
+                                    "The {} profile uses {:?} normals and {:?}, names stating otherwise win",
+                                    library.name(),
+                                    profile.normal_format,
+                                    profile.roughness_format,
+                                ));
+                            }
+                            if ui.checkbox(&mut self.auto_crop, "Auto-Crop Uniform Borders")
+                                .on_hover_text("Removes flat colored margins from scans and padded atlas tiles before the size check")
+                                .changed() {
+                                self.reload_input_maps();
+                            }
+                            let mut script_changed = false;
+                            ui.horizontal(|ui| {
+                                let name = self.script.as_ref()
+                                    .map_or("None".to_string(), |path| path.file_name().unwrap_or_default().to_string_lossy().to_string());
+                                ui.label(format!("Script: {}", name))
+                                    .on_hover_text("Rhai script run on each map between loading and packing, with optional \
+                                        functions named after the slots, e.g. fn roughness(v) { v * 0.8 } or \
+                                        fn albedo(r, g, b, a) { [r, g * 0.9, b, a] }");
+                                if ui.button("Select...").clicked() {
+                                    if let Some(path) = rfd::FileDialog::new()
+                                        .add_filter("Rhai script", &["rhai"])
+                                        .pick_file() {
+                                        self.script = Some(path);
+                                        script_changed = true;
+                                    }
+                                }
+                                if self.script.is_some() {
+                                    script_changed |= ui.small_button("Reload")
+                                        .on_hover_text("Run the script again after editing it")
+                                        .clicked();
+                                    if ui.small_button("Clear").clicked() {
+                                        self.script = None;
+                                        script_changed = true;
+                                    }
+                                }
+                            });
+                            if script_changed {
+                                self.reload_input_maps();
+                            }
+                            if self.grayscale_palette.show(ui) {
+                                self.update_grayscale_textures(ui.ctx());
+                            }
+                            if self.preview_resolution.show(ui) {
+                                self.regenerate_previews(ui.ctx());
+                            }
+
+                            self.external_editor.show(ui);
+
+                            if let Some((image_type, path)) = self.library.show(ui) {
+                                // A whole file replaces any packed channel the slot had
+                                self.packed_channels.set(image_type, None);
+                                self.set_input_map(image_type, Some(path));
+                            }
+
                             // Texture Maps
                             CollapsingHeader::new("Texture Maps")
                                 .default_open(true)
@@ -484,9 +2392,52 @@ impl App for TerrainApp {
                                                     _ => ui.label(""),
                                                 };
                                             }
+                                            ComboBox::from_label("Source Alpha")
+                                                .selected_text(match self.albedo_alpha_mode {
+                                                    AlbedoAlphaMode::Discard => "Discard",
+                                                    AlbedoAlphaMode::ExportMask => "Export as Mask",
+                                                    AlbedoAlphaMode::Unpremultiply => "Undo Premultiply",
+                                                })
+                                                .show_ui(ui, |ui| {
+                                                    ui.selectable_value(&mut self.albedo_alpha_mode, AlbedoAlphaMode::Discard, "Discard");
+                                                    ui.selectable_value(&mut self.albedo_alpha_mode, AlbedoAlphaMode::ExportMask, "Export as Mask");
+                                                    ui.selectable_value(&mut self.albedo_alpha_mode, AlbedoAlphaMode::Unpremultiply, "Undo Premultiply");
+                                                })
+                                                .response
+                                                .on_hover_text("Height always replaces albedo alpha in the packed output");
+                                            if let Some(path) = self.input_slot("albedo").0.cloned() {
+                                                if ui.button("Open in Editor").clicked() {
+                                                    self.external_editor.open("albedo", &path);
+                                                }
+                                            }
+                                            if let Some(frame) = self.show_source_details(ui, "albedo") {
+                                                self.select_frame("albedo", frame);
+                                            }
+                                            if self.albedo_preview_color.show(ui) {
+                                                self.update_albedo_texture(ui.ctx());
+                                            }
+                                            if self.slope_tint.show(ui, self.height_image.is_some()) {
+                                                self.update_albedo_texture(ui.ctx());
+                                            }
                                             if let Some(texture) = &self.albedo_texture {
                                                 self.display_image(ui, texture);
                                             }
+                                            self.tiling_preview.show(ui, self.albedo_image.as_ref().map(|img| &img.downscaled));
+                                        });
+
+                                    // Packed grayscale source
+                                    CollapsingHeader::new("Packed AO/Roughness/Height (Optional)")
+                                        .default_open(false)
+                                        .show(ui, |ui| {
+                                            ui.label("Fills the AO, roughness and height slots from the channels of one texture");
+                                            packed_input::mapping_editor(ui, &mut self.packed_mapping);
+                                            if ui.button("Select Packed Texture").clicked() {
+                                                if let Some(path) = rfd::FileDialog::new()
+                                                    .add_filter("Image files", &Self::SUPPORTED_FORMATS)
+                                                    .pick_file() {
+                                                    self.load_packed_input(path);
+                                                }
+                                            }
                                         });
 
                                     // Ambient Occlusion Map
@@ -498,6 +2449,7 @@ impl App for TerrainApp {
                                                     if let Some(path) = rfd::FileDialog::new()
                                                         .add_filter("Image files", &Self::SUPPORTED_FORMATS)
                                                         .pick_file() {
+                                                        self.packed_channels.ambient_occlusion = None;
                                                         self.ambient_occlusion_map = Some(path.clone());
                                                         self.ao_load_state = ImageLoadState::Loading;
                                                         self.load_image(path, "ao".to_string());
@@ -508,13 +2460,21 @@ impl App for TerrainApp {
                                                 }
                                             });
                                             if let Some(path) = &self.ambient_occlusion_map {
-                                                ui.label(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+                                                ui.label(packed_input::slot_label(path, self.packed_channels.ambient_occlusion));
                                                 match &self.ao_load_state {
                                                     ImageLoadState::Loading => ui.spinner(),
                                                     ImageLoadState::Error(e) => ui.label(format!("Error: {}", e)),
                                                     _ => ui.label(""),
                                                 };
                                             }
+                                            if let Some(path) = self.input_slot("ao").0.cloned() {
+                                                if ui.button("Open in Editor").clicked() {
+                                                    self.external_editor.open("ao", &path);
+                                                }
+                                            }
+                                            if let Some(frame) = self.show_source_details(ui, "ao") {
+                                                self.select_frame("ao", frame);
+                                            }
                                             if let Some(texture) = &self.ao_texture {
                                                 self.display_image(ui, texture);
                                             }
@@ -529,6 +2489,7 @@ impl App for TerrainApp {
                                                     if let Some(path) = rfd::FileDialog::new()
                                                         .add_filter("Image files", &Self::SUPPORTED_FORMATS)
                                                         .pick_file() {
+                                                        self.packed_channels.height = None;
                                                         self.height_map = Some(path.clone());
                                                         self.height_load_state = ImageLoadState::Loading;
                                                         self.load_image(path, "height".to_string());
@@ -538,18 +2499,149 @@ impl App for TerrainApp {
                                                     self.clear_height_map();
                                                 }
                                             });
+                                            if self.height_map.is_none() {
+                                                ui.add(egui::Slider::new(&mut self.default_height, 0..=255).text("Height Without a Map"))
+                                                    .on_hover_text("Albedo alpha when there's no height map, 255 by default. \
+                                                        Match the neutral height your terrain shader blends with.");
+                                            }
                                             if let Some(path) = &self.height_map {
-                                                ui.label(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+                                                ui.label(packed_input::slot_label(path, self.packed_channels.height));
                                                 match &self.height_load_state {
                                                     ImageLoadState::Loading => ui.spinner(),
                                                     ImageLoadState::Error(e) => ui.label(format!("Error: {}", e)),
                                                     _ => ui.label(""),
                                                 };
                                             }
+                                            CollapsingHeader::new("Height Blend Contrast")
+                                                .default_open(false)
+                                                .show(ui, |ui| {
+                                                    ui.label("Only affects the height packed into albedo alpha");
+                                                    ui.add(egui::Slider::new(&mut self.height_alpha.contrast, 0.0..=4.0).text("Contrast"))
+                                                        .on_hover_text("Higher values give sharper height blending between textures");
+                                                    ui.add(egui::Slider::new(&mut self.height_alpha.bias, -0.5..=0.5).text("Bias"));
+                                                    if ui.button("Reset").clicked() {
+                                                        self.height_alpha = Default::default();
+                                                    }
+                                                });
+                                            CollapsingHeader::new("Parallax")
+                                                .default_open(false)
+                                                .show(ui, |ui| {
+                                                    ui.checkbox(&mut self.parallax.invert, "Store as Depth")
+                                                        .on_hover_text("For POM shaders that expect white to be the deepest point");
+                                                    ui.add(egui::Slider::new(&mut self.parallax.mid_level, 0.0..=1.0).text("Mid Level"))
+                                                        .on_hover_text("Height that sits on the surface plane, remapped to 0.5");
+                                                    ui.add(egui::Slider::new(&mut self.parallax.edge_padding, 0..=64).text("Edge Padding").suffix(" px"));
+                                                    if ui.button("Reset").clicked() {
+                                                        self.parallax = Default::default();
+                                                    }
+                                                });
+                                            if self.height_map.is_none() {
+                                                CollapsingHeader::new("Estimate From Albedo")
+                                                    .default_open(false)
+                                                    .show(ui, |ui| {
+                                                        let estimate = &mut self.height_estimate;
+                                                        ui.checkbox(&mut estimate.enabled, "Estimate height from albedo luminance")
+                                                            .on_hover_text("Used only when no height map is loaded");
+                                                        ui.add_enabled_ui(estimate.enabled, |ui| {
+                                                            ui.add(egui::Slider::new(&mut estimate.blur_radius, 0..=32).text("Blur"));
+                                                            ui.add(egui::Slider::new(&mut estimate.black_level, 0.0..=1.0).text("Black Level"));
+                                                            ui.add(egui::Slider::new(&mut estimate.white_level, 0.0..=1.0).text("White Level"));
+                                                            ui.checkbox(&mut estimate.invert, "Invert");
+                                                        });
+                                                    });
+                                            }
+                                            if let Some(path) = self.input_slot("height").0.cloned() {
+                                                if ui.button("Open in Editor").clicked() {
+                                                    self.external_editor.open("height", &path);
+                                                }
+                                            }
+                                            if let Some(frame) = self.show_source_details(ui, "height") {
+                                                self.select_frame("height", frame);
+                                            }
                                             if let Some(texture) = &self.height_texture {
                                                 self.display_image(ui, texture);
                                             }
                                         });
+
+                                    // Opacity Map, exported on its own for cutout decals
+                                    CollapsingHeader::new("Opacity Map (Optional)")
+                                        .default_open(true)
+                                        .show(ui, |ui| {
+                                            ui.horizontal(|ui| {
+                                                if ui.button("Select Opacity Map").clicked() {
+                                                    if let Some(path) = rfd::FileDialog::new()
+                                                        .add_filter("Image files", &Self::SUPPORTED_FORMATS)
+                                                        .pick_file() {
+                                                        self.opacity_map = Some(path.clone());
+                                                        self.opacity_load_state = ImageLoadState::Loading;
+                                                        self.load_image(path, "opacity".to_string());
+                                                    }
+                                                }
+                                                if ui.button("Clear").clicked() {
+                                                    self.clear_opacity_map();
+                                                }
+                                            });
+                                            if let Some(path) = &self.opacity_map {
+                                                ui.label(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+                                                match &self.opacity_load_state {
+                                                    ImageLoadState::Loading => ui.spinner(),
+                                                    ImageLoadState::Error(e) => ui.label(format!("Error: {}", e)),
+                                                    _ => ui.label(""),
+                                                };
+                                            }
+                                            if let Some(path) = self.input_slot("opacity").0.cloned() {
+                                                if ui.button("Open in Editor").clicked() {
+                                                    self.external_editor.open("opacity", &path);
+                                                }
+                                            }
+                                            if let Some(frame) = self.show_source_details(ui, "opacity") {
+                                                self.select_frame("opacity", frame);
+                                            }
+                                            if let Some(texture) = &self.opacity_texture {
+                                                self.display_image(ui, texture);
+                                            }
+                                        });
+
+                                    // Translucency Map, passed through for foliage-heavy materials
+                                    CollapsingHeader::new("Translucency Map (Optional)")
+                                        .default_open(true)
+                                        .show(ui, |ui| {
+                                            ui.horizontal(|ui| {
+                                                if ui.button("Select Translucency Map").clicked() {
+                                                    if let Some(path) = rfd::FileDialog::new()
+                                                        .add_filter("Image files", &Self::SUPPORTED_FORMATS)
+                                                        .pick_file() {
+                                                        self.translucency_map = Some(path.clone());
+                                                        self.translucency_load_state = ImageLoadState::Loading;
+                                                        self.load_image(path, "translucency".to_string());
+                                                    }
+                                                }
+                                                if ui.button("Clear").clicked() {
+                                                    self.clear_translucency_map();
+                                                }
+                                            });
+                                            if let Some(path) = &self.translucency_map {
+                                                ui.label(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+                                                match &self.translucency_load_state {
+                                                    ImageLoadState::Loading => ui.spinner(),
+                                                    ImageLoadState::Error(e) => ui.label(format!("Error: {}", e)),
+                                                    _ => ui.label(""),
+                                                };
+                                            }
+                                            ui.add_enabled(self.two_channel_normals && self.packing_layout == layouts::TERRAIN3D, egui::Checkbox::new(&mut self.pack_translucency, "Pack into Normal Blue Channel"))
+                                                .on_hover_text("Uses the channel freed by two-channel normals instead of a separate texture. DDS normals are then BC7, as BC5 has no blue.");
+                                            if let Some(path) = self.input_slot("translucency").0.cloned() {
+                                                if ui.button("Open in Editor").clicked() {
+                                                    self.external_editor.open("translucency", &path);
+                                                }
+                                            }
+                                            if let Some(frame) = self.show_source_details(ui, "translucency") {
+                                                self.select_frame("translucency", frame);
+                                            }
+                                            if let Some(texture) = &self.translucency_texture {
+                                                self.display_image(ui, texture);
+                                            }
+                                        });
                                 });
 
                             // Normal Maps
@@ -575,6 +2667,8 @@ impl App for TerrainApp {
                                                     ui.selectable_value(&mut self.normal_map_format, NormalMapFormat::OpenGL, "OpenGL");
                                                     ui.selectable_value(&mut self.normal_map_format, NormalMapFormat::DirectX, "DirectX");
                                                 });
+                                            ui.checkbox(&mut self.reconstruct_normal_z, "Reconstruct Z (two-channel input)")
+                                                .on_hover_text("For normal maps that only store X/Y, such as BC5 exports");
                                             if let Some(path) = &self.normal_map {
                                                 ui.label(path.file_name().unwrap_or_default().to_string_lossy().to_string());
                                                 match &self.normal_load_state {
@@ -583,6 +2677,15 @@ impl App for TerrainApp {
                                                     _ => ui.label(""),
                                                 };
                                             }
+                                            if let Some(path) = self.input_slot("normal").0.cloned() {
+                                                if ui.button("Open in Editor").clicked() {
+                                                    self.external_editor.open("normal", &path);
+                                                }
+                                            }
+                                            if let Some(frame) = self.show_source_details(ui, "normal") {
+                                                self.select_frame("normal", frame);
+                                            }
+                                            self.show_normal_alpha_options(ui);
                                             if let Some(texture) = &self.normal_texture {
                                                 self.display_image(ui, texture);
                                             }
@@ -597,6 +2700,7 @@ impl App for TerrainApp {
                                                     if let Some(path) = rfd::FileDialog::new()
                                                         .add_filter("Image files", &Self::SUPPORTED_FORMATS)
                                                         .pick_file() {
+                                                        self.packed_channels.roughness = None;
                                                         self.roughness_map = Some(path.clone());
                                                         self.roughness_load_state = ImageLoadState::Loading;
                                                         self.load_image(path, "roughness".to_string());
@@ -612,14 +2716,65 @@ impl App for TerrainApp {
                                                     ui.selectable_value(&mut self.roughness_format, RoughnessFormat::Roughness, "Roughness");
                                                     ui.selectable_value(&mut self.roughness_format, RoughnessFormat::Smoothness, "Smoothness");
                                                 });
+                                            self.update_roughness_preview(ui.ctx());
+                                            if let Some(swatches) = &self.roughness_swatches {
+                                                // Layouts with a smoothness channel store the inverse
+                                                let output_smoothness = layouts::find(&self.packing_layout)
+                                                    .is_ok_and(|layout| layout.uses(ChannelSource::Smoothness));
+                                                roughness::conversion_indicator(
+                                                    ui,
+                                                    swatches,
+                                                    self.roughness_format == RoughnessFormat::Smoothness,
+                                                    output_smoothness,
+                                                    &roughness::combined_lut(&self.roughness_curve, &self.roughness_adjust),
+                                                );
+                                            }
+                                            CollapsingHeader::new("Roughness Adjustment")
+                                                .default_open(false)
+                                                .show(ui, |ui| {
+                                                    let adjust = &mut self.roughness_adjust;
+                                                    ui.add(egui::Slider::new(&mut adjust.output_min, 0.0..=1.0).text("Remap Min"));
+                                                    ui.add(egui::Slider::new(&mut adjust.output_max, 0.0..=1.0).text("Remap Max"));
+                                                    ui.add(egui::Slider::new(&mut adjust.clamp_min, 0.0..=1.0).text("Clamp Min"))
+                                                        .on_hover_text("Fully smooth terrain causes blinding specular highlights, 0.2 is a safe floor");
+                                                    ui.add(egui::Slider::new(&mut adjust.clamp_max, 0.0..=1.0).text("Clamp Max"));
+                                                    if ui.button("Reset").clicked() {
+                                                        *adjust = Default::default();
+                                                    }
+                                                });
+                                            CollapsingHeader::new("Roughness Curve")
+                                                .default_open(false)
+                                                .show(ui, |ui| {
+                                                    ui.label("Double click to add a point, right click to remove one");
+                                                    roughness::curve_editor(ui, &mut self.roughness_curve);
+                                                    if ui.button("Reset Curve").clicked() {
+                                                        self.roughness_curve = Default::default();
+                                                    }
+                                                    if let Some(texture) = &self.roughness_preview {
+                                                        self.display_image(ui, texture);
+                                                    }
+                                                });
+                                            if self.roughness_map.is_none() {
+                                                ui.add(egui::Slider::new(&mut self.default_roughness, 0..=255).text("Roughness Without a Map"))
+                                                    .on_hover_text("Normal alpha when there's no roughness map, 128 by default. \
+                                                        Written as it is, the curve and remap below don't change it.");
+                                            }
                                             if let Some(path) = &self.roughness_map {
-                                                ui.label(path.file_name().unwrap_or_default().to_string_lossy().to_string());
+                                                ui.label(packed_input::slot_label(path, self.packed_channels.roughness));
                                                 match &self.roughness_load_state {
                                                     ImageLoadState::Loading => ui.spinner(),
                                                     ImageLoadState::Error(e) => ui.label(format!("Error: {}", e)),
                                                     _ => ui.label(""),
                                                 };
                                             }
+                                            if let Some(path) = self.input_slot("roughness").0.cloned() {
+                                                if ui.button("Open in Editor").clicked() {
+                                                    self.external_editor.open("roughness", &path);
+                                                }
+                                            }
+                                            if let Some(frame) = self.show_source_details(ui, "roughness") {
+                                                self.select_frame("roughness", frame);
+                                            }
                                             if let Some(texture) = &self.roughness_texture {
                                                 self.display_image(ui, texture);
                                             }
@@ -640,23 +2795,342 @@ impl App for TerrainApp {
                             if let Some(path) = &self.output_directory {
                                 ui.label(path.to_string_lossy().to_string());
                             }
-                            
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.set_subfolder, "Folder Per Set")
+                                    .on_hover_text("Writes into a folder inside the output directory, so several sets can share one output directory");
+                                if self.set_subfolder {
+                                    ui.add(egui::TextEdit::singleline(&mut self.set_folder_name)
+                                        .hint_text(file_names::set_name(self.albedo_map.as_deref())))
+                                        .on_hover_text("Name of the folder, empty for the set name from the albedo file");
+                                }
+                            });
+                            if self.set_subfolder {
+                                match file_names::validate_subfolder(&self.set_folder_name) {
+                                    Ok(()) => {
+                                        if let Some(dir) = self.export_directory() {
+                                            ui.label(format!("Exports to {}", dir.display()));
+                                        }
+                                    }
+                                    Err(e) => {
+                                        ui.colored_label(egui::Color32::LIGHT_RED, e);
+                                    }
+                                }
+                            }
+
+                            ui.horizontal(|ui| {
+                                let current = self.export_targets.iter()
+                                    .find(|(_, dir)| Some(*dir) == self.output_directory.as_ref())
+                                    .map(|(name, _)| name.clone());
+                                ComboBox::from_label("Export Target")
+                                    .selected_text(current.clone().unwrap_or_else(|| "Custom".to_string()))
+                                    .show_ui(ui, |ui| {
+                                        for (name, dir) in &self.export_targets {
+                                            if ui.selectable_label(current.as_ref() == Some(name), name)
+                                                .on_hover_text(dir.to_string_lossy())
+                                                .clicked() {
+                                                self.output_directory = Some(dir.clone());
+                                            }
+                                        }
+                                    });
+                                if let Some(name) = current {
+                                    if ui.button("Remove Target").clicked() {
+                                        self.export_targets.remove(&name);
+                                    }
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.text_edit_singleline(&mut self.new_target_name);
+                                let name = self.new_target_name.trim().to_string();
+                                let ready = !name.is_empty() && self.output_directory.is_some();
+                                if ui.add_enabled(ready, egui::Button::new("Save As Target"))
+                                    .on_hover_text("Names the output directory so it can be picked again or exported to with --target. \
+                                        Saved relative to the project file.")
+                                    .clicked() {
+                                    if let Some(dir) = &self.output_directory {
+                                        self.export_targets.insert(name, dir.clone());
+                                        self.new_target_name.clear();
+                                    }
+                                }
+                            });
+
+                            layouts::show_selector(ui, &mut self.packing_layout);
+                            let terrain3d = self.packing_layout == layouts::TERRAIN3D;
+                            if !terrain3d {
+                                ui.label("16-bit PNGs, punch-through alpha and two-channel normals only apply to the Terrain3D layout");
+                            }
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.export_albedo, "Export Albedo")
+                                    .on_hover_text("Albedo/height and everything made from the albedo, e.g. the color map and palette");
+                                ui.checkbox(&mut self.export_normal, "Export Normal")
+                                    .on_hover_text("Normal/roughness and the other textures at the normal size");
+                            });
+                            if !(self.export_albedo && self.export_normal) {
+                                ui.label("Files of the other textures already in the output directory are left as they are");
+                            }
+
+                            ui.horizontal(|ui| {
+                                ui.label("File Names:");
+                                ui.add(egui::TextEdit::singleline(&mut self.file_name_template)
+                                    .hint_text(file_names::DEFAULT_TEMPLATE))
+                                    .on_hover_text(file_names::TEMPLATE_HELP);
+                            });
+                            CollapsingHeader::new("Map Names")
+                                .default_open(false)
+                                .show(ui, |ui| {
+                                    ui.label("What {map} becomes for each output, e.g. _AH for albedo with {set}{map}.{ext}. Empty keeps the output's name.");
+                                    egui::Grid::new("map_names").num_columns(2).show(ui, |ui| {
+                                        for (map, description) in file_names::OUTPUTS {
+                                            ui.label(description);
+                                            let name = self.map_names.entry(map.to_string()).or_default();
+                                            ui.add(egui::TextEdit::singleline(name).hint_text(map));
+                                            ui.end_row();
+                                        }
+                                    });
+                                    // Unnamed outputs aren't kept, so projects only list the names given
+                                    self.map_names.retain(|_, name| !name.trim().is_empty());
+                                });
+                            match self.file_names().validate() {
+                                Ok(()) => {
+                                    let size = self.albedo_image.as_ref().map_or((2048, 2048), |img| img.original.dimensions());
+                                    let ext = if self.output_format == OutputFormat::DDS { "dds" } else { "png" };
+                                    ui.label(format!("e.g. {}", self.file_names().name("albedo", size, ext)));
+                                }
+                                Err(e) => {
+                                    ui.colored_label(egui::Color32::LIGHT_RED, e);
+                                }
+                            }
+                            self.overwrite_mode.show(ui);
+
                             ComboBox::from_label("Output Format")
                                 .selected_text(format!("{:?}", self.output_format))
                                 .show_ui(ui, |ui| {
                                     ui.selectable_value(&mut self.output_format, OutputFormat::PNG, "PNG");
                                     ui.selectable_value(&mut self.output_format, OutputFormat::DDS, "DDS");
                                 });
+                            ui.horizontal(|ui| {
+                                for format in OutputFormat::ALL.into_iter().filter(|format| *format != self.output_format) {
+                                    let mut also = self.additional_formats.contains(&format);
+                                    if ui.checkbox(&mut also, format!("Also Write {:?}", format))
+                                        .on_hover_text("Writes the same packed textures in this format too, in the same run")
+                                        .changed() {
+                                        self.additional_formats.retain(|other| *other != format);
+                                        if also {
+                                            self.additional_formats.push(format);
+                                        }
+                                    }
+                                }
+                            });
+                            let output_formats = self.output_formats();
+
+                            if output_formats.contains(&OutputFormat::PNG) {
+                                ui.add_enabled(terrain3d, egui::Checkbox::new(&mut self.sixteen_bit_png, "16-Bit Packed PNGs"))
+                                    .on_hover_text("Writes albedo/height and normal/roughness with 16 bits per channel, \
+                                        computed without rounding to 8 bits in between. Two-channel normals stay 8-bit.");
+                            }
+
+                            if output_formats.contains(&OutputFormat::DDS) {
+                                ComboBox::from_label("DDS Quality")
+                                    .selected_text(format!("{:?}", self.dds_quality))
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(&mut self.dds_quality, DdsQuality::Fast, "Fast");
+                                        ui.selectable_value(&mut self.dds_quality, DdsQuality::Normal, "Normal");
+                                        ui.selectable_value(&mut self.dds_quality, DdsQuality::Slow, "Slow");
+                                    })
+                                    .response
+                                    .on_hover_text("Slow compresses with fewer artifacts and takes several times longer");
+                                ui.add_enabled(terrain3d, egui::Checkbox::new(&mut self.punch_through_alpha, "Albedo BC1 Punch-Through Alpha"))
+                                    .on_hover_text("Half the size of BC3, for when albedo alpha is a mask rather than height");
+                                if self.punch_through_alpha && terrain3d {
+                                    ui.add(egui::Slider::new(&mut self.alpha_threshold, 1..=255).text("Alpha Threshold"));
+                                }
+                            }
+
+                            for (label, size) in [
+                                ("Albedo/Height Size", &mut self.albedo_output_size),
+                                ("Normal/Roughness Size", &mut self.normal_output_size),
+                            ] {
+                                ComboBox::from_label(label)
+                                    .selected_text(resize::size_label(*size))
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(size, None, "Source");
+                                        for option in resize::OUTPUT_SIZES {
+                                            ui.selectable_value(size, Some(option), option.to_string());
+                                        }
+                                    });
+                            }
+
+                            let albedo_width = self.albedo_output_width();
+                            self.texel_density.show(ui, albedo_width);
+
+                            if self.albedo_output_size.is_some() || self.normal_output_size.is_some() {
+                                ui.checkbox(&mut self.seamless_resize, "Seamless Resize")
+                                    .on_hover_text("Filters across the opposite edge when shrinking so tileable maps keep tiling. \
+                                        Turn off for sources that don't tile.");
+                            }
+
+                            ui.add_enabled(terrain3d, egui::Checkbox::new(&mut self.two_channel_normals, "Two-Channel Normals (BC5)"))
+                                .on_hover_text("Stores only normal X/Y, with roughness moved to its own BC4 texture. \
+                                    The terrain shader must rebuild Z as sqrt(1 - x² - y²).");
+
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.skip_up_to_date, "Skip Up-to-Date Outputs")
+                                    .on_hover_text("Leaves the output directory alone when it already holds an untouched export \
+                                        of the same sources with the same settings");
+                                ui.checkbox(&mut self.use_export_cache, "Reuse Cached Outputs")
+                                    .on_hover_text("Skips processing when the same sources were already exported with the same settings");
+                                if self.use_export_cache {
+                                    ComboBox::from_label("Place As")
+                                        .selected_text(format!("{:?}", self.link_mode))
+                                        .show_ui(ui, |ui| {
+                                            ui.selectable_value(&mut self.link_mode, LinkMode::Copy, "Copy");
+                                            ui.selectable_value(&mut self.link_mode, LinkMode::Hardlink, "Hardlink");
+                                            ui.selectable_value(&mut self.link_mode, LinkMode::Symlink, "Symlink")
+                                                .on_hover_text("The outputs point into the cache, so their entries are kept until \
+                                                    the cache folder is deleted by hand, which breaks those exports");
+                                        });
+                                    if ui.button("Clear Cache")
+                                        .on_hover_text("Removes the cached exports, except those that symlinked outputs still point to")
+                                        .clicked() {
+                                        if let Err(e) = export_cache::clear() {
+                                            self.processing_state = ProcessingState::Error(e);
+                                        }
+                                    }
+                                }
+                            });
+
+                            ui.horizontal(|ui| {
+                                ui.label("After Export:");
+                                ui.add(egui::TextEdit::singleline(&mut self.post_export_command)
+                                    .hint_text("e.g. cp {files} ~/godot/terrain/{name}/"))
+                                    .on_hover_text("Shell command run from the output directory after each successful export. \
+                                        {output} is the output directory, {name} its folder name and {files} the written files.");
+                            });
+
+                            ui.checkbox(&mut self.export_color_map, "Export Color Map");
+                            if self.export_color_map {
+                                ComboBox::from_label("Color Map Resolution")
+                                    .selected_text(self.color_map_resolution.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for resolution in colormap::COLOR_MAP_RESOLUTIONS {
+                                            ui.selectable_value(&mut self.color_map_resolution, resolution, resolution.to_string());
+                                        }
+                                    });
+                                ui.add(egui::Slider::new(&mut self.color_map_blur, 0..=64).text("Color Map Blur"));
+                            }
+
+                            ui.checkbox(&mut self.export_average_color, "Export Average Color Texture")
+                                .on_hover_text("4x4 texture of the average albedo after AO, the color is always written to manifest.json");
+
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.export_palette, "Export Dominant Palette");
+                                if self.export_palette {
+                                    ui.add(egui::Slider::new(&mut self.palette_size, 2..=16).text("Colors"));
+                                }
+                            });
+
+                            ui.checkbox(&mut self.export_preview, "Export Preview Thumbnail")
+                                .on_hover_text("preview.png of the material lit on a flat tile, e.g. for asset review pages");
+
+                            ui.checkbox(&mut self.export_macro_variation, "Export Macro Variation Map");
+                            if self.export_macro_variation {
+                                let settings = &mut self.macro_variation;
+                                ComboBox::from_label("Macro Variation Source")
+                                    .selected_text(match settings.source {
+                                        MacroSource::AlbedoLuminance => "Albedo Luminance",
+                                        MacroSource::Noise => "Noise",
+                                    })
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(&mut settings.source, MacroSource::AlbedoLuminance, "Albedo Luminance");
+                                        ui.selectable_value(&mut settings.source, MacroSource::Noise, "Noise");
+                                    });
+                                ComboBox::from_label("Macro Variation Resolution")
+                                    .selected_text(settings.resolution.to_string())
+                                    .show_ui(ui, |ui| {
+                                        for resolution in colormap::COLOR_MAP_RESOLUTIONS {
+                                            ui.selectable_value(&mut settings.resolution, resolution, resolution.to_string());
+                                        }
+                                    });
+                                ui.add(egui::Slider::new(&mut settings.scale, 0.01..=1.0).text("Feature Scale"));
+                                ui.add(egui::Slider::new(&mut settings.contrast, 0.0..=4.0).text("Contrast"));
+                                if settings.source == MacroSource::Noise {
+                                    ui.add(egui::DragValue::new(&mut settings.seed).prefix("Seed: "));
+                                }
+                            }
                         });
 
+                    // Terrain tools
+                    self.heightmap_tool.show(ui, self.output_directory.as_ref());
+                    let export_directory = self.export_directory();
+                    // Every export writes a manifest, which identifies the set
+                    let current_set = export_directory.as_ref().map(|dir| dir.join(self.file_names().manifest()));
+                    self.blend_preview.show(ui, current_set.as_deref(), &self.file_names());
+                    if self.plugins.show(ui) {
+                        self.plugins.run(self.plugin_sources(), self.overwrite_mode);
+                    }
+                    self.splatmap_converter.show(ui, self.output_directory.as_ref());
+                    self.export_comparer.show(ui, export_directory.as_ref());
+                    self.export_history.show(ui, export_directory.as_ref());
+                    self.convention_checker.show(ui);
+                    self.texture_inspector.show(ui);
+                    self.export_reorganizer.show(ui, &self.file_names());
+                    self.texture_budget.show(ui, self.output_directory.as_ref(), &self.file_names());
+                    self.batch_exporter.show(ui);
+                    if self.set_scanner.show(ui) {
+                        for project in self.set_scanner.jobs(&self.to_project()) {
+                            self.export_queue.add(project);
+                        }
+                    }
+                    if let Some(project) = self.export_queue.show(ui) {
+                        // A new project, the job's settings aren't saved anywhere yet
+                        self.apply_project(project);
+                        self.project_path = None;
+                    }
+                    if self.hot_folder.show(ui) {
+                        self.hot_folder.start(self.to_project());
+                    }
+
                     // Show processing status
                     match &self.processing_state {
                         ProcessingState::Processing => {
-                            ui.spinner();
-                            ui.label("Processing...");
+                            ui.horizontal(|ui| {
+                                ui.spinner();
+                                ui.label("Processing...");
+                                if let Some(progress) = &self.encode_progress {
+                                    if ui.button("Cancel").on_hover_text("Stops at the next chunk of DDS encoding, the output directory is left as it was").clicked() {
+                                        progress.cancel();
+                                    }
+                                }
+                            });
+                            if let Some(progress) = &self.encode_progress {
+                                ui.add(egui::ProgressBar::new(progress.fraction()).text(format!("Encoding DDS {:.0}%", progress.fraction() * 100.0)));
+                                ui.ctx().request_repaint();
+                            }
                         }
                         ProcessingState::Done => {
-                            ui.label("Processing complete");
+                            ui.horizontal(|ui| {
+                                ui.label("Processing complete");
+                                if let Some(dir) = self.export_directory() {
+                                    if ui.button("Open Output Folder").clicked() {
+                                        opener::open(dir).ok();
+                                    }
+                                }
+                            });
+                            CollapsingHeader::new(format!("Exported Files ({})", self.exported_files.len()))
+                                .default_open(false)
+                                .show(ui, |ui| {
+                                    for file in &self.exported_files {
+                                        ui.horizontal(|ui| {
+                                            if ui.small_button("Reveal").clicked() {
+                                                opener::reveal(file).ok();
+                                            }
+                                            let ext = file.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+                                            if Self::SUPPORTED_FORMATS.contains(&ext.as_str()) && ui.small_button("Inspect").clicked() {
+                                                self.texture_inspector.open(file.clone());
+                                            }
+                                            ui.label(file.file_name().unwrap_or_default().to_string_lossy().to_string());
+                                        });
+                                    }
+                                });
                         }
                         ProcessingState::Error(e) => {
                             ui.label(format!("Error: {}", e));
@@ -665,35 +3139,258 @@ impl App for TerrainApp {
                     }
 
                     ui.add_space(8.0);
-                    let run_button = ui.add_enabled_ui(
-                        self.are_required_images_loaded() && 
-                        !matches!(self.processing_state, ProcessingState::Processing),
-                        |ui| {
-                            ui.button("Run")
+                    if let Some(estimate) = &self.export_estimate {
+                        ui.label(format!("Estimated: {}", estimate));
+                    }
+                    ui.horizontal(|ui| {
+                        let run_button = ui.add_enabled_ui(
+                            self.are_required_images_loaded() && 
+                            !matches!(self.processing_state, ProcessingState::Processing),
+                            |ui| {
+                                ui.button("Run")
+                            }
+                        ).inner;
+
+                        if run_button.clicked() {
+                            self.start_export();
                         }
-                    ).inner;
-                    
-                    if run_button.clicked() {
-                        if let Err(e) = self.process_and_save_images() {
-                            self.processing_state = ProcessingState::Error(e);
+                        // Queued jobs export from a snapshot, so setup can go on meanwhile
+                        if ui.add_enabled(
+                            self.are_required_images_loaded() && self.output_directory.is_some(),
+                            egui::Button::new("Add to Queue"),
+                        ).on_hover_text("Exports the current maps and settings from the Export Queue, after the jobs before it").clicked() {
+                            self.export_queue.add(self.to_project());
                         }
-                    }
+                    });
                 });
             });
         });
+
+        // Settings only change through input or the results above, so hover
+        // and animation repaints don't snapshot them
+        let input = ctx.input(|input| {
+            input.pointer.any_down() || input.events.iter().any(|event| !matches!(
+                event,
+                egui::Event::PointerMoved(_) | egui::Event::MouseMoved(_) | egui::Event::PointerGone | egui::Event::WindowFocused(_)
+            ))
+        });
+        if input || received {
+            // Slider drags and typing become one undo step once they're done
+            let settled = !ctx.input(|input| input.pointer.any_down()) && ctx.memory(|memory| memory.focused().is_none());
+            let project = self.to_project();
+            self.undo_stack.track(&project, settled);
+            let estimate = self.timing_key(&project).and_then(|key| self.timing_stats.describe(&[key]));
+            if estimate != self.export_estimate {
+                self.export_estimate = estimate;
+                ctx.request_repaint();
+            }
+        }
+
+        LastUsed {
+            output_directory: self.output_directory.clone(),
+            output_format: self.output_format,
+            normal_map_format: self.normal_map_format,
+            roughness_format: self.roughness_format,
+        }.remember(&mut self.last_used);
+    }
+}
+
+fn print_plan(name: Option<&str>, report: &ExportReport) {
+    for output in &report.planned {
+        let size = output.dimensions.map_or(String::new(), |[width, height]| format!("{}x{}", width, height));
+        let file = match name {
+            Some(name) => format!("{}/{}", name, output.file),
+            None => output.file.clone(),
+        };
+        println!("{:<40} {:>11} {:<14} {}", file, size, output.format, preflight::format_bytes(output.estimated_bytes));
     }
+    let total = report.planned.iter().map(|output| output.estimated_bytes).sum();
+    println!("{} files, about {}", report.planned.len(), preflight::format_bytes(total));
 }
 
 fn main() -> eframe::Result<()> {
+    let launch = LaunchOptions::parse(std::env::args_os()).unwrap_or_else(|e| e.exit());
+    if let Err(e) = config::init(launch.config.as_deref()) {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    }
+
+    if let Some(port) = launch.serve {
+        let project = match &launch.project {
+            Some(path) => Project::load_with_defaults(path),
+            None => Ok(Project::with_defaults()),
+        };
+        let project = project.unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(report::EXIT_VALIDATION_FAILED);
+        });
+        let base = launch.project.as_deref().and_then(Path::parent)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let base = std::path::absolute(base).unwrap_or_else(|_| base.to_path_buf());
+        // The server only returns when it can't listen, 2 stays with command line errors
+        if let Err(e) = server::serve(port, project, base) {
+            eprintln!("{}", e);
+            std::process::exit(report::EXIT_ENCODE_FAILED);
+        }
+        return Ok(());
+    }
+
+    if let Some(root) = &launch.validate {
+        let sets = match validate::validate_folder(root, launch.rectangular) {
+            Ok(sets) => sets,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(report::EXIT_VALIDATION_FAILED);
+            }
+        };
+        if launch.json {
+            report::print_json(&sets);
+        } else {
+            validate::print_report(root, &sets);
+        }
+        let passed = sets.iter().all(|set| set.passed());
+        std::process::exit(if passed { 0 } else { report::EXIT_VALIDATION_FAILED });
+    }
+
+    if let Some(runs) = launch.benchmark {
+        let result = launch.headless_project().and_then(|project| benchmark::run(&project, runs));
+        match result {
+            Ok(report) if launch.json => report::print_json(&report),
+            Ok(report) => benchmark::print_report(&report),
+            Err(e) => {
+                eprintln!("Benchmark failed: {}", e);
+                std::process::exit(report::EXIT_VALIDATION_FAILED);
+            }
+        }
+        std::process::exit(0);
+    }
+
+    if let Some(path) = &launch.batch {
+        let reports = match batch::load_batch(path) {
+            Ok(mut sets) => {
+                for set in &mut sets {
+                    set.project.export_preview |= launch.preview;
+                    if let Some(mode) = launch.overwrite_mode {
+                        set.project.overwrite_mode = mode;
+                    }
+                }
+                let mut reports = Vec::new();
+                batch::run_batch(sets, launch.dry_run, |_, report| {
+                    if !launch.json {
+                        let name = report.name.as_deref().unwrap_or_default();
+                        match &report.error {
+                            None if launch.dry_run => print_plan(Some(name), &report),
+                            None if report.up_to_date => println!("{}: up to date", name),
+                            None if report.skipped => println!("{}: skipped, already in the output directory", name),
+                            None => println!("{}: {} files", name, report.outputs.len()),
+                            Some(e) => eprintln!("{}: {}", name, e),
+                        }
+                    }
+                    reports.push(report);
+                });
+                reports
+            }
+            Err(e) => {
+                if !launch.json {
+                    eprintln!("{}", e);
+                }
+                vec![ExportReport::new(Default::default()).fail(ExportStatus::ValidationFailed, e)]
+            }
+        };
+        let code = report::exit_code(&reports);
+        if launch.json {
+            report::print_json(&reports);
+        } else {
+            if code != 0 {
+                let failed = reports.iter().filter(|r| r.error.is_some()).count();
+                eprintln!("{} of {} sets failed", failed, reports.len());
+            }
+            if !launch.dry_run {
+                let outputs: Vec<_> = reports.iter()
+                    .filter(|r| r.error.is_none())
+                    .map(|r| (r.name.clone().unwrap_or_default(), r.outputs.clone()))
+                    .collect();
+                println!();
+                match budget::measure_sets(&outputs) {
+                    Ok(entries) => budget::print_summary(&entries),
+                    Err(e) => eprintln!("Texture budget: {}", e),
+                }
+            }
+        }
+        std::process::exit(code);
+    }
+
+    if launch.headless {
+        let report = match launch.headless_project() {
+            Ok(project) => TerrainApp::run_headless(project, launch.dry_run),
+            Err(e) => ExportReport::new(Default::default()).fail(ExportStatus::ValidationFailed, e),
+        };
+        if launch.json {
+            report::print_json(&report);
+        } else {
+            for warning in &report.warnings {
+                eprintln!("Warning: {}", warning);
+            }
+            match &report.error {
+                None if launch.dry_run => print_plan(None, &report),
+                None if report.skipped => println!("Skipped, already in the output directory"),
+                None => report.outputs.iter().for_each(|file| println!("{}", file.display())),
+                Some(e) => eprintln!("Export failed: {}", e),
+            }
+        }
+        std::process::exit(report::exit_code(std::slice::from_ref(&report)));
+    }
+
     let options = NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([640.0, 800.0]), // Adjusted for vertical layout
         ..Default::default()
     };
 
+    // Files opened while a window is running go to that window, unless the
+    // launch also asks for an export of its own
+    let instance = if launch.new_window || launch.export {
+        None
+    } else {
+        match single_instance::launch(launch.project.as_deref(), &launch.inputs) {
+            single_instance::Launch::Forwarded => return Ok(()),
+            single_instance::Launch::Primary(instance) => instance,
+        }
+    };
+    let instance = instance.as_ref();
+
+    let title = if paths::portable_config().is_some() { "Terrain 3D Prepare (Portable)" } else { "Terrain 3D Prepare" };
     run_native(
-        "Terrain 3D Prepare",
+        title,
         options,
-        Box::new(|_cc| Ok(Box::new(TerrainApp::default()))),
+        Box::new(move |cc| {
+            fonts::install_fallback_fonts(&cc.egui_ctx);
+            let mut app = TerrainApp::default();
+            if let Some(instance) = instance {
+                instance.listen(cc.egui_ctx.clone(), app.forwarded_sender.clone());
+            }
+            match launch.project {
+                Some(path) => app.open_project(path),
+                None => {
+                    let mut project = Project::with_defaults();
+                    // Asking is only possible with a window, so a new session
+                    // starts with it unless the defaults file says otherwise
+                    if config::defaults().get("overwrite_mode").is_none() {
+                        project.overwrite_mode = OverwriteMode::Ask;
+                    }
+                    if let Some(last_used) = &app.last_used {
+                        last_used.apply(&mut project);
+                    }
+                    app.apply_project(project);
+                }
+            }
+            if !launch.inputs.is_empty() {
+                app.assign_input_files(launch.inputs);
+            }
+            app.pending_export = launch.export;
+            app.exit_after_export = launch.exit;
+            Ok(Box::new(app))
+        }),
     )
 }
@@ -0,0 +1,129 @@
+use image::RgbaImage;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::paths;
+use crate::texel_density::TexelDensity;
+
+// Written next to the exported textures to describe what was produced
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ExportManifest {
+    pub tool_version: String,
+    pub average_color: Option<AverageColor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub texel_density: Option<TexelDensity>,
+    // Hash of the sources and settings the files were made from, only
+    // recorded when skipping or caching exports is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub export_key: Option<String>,
+    // Content hash of every file written next to the manifest
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, String>,
+}
+
+fn file_hash(path: &Path) -> Option<String> {
+    fs::read(paths::long_path(path)).ok().map(|bytes| format!("{:016x}", xxh3_64(&bytes)))
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AverageColor {
+    pub linear: [f32; 3],
+    pub srgb: [u8; 3],
+}
+
+impl ExportManifest {
+    pub fn new() -> Self {
+        Self {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            ..Default::default()
+        }
+    }
+
+    // name is the manifest's file name, which follows the export's template
+    pub fn load(output_dir: &Path, name: &str) -> Option<Self> {
+        let text = fs::read_to_string(paths::long_path(&output_dir.join(name))).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    // Starts from the manifest already in the directory, so the files a
+    // partial export leaves alone stay listed with their hashes
+    pub fn for_partial_update(output_dir: &Path, name: &str) -> Self {
+        let fresh = Self::new();
+        match Self::load(output_dir, name) {
+            Some(existing) => Self { tool_version: fresh.tool_version, ..existing },
+            None => fresh,
+        }
+    }
+
+    // Every file of the export, which is all of output_dir besides the
+    // manifest as long as it's the staging folder
+    pub fn record_files(&mut self, output_dir: &Path, name: &str) -> Result<(), String> {
+        for entry in fs::read_dir(output_dir).map_err(|e| e.to_string())? {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let file = entry.file_name().to_string_lossy().to_string();
+            if file != name && entry.file_type().is_ok_and(|t| t.is_file()) {
+                let hash = file_hash(&entry.path()).ok_or_else(|| format!("Failed to read {}", file))?;
+                self.files.insert(file, hash);
+            }
+        }
+        Ok(())
+    }
+
+    // The files of an earlier export made from the same key, as long as every
+    // one of them is still there unchanged
+    pub fn up_to_date_files(output_dir: &Path, name: &str, key: &str) -> Option<Vec<PathBuf>> {
+        let manifest = Self::load(output_dir, name)?;
+        if manifest.export_key.as_deref() != Some(key) || manifest.files.is_empty() {
+            return None;
+        }
+        let mut files = vec![output_dir.join(name)];
+        for (name, hash) in &manifest.files {
+            let path = output_dir.join(name);
+            if file_hash(&path).as_ref() != Some(hash) {
+                return None;
+            }
+            files.push(path);
+        }
+        files.sort();
+        Some(files)
+    }
+
+    pub fn save(&self, output_dir: &Path, name: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(output_dir.join(name), json).map_err(|e| e.to_string())
+    }
+}
+
+fn srgb_to_linear(v: u8) -> f32 {
+    let v = v as f32 / 255.0;
+    if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(v: f32) -> u8 {
+    let v = if v <= 0.0031308 { v * 12.92 } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 };
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// Averaged in linear space so dark and bright texels weigh in like they do
+// once the texture is minified on distant terrain
+pub fn average_color(albedo: &RgbaImage) -> AverageColor {
+    let sum = albedo.par_chunks(4)
+        .map(|p| [srgb_to_linear(p[0]) as f64, srgb_to_linear(p[1]) as f64, srgb_to_linear(p[2]) as f64])
+        .reduce(|| [0.0; 3], |a, b| [a[0] + b[0], a[1] + b[1], a[2] + b[2]]);
+    let count = (albedo.width() as f64 * albedo.height() as f64).max(1.0);
+    let linear = sum.map(|c| (c / count) as f32);
+    AverageColor {
+        linear,
+        srgb: linear.map(linear_to_srgb),
+    }
+}
+
+// Tiny solid texture for engines that tint distant terrain per material
+pub fn average_color_texture(color: &AverageColor) -> RgbaImage {
+    let [r, g, b] = color.srgb;
+    RgbaImage::from_pixel(4, 4, image::Rgba([r, g, b, 255]))
+}
@@ -0,0 +1,321 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::project::Project;
+use crate::{paths, NormalMapFormat, RoughnessFormat};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Slot {
+    Albedo,
+    Height,
+    AmbientOcclusion,
+    Normal,
+    Roughness,
+    Smoothness,
+    Translucency,
+    Opacity,
+}
+
+// Names used by common texturing tools, checked in order so e.g. a
+// "normal_mask" file counts as a normal map
+const SLOT_NAMES: [(Slot, &[&str]); 8] = [
+    (Slot::Albedo, &["albedo", "basecolor", "basecolour", "diffuse", "color", "colour", "col", "diff"]),
+    (Slot::Normal, &["normal", "normalgl", "normaldx", "nrm", "nor", "norm"]),
+    (Slot::Height, &["height", "displacement", "disp", "bump"]),
+    (Slot::AmbientOcclusion, &["ao", "ambientocclusion", "occlusion", "occ"]),
+    (Slot::Smoothness, &["gloss", "glossiness", "smoothness", "smooth"]),
+    (Slot::Roughness, &["roughness", "rough", "rgh"]),
+    (Slot::Translucency, &["translucency", "transmission", "sss", "subsurface"]),
+    (Slot::Opacity, &["opacity", "alpha", "mask"]),
+];
+
+// Lowercase name tokens with their byte offset in the stem
+fn tokens(stem: &str) -> Vec<(usize, String)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in stem.char_indices().chain([(stem.len(), ' ')]) {
+        match (c.is_ascii_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(first)) => {
+                tokens.push((first, stem[first..i].to_lowercase()));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+// The slot and the offset of the token that named it
+fn find_slot(stem: &str) -> Option<(Slot, usize)> {
+    let tokens = tokens(stem);
+    SLOT_NAMES.iter().find_map(|(slot, names)| {
+        let (offset, _) = tokens.iter().find(|(_, token)| names.contains(&token.as_str()))?;
+        Some((*slot, *offset))
+    })
+}
+
+// Guesses the slot from the naming conventions of common texturing tools,
+// e.g. rock_BaseColor.png or Rock_Normal_OpenGL.exr
+pub fn guess_slot(path: &Path) -> Option<Slot> {
+    find_slot(&path.file_stem()?.to_string_lossy()).map(|(slot, _)| slot)
+}
+
+// The part of the name before the map type, shared by every map of a set,
+// e.g. Rock023_2K for Rock023_2K_Color.png. Empty for names like normal.png.
+pub fn set_prefix(path: &Path) -> Option<String> {
+    let stem = path.file_stem()?.to_string_lossy();
+    let (_, offset) = find_slot(&stem)?;
+    Some(stem[..offset].trim_end_matches(|c: char| !c.is_ascii_alphanumeric()).to_string())
+}
+
+pub fn map_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(paths::long_path(dir)) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries.flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .map(|entry| dir.join(entry.file_name()))
+        .filter(|path| {
+            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+            crate::TerrainApp::SUPPORTED_FORMATS.contains(&ext.as_str())
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+// Asset libraries whose maps follow a known convention
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SourceLibrary {
+    Megascans,
+    AmbientCg,
+    Poliigon,
+}
+
+// The conventions a library's maps use unless a file name says otherwise
+pub struct LibraryProfile {
+    pub normal_format: NormalMapFormat,
+    pub roughness_format: RoughnessFormat,
+}
+
+impl SourceLibrary {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Megascans => "Megascans",
+            Self::AmbientCg => "AmbientCG",
+            Self::Poliigon => "Poliigon",
+        }
+    }
+
+    pub fn profile(self) -> LibraryProfile {
+        match self {
+            Self::Megascans => LibraryProfile {
+                normal_format: NormalMapFormat::OpenGL,
+                roughness_format: RoughnessFormat::Roughness,
+            },
+            // Ships NormalGL and NormalDX side by side, the names decide
+            Self::AmbientCg => LibraryProfile {
+                normal_format: NormalMapFormat::OpenGL,
+                roughness_format: RoughnessFormat::Roughness,
+            },
+            // Older sets come with GLOSS maps only
+            Self::Poliigon => LibraryProfile {
+                normal_format: NormalMapFormat::OpenGL,
+                roughness_format: RoughnessFormat::Smoothness,
+            },
+        }
+    }
+}
+
+// Megascans downloads carry a JSON description of the asset next to the maps
+fn has_megascans_metadata(dir: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(paths::long_path(dir)) else {
+        return false;
+    };
+    entries.flatten()
+        .map(|entry| dir.join(entry.file_name()))
+        .filter(|path| path.extension().is_some_and(|e| e.eq_ignore_ascii_case("json")))
+        .filter(|path| fs::metadata(path).is_ok_and(|m| m.len() < 1 << 20))
+        .any(|path| fs::read_to_string(path).is_ok_and(|text| {
+            let text = text.to_lowercase();
+            text.contains("megascans") || text.contains("quixel")
+        }))
+}
+
+// Recognizes a library by its file names, e.g. Rock030_2K-JPG_Color.jpg for
+// AmbientCG or RockFace_COL_3K.jpg for Poliigon
+pub fn detect_library(files: &[PathBuf]) -> Option<SourceLibrary> {
+    let stems: Vec<String> = files.iter()
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+        .collect();
+    let lower = |stem: &String| stem.to_lowercase();
+    if stems.iter().map(lower).any(|stem| stem.contains("k-jpg") || stem.contains("k-png") || stem.contains("ambientcg")) {
+        return Some(SourceLibrary::AmbientCg);
+    }
+    let poliigon_tokens = ["COL", "NRM", "GLOSS", "REFL", "DISP"];
+    if stems.iter().any(|stem| {
+        let tokens: Vec<&str> = stem.split(|c: char| !c.is_ascii_alphanumeric()).collect();
+        stem.to_lowercase().starts_with("poliigon")
+            || (tokens.iter().any(|token| poliigon_tokens.contains(token))
+                && tokens.iter().any(|token| token.len() > 1 && token.ends_with('K') && token[..token.len() - 1].bytes().all(|b| b.is_ascii_digit())))
+    }) {
+        return Some(SourceLibrary::Poliigon);
+    }
+    let mut dirs: Vec<&Path> = files.iter().filter_map(|path| path.parent()).collect();
+    dirs.dedup();
+    if stems.iter().map(lower).any(|stem| stem.contains("megascans") || stem.contains("quixel"))
+        || dirs.into_iter().any(has_megascans_metadata) {
+        return Some(SourceLibrary::Megascans);
+    }
+    None
+}
+
+// A normal map's convention when its name states it, e.g. Rock_NormalDX.png
+pub fn named_normal_format(path: &Path) -> Option<NormalMapFormat> {
+    let tokens = tokens(&path.file_stem()?.to_string_lossy());
+    tokens.iter().find_map(|(_, token)| match token.as_str() {
+        "normalgl" | "gl" | "opengl" => Some(NormalMapFormat::OpenGL),
+        "normaldx" | "dx" | "directx" => Some(NormalMapFormat::DirectX),
+        _ => None,
+    })
+}
+
+// Conventions for an assigned set: the library's profile, then anything the
+// file names state. Also returns the library when one was recognized.
+pub fn conventions(
+    maps: &[(Slot, PathBuf)],
+    normal_format: &mut NormalMapFormat,
+    roughness_format: &mut RoughnessFormat,
+) -> Option<SourceLibrary> {
+    let files: Vec<PathBuf> = maps.iter().map(|(_, path)| path.clone()).collect();
+    let library = detect_library(&files);
+    if let Some(library) = library {
+        let profile = library.profile();
+        *normal_format = profile.normal_format;
+        *roughness_format = profile.roughness_format;
+    }
+    for (slot, path) in maps {
+        match slot {
+            Slot::Normal => {
+                if let Some(format) = named_normal_format(path) {
+                    *normal_format = format;
+                }
+            }
+            Slot::Roughness => *roughness_format = RoughnessFormat::Roughness,
+            Slot::Smoothness => *roughness_format = RoughnessFormat::Smoothness,
+            _ => {}
+        }
+    }
+    library
+}
+
+impl Slot {
+    // Name of the editor slot the map loads into, smoothness going into the
+    // roughness slot
+    pub fn input_name(self) -> &'static str {
+        match self {
+            Slot::Albedo => "albedo",
+            Slot::Height => "height",
+            Slot::AmbientOcclusion => "ao",
+            Slot::Normal => "normal",
+            Slot::Roughness | Slot::Smoothness => "roughness",
+            Slot::Translucency => "translucency",
+            Slot::Opacity => "opacity",
+        }
+    }
+}
+
+// Sorts files into slots by name. The first file per slot wins, e.g. when
+// both a PNG and an EXR were exported, and files no name matched come back
+// separately.
+pub fn assign(files: &[PathBuf]) -> (Vec<(Slot, PathBuf)>, Vec<PathBuf>) {
+    let mut maps: Vec<(Slot, PathBuf)> = Vec::new();
+    let mut unrecognized = Vec::new();
+    for path in files {
+        match guess_slot(path) {
+            Some(slot) => {
+                if !maps.iter().any(|(other, _)| other.input_name() == slot.input_name()) {
+                    maps.push((slot, path.clone()));
+                }
+            }
+            None => unrecognized.push(path.clone()),
+        }
+    }
+    (maps, unrecognized)
+}
+
+// Folders replaced by the textures directly inside them
+pub fn expand_folders(paths: &[PathBuf]) -> Vec<PathBuf> {
+    paths.iter()
+        .flat_map(|path| if path.is_dir() { map_files(path) } else { vec![path.clone()] })
+        .collect()
+}
+
+pub fn apply(project: &mut Project, maps: &[(Slot, PathBuf)]) {
+    for (slot, path) in maps {
+        let target = match slot {
+            Slot::Albedo => &mut project.albedo_map,
+            Slot::Height => &mut project.height_map,
+            Slot::AmbientOcclusion => &mut project.ambient_occlusion_map,
+            Slot::Normal => &mut project.normal_map,
+            Slot::Roughness | Slot::Smoothness => &mut project.roughness_map,
+            Slot::Translucency => &mut project.translucency_map,
+            Slot::Opacity => &mut project.opacity_map,
+        };
+        *target = Some(path.clone());
+    }
+    if project.library_conventions {
+        conventions(maps, &mut project.normal_map_format, &mut project.roughness_format);
+    } else {
+        // Without profiles only the roughness slot's own name counts
+        for (slot, _) in maps {
+            match slot {
+                Slot::Roughness => project.roughness_format = RoughnessFormat::Roughness,
+                Slot::Smoothness => project.roughness_format = RoughnessFormat::Smoothness,
+                _ => {}
+            }
+        }
+    }
+}
+
+// A copy of the settings with the set's maps filled in
+pub fn set_project(settings: &Project, files: &[PathBuf], output: PathBuf) -> Result<Project, String> {
+    let mut project = Project {
+        albedo_map: None,
+        height_map: None,
+        ambient_occlusion_map: None,
+        normal_map: None,
+        roughness_map: None,
+        translucency_map: None,
+        opacity_map: None,
+        output_directory: Some(output),
+        ..settings.clone()
+    };
+    apply(&mut project, &assign(files).0);
+    if project.albedo_map.is_none() || project.normal_map.is_none() {
+        return Err("No albedo or normal map found".to_string());
+    }
+    Ok(project)
+}
+
+fn collect_folders(dir: &Path, folders: &mut Vec<PathBuf>) -> Result<(), String> {
+    folders.push(dir.to_path_buf());
+    let mut children: Vec<PathBuf> = fs::read_dir(paths::long_path(dir)).map_err(|e| format!("{}: {}", dir.display(), e))?
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|entry| dir.join(entry.file_name()))
+        .collect();
+    children.sort();
+    for child in children {
+        collect_folders(&child, folders)?;
+    }
+    Ok(())
+}
+
+// Root and every folder below it, parents before their children
+pub fn folders_under(root: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut folders = Vec::new();
+    collect_folders(root, &mut folders)?;
+    Ok(folders)
+}
@@ -0,0 +1,75 @@
+use image::{imageops::FilterType, RgbaImage};
+use rayon::prelude::*;
+
+pub const PREVIEW_SIZE: u32 = 256;
+
+// Sun from the upper left and a little in front, the same for every set so
+// thumbnails compare side by side
+const LIGHT: [f32; 3] = [-0.45, 0.55, 0.7];
+const AMBIENT: f32 = 0.2;
+// Reflectance of dielectrics at normal incidence
+const F0: f32 = 0.04;
+
+fn srgb_to_linear(v: u8) -> f32 {
+    let v = v as f32 / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(v: f32) -> u8 {
+    let v = v.clamp(0.0, 1.0);
+    let v = if v <= 0.003_130_8 { v * 12.92 } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 };
+    (v * 255.0).round() as u8
+}
+
+pub fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt().max(f32::EPSILON);
+    [v[0] / len, v[1] / len, v[2] / len]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+// Lambert diffuse plus GGX specular for one texel, from sRGB albedo, a
+// normal in tile space and the roughness byte as packed
+pub fn shade(albedo: [u8; 3], n: [f32; 3], roughness: u8) -> [u8; 3] {
+    let light = normalize(LIGHT);
+    let half = normalize([light[0], light[1], light[2] + 1.0]);
+    // GGX alpha, kept off zero so smooth texels don't become a single point
+    let roughness = (roughness.max(8) as f32 / 255.0).powi(2);
+
+    let n_dot_l = dot(n, light).max(0.0);
+    let n_dot_h = dot(n, half).max(0.0);
+    let d = roughness * roughness / (std::f32::consts::PI * (n_dot_h * n_dot_h * (roughness * roughness - 1.0) + 1.0).powi(2));
+    let fresnel = F0 + (1.0 - F0) * (1.0 - dot(half, light).max(0.0)).powi(5);
+    let specular = d * fresnel / 4.0 * n_dot_l;
+    albedo.map(|c| linear_to_srgb(srgb_to_linear(c) * (n_dot_l + AMBIENT) + specular))
+}
+
+// Normal from the packed OpenGL encoding
+pub fn decode_normal(packed: &[u8]) -> [f32; 3] {
+    normalize([packed[0], packed[1], packed[2]].map(|v| v as f32 / 127.5 - 1.0))
+}
+
+// The packed material lit on a flat tile seen from straight above, from the
+// textures as exported: albedo with height in alpha and OpenGL normals with
+// roughness in alpha
+pub fn render(albedo: &RgbaImage, normal: &RgbaImage, size: u32) -> RgbaImage {
+    let albedo = image::imageops::resize(albedo, size, size, FilterType::Triangle);
+    let normal = image::imageops::resize(normal, size, size, FilterType::Triangle);
+
+    let mut preview = RgbaImage::new(size, size);
+    preview.par_chunks_mut(4).enumerate().for_each(|(i, pixel)| {
+        let (x, y) = (i as u32 % size, i as u32 / size);
+        let a = albedo.get_pixel(x, y);
+        let packed = normal.get_pixel(x, y);
+        let color = shade([a[0], a[1], a[2]], decode_normal(&packed.0), packed[3]);
+        pixel[..3].copy_from_slice(&color);
+        pixel[3] = 255;
+    });
+    preview
+}
@@ -0,0 +1,53 @@
+use image::{GrayImage, RgbaImage};
+use rayon::prelude::*;
+
+// Rebuilds Z from X/Y for two-channel normal maps where blue was dropped,
+// e.g. textures that went through BC5
+pub fn reconstruct_z(normal: &mut RgbaImage) {
+    normal.par_chunks_mut(4).for_each(|p| {
+        let x = p[0] as f32 / 127.5 - 1.0;
+        let y = p[1] as f32 / 127.5 - 1.0;
+        let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+        p[2] = ((z * 0.5 + 0.5) * 255.0).round() as u8;
+    });
+}
+
+// Whether blue is the normal's Z, told apart from two-channel maps with
+// blue empty or holding translucency by most texels having at least about
+// the Z their X/Y leave. Sources aren't always normalized, so more is fine.
+pub fn holds_z(normal: &RgbaImage) -> bool {
+    let with_z = normal.par_chunks(4)
+        .filter(|p| {
+            let x = p[0] as f32 / 127.5 - 1.0;
+            let y = p[1] as f32 / 127.5 - 1.0;
+            let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+            p[2] >= 128 && p[2] as f32 + 32.0 >= (z * 0.5 + 0.5) * 255.0
+        })
+        .count();
+    with_z * 2 > normal.pixels().len()
+}
+
+// BC5 only keeps red and green, so a two-channel normal carrying translucency
+// in blue is written as BC7 instead
+pub fn two_channel_dds_format(translucency_in_blue: bool) -> image_dds::ImageFormat {
+    if translucency_in_blue {
+        image_dds::ImageFormat::BC7RgbaUnorm
+    } else {
+        image_dds::ImageFormat::BC5RgUnorm
+    }
+}
+
+// Splits a packed normal/roughness texture into an X/Y only normal map and a
+// single channel roughness map. The shader has to rebuild Z as
+// sqrt(1 - x² - y²), so blue is left empty.
+pub fn split_two_channel(packed: &RgbaImage) -> (RgbaImage, GrayImage) {
+    let mut normal = packed.clone();
+    normal.par_chunks_mut(4).for_each(|p| {
+        p[2] = 0;
+        p[3] = 255;
+    });
+    let roughness = GrayImage::from_fn(packed.width(), packed.height(), |x, y| {
+        image::Luma([packed.get_pixel(x, y)[3]])
+    });
+    (normal, roughness)
+}
@@ -0,0 +1,81 @@
+use egui::{Color32, ComboBox, Context, Ui};
+use serde::{Deserialize, Serialize};
+
+// What an export does when its textures are already in the output directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum OverwriteMode {
+    #[default]
+    Overwrite,
+    Ask,
+    Skip,
+    Version,
+}
+
+impl OverwriteMode {
+    const ALL: [OverwriteMode; 4] = [Self::Overwrite, Self::Ask, Self::Skip, Self::Version];
+
+    // The mode for exports run in the background, where nobody is there to
+    // answer the prompt
+    pub fn unattended(self) -> Self {
+        if self == Self::Ask { Self::Overwrite } else { self }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Overwrite => "Overwrite",
+            Self::Ask => "Ask",
+            Self::Skip => "Skip Export",
+            Self::Version => "Write Versioned",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Overwrite => "Replaces the existing textures",
+            Self::Ask => "Asks before replacing them, exports without a window fail instead",
+            Self::Skip => "Leaves the output directory alone and writes nothing",
+            Self::Version => "Writes the textures next to the existing ones as albedo_v2.png, albedo_v3.png and so on",
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) {
+        ComboBox::from_label("Existing Files")
+            .selected_text(self.label())
+            .show_ui(ui, |ui| {
+                for mode in Self::ALL {
+                    ui.selectable_value(self, mode, mode.label()).on_hover_text(mode.description());
+                }
+            })
+            .response
+            .on_hover_text("What happens when the textures are already in the output directory");
+    }
+}
+
+// Why an export without a window stops at existing textures in Ask mode
+pub fn cannot_ask(existing: &[String]) -> String {
+    format!("{} already in the output directory and an export without a window can't ask about them", existing.join(", "))
+}
+
+// Asks what to do about the existing files, where says where they are. Skip
+// means the export is cancelled.
+pub fn show_prompt(ctx: &Context, place: &str, existing: &[String]) -> Option<OverwriteMode> {
+    let mut choice = None;
+    egui::Window::new("Overwrite Existing Files?")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            ui.colored_label(Color32::YELLOW, format!("Already {}: {}", place, existing.join(", ")));
+            ui.horizontal(|ui| {
+                for mode in [OverwriteMode::Overwrite, OverwriteMode::Version] {
+                    if ui.button(mode.label()).on_hover_text(mode.description()).clicked() {
+                        choice = Some(mode);
+                    }
+                }
+                if ui.button("Cancel").clicked() {
+                    choice = Some(OverwriteMode::Skip);
+                }
+            });
+        });
+    choice
+}
@@ -0,0 +1,114 @@
+use image::{DynamicImage, ImageBuffer, Rgba, Rgba32FImage};
+use rayon::prelude::*;
+
+use crate::height_alpha::{HeightAlphaSettings, ParallaxSettings};
+use crate::resize;
+use crate::roughness::{RoughnessAdjust, RoughnessCurve};
+
+pub type Rgba16Image = ImageBuffer<Rgba<u16>, Vec<u16>>;
+
+// The packed textures computed in floating point end to end, so 16-bit
+// sources keep their precision and nothing is rounded to 8 bits before the
+// final quantization. Mirrors the 8-bit pipeline step for step.
+
+fn to_rgba16(image: Rgba32FImage) -> Rgba16Image {
+    DynamicImage::ImageRgba32F(image).to_rgba16()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn pack_albedo_height(
+    albedo: &DynamicImage,
+    ao: Option<&DynamicImage>,
+    height: Option<&DynamicImage>,
+    unpremultiply: bool,
+    height_alpha: &HeightAlphaSettings,
+    parallax: &ParallaxSettings,
+    default_height: u8,
+    max_size: Option<u32>,
+    wrap: bool,
+) -> Rgba16Image {
+    let mut packed = albedo.to_rgba32f();
+    let ao = ao.map(|ao| ao.to_luma32f());
+    let height = height.map(|height| height.to_luma32f());
+    let (width, image_height) = packed.dimensions();
+    let padding = parallax.edge_padding;
+    packed.par_chunks_mut(4).enumerate().for_each(|(i, p)| {
+        let (x, y) = ((i % width as usize) as u32, (i / width as usize) as u32);
+        if unpremultiply && p[3] > 0.0 {
+            for c in 0..3 {
+                p[c] = (p[c] / p[3]).min(1.0);
+            }
+        }
+        if let Some(ao) = &ao {
+            let ao = ao.get_pixel(x, y)[0];
+            for value in &mut p[..3] {
+                *value *= ao;
+            }
+        }
+        p[3] = match &height {
+            Some(height) => {
+                let value = parallax.apply(height_alpha.apply(height.get_pixel(x, y)[0])).clamp(0.0, 1.0);
+                let distance = x.min(y).min(width - 1 - x).min(image_height - 1 - y);
+                if distance < padding {
+                    let t = distance as f32 / padding as f32;
+                    let t = t * t * (3.0 - 2.0 * t);
+                    0.5 + (value - 0.5) * t
+                } else {
+                    value
+                }
+            }
+            None => default_height as f32 / 255.0,
+        };
+    });
+    to_rgba16(resize::downsample(&packed, max_size, wrap))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn pack_normal_roughness(
+    normal: &DynamicImage,
+    roughness: Option<&DynamicImage>,
+    reconstruct_z: bool,
+    flip_green: bool,
+    invert_roughness: bool,
+    curve: &RoughnessCurve,
+    adjust: &RoughnessAdjust,
+    default_roughness: u8,
+    max_size: Option<u32>,
+    wrap: bool,
+) -> Rgba16Image {
+    let mut packed = normal.to_rgba32f();
+    let roughness = roughness.map(|roughness| roughness.to_luma32f());
+    let width = packed.width();
+    packed.par_chunks_mut(4).enumerate().for_each(|(i, p)| {
+        if reconstruct_z {
+            let (x, y) = (p[0] * 2.0 - 1.0, p[1] * 2.0 - 1.0);
+            p[2] = (1.0 - x * x - y * y).max(0.0).sqrt() * 0.5 + 0.5;
+        }
+        if flip_green {
+            p[1] = 1.0 - p[1];
+        }
+        p[3] = match &roughness {
+            Some(roughness) => {
+                let value = roughness.get_pixel((i % width as usize) as u32, (i / width as usize) as u32)[0];
+                let value = if invert_roughness { 1.0 - value } else { value };
+                adjust.apply(curve.evaluate(value)).clamp(0.0, 1.0)
+            }
+            None => default_roughness as f32 / 255.0,
+        };
+    });
+
+    let (width, height) = packed.dimensions();
+    let mut resized = resize::downsample(&packed, max_size, wrap);
+    if resized.dimensions() != (width, height) {
+        resized.par_chunks_mut(4).for_each(|p| {
+            let v = [0, 1, 2].map(|c| p[c] * 2.0 - 1.0);
+            let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+            if length > 1e-4 {
+                for c in 0..3 {
+                    p[c] = v[c] / length * 0.5 + 0.5;
+                }
+            }
+        });
+    }
+    to_rgba16(resized)
+}
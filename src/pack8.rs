@@ -0,0 +1,81 @@
+use image::{GrayImage, RgbaImage};
+use rayon::prelude::*;
+
+use crate::height_alpha;
+
+// The 8-bit packing steps of an export, one function per step so the
+// benchmark times exactly the work an export does. pack16 mirrors them in
+// floating point.
+
+// Undo premultiplied alpha so edges of cutouts don't darken
+pub fn unpremultiply(texture: &mut RgbaImage) {
+    texture.par_chunks_mut(4).for_each(|pixel| {
+        let alpha = pixel[3] as f32 / 255.0;
+        if alpha > 0.0 {
+            for value in &mut pixel[..3] {
+                *value = (*value as f32 / alpha).min(255.0) as u8;
+            }
+        }
+    });
+}
+
+pub fn multiply_ao(texture: &mut RgbaImage, ao: &GrayImage) {
+    let width = texture.width() as usize;
+    texture.par_chunks_mut(4).enumerate().for_each(|(i, pixel)| {
+        let x = (i % width) as u32;
+        let y = (i / width) as u32;
+        let ao_val = ao.get_pixel(x, y)[0] as f32 / 255.0;
+        pixel[0] = (pixel[0] as f32 * ao_val) as u8;
+        pixel[1] = (pixel[1] as f32 * ao_val) as u8;
+        pixel[2] = (pixel[2] as f32 * ao_val) as u8;
+    });
+}
+
+// Height through the lookup table into alpha, or the neutral height when
+// there is none
+pub fn pack_height(texture: &mut RgbaImage, height: Option<&GrayImage>, lut: &[u8; 256], default_height: u8, edge_padding: u32) {
+    let width = texture.width() as usize;
+    if let Some(height) = height {
+        texture.par_chunks_mut(4).enumerate().for_each(|(i, pixel)| {
+            let x = (i % width) as u32;
+            let y = (i / width) as u32;
+            pixel[3] = lut[height.get_pixel(x, y)[0] as usize];
+        });
+        height_alpha::pad_edges(texture, edge_padding);
+    } else {
+        texture.par_chunks_mut(4).for_each(|pixel| {
+            pixel[3] = default_height;
+        });
+    }
+}
+
+// OpenGL normals with roughness in alpha, shaped by the lookup table. Without
+// a roughness map every texel gets default_roughness as it is, like the
+// default height.
+pub fn pack_normal_roughness(
+    normal: &mut RgbaImage,
+    roughness: Option<&GrayImage>,
+    directx: bool,
+    smoothness: bool,
+    lut: &[u8; 256],
+    default_roughness: u8,
+) {
+    let width = normal.width() as usize;
+    if directx {
+        normal.par_chunks_mut(4).for_each(|p| {
+            p[1] = 255 - p[1]; // Invert green channel
+        });
+    }
+    match roughness {
+        // Response curve, remap and clamp so no texel ends up fully smooth
+        Some(roughness) => normal.par_chunks_mut(4).enumerate().for_each(|(i, pixel)| {
+            let x = (i % width) as u32;
+            let y = (i / width) as u32;
+            let value = roughness.get_pixel(x, y)[0];
+            pixel[3] = lut[if smoothness { 255 - value } else { value } as usize];
+        }),
+        None => normal.par_chunks_mut(4).for_each(|pixel| {
+            pixel[3] = default_roughness;
+        }),
+    }
+}
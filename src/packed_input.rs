@@ -0,0 +1,150 @@
+use egui::{ComboBox, Ui};
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+impl Channel {
+    const ALL: [Channel; 4] = [Channel::Red, Channel::Green, Channel::Blue, Channel::Alpha];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn short_name(self) -> &'static str {
+        match self {
+            Channel::Red => "R",
+            Channel::Green => "G",
+            Channel::Blue => "B",
+            Channel::Alpha => "A",
+        }
+    }
+}
+
+// Which channel of a packed source feeds each grayscale slot. None means the
+// slot is loaded from its own file as usual.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelMapping {
+    pub ambient_occlusion: Option<Channel>,
+    pub roughness: Option<Channel>,
+    pub height: Option<Channel>,
+}
+
+impl Default for ChannelMapping {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+impl ChannelMapping {
+    pub const NONE: Self = Self {
+        ambient_occlusion: None,
+        roughness: None,
+        height: None,
+    };
+
+    // Occlusion, roughness, metallic. Metallic has no slot here.
+    pub fn arm() -> Self {
+        Self {
+            ambient_occlusion: Some(Channel::Red),
+            roughness: Some(Channel::Green),
+            height: None,
+        }
+    }
+
+    // Occlusion, roughness, displacement
+    pub fn ord() -> Self {
+        Self {
+            ambient_occlusion: Some(Channel::Red),
+            roughness: Some(Channel::Green),
+            height: Some(Channel::Blue),
+        }
+    }
+
+    pub fn channel_for(&self, image_type: &str) -> Option<Channel> {
+        match image_type {
+            "ao" => self.ambient_occlusion,
+            "roughness" => self.roughness,
+            "height" => self.height,
+            _ => None,
+        }
+    }
+
+    pub fn slots(&self) -> [(&'static str, Option<Channel>); 3] {
+        [
+            ("ao", self.ambient_occlusion),
+            ("roughness", self.roughness),
+            ("height", self.height),
+        ]
+    }
+
+    pub fn set(&mut self, image_type: &str, channel: Option<Channel>) {
+        match image_type {
+            "ao" => self.ambient_occlusion = channel,
+            "roughness" => self.roughness = channel,
+            "height" => self.height = channel,
+            _ => {}
+        }
+    }
+}
+
+// Deeper sources keep 16 bits so a packed height doesn't lose precision
+pub fn extract_channel(img: &DynamicImage, channel: Channel) -> DynamicImage {
+    let c = channel.index();
+    match img {
+        DynamicImage::ImageLuma8(_) | DynamicImage::ImageLumaA8(_)
+        | DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_) => {
+            let rgba = img.to_rgba8();
+            DynamicImage::ImageLuma8(GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+                Luma([rgba.get_pixel(x, y)[c]])
+            }))
+        }
+        _ => {
+            let rgba = img.to_rgba16();
+            DynamicImage::ImageLuma16(ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+                Luma([rgba.get_pixel(x, y)[c]])
+            }))
+        }
+    }
+}
+
+pub fn slot_label(path: &Path, channel: Option<Channel>) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    match channel {
+        Some(channel) => format!("{} ({})", name, channel.short_name()),
+        None => name,
+    }
+}
+
+pub fn mapping_editor(ui: &mut Ui, mapping: &mut ChannelMapping) {
+    ui.horizontal(|ui| {
+        if ui.button("ARM").on_hover_text("AO in red, roughness in green, metallic in blue").clicked() {
+            *mapping = ChannelMapping::arm();
+        }
+        if ui.button("ORD").on_hover_text("AO in red, roughness in green, height in blue").clicked() {
+            *mapping = ChannelMapping::ord();
+        }
+    });
+    for (label, slot) in [
+        ("AO Channel", &mut mapping.ambient_occlusion),
+        ("Roughness Channel", &mut mapping.roughness),
+        ("Height Channel", &mut mapping.height),
+    ] {
+        ComboBox::from_label(label)
+            .selected_text(slot.map_or("Unused", |c| c.short_name()))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(slot, None, "Unused");
+                for channel in Channel::ALL {
+                    ui.selectable_value(slot, Some(channel), channel.short_name());
+                }
+            });
+    }
+}
@@ -0,0 +1,102 @@
+use image::{imageops::FilterType, RgbaImage};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+const SAMPLE_SIZE: u32 = 128;
+const ITERATIONS: usize = 20;
+pub const SWATCH_SIZE: u32 = 64;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PaletteEntry {
+    pub srgb: [u8; 3],
+    pub hex: String,
+    // Fraction of the albedo closest to this color
+    pub weight: f32,
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+}
+
+fn nearest(centers: &[[f32; 3]], color: [f32; 3]) -> usize {
+    (0..centers.len())
+        .min_by(|a, b| distance(centers[*a], color).total_cmp(&distance(centers[*b], color)))
+        .unwrap_or(0)
+}
+
+// K-means over a downsampled copy of the albedo. Centers start from a
+// farthest point pass so results are stable between runs.
+pub fn extract_palette(albedo: &RgbaImage, count: usize) -> Vec<PaletteEntry> {
+    let sample = image::imageops::resize(albedo, SAMPLE_SIZE, SAMPLE_SIZE, FilterType::Triangle);
+    let colors: Vec<[f32; 3]> = sample.pixels().map(|p| [p[0] as f32, p[1] as f32, p[2] as f32]).collect();
+    let count = count.clamp(1, colors.len());
+
+    let mean = colors.iter().fold([0.0; 3], |a, c| [a[0] + c[0], a[1] + c[1], a[2] + c[2]])
+        .map(|c| c / colors.len() as f32);
+    let mut centers = vec![mean];
+    while centers.len() < count {
+        let farthest = colors.iter()
+            .max_by(|a, b| {
+                let da = centers.iter().map(|c| distance(*c, **a)).fold(f32::MAX, f32::min);
+                let db = centers.iter().map(|c| distance(*c, **b)).fold(f32::MAX, f32::min);
+                da.total_cmp(&db)
+            })
+            .copied()
+            .unwrap_or(mean);
+        centers.push(farthest);
+    }
+
+    let mut assignments = vec![0usize; colors.len()];
+    for _ in 0..ITERATIONS {
+        assignments.par_iter_mut().zip(colors.par_iter()).for_each(|(a, c)| *a = nearest(&centers, *c));
+        let mut sums = vec![([0.0f32; 3], 0usize); count];
+        for (a, c) in assignments.iter().zip(&colors) {
+            let (sum, n) = &mut sums[*a];
+            for i in 0..3 {
+                sum[i] += c[i];
+            }
+            *n += 1;
+        }
+        let mut moved = false;
+        for (center, (sum, n)) in centers.iter_mut().zip(sums) {
+            if n > 0 {
+                let updated = sum.map(|s| s / n as f32);
+                moved |= distance(*center, updated) > 0.01;
+                *center = updated;
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+
+    let mut entries: Vec<PaletteEntry> = centers.iter().enumerate()
+        .map(|(i, center)| {
+            let srgb = center.map(|c| c.round().clamp(0.0, 255.0) as u8);
+            PaletteEntry {
+                srgb,
+                hex: format!("#{:02x}{:02x}{:02x}", srgb[0], srgb[1], srgb[2]),
+                weight: assignments.iter().filter(|a| **a == i).count() as f32 / colors.len() as f32,
+            }
+        })
+        .filter(|entry| entry.weight > 0.0)
+        .collect();
+    entries.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+    entries
+}
+
+// Writes the entries as JSON and a strip of equal swatches, most common
+// color first
+pub fn save_palette(entries: &[PaletteEntry], json_path: &Path, strip_path: &Path) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(entries).map_err(|e| e.to_string())?;
+    fs::write(json_path, json).map_err(|e| e.to_string())?;
+
+    let width = SWATCH_SIZE * entries.len().max(1) as u32;
+    let strip = RgbaImage::from_fn(width, SWATCH_SIZE, |x, _| {
+        let [r, g, b] = entries.get((x / SWATCH_SIZE) as usize).map_or([0; 3], |e| e.srgb);
+        image::Rgba([r, g, b, 255])
+    });
+    strip.save(strip_path).map_err(|e| e.to_string())
+}
@@ -0,0 +1,137 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::config::CONFIG_FILE;
+
+const APP_DIR: &str = "terrain_3d_prepare";
+// Settings folder of a portable install, next to the executable
+const PORTABLE_DIR: &str = "settings";
+
+// A terrain3d_prepare.toml next to the executable makes the install portable:
+// it holds the defaults and every other setting lives beside it, so a
+// configured copy can go onto another machine or a network share as is
+pub fn portable_config() -> Option<&'static Path> {
+    static PORTABLE: OnceLock<Option<PathBuf>> = OnceLock::new();
+    PORTABLE.get_or_init(|| {
+        let exe = std::env::current_exe().ok()?;
+        Some(exe.parent()?.join(CONFIG_FILE)).filter(|path| path.is_file())
+    })
+    .as_deref()
+}
+
+// Per-user directory for settings and statistics that outlive a session, or
+// the portable one
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(config) = portable_config() {
+        return config.parent().map(|dir| dir.join(PORTABLE_DIR));
+    }
+    let base = if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+    base.map(|base| base.join(APP_DIR))
+}
+
+// Path expressed from base, e.g. ../exports from /a/project to /a/exports.
+// None when there is no relative route, such as another drive on Windows.
+pub fn relative_to(path: &Path, base: &Path) -> Option<PathBuf> {
+    use std::path::Component;
+
+    if !path.is_absolute() || !base.is_absolute() {
+        return None;
+    }
+    let path: Vec<Component> = path.components().filter(|c| !matches!(c, Component::CurDir)).collect();
+    let base: Vec<Component> = base.components().filter(|c| !matches!(c, Component::CurDir)).collect();
+    // Different drives or shares share no root to go through
+    if path.first() != base.first() || path.iter().chain(&base).any(|c| matches!(c, Component::ParentDir)) {
+        return None;
+    }
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let mut relative: PathBuf = base[common..].iter().map(|_| Component::ParentDir).collect();
+    relative.extend(&path[common..]);
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    Some(relative)
+}
+
+// Replaces ${PROJECT_ROOT} with root and any other ${NAME} with that
+// environment variable, so one job file works on every machine that sets
+// them, e.g. ${TEXTURE_LIBRARY}/rock/albedo.png
+pub fn expand_variables(path: &Path, root: &Path) -> Result<PathBuf, String> {
+    let Some(text) = path.to_str().filter(|text| text.contains("${")) else {
+        return Ok(path.to_path_buf());
+    };
+    let root = if root.as_os_str().is_empty() { Path::new(".") } else { root };
+    let mut expanded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| format!("Unclosed variable in {}", text))? + start;
+        let name = &rest[start + 2..end];
+        let value = match name {
+            "PROJECT_ROOT" => root.to_string_lossy().to_string(),
+            _ => std::env::var(name).map_err(|_| format!("{} uses ${{{}}}, which isn't set", text, name))?,
+        };
+        expanded.push_str(&value);
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(PathBuf::from(expanded))
+}
+
+// Windows rejects paths over MAX_PATH (260 characters) unless they carry the
+// extended-length prefix. Output directories get deep quickly with long set
+// names, and files are joined onto them afterwards, so every absolute path is
+// converted rather than only the ones already over the limit.
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> PathBuf {
+    use std::ffi::OsString;
+    use std::path::{Component, Prefix};
+
+    // Verbatim paths skip normalization, so resolve relative parts and
+    // forward slashes first
+    let Ok(absolute) = std::path::absolute(path) else {
+        return path.to_path_buf();
+    };
+    let mut components = absolute.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return absolute;
+    };
+    let mut prefixed = match prefix.kind() {
+        Prefix::Disk(_) => {
+            let mut prefixed = OsString::from(r"\\?\");
+            prefixed.push(prefix.as_os_str());
+            prefixed
+        }
+        Prefix::UNC(server, share) => {
+            let mut prefixed = OsString::from(r"\\?\UNC\");
+            prefixed.push(server);
+            prefixed.push(r"\");
+            prefixed.push(share);
+            prefixed
+        }
+        // Already verbatim or a device path
+        _ => return absolute,
+    };
+    let mut is_root = true;
+    for component in components.filter(|c| !matches!(c, Component::RootDir)) {
+        prefixed.push(r"\");
+        prefixed.push(component.as_os_str());
+        is_root = false;
+    }
+    if is_root {
+        prefixed.push(r"\");
+    }
+    PathBuf::from(prefixed)
+}
+
+#[cfg(not(windows))]
+pub fn long_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
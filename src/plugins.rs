@@ -0,0 +1,448 @@
+use egui::{CollapsingHeader, ComboBox, Context, Ui};
+use image::DynamicImage;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use crate::overwrite::{self, OverwriteMode};
+use crate::packed_input::{self, Channel};
+use crate::project::SLOTS;
+use crate::scripting::Script;
+use crate::{export_cache, file_names, frames, paths, ProcessingState};
+
+pub type Maps = BTreeMap<String, DynamicImage>;
+
+const MANIFEST: &str = "plugin.toml";
+
+// A map processor contributed from outside the app. It gets the input maps
+// it asks for and returns its output maps, which become new source files
+// for those slots.
+pub trait MapProcessor: Send + Sync {
+    fn name(&self) -> &str;
+    fn description(&self) -> &str;
+    fn inputs(&self) -> &[String];
+    fn outputs(&self) -> &[String];
+    fn process(&self, maps: &Maps) -> Result<Maps, String>;
+}
+
+// plugin.toml in a folder of the plugins directory, e.g.
+//
+//   name = "De-light"
+//   inputs = ["albedo", "ao"]
+//   outputs = ["albedo"]
+//   command = ["delight.exe", "{input}", "{output}"]
+//
+// The command gets a folder with the inputs as <slot>.png and one to write
+// the outputs to under the same names. A script = "file.rhai" instead runs
+// its fn process(p) for every pixel.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    name: String,
+    #[serde(default)]
+    description: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    #[serde(default)]
+    command: Vec<String>,
+    script: Option<PathBuf>,
+}
+
+struct CommandProcessor {
+    manifest: Manifest,
+    dir: PathBuf,
+}
+
+// Every run gets its own exchange folder
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+impl CommandProcessor {
+    fn run(&self, exchange: &Path, maps: &Maps) -> Result<Maps, String> {
+        let (input, output) = (exchange.join("input"), exchange.join("output"));
+        for dir in [&input, &output] {
+            fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        for (slot, img) in maps {
+            // PNG has no float formats, those are written with 16 bits
+            let img = match img {
+                DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => &DynamicImage::ImageRgba16(img.to_rgba16()),
+                img => img,
+            };
+            img.save(input.join(format!("{}.png", slot))).map_err(|e| format!("Failed to write the {} map: {}", slot, e))?;
+        }
+
+        let placeholders = |arg: &String| arg
+            .replace("{input}", &input.to_string_lossy())
+            .replace("{output}", &output.to_string_lossy());
+        let (program, args) = self.manifest.command.split_first().ok_or("The plugin has no command")?;
+        // Programs shipped inside the plugin folder are found without a path
+        let program = Some(self.dir.join(program)).filter(|path| path.is_file()).unwrap_or_else(|| PathBuf::from(program));
+        let result = Command::new(&program)
+            .args(args.iter().map(placeholders))
+            .current_dir(&self.dir)
+            .output()
+            .map_err(|e| format!("Failed to run {}: {}", program.display(), e))?;
+        if !result.status.success() {
+            let stderr = String::from_utf8_lossy(&result.stderr);
+            let last_line = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or_default();
+            return Err(format!("{} failed with {}: {}", self.manifest.name, result.status, last_line));
+        }
+
+        self.manifest.outputs.iter()
+            .map(|slot| {
+                let path = output.join(format!("{}.png", slot));
+                if !path.is_file() {
+                    return Err(format!("{} didn't write {}.png", self.manifest.name, slot));
+                }
+                frames::open_single(&path).map(|img| (slot.clone(), img))
+            })
+            .collect()
+    }
+}
+
+impl MapProcessor for CommandProcessor {
+    fn name(&self) -> &str {
+        &self.manifest.name
+    }
+
+    fn description(&self) -> &str {
+        &self.manifest.description
+    }
+
+    fn inputs(&self) -> &[String] {
+        &self.manifest.inputs
+    }
+
+    fn outputs(&self) -> &[String] {
+        &self.manifest.outputs
+    }
+
+    fn process(&self, maps: &Maps) -> Result<Maps, String> {
+        let exchange = std::env::temp_dir()
+            .join(format!("terrain_3d_prepare_plugin_{}_{}", std::process::id(), RUNS.fetch_add(1, Ordering::Relaxed)));
+        let result = self.run(&exchange, maps);
+        fs::remove_dir_all(&exchange).ok();
+        result
+    }
+}
+
+struct ScriptProcessor {
+    manifest: Manifest,
+    script: Script,
+}
+
+impl MapProcessor for ScriptProcessor {
+    fn name(&self) -> &str {
+        &self.manifest.name
+    }
+
+    fn description(&self) -> &str {
+        &self.manifest.description
+    }
+
+    fn inputs(&self) -> &[String] {
+        &self.manifest.inputs
+    }
+
+    fn outputs(&self) -> &[String] {
+        &self.manifest.outputs
+    }
+
+    fn process(&self, maps: &Maps) -> Result<Maps, String> {
+        self.script.process_set(maps, &self.manifest.outputs)
+    }
+}
+
+pub struct Plugin {
+    // Name of the plugin's folder, also used in the names of its outputs
+    pub id: String,
+    pub processor: Box<dyn MapProcessor>,
+}
+
+pub fn plugins_dir() -> Option<PathBuf> {
+    paths::config_dir().map(|dir| dir.join("plugins"))
+}
+
+fn load_plugin(dir: &Path) -> Result<Box<dyn MapProcessor>, String> {
+    let text = fs::read_to_string(paths::long_path(&dir.join(MANIFEST))).map_err(|e| e.to_string())?;
+    let manifest: Manifest = toml::from_str(&text).map_err(|e| e.to_string())?;
+    if let Some(slot) = manifest.inputs.iter().chain(&manifest.outputs).find(|slot| !SLOTS.contains(&slot.as_str())) {
+        return Err(format!("Unknown map slot {}, expected one of {}", slot, SLOTS.join(", ")));
+    }
+    if manifest.inputs.is_empty() || manifest.outputs.is_empty() {
+        return Err("A plugin needs at least one input and one output".to_string());
+    }
+    match (&manifest.script, manifest.command.is_empty()) {
+        (Some(script), true) => {
+            let script = Script::load(&dir.join(script))?;
+            if !script.has_function("process", 1) {
+                return Err("The script has no fn process(p)".to_string());
+            }
+            Ok(Box::new(ScriptProcessor { manifest, script }))
+        }
+        (None, false) => Ok(Box::new(CommandProcessor { manifest, dir: dir.to_path_buf() })),
+        _ => Err("A plugin needs either a command or a script".to_string()),
+    }
+}
+
+// Every plugin folder in the plugins directory, with the ones that failed to
+// load reported by folder name
+pub fn load_plugins(root: &Path) -> (Vec<Plugin>, Vec<String>) {
+    let mut plugins = Vec::new();
+    let mut errors = Vec::new();
+    let Ok(entries) = fs::read_dir(paths::long_path(root)) else {
+        return (plugins, errors);
+    };
+    let mut dirs: Vec<PathBuf> = entries.flatten()
+        .map(|entry| root.join(entry.file_name()))
+        .filter(|path| path.join(MANIFEST).is_file())
+        .collect();
+    dirs.sort();
+    for dir in dirs {
+        let id = dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+        match load_plugin(&dir) {
+            Ok(processor) => plugins.push(Plugin { id, processor }),
+            Err(e) => errors.push(format!("{}: {}", id, e)),
+        }
+    }
+    (plugins, errors)
+}
+
+// Where a slot's source map comes from, as the main loader reads it
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub path: PathBuf,
+    pub frame: usize,
+    pub channel: Option<Channel>,
+}
+
+fn load_source(source: &Source) -> Result<DynamicImage, String> {
+    let (img, _) = frames::open_frame(&paths::long_path(&source.path), source.frame)?;
+    Ok(match source.channel {
+        Some(channel) => packed_input::extract_channel(&img, channel),
+        None => img,
+    })
+}
+
+// Outputs are written next to the source they replace, or next to the
+// first input for slots that had none, e.g. rock_albedo_delight.png
+fn output_path(id: &str, slot: &str, sources: &BTreeMap<String, Source>, first_input: &Path) -> PathBuf {
+    let (base, suffix) = match sources.get(slot) {
+        Some(source) => (&source.path, id.to_string()),
+        None => (&first_input.to_path_buf(), format!("{}_{}", slot, id)),
+    };
+    let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+    base.with_file_name(format!("{}_{}.png", stem, suffix))
+}
+
+// The path of every output slot, none while the first input is missing
+fn output_paths(plugin: &Plugin, sources: &BTreeMap<String, Source>) -> Vec<(String, PathBuf)> {
+    let processor = &plugin.processor;
+    let Some(first_input) = sources.get(&processor.inputs()[0]) else {
+        return Vec::new();
+    };
+    processor.outputs().iter()
+        .map(|slot| (slot.clone(), output_path(&plugin.id, slot, sources, &first_input.path)))
+        .collect()
+}
+
+fn versioned(path: &Path, version: u32) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(file_names::versioned_name(&name, version))
+}
+
+// Runs map processors from the plugins directory on the current set
+pub struct Plugins {
+    plugins: Vec<Arc<Plugin>>,
+    load_errors: Vec<String>,
+    scanned: bool,
+    selected: usize,
+    state: ProcessingState,
+    // Sources of a run waiting on the overwrite prompt, with the outputs
+    // already next to them
+    prompt: Option<(BTreeMap<String, Source>, Vec<String>)>,
+    sender: Sender<Result<Vec<(String, PathBuf)>, String>>,
+    receiver: Receiver<Result<Vec<(String, PathBuf)>, String>>,
+}
+
+impl Default for Plugins {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            plugins: Vec::new(),
+            load_errors: Vec::new(),
+            scanned: false,
+            selected: 0,
+            state: ProcessingState::NotStarted,
+            prompt: None,
+            sender,
+            receiver,
+        }
+    }
+}
+
+impl Plugins {
+    fn rescan(&mut self) {
+        let (plugins, errors) = plugins_dir().map(|dir| load_plugins(&dir)).unwrap_or_default();
+        self.plugins = plugins.into_iter().map(Arc::new).collect();
+        self.load_errors = errors;
+        self.selected = self.selected.min(self.plugins.len().saturating_sub(1));
+        self.scanned = true;
+    }
+
+    // Runs the selected plugin on the slots' sources, overwrite says what
+    // happens to outputs of an earlier run
+    pub fn run(&mut self, sources: BTreeMap<String, Source>, overwrite: OverwriteMode) {
+        let Some(plugin) = self.plugins.get(self.selected).cloned() else {
+            return;
+        };
+        let mut outputs = output_paths(&plugin, &sources);
+        let exists = |path: &PathBuf| paths::long_path(path).exists();
+        if outputs.iter().any(|(_, path)| exists(path)) {
+            match overwrite {
+                OverwriteMode::Overwrite => {}
+                OverwriteMode::Ask => {
+                    let existing = outputs.iter()
+                        .filter(|(_, path)| exists(path))
+                        .map(|(_, path)| path.file_name().unwrap_or_default().to_string_lossy().to_string())
+                        .collect();
+                    self.prompt = Some((sources, existing));
+                    return;
+                }
+                OverwriteMode::Skip => {
+                    // Nothing is written, the outputs already there go into the slots
+                    outputs.retain(|(_, path)| exists(path));
+                    self.sender.send(Ok(outputs)).ok();
+                    self.state = ProcessingState::Processing;
+                    return;
+                }
+                OverwriteMode::Version => {
+                    let version = (2..)
+                        .find(|version| outputs.iter().all(|(_, path)| !exists(&versioned(path, *version))))
+                        .unwrap_or(2);
+                    for (_, path) in &mut outputs {
+                        *path = versioned(path, version);
+                    }
+                }
+            }
+        }
+        let tx = self.sender.clone();
+        self.state = ProcessingState::Processing;
+        thread::spawn(move || {
+            let result = (|| {
+                let processor = &plugin.processor;
+                let mut maps = Maps::new();
+                for slot in processor.inputs() {
+                    let source = sources.get(slot).ok_or_else(|| format!("{} needs a {} map", processor.name(), slot))?;
+                    maps.insert(slot.clone(), load_source(source).map_err(|e| format!("Failed to load {} map: {}", slot, e))?);
+                }
+                let maps = processor.process(&maps)?;
+                outputs.into_iter()
+                    .map(|(slot, path)| {
+                        let img = maps.get(&slot).ok_or_else(|| format!("{} returned no {} map", processor.name(), slot))?;
+                        // Never written in place, an earlier output may be linked into the export cache
+                        export_cache::replace_file(&paths::long_path(&path), |partial| img.save(partial).map_err(|e| e.to_string()))
+                            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                        Ok((slot, path))
+                    })
+                    .collect::<Result<Vec<_>, String>>()
+            })();
+            tx.send(result).ok();
+        });
+    }
+
+    // Slots to load the written outputs into
+    pub fn poll(&mut self, ctx: &Context) -> Vec<(String, PathBuf)> {
+        if let Some((_, existing)) = &self.prompt {
+            match overwrite::show_prompt(ctx, "next to the sources", existing) {
+                Some(OverwriteMode::Skip) => self.prompt = None,
+                Some(choice) => {
+                    let (sources, _) = self.prompt.take().unwrap();
+                    self.run(sources, choice);
+                }
+                None => {}
+            }
+        }
+        match self.receiver.try_recv() {
+            Ok(result) => {
+                ctx.request_repaint();
+                match result {
+                    Ok(outputs) => {
+                        self.state = ProcessingState::Done;
+                        outputs
+                    }
+                    Err(e) => {
+                        self.state = ProcessingState::Error(e);
+                        Vec::new()
+                    }
+                }
+            }
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // Returns true when the selected plugin should run
+    pub fn show(&mut self, ui: &mut Ui) -> bool {
+        let mut run = false;
+        CollapsingHeader::new("Plugins")
+            .default_open(false)
+            .show(ui, |ui| {
+                if !self.scanned {
+                    self.rescan();
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Rescan").clicked() {
+                        self.rescan();
+                    }
+                    if let Some(dir) = plugins_dir() {
+                        if ui.button("Open Plugins Folder")
+                            .on_hover_text(dir.display().to_string())
+                            .clicked() {
+                            fs::create_dir_all(&dir).ok();
+                            opener::open(&dir).ok();
+                        }
+                    }
+                });
+                for error in &self.load_errors {
+                    ui.colored_label(egui::Color32::YELLOW, error);
+                }
+                if self.plugins.is_empty() {
+                    ui.label(format!("No plugins yet, each one is a folder with a {} in the plugins folder", MANIFEST));
+                    return;
+                }
+
+                ComboBox::from_label("Plugin")
+                    .selected_text(self.plugins[self.selected].processor.name())
+                    .show_ui(ui, |ui| {
+                        for (i, plugin) in self.plugins.iter().enumerate() {
+                            ui.selectable_value(&mut self.selected, i, plugin.processor.name());
+                        }
+                    });
+                let processor = &self.plugins[self.selected].processor;
+                if !processor.description().is_empty() {
+                    ui.label(processor.description());
+                }
+                ui.label(format!("Reads {}, writes {}", processor.inputs().join(", "), processor.outputs().join(", ")));
+
+                let busy = matches!(self.state, ProcessingState::Processing) || self.prompt.is_some();
+                if ui.add_enabled(!busy, egui::Button::new("Run on Current Maps"))
+                    .on_hover_text("The outputs are saved next to their sources and replace them in the slots, Existing Files decides about earlier ones")
+                    .clicked() {
+                    run = true;
+                }
+                match &self.state {
+                    ProcessingState::Processing => { ui.spinner(); }
+                    ProcessingState::Done => { ui.label("Done"); }
+                    ProcessingState::Error(e) => { ui.colored_label(egui::Color32::RED, e); }
+                    _ => {}
+                }
+            });
+        run
+    }
+}
@@ -0,0 +1,58 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Quoted for the shell the command runs in, so paths with spaces or shell
+// characters stay one argument
+fn quote(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        format!("\"{}\"", path)
+    } else {
+        format!("'{}'", path.replace('\'', "'\\''"))
+    }
+}
+
+// {output} is the output directory, {name} its folder name and {files} every
+// file the export wrote
+pub fn expand(command: &str, output_dir: &Path, files: &[PathBuf]) -> String {
+    let name = Path::new(output_dir.file_name().unwrap_or_default());
+    let files: Vec<String> = files.iter().map(|file| quote(file)).collect();
+    command
+        .replace("{output}", &quote(output_dir))
+        .replace("{name}", &quote(name))
+        .replace("{files}", &files.join(" "))
+}
+
+// cmd gets the command line as written. Passed as an argument, the quotes
+// would be escaped as \" which cmd doesn't understand. /S makes cmd strip
+// only the outer pair.
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+    let mut shell = Command::new("cmd");
+    shell.raw_arg(format!("/S /C \"{}\"", command));
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+// Runs the command through the platform shell from the output directory and
+// waits for it, e.g. to copy the set into an engine project
+pub fn run(command: &str, output_dir: &Path, files: &[PathBuf]) -> Result<(), String> {
+    let expanded = expand(command, output_dir, files);
+    let output = shell(&expanded)
+        .current_dir(output_dir)
+        .output()
+        .map_err(|e| format!("Failed to run post-export command: {}", e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let detail = stderr.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or_default();
+    Err(format!("Export finished but the post-export command failed ({}): {}", output.status, detail.trim()))
+}
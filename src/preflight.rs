@@ -0,0 +1,41 @@
+use std::fs::{self, OpenOptions};
+use std::path::Path;
+
+use crate::paths;
+
+const PROBE_FILE: &str = ".terrain_3d_prepare_write_test";
+
+pub fn format_bytes(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= 1024.0 * MB {
+        format!("{:.1} GB", bytes as f64 / (1024.0 * MB))
+    } else {
+        format!("{:.1} MB", bytes as f64 / MB)
+    }
+}
+
+// Fails fast before an export starts instead of partway through writing it
+pub fn check_output_directory(dir: &Path, required_bytes: u64) -> Result<(), String> {
+    if !dir.is_dir() {
+        return Err(format!("Output directory {} does not exist", dir.display()));
+    }
+
+    // Permissions alone don't tell the whole story (read-only mounts, ACLs),
+    // so actually create a file
+    let probe = paths::long_path(dir).join(PROBE_FILE);
+    OpenOptions::new().write(true).create(true).truncate(true).open(&probe)
+        .map_err(|e| format!("Output directory {} is not writable: {}", dir.display(), e))?;
+    fs::remove_file(&probe).ok();
+
+    let available = fs2::available_space(dir)
+        .map_err(|e| format!("Failed to query free space: {}", e))?;
+    if available < required_bytes {
+        return Err(format!(
+            "Not enough free space in {}: about {} needed, {} available",
+            dir.display(),
+            format_bytes(required_bytes),
+            format_bytes(available)
+        ));
+    }
+    Ok(())
+}
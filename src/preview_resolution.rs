@@ -0,0 +1,79 @@
+use egui::{ComboBox, Ui};
+use image::{imageops::FilterType, DynamicImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+
+const PREFERENCE_FILE: &str = "preview_resolution.json";
+
+// Longest side of the in-app previews. Small keeps browsing many sets quick,
+// large shows the detail of 4K sources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum PreviewResolution {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl PreviewResolution {
+    const ALL: [PreviewResolution; 3] = [Self::Low, Self::Medium, Self::High];
+
+    pub fn pixels(self) -> u32 {
+        match self {
+            Self::Low => 256,
+            Self::Medium => 512,
+            Self::High => 1024,
+        }
+    }
+
+    // Previews keep the aspect ratio of rectangular maps. Sources smaller
+    // than the resolution are scaled up so every preview has the same size.
+    pub fn downscale(self, img: &DynamicImage) -> RgbaImage {
+        let (width, height) = (img.width(), img.height());
+        let scale = self.pixels() as f32 / width.max(height) as f32;
+        img.resize_exact(
+            ((width as f32 * scale) as u32).max(1),
+            ((height as f32 * scale) as u32).max(1),
+            FilterType::Nearest,
+        ).to_rgba8()
+    }
+
+    // Returns true when the resolution changed
+    pub fn show(&mut self, ui: &mut Ui) -> bool {
+        let before = *self;
+        ComboBox::from_label("Preview Resolution")
+            .selected_text(self.pixels().to_string())
+            .show_ui(ui, |ui| {
+                for resolution in Self::ALL {
+                    ui.selectable_value(self, resolution, resolution.pixels().to_string());
+                }
+            })
+            .response
+            .on_hover_text("Size of the map previews, exports always use the full resolution");
+        if before != *self {
+            self.save();
+            return true;
+        }
+        false
+    }
+
+    // Like the preview palette, a per-user preference rather than part of a project
+    pub fn load() -> Self {
+        paths::config_dir()
+            .and_then(|dir| std::fs::read_to_string(dir.join(PREFERENCE_FILE)).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(self) {
+        let Some(dir) = paths::config_dir() else {
+            return;
+        };
+        if std::fs::create_dir_all(&dir).is_ok() {
+            if let Ok(text) = serde_json::to_string(&self) {
+                std::fs::write(dir.join(PREFERENCE_FILE), text).ok();
+            }
+        }
+    }
+}
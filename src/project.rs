@@ -0,0 +1,739 @@
+use clap::error::ErrorKind;
+use clap::{ArgGroup, CommandFactory, FromArgMatches, Parser};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+use crate::config;
+use crate::encoding::MapEncoding;
+use crate::height_alpha::{HeightAlphaSettings, HeightEstimateSettings, ParallaxSettings};
+use crate::export_cache::LinkMode;
+use crate::file_names;
+use crate::macro_variation::MacroVariationSettings;
+use crate::overwrite::OverwriteMode;
+use crate::packed_input::ChannelMapping;
+use crate::layouts;
+use crate::map_names;
+use crate::paths;
+use crate::report;
+use crate::scripting;
+use crate::review::ReviewStatus;
+use crate::texel_density::TexelDensitySettings;
+use crate::roughness::{RoughnessAdjust, RoughnessCurve};
+use crate::{AlbedoAlphaMode, DdsQuality, NormalMapFormat, OutputFormat, RoughnessFormat};
+
+pub const PROJECT_EXTENSION: &str = "t3dp";
+// Project written next to the textures of every export, e.g. project.t3dp.json
+pub const SIDECAR_EXTENSION: &str = "t3dp.json";
+// Map slot names as used by the editor, the map flags and plugins, in input
+// slot order
+pub const SLOTS: [&str; 7] = ["albedo", "height", "ao", "normal", "roughness", "translucency", "opacity"];
+
+pub fn is_sidecar(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name.to_string_lossy().to_lowercase().ends_with(&format!(".{}", SIDECAR_EXTENSION)))
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportTarget {
+    pub directory: PathBuf,
+    // Same fallback as Project::output_directory_absolute
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub absolute: Option<PathBuf>,
+}
+
+// Relative to base when there's a way there, keeping the absolute path
+// alongside as a fallback
+fn store_relative(directory: &Path, base: &Path) -> (PathBuf, Option<PathBuf>) {
+    match paths::relative_to(directory, base) {
+        Some(relative) => (relative, Some(directory.to_path_buf())),
+        None => (directory.to_path_buf(), None),
+    }
+}
+
+// Resolves a stored directory, falling back to the absolute path it was
+// saved from when the relative one leads nowhere but that one exists
+fn resolve_directory(directory: &Path, absolute: Option<PathBuf>, base: &Path) -> PathBuf {
+    if directory.is_absolute() {
+        return directory.to_path_buf();
+    }
+    let resolved = base.join(directory);
+    match absolute {
+        Some(absolute) if !resolved.is_dir() && absolute.is_dir() => absolute,
+        _ => resolved,
+    }
+}
+
+// Everything needed to reproduce an export: source maps, output location and
+// every packing option. Missing fields fall back to defaults so older project
+// files keep loading as options are added.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Project {
+    pub albedo_map: Option<PathBuf>,
+    pub height_map: Option<PathBuf>,
+    pub ambient_occlusion_map: Option<PathBuf>,
+    pub normal_map: Option<PathBuf>,
+    pub roughness_map: Option<PathBuf>,
+    pub translucency_map: Option<PathBuf>,
+    pub opacity_map: Option<PathBuf>,
+    pub normal_map_format: NormalMapFormat,
+    // Set the normal and roughness conventions from the maps' names and the
+    // library they come from when maps are assigned by name
+    pub library_conventions: bool,
+    pub rectangular_mode: bool,
+    pub auto_crop: bool,
+    pub input_frames: BTreeMap<PathBuf, usize>,
+    // Declared encoding per slot, slots left on auto aren't stored
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub input_encodings: BTreeMap<String, MapEncoding>,
+    // Rhai script transforming the maps between loading and packing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<PathBuf>,
+    pub albedo_alpha_mode: AlbedoAlphaMode,
+    pub roughness_format: RoughnessFormat,
+    pub reconstruct_normal_z: bool,
+    pub roughness_adjust: RoughnessAdjust,
+    pub roughness_curve: RoughnessCurve,
+    pub height_alpha: HeightAlphaSettings,
+    pub parallax: ParallaxSettings,
+    // Alpha written as it is when there's no height or roughness map
+    pub default_height: u8,
+    pub default_roughness: u8,
+    pub height_estimate: HeightEstimateSettings,
+    pub output_directory: Option<PathBuf>,
+    // Write into a folder per set inside output_directory, named
+    // set_folder_name or after the set when that's empty
+    pub set_subfolder: bool,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub set_folder_name: String,
+    // Where output_directory pointed when it was saved relative to the
+    // project file, used when the relative path doesn't resolve
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_directory_absolute: Option<PathBuf>,
+    // Output locations saved under a name, e.g. one per engine project
+    pub export_targets: BTreeMap<String, ExportTarget>,
+    pub output_format: OutputFormat,
+    // Formats written from the same packed textures along with output_format
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub additional_formats: Vec<OutputFormat>,
+    pub dds_quality: DdsQuality,
+    // Name of the preset deciding which maps go into which output channels
+    pub packing_layout: String,
+    // Names of the written textures, see file_names for the tokens
+    pub file_name_template: String,
+    // What {map} becomes per output, e.g. _AH for albedo
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub map_names: BTreeMap<String, String>,
+    pub overwrite_mode: OverwriteMode,
+    // Which of the two texture groups an export writes, so a partial update
+    // leaves the other one in the output directory as it was
+    pub export_albedo: bool,
+    pub export_normal: bool,
+    pub two_channel_normals: bool,
+    pub pack_translucency: bool,
+    pub packed_channels: ChannelMapping,
+    pub albedo_output_size: Option<u32>,
+    pub normal_output_size: Option<u32>,
+    pub seamless_resize: bool,
+    pub punch_through_alpha: bool,
+    pub sixteen_bit_png: bool,
+    // Shell command run after each successful export, empty for none
+    pub post_export_command: String,
+    pub use_export_cache: bool,
+    // Leave the output alone when it was made from the same sources and
+    // settings and hasn't been touched since
+    pub skip_up_to_date: bool,
+    pub link_mode: LinkMode,
+    pub alpha_threshold: u8,
+    pub export_color_map: bool,
+    pub color_map_resolution: u32,
+    pub color_map_blur: u32,
+    pub export_average_color: bool,
+    pub export_palette: bool,
+    pub palette_size: usize,
+    pub export_preview: bool,
+    pub export_macro_variation: bool,
+    pub macro_variation: MacroVariationSettings,
+    pub texel_density: TexelDensitySettings,
+    // Art review of the set, kept with it but not part of the export
+    pub review_status: ReviewStatus,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+}
+
+impl Default for Project {
+    fn default() -> Self {
+        Self {
+            albedo_map: None,
+            height_map: None,
+            ambient_occlusion_map: None,
+            normal_map: None,
+            roughness_map: None,
+            translucency_map: None,
+            opacity_map: None,
+            normal_map_format: Default::default(),
+            library_conventions: true,
+            rectangular_mode: false,
+            auto_crop: false,
+            input_frames: BTreeMap::new(),
+            input_encodings: BTreeMap::new(),
+            script: None,
+            albedo_alpha_mode: Default::default(),
+            roughness_format: Default::default(),
+            reconstruct_normal_z: false,
+            roughness_adjust: Default::default(),
+            roughness_curve: Default::default(),
+            height_alpha: Default::default(),
+            parallax: Default::default(),
+            default_height: 255,
+            default_roughness: 128,
+            height_estimate: Default::default(),
+            output_directory: None,
+            set_subfolder: false,
+            set_folder_name: String::new(),
+            output_directory_absolute: None,
+            export_targets: BTreeMap::new(),
+            output_format: Default::default(),
+            additional_formats: Vec::new(),
+            dds_quality: Default::default(),
+            packing_layout: layouts::TERRAIN3D.to_string(),
+            file_name_template: file_names::DEFAULT_TEMPLATE.to_string(),
+            map_names: BTreeMap::new(),
+            overwrite_mode: Default::default(),
+            export_albedo: true,
+            export_normal: true,
+            two_channel_normals: false,
+            pack_translucency: false,
+            packed_channels: Default::default(),
+            albedo_output_size: None,
+            normal_output_size: None,
+            seamless_resize: true,
+            punch_through_alpha: false,
+            sixteen_bit_png: false,
+            post_export_command: String::new(),
+            use_export_cache: false,
+            skip_up_to_date: false,
+            link_mode: Default::default(),
+            alpha_threshold: 128,
+            export_color_map: false,
+            color_map_resolution: 256,
+            color_map_blur: 8,
+            export_average_color: false,
+            export_palette: false,
+            palette_size: 6,
+            export_preview: false,
+            export_macro_variation: false,
+            macro_variation: Default::default(),
+            texel_density: Default::default(),
+            review_status: Default::default(),
+            notes: String::new(),
+        }
+    }
+}
+
+impl Project {
+    // A new project starting from the defaults file, when there is one
+    pub fn with_defaults() -> Self {
+        serde_json::from_value(config::defaults().clone()).unwrap_or_default()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(paths::long_path(path)).map_err(|e| format!("Failed to read project: {}", e))?;
+        let mut project: Project = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid project file: {}", e))?;
+        // Relative paths are relative to the project file so a project can
+        // travel with its textures
+        project.resolve(path.parent().unwrap_or(Path::new(".")))?;
+        Ok(project)
+    }
+
+    // A project given on the command line, the defaults file filling in the
+    // fields it doesn't set
+    pub fn load_with_defaults(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(paths::long_path(path)).map_err(|e| format!("Failed to read project: {}", e))?;
+        let fields: serde_json::Map<String, Value> = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid project file: {}", e))?;
+        let mut merged = config::defaults().as_object().cloned().unwrap_or_default();
+        merged.extend(fields);
+        let mut project: Project = serde_json::from_value(Value::Object(merged))
+            .map_err(|e| format!("Invalid project file: {}", e))?;
+        project.resolve(path.parent().unwrap_or(Path::new(".")))?;
+        Ok(project)
+    }
+
+    // Makes paths absolute against base and repairs values the editor can't
+    // work with. Variables in paths are expanded with base as the project
+    // root, so a project saved from the editor afterwards holds the expanded
+    // paths.
+    pub fn resolve(&mut self, base: &Path) -> Result<(), String> {
+        if self.roughness_curve.points.len() < 2 {
+            self.roughness_curve = Default::default();
+        }
+        for slot in [
+            &mut self.albedo_map,
+            &mut self.height_map,
+            &mut self.ambient_occlusion_map,
+            &mut self.normal_map,
+            &mut self.roughness_map,
+            &mut self.translucency_map,
+            &mut self.opacity_map,
+            &mut self.script,
+            &mut self.output_directory,
+        ] {
+            if let Some(p) = slot.as_mut() {
+                *p = paths::expand_variables(p, base)?;
+            }
+        }
+        for target in self.export_targets.values_mut() {
+            target.directory = paths::expand_variables(&target.directory, base)?;
+        }
+
+        for slot in [
+            &mut self.albedo_map,
+            &mut self.height_map,
+            &mut self.ambient_occlusion_map,
+            &mut self.normal_map,
+            &mut self.roughness_map,
+            &mut self.translucency_map,
+            &mut self.opacity_map,
+            &mut self.script,
+        ] {
+            if let Some(p) = slot.as_mut().filter(|p| p.is_relative()) {
+                *p = base.join(&*p);
+            }
+        }
+        let absolute = self.output_directory_absolute.take();
+        if let Some(directory) = &mut self.output_directory {
+            *directory = resolve_directory(directory, absolute, base);
+        }
+        for target in self.export_targets.values_mut() {
+            target.directory = resolve_directory(&target.directory, target.absolute.take(), base);
+        }
+        self.input_frames = std::mem::take(&mut self.input_frames).into_iter()
+            .map(|(path, frame)| {
+                let path = paths::expand_variables(&path, base)?;
+                Ok((if path.is_relative() { base.join(path) } else { path }, frame))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(())
+    }
+
+    // One entry per input slot, empty slots included
+    pub fn input_paths(&self) -> Vec<Option<PathBuf>> {
+        [
+            &self.albedo_map,
+            &self.height_map,
+            &self.ambient_occlusion_map,
+            &self.normal_map,
+            &self.roughness_map,
+            &self.translucency_map,
+            &self.opacity_map,
+        ]
+        .into_iter()
+        .cloned()
+        .collect()
+    }
+
+    // The maps that are set, under their slot names
+    pub fn named_inputs(&self) -> BTreeMap<String, PathBuf> {
+        SLOTS
+            .into_iter()
+            .zip(self.input_paths())
+            .filter_map(|(slot, path)| Some((slot.to_string(), path?)))
+            .collect()
+    }
+
+    // Slot names as used by the editor and the map flags
+    pub fn set_map(&mut self, slot: &str, path: Option<PathBuf>) -> Result<(), String> {
+        let target = match slot {
+            "albedo" => &mut self.albedo_map,
+            "height" => &mut self.height_map,
+            "ao" => &mut self.ambient_occlusion_map,
+            "normal" => &mut self.normal_map,
+            "roughness" => &mut self.roughness_map,
+            "translucency" => &mut self.translucency_map,
+            "opacity" => &mut self.opacity_map,
+            other => return Err(format!("Unknown map slot {}", other)),
+        };
+        *target = path;
+        Ok(())
+    }
+
+    // Only the options that affect output pixels, without any locations. A
+    // script counts by its contents.
+    pub fn settings_json(&self) -> String {
+        let settings = Project {
+            albedo_map: None,
+            height_map: None,
+            ambient_occlusion_map: None,
+            normal_map: None,
+            roughness_map: None,
+            translucency_map: None,
+            opacity_map: None,
+            script: None,
+            output_directory: None,
+            set_subfolder: false,
+            set_folder_name: String::new(),
+            export_targets: BTreeMap::new(),
+            post_export_command: String::new(),
+            use_export_cache: false,
+            skip_up_to_date: false,
+            link_mode: Default::default(),
+            overwrite_mode: Default::default(),
+            review_status: Default::default(),
+            notes: String::new(),
+            ..self.clone()
+        };
+        let Some(script) = &self.script else {
+            return serde_json::to_string(&settings).unwrap_or_default();
+        };
+        let mut json = serde_json::to_value(&settings).unwrap_or_default();
+        if let Some(object) = json.as_object_mut() {
+            object.insert("script_hash".to_string(), scripting::script_hash(script).into());
+        }
+        json.to_string()
+    }
+
+    // Output locations are written relative to the project file so teammates
+    // with their checkouts elsewhere still export to the same place
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let mut project = self.clone();
+        if let Some(base) = std::path::absolute(path).ok().as_deref().and_then(Path::parent) {
+            if let Some(directory) = &self.output_directory {
+                (project.output_directory, project.output_directory_absolute) = {
+                    let (relative, absolute) = store_relative(directory, base);
+                    (Some(relative), absolute)
+                };
+            }
+            for target in project.export_targets.values_mut() {
+                (target.directory, target.absolute) = store_relative(&target.directory, base);
+            }
+        }
+        let json = serde_json::to_string_pretty(&project).map_err(|e| e.to_string())?;
+        fs::write(paths::long_path(path), json).map_err(|e| format!("Failed to write project: {}", e))
+    }
+
+    // Where an export writes the set's files
+    pub fn export_directory(&self) -> Option<PathBuf> {
+        let dir = self.output_directory.as_ref()?;
+        Some(file_names::set_directory(dir, self.set_subfolder, &self.set_folder_name, self.albedo_map.as_deref()))
+    }
+
+    // What the set's files are called in the export directory
+    pub fn file_names(&self) -> file_names::FileNames {
+        file_names::FileNames::new(&self.file_name_template, &self.map_names, file_names::set_name(self.albedo_map.as_deref()))
+    }
+
+    // The setup as exported into output_dir. Sources stay absolute and the
+    // output is the sidecar's own folder, so loading it later as a project
+    // reproduces the export in place.
+    pub fn sidecar_json(&self, output_dir: &Path) -> Result<String, String> {
+        let sidecar = Project {
+            output_directory: Some(PathBuf::from(".")),
+            output_directory_absolute: Some(output_dir.to_path_buf()),
+            set_subfolder: false,
+            ..self.clone()
+        };
+        serde_json::to_string_pretty(&sidecar).map_err(|e| e.to_string())
+    }
+}
+
+// Command line: terrain_3d_prepare [project.t3dp] [maps or folders...] [--export] [--exit]
+// or without a window: terrain_3d_prepare [project.t3dp] --albedo a.png
+// --normal n.png --out ./out --format dds
+#[derive(Debug, Default)]
+pub struct LaunchOptions {
+    pub project: Option<PathBuf>,
+    // Maps or set folders given without a flag, e.g. by "Open with", sorted
+    // into slots by their names
+    pub inputs: Vec<PathBuf>,
+    // Start an export as soon as the project's maps have loaded
+    pub export: bool,
+    // Close the window once that export finishes
+    pub exit: bool,
+    // Export without opening a window at all
+    pub headless: bool,
+    // Map slots and output settings given on the command line, applied on
+    // top of the project
+    pub maps: Vec<(&'static str, PathBuf)>,
+    pub output_directory: Option<PathBuf>,
+    // One of the project's named export targets, used as the output directory
+    pub target: Option<String>,
+    // The first of the formats given, e.g. png,dds, the rest are written too
+    pub output_format: Option<OutputFormat>,
+    pub additional_formats: Vec<OutputFormat>,
+    // Packing layout preset used instead of the project's
+    pub layout: Option<String>,
+    // Map transform script used instead of the project's
+    pub script: Option<PathBuf>,
+    // Batch file exported set by set without a window
+    pub batch: Option<PathBuf>,
+    // Print a JSON summary of a headless or batch run instead of plain lines
+    pub json: bool,
+    // Validate and list the outputs without writing anything
+    pub dry_run: bool,
+    // Render a lit preview.png with every export
+    pub preview: bool,
+    // Process the maps this many times and report how long each stage took
+    pub benchmark: Option<usize>,
+    // Defaults file used instead of the terrain3d_prepare.toml found
+    pub config: Option<PathBuf>,
+    // Folder of textures to check against the size rules without exporting
+    pub validate: Option<PathBuf>,
+    // Check with the trim sheet rules instead of square power of two
+    pub rectangular: bool,
+    // Local port to take requests from editor plugins on instead of opening
+    // a window
+    pub serve: Option<u16>,
+    // Open another window instead of handing the files to a running one
+    pub new_window: bool,
+    // What a run without a window does about textures already in the output
+    // directory, instead of the project's setting
+    pub overwrite_mode: Option<OverwriteMode>,
+}
+
+// Everything that turns a run into an export without a window
+const OVERRIDE_ARGS: [&str; 12] = [
+    "albedo", "height", "ao", "normal", "roughness", "translucency", "opacity", "out", "target", "format", "layout", "script",
+];
+
+// The arguments as clap reads them. Whether a path is a project or a map is
+// only known once they're sorted, so the rules about that are checked in
+// LaunchOptions::parse.
+#[derive(Parser)]
+#[command(name = "terrain_3d_prepare", version, about = "Packs terrain texture sets for Terrain3D and other engines")]
+#[command(group(ArgGroup::new("maps").multiple(true).args(SLOTS)))]
+#[command(group(ArgGroup::new("overrides").multiple(true).args(OVERRIDE_ARGS)))]
+#[command(group(ArgGroup::new("sources").multiple(true).args(["paths", "albedo", "height", "ao", "normal", "roughness", "translucency", "opacity"])))]
+struct Args {
+    // Arguments stay OS strings so project paths that aren't valid Unicode
+    // on this platform still open
+    #[arg(value_name = "PROJECT OR MAPS", help = "A project file and maps or set folders, sorted into slots by their names")]
+    paths: Vec<PathBuf>,
+    #[arg(long, conflicts_with_all = ["headless", "overrides", "json", "dry_run", "preview", "existing"],
+        help = "Start an export as soon as the project's maps have loaded")]
+    export: bool,
+    #[arg(long, requires = "export", help = "Close the window once that export finishes")]
+    exit: bool,
+    #[arg(long, conflicts_with_all = ["headless", "batch", "overrides", "json", "dry_run", "preview", "existing"],
+        help = "Open another window instead of handing the files to a running one")]
+    new_window: bool,
+    #[arg(long, help = "Export without opening a window")]
+    headless: bool,
+    #[arg(long, value_name = "MAP", help = "Albedo map used instead of the project's")]
+    albedo: Option<PathBuf>,
+    #[arg(long, value_name = "MAP", help = "Height map used instead of the project's")]
+    height: Option<PathBuf>,
+    #[arg(long, value_name = "MAP", help = "Ambient occlusion map used instead of the project's")]
+    ao: Option<PathBuf>,
+    #[arg(long, value_name = "MAP", help = "Normal map used instead of the project's")]
+    normal: Option<PathBuf>,
+    #[arg(long, value_name = "MAP", help = "Roughness map used instead of the project's")]
+    roughness: Option<PathBuf>,
+    #[arg(long, value_name = "MAP", help = "Translucency map used instead of the project's")]
+    translucency: Option<PathBuf>,
+    #[arg(long, value_name = "MAP", help = "Opacity map used instead of the project's")]
+    opacity: Option<PathBuf>,
+    #[arg(long, value_name = "DIR", help = "Output directory")]
+    out: Option<PathBuf>,
+    #[arg(long, value_name = "NAME", conflicts_with = "out", help = "One of the project's export targets as the output directory")]
+    target: Option<String>,
+    #[arg(long, value_name = "png|dds", value_delimiter = ',', value_parser = parse_format,
+        help = "Output format, the first of a list like png,dds is the project's and the rest are written too")]
+    format: Vec<OutputFormat>,
+    #[arg(long, value_name = "NAME", value_parser = parse_layout, help = "Packing layout preset used instead of the project's")]
+    layout: Option<String>,
+    #[arg(long, value_name = "FILE.rhai", help = "Map transform script used instead of the project's")]
+    script: Option<PathBuf>,
+    #[arg(long, value_name = "BATCH.json", conflicts_with_all = ["paths", "overrides", "export", "headless"],
+        help = "Export every set of a batch file without a window")]
+    batch: Option<PathBuf>,
+    #[arg(long, help = "Print a JSON summary on stdout")]
+    json: bool,
+    #[arg(long, help = "Only validate and list the files an export would write")]
+    dry_run: bool,
+    #[arg(long, help = "Also render a lit preview.png of each set")]
+    preview: bool,
+    #[arg(long, value_name = "overwrite|skip|version", value_parser = parse_existing,
+        help = "What to do about textures already in the output directory, instead of the project's setting")]
+    existing: Option<OverwriteMode>,
+    #[arg(long, value_name = "RUNS", requires = "sources",
+        conflicts_with_all = ["batch", "export", "exit", "headless", "dry_run", "preview", "existing", "script"],
+        help = "Process the maps this many times and report how long each stage took")]
+    benchmark: Option<NonZeroUsize>,
+    #[arg(long, value_name = "FILE", help = "Defaults file used instead of the terrain3d_prepare.toml found")]
+    config: Option<PathBuf>,
+    #[arg(long, value_name = "FOLDER",
+        conflicts_with_all = ["paths", "overrides", "export", "exit", "headless", "batch", "dry_run", "preview", "existing", "benchmark"],
+        help = "Check a folder of textures against the size rules without exporting")]
+    validate: Option<PathBuf>,
+    #[arg(long, requires = "validate", help = "Validate with the trim sheet rules instead of square power of two")]
+    rectangular: bool,
+    #[arg(long, value_name = "PORT",
+        conflicts_with_all = ["overrides", "export", "exit", "headless", "batch", "validate", "json", "dry_run", "rectangular", "preview", "existing", "benchmark"],
+        help = "Take requests from editor plugins on this local port instead of opening a window")]
+    serve: Option<u16>,
+}
+
+fn parse_format(format: &str) -> Result<OutputFormat, String> {
+    match format.trim().to_lowercase().as_str() {
+        "png" => Ok(OutputFormat::PNG),
+        "dds" => Ok(OutputFormat::DDS),
+        other => Err(format!("Unknown format {}, expected png or dds", other)),
+    }
+}
+
+fn parse_existing(mode: &str) -> Result<OverwriteMode, String> {
+    match mode.trim().to_lowercase().as_str() {
+        "overwrite" => Ok(OverwriteMode::Overwrite),
+        "skip" => Ok(OverwriteMode::Skip),
+        "version" => Ok(OverwriteMode::Version),
+        other => Err(format!("Unknown mode {}, expected overwrite, skip or version", other)),
+    }
+}
+
+fn parse_layout(name: &str) -> Result<String, String> {
+    layouts::find(name).map(|layout| layout.name.to_string())
+}
+
+impl LaunchOptions {
+    // Errors, --help and --version come back as clap errors, exit() prints
+    // them and exits with 2, or 0 for the latter two
+    pub fn parse(args: impl IntoIterator<Item = OsString>) -> Result<Self, clap::Error> {
+        let mut command = Args::command().after_help(format!(
+            "Maps, --out, --target, --format, --layout and --script export without a window, as do --json, --dry-run \
+            and --preview outside a batch.\n\
+            --layout packs for another engine, one of: {}.\n\
+            Defaults come from --config, otherwise {} is looked for in the working directory, its parents and the \
+            settings folder. One next to the executable makes the install portable, with its settings kept in a folder \
+            beside it. Project files and command line values take precedence over it.\n\
+            Exit codes: 0 success, 2 usage, {} invalid inputs, {} processing or writing failed",
+            layouts::LAYOUTS.iter().map(|layout| layout.name).collect::<Vec<_>>().join(", "),
+            config::CONFIG_FILE,
+            report::EXIT_VALIDATION_FAILED,
+            report::EXIT_ENCODE_FAILED,
+        ));
+        let matches = command.try_get_matches_from_mut(args)?;
+        let args = Args::from_arg_matches(&matches).map_err(|e| e.format(&mut command))?;
+        let mut error = |kind, message: String| Err(command.error(kind, message));
+
+        let maps = [
+            ("albedo", args.albedo),
+            ("height", args.height),
+            ("ao", args.ao),
+            ("normal", args.normal),
+            ("roughness", args.roughness),
+            ("translucency", args.translucency),
+            ("opacity", args.opacity),
+        ];
+        let overrides = matches.contains_id("overrides");
+        let mut options = Self {
+            export: args.export,
+            exit: args.exit,
+            headless: args.headless,
+            maps: maps.into_iter().filter_map(|(slot, path)| Some((slot, path?))).collect(),
+            output_directory: args.out,
+            target: args.target,
+            output_format: args.format.first().copied(),
+            additional_formats: args.format.iter().skip(1).copied().collect(),
+            layout: args.layout,
+            script: args.script,
+            batch: args.batch,
+            json: args.json,
+            dry_run: args.dry_run,
+            preview: args.preview,
+            benchmark: args.benchmark.map(NonZeroUsize::get),
+            config: args.config,
+            validate: args.validate,
+            rectangular: args.rectangular,
+            serve: args.serve,
+            new_window: args.new_window,
+            overwrite_mode: args.existing,
+            ..Default::default()
+        };
+        for path in args.paths {
+            let is_project = path.extension().is_some_and(|e| e.eq_ignore_ascii_case(PROJECT_EXTENSION))
+                || is_sidecar(&path);
+            if is_project && options.project.is_some() {
+                return error(ErrorKind::ArgumentConflict, format!("Unexpected second project {}", path.display()));
+            }
+            if is_project {
+                options.project = Some(path);
+            } else {
+                options.inputs.push(path);
+            }
+        }
+        if options.serve.is_some() && !options.inputs.is_empty() {
+            return error(ErrorKind::ArgumentConflict, "--serve can only be combined with a project file".to_string());
+        }
+        if options.serve.is_some() || options.validate.is_some() || options.benchmark.is_some() {
+            return Ok(options);
+        }
+        // Maps, an output or a summary asked for on the command line always
+        // mean an export without a window
+        options.headless |= overrides || (options.batch.is_none() && (options.json || options.dry_run || options.preview));
+        if options.headless && options.project.is_none() && options.maps.is_empty() && options.inputs.is_empty() {
+            return error(ErrorKind::MissingRequiredArgument, "A headless export needs a project file or maps".to_string());
+        }
+        if options.target.is_some() && options.project.is_none() {
+            return error(ErrorKind::MissingRequiredArgument, "--target needs a project file".to_string());
+        }
+        if let Some(missing) = options.inputs.iter().find(|path| !path.exists()) {
+            return error(ErrorKind::ValueValidation, format!("{} doesn't exist", missing.display()));
+        }
+        if options.export && options.project.is_none() {
+            return error(ErrorKind::MissingRequiredArgument, "--export needs a project file".to_string());
+        }
+        Ok(options)
+    }
+
+    // The project a headless export runs, command line values taking
+    // precedence over the project file and that over the defaults file
+    pub fn headless_project(&self) -> Result<Project, String> {
+        let mut project = match &self.project {
+            Some(path) => Project::load_with_defaults(path)?,
+            None => Project::with_defaults(),
+        };
+        let (maps, unrecognized) = map_names::assign(&map_names::expand_folders(&self.inputs));
+        // Stray files in a set folder are skipped, but a map named directly
+        // has to land somewhere
+        if let Some(path) = unrecognized.iter().find(|path| self.inputs.contains(path)) {
+            return Err(format!("Can't tell which map {} is, pass it with a flag like --albedo", path.display()));
+        }
+        map_names::apply(&mut project, &maps);
+        for (slot, path) in &self.maps {
+            project.set_map(slot, Some(path.clone()))?;
+        }
+        if let Some(dir) = &self.output_directory {
+            project.output_directory = Some(dir.clone());
+        }
+        if let Some(script) = &self.script {
+            project.script = Some(script.clone());
+        }
+        if let Some(name) = &self.target {
+            let target = project.export_targets.get(name).ok_or_else(|| {
+                let names: Vec<&str> = project.export_targets.keys().map(String::as_str).collect();
+                format!("The project has no export target named {} (targets: {})", name,
+                    if names.is_empty() { "none".to_string() } else { names.join(", ") })
+            })?;
+            project.output_directory = Some(target.directory.clone());
+        }
+        if let Some(format) = self.output_format {
+            project.output_format = format;
+            project.additional_formats = self.additional_formats.clone();
+        }
+        if let Some(layout) = &self.layout {
+            project.packing_layout = layout.clone();
+        }
+        project.export_preview |= self.preview;
+        if let Some(mode) = self.overwrite_mode {
+            project.overwrite_mode = mode;
+        }
+        Ok(project)
+    }
+}
@@ -0,0 +1,139 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::paths;
+
+const MAGIC: &[u8; 4] = b"T3DP";
+// The DDS header's 44 reserved bytes start after the file magic and seven
+// header fields. Readers ignore them, and ddsfile keeps them when rewriting.
+const RESERVED_OFFSET: u64 = 32;
+const RESERVED_LEN: usize = 44;
+
+// Convention flags
+pub const DIRECTX_SOURCE_NORMALS: u32 = 1;
+pub const SMOOTHNESS_SOURCE: u32 = 1 << 1;
+pub const TWO_CHANNEL_NORMALS: u32 = 1 << 2;
+pub const TRANSLUCENCY_IN_NORMAL: u32 = 1 << 3;
+pub const HEIGHT_IN_ALBEDO_ALPHA: u32 = 1 << 4;
+pub const RECONSTRUCTED_NORMAL_Z: u32 = 1 << 5;
+
+const CONVENTION_NAMES: [(u32, &str); 6] = [
+    (DIRECTX_SOURCE_NORMALS, "DirectX source normals flipped to OpenGL"),
+    (SMOOTHNESS_SOURCE, "smoothness source"),
+    (TWO_CHANNEL_NORMALS, "two-channel normals"),
+    (TRANSLUCENCY_IN_NORMAL, "translucency in normal blue"),
+    (HEIGHT_IN_ALBEDO_ALPHA, "height in albedo alpha"),
+    (RECONSTRUCTED_NORMAL_Z, "normal Z reconstructed"),
+];
+
+// Where a packed texture came from, small enough to live inside the texture
+// itself so it survives without the manifest
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Provenance {
+    pub version: [u8; 3],
+    pub conventions: u32,
+    // Contents of every input file, in slot order
+    pub sources_hash: u64,
+    pub settings_hash: u64,
+}
+
+impl Provenance {
+    pub fn new(settings: &str, inputs: &[Option<PathBuf>], conventions: u32) -> Result<Self, String> {
+        let mut sources = Xxh3::new();
+        for input in inputs {
+            sources.update(&[input.is_some() as u8]);
+            if let Some(input) = input {
+                let bytes = fs::read(paths::long_path(input)).map_err(|e| format!("{}: {}", input.display(), e))?;
+                sources.update(&(bytes.len() as u64).to_le_bytes());
+                sources.update(&bytes);
+            }
+        }
+        let mut version = [0; 3];
+        for (part, value) in version.iter_mut().zip(env!("CARGO_PKG_VERSION").split('.')) {
+            *part = value.parse().unwrap_or(0);
+        }
+        Ok(Self {
+            version,
+            conventions,
+            sources_hash: sources.digest(),
+            settings_hash: xxhash_rust::xxh3::xxh3_64(settings.as_bytes()),
+        })
+    }
+
+    fn to_bytes(self) -> [u8; RESERVED_LEN] {
+        let mut bytes = [0; RESERVED_LEN];
+        bytes[0..4].copy_from_slice(MAGIC);
+        bytes[4..7].copy_from_slice(&self.version);
+        bytes[8..12].copy_from_slice(&self.conventions.to_le_bytes());
+        bytes[12..20].copy_from_slice(&self.sources_hash.to_le_bytes());
+        bytes[20..28].copy_from_slice(&self.settings_hash.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; RESERVED_LEN]) -> Option<Self> {
+        if &bytes[0..4] != MAGIC {
+            return None;
+        }
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+        Some(Self {
+            version: [bytes[4], bytes[5], bytes[6]],
+            conventions: u32::from_le_bytes(bytes[8..12].try_into().unwrap()),
+            sources_hash: u64_at(12),
+            settings_hash: u64_at(20),
+        })
+    }
+
+    pub fn describe(&self) -> Vec<String> {
+        let conventions: Vec<&str> = CONVENTION_NAMES.iter()
+            .filter(|(flag, _)| self.conventions & flag != 0)
+            .map(|(_, name)| *name)
+            .collect();
+        vec![
+            format!("Written by terrain_3d_prepare {}.{}.{}", self.version[0], self.version[1], self.version[2]),
+            format!("Sources {:016x}, settings {:016x}", self.sources_hash, self.settings_hash),
+            format!("Conventions: {}", if conventions.is_empty() { "defaults".to_string() } else { conventions.join(", ") }),
+        ]
+    }
+}
+
+fn is_dds(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("dds"))
+}
+
+pub fn stamp(path: &Path, provenance: &Provenance) -> Result<(), String> {
+    let mut file = OpenOptions::new().read(true).write(true).open(paths::long_path(path)).map_err(|e| e.to_string())?;
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic).map_err(|e| e.to_string())?;
+    if &magic != b"DDS " {
+        return Err(format!("{} is not a DDS file", path.display()));
+    }
+    file.seek(SeekFrom::Start(RESERVED_OFFSET)).map_err(|e| e.to_string())?;
+    file.write_all(&provenance.to_bytes()).map_err(|e| e.to_string())
+}
+
+// Stamps every DDS written into an export directory
+pub fn stamp_outputs(dir: &Path, provenance: &Provenance) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if is_dds(&path) {
+            stamp(&path, provenance).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+// None for non-DDS files and textures written by other tools
+pub fn read(path: &Path) -> Option<Provenance> {
+    if !is_dds(path) {
+        return None;
+    }
+    let mut file = fs::File::open(paths::long_path(path)).ok()?;
+    let mut header = [0; RESERVED_OFFSET as usize + RESERVED_LEN];
+    file.read_exact(&mut header).ok()?;
+    if &header[0..4] != b"DDS " {
+        return None;
+    }
+    Provenance::from_bytes(header[RESERVED_OFFSET as usize..].try_into().unwrap())
+}
@@ -0,0 +1,130 @@
+use egui::Ui;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::paths;
+
+const RECENT_FILE: &str = "recent.json";
+const MAX_RECENT: usize = 10;
+
+pub enum RecentChoice {
+    Project(PathBuf),
+    // Files or folders to assign to slots by name
+    Maps(Vec<PathBuf>),
+}
+
+// Projects and source maps opened lately, newest first, kept across sessions
+// like the preview palette so a set from yesterday is two clicks away
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecentItems {
+    projects: Vec<PathBuf>,
+    maps: Vec<PathBuf>,
+}
+
+fn push_front(list: &mut Vec<PathBuf>, path: PathBuf) -> bool {
+    if list.first() == Some(&path) {
+        return false;
+    }
+    list.retain(|item| *item != path);
+    list.insert(0, path);
+    list.truncate(MAX_RECENT);
+    true
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().to_string()
+}
+
+impl RecentItems {
+    pub fn load() -> Self {
+        paths::config_dir()
+            .and_then(|dir| std::fs::read_to_string(dir.join(RECENT_FILE)).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let Some(dir) = paths::config_dir() else {
+            return;
+        };
+        if std::fs::create_dir_all(&dir).is_ok() {
+            if let Ok(text) = serde_json::to_string_pretty(self) {
+                std::fs::write(dir.join(RECENT_FILE), text).ok();
+            }
+        }
+    }
+
+    pub fn add_project(&mut self, path: PathBuf) {
+        if push_front(&mut self.projects, path) {
+            self.save();
+        }
+    }
+
+    pub fn add_map(&mut self, path: PathBuf) {
+        if push_front(&mut self.maps, path) {
+            self.save();
+        }
+    }
+
+    // Folders of the recent maps, each loads as a whole set
+    fn folders(&self) -> Vec<&Path> {
+        let mut folders: Vec<&Path> = Vec::new();
+        for folder in self.maps.iter().filter_map(|path| path.parent()) {
+            if !folders.contains(&folder) {
+                folders.push(folder);
+            }
+        }
+        folders
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) -> Option<RecentChoice> {
+        let mut choice = None;
+        let empty = self.projects.is_empty() && self.maps.is_empty();
+        ui.add_enabled_ui(!empty, |ui| {
+            ui.menu_button("Recent", |ui| {
+                // Entries that have gone away stay listed but can't be picked
+                let entry = |ui: &mut Ui, label: String, path: &Path| {
+                    let button = ui.add_enabled(path.exists(), egui::Button::new(label))
+                        .on_hover_text(path.display().to_string())
+                        .on_disabled_hover_text(format!("{} no longer exists", path.display()));
+                    button.clicked()
+                };
+                if !self.projects.is_empty() {
+                    ui.label("Projects");
+                    for path in &self.projects {
+                        if entry(ui, file_name(path), path) {
+                            choice = Some(RecentChoice::Project(path.clone()));
+                        }
+                    }
+                    ui.separator();
+                }
+                if !self.maps.is_empty() {
+                    ui.label("Map Folders").on_hover_text("Loads every map in the folder into the slot its name suggests");
+                    for folder in self.folders() {
+                        if entry(ui, file_name(folder), folder) {
+                            choice = Some(RecentChoice::Maps(vec![folder.to_path_buf()]));
+                        }
+                    }
+                    ui.separator();
+                    ui.label("Maps");
+                    for path in &self.maps {
+                        if entry(ui, file_name(path), path) {
+                            choice = Some(RecentChoice::Maps(vec![path.clone()]));
+                        }
+                    }
+                    ui.separator();
+                }
+                if ui.button("Clear Recent").clicked() {
+                    *self = Self::default();
+                    self.save();
+                    ui.close_menu();
+                }
+                if choice.is_some() {
+                    ui.close_menu();
+                }
+            });
+        });
+        choice
+    }
+}
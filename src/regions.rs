@@ -0,0 +1,301 @@
+use image::{GrayImage, ImageBuffer, Luma, Rgb, RgbaImage};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::godot_resource::{self, GodotImage};
+use crate::heightmap::HeightBuffer;
+use crate::splatmap;
+
+pub const REGION_SIZES: [u32; 6] = [64, 128, 256, 512, 1024, 2048];
+
+// Raw Terrain3D control bits, one u32 per pixel
+pub type ControlBuffer = ImageBuffer<Luma<u32>, Vec<u32>>;
+
+#[derive(Debug, Clone)]
+pub struct RegionLayout {
+    pub region_size: u32,
+    pub excluded: HashSet<(i32, i32)>,
+    // Location of the top-left region, None centers the grid on the origin
+    pub origin: Option<(i32, i32)>,
+}
+
+impl Default for RegionLayout {
+    fn default() -> Self {
+        Self {
+            region_size: 256,
+            excluded: HashSet::new(),
+            origin: None,
+        }
+    }
+}
+
+impl RegionLayout {
+    pub fn grid(&self, width: u32, height: u32) -> (u32, u32) {
+        (width.div_ceil(self.region_size), height.div_ceil(self.region_size))
+    }
+
+    // Region locations are centered on the origin like Terrain3D's world grid
+    // unless an explicit origin was imported
+    pub fn location(&self, col: u32, row: u32, cols: u32, rows: u32) -> (i32, i32) {
+        let (ox, oy) = self.origin.unwrap_or((-((cols / 2) as i32), -((rows / 2) as i32)));
+        (col as i32 + ox, row as i32 + oy)
+    }
+
+    pub fn toggle(&mut self, location: (i32, i32)) {
+        if !self.excluded.remove(&location) {
+            self.excluded.insert(location);
+        }
+    }
+
+    pub fn is_excluded(&self, location: (i32, i32)) -> bool {
+        self.excluded.contains(&location)
+    }
+}
+
+// Matches Terrain3D's region file naming, e.g. terrain3d_01-02
+pub fn region_name(location: (i32, i32)) -> String {
+    let part = |v: i32| format!("{}{:02}", if v < 0 { "-" } else { "_" }, v.abs());
+    format!("terrain3d{}{}", part(location.0), part(location.1))
+}
+
+pub fn parse_region_name(name: &str) -> Option<(i32, i32)> {
+    let rest = name.strip_prefix("terrain3d")?;
+    if rest.len() < 6 || !rest.is_char_boundary(6) {
+        return None;
+    }
+    let part = |s: &str| -> Option<i32> {
+        let value: i32 = s[1..3].parse().ok()?;
+        match &s[0..1] {
+            "-" => Some(-value),
+            "_" => Some(value),
+            _ => None,
+        }
+    };
+    Some((part(&rest[0..3])?, part(&rest[3..6])?))
+}
+
+pub struct RegionData<'a> {
+    pub heights: &'a HeightBuffer,
+    pub control: Option<&'a ControlBuffer>,
+    pub color: Option<&'a RgbaImage>,
+    pub water: Option<&'a GrayImage>,
+}
+
+// Writes each included region as a float EXR with heights in world units,
+// plus control, color and water maps when the source has them. Partial regions at
+// the right and bottom edges repeat the last row/column.
+pub fn export_regions(
+    data: &RegionData,
+    layout: &RegionLayout,
+    height_scale: f32,
+    height_offset: f32,
+    output_dir: &Path,
+) -> Result<usize, String> {
+    let heights = data.heights;
+    let (cols, rows) = layout.grid(heights.width(), heights.height());
+    let size = layout.region_size;
+    let mut written = 0;
+
+    for row in 0..rows {
+        for col in 0..cols {
+            let location = layout.location(col, row, cols, rows);
+            if layout.is_excluded(location) {
+                continue;
+            }
+            let source = |x: u32, y: u32| {
+                ((col * size + x).min(heights.width() - 1), (row * size + y).min(heights.height() - 1))
+            };
+            let name = region_name(location);
+
+            let region: ImageBuffer<Rgb<f32>, Vec<f32>> = ImageBuffer::from_fn(size, size, |x, y| {
+                let (sx, sy) = source(x, y);
+                let h = heights.get_pixel(sx, sy)[0] * height_scale + height_offset;
+                Rgb([h, h, h])
+            });
+            region.save(output_dir.join(format!("{}_height.exr", name)))
+                .map_err(|e| e.to_string())?;
+
+            if let Some(control) = data.control {
+                let bits: Vec<u32> = (0..size * size)
+                    .map(|i| {
+                        let (sx, sy) = source(i % size, i / size);
+                        control.get_pixel(sx, sy)[0]
+                    })
+                    .collect();
+                splatmap::save_control_exr(size, size, &bits, output_dir.join(format!("{}_control.exr", name)))?;
+            }
+
+            if let Some(color) = data.color {
+                let region = RgbaImage::from_fn(size, size, |x, y| {
+                    let (sx, sy) = source(x, y);
+                    *color.get_pixel(sx, sy)
+                });
+                region.save(output_dir.join(format!("{}_color.png", name)))
+                    .map_err(|e| e.to_string())?;
+            }
+
+            if let Some(water) = data.water {
+                let region = GrayImage::from_fn(size, size, |x, y| {
+                    let (sx, sy) = source(x, y);
+                    *water.get_pixel(sx, sy)
+                });
+                region.save(output_dir.join(format!("{}_water.png", name)))
+                    .map_err(|e| e.to_string())?;
+            }
+
+            written += 1;
+        }
+    }
+
+    Ok(written)
+}
+
+pub struct ImportedTerrain {
+    pub region_size: u32,
+    pub origin: (i32, i32),
+    // Regions inside the stitched bounds that had no data file
+    pub missing: HashSet<(i32, i32)>,
+    // Heights in world units
+    pub heights: HeightBuffer,
+    pub control: Option<ControlBuffer>,
+    pub color: Option<RgbaImage>,
+}
+
+struct RegionFile {
+    location: (i32, i32),
+    size: u32,
+    height: Option<GodotImage>,
+    control: Option<GodotImage>,
+    color: Option<GodotImage>,
+}
+
+fn read_region_file(path: &Path, fallback_location: Option<(i32, i32)>) -> Result<RegionFile, String> {
+    let file = godot_resource::read_resource(path)?;
+    if file.main_type != "Terrain3DRegion" {
+        return Err(format!("Expected a Terrain3DRegion, found {}", file.main_type));
+    }
+    let region = file.main().ok_or("Resource file is empty")?;
+
+    let location = match region.property("location") {
+        Some(godot_resource::Variant::VectorI(v)) if v.len() == 2 => (v[0], v[1]),
+        _ => fallback_location.ok_or("Region has no location")?,
+    };
+    let image = |name: &str| -> Result<Option<GodotImage>, String> {
+        match region.property(name).and_then(|obj| file.resolve(obj)) {
+            Some(resource) => godot_resource::image_from_resource(resource).map(Some),
+            None => Ok(None),
+        }
+    };
+    let height = image("height_map")?;
+    let control = image("control_map")?;
+    let color = image("color_map")?;
+
+    let size = region.property("region_size")
+        .and_then(godot_resource::Variant::as_int)
+        .map(|s| s as u32)
+        .or_else(|| height.as_ref().map(|h| h.width))
+        .ok_or("Region has no size")?;
+
+    Ok(RegionFile { location, size, height, control, color })
+}
+
+fn expect_format(image: &GodotImage, formats: &[&str], bytes_per_pixel: usize) -> Result<(), String> {
+    if !formats.contains(&image.format.as_str()) {
+        return Err(format!("Unexpected image format {}", image.format));
+    }
+    if image.data.len() < (image.width * image.height) as usize * bytes_per_pixel {
+        return Err("Image data is truncated".to_string());
+    }
+    Ok(())
+}
+
+// Reads every terrain3d_XX_YY.res region in a Terrain3D data directory and
+// stitches height, control and color maps into single images
+pub fn import_terrain3d_data(dir: &Path) -> Result<ImportedTerrain, String> {
+    let mut regions = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("res") {
+            continue;
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let Some(location) = parse_region_name(&stem) else {
+            continue;
+        };
+        let region = read_region_file(&path, Some(location))
+            .map_err(|e| format!("{}: {}", stem, e))?;
+        regions.push(region);
+    }
+
+    let first = regions.first().ok_or("No Terrain3D region files found")?;
+    let size = first.size;
+    if regions.iter().any(|r| r.size != size) {
+        return Err("Regions have mismatched sizes".to_string());
+    }
+
+    let min_x = regions.iter().map(|r| r.location.0).min().unwrap();
+    let min_y = regions.iter().map(|r| r.location.1).min().unwrap();
+    let max_x = regions.iter().map(|r| r.location.0).max().unwrap();
+    let max_y = regions.iter().map(|r| r.location.1).max().unwrap();
+    let cols = (max_x - min_x + 1) as u32;
+    let rows = (max_y - min_y + 1) as u32;
+
+    let mut heights = HeightBuffer::new(cols * size, rows * size);
+    let has_control = regions.iter().any(|r| r.control.is_some());
+    let has_color = regions.iter().any(|r| r.color.is_some());
+    let mut control = has_control.then(|| ControlBuffer::new(cols * size, rows * size));
+    let mut color = has_color.then(|| {
+        RgbaImage::from_pixel(cols * size, rows * size, image::Rgba([255, 255, 255, 128]))
+    });
+
+    let mut missing: HashSet<(i32, i32)> = (min_y..=max_y)
+        .flat_map(|y| (min_x..=max_x).map(move |x| (x, y)))
+        .collect();
+
+    for region in &regions {
+        missing.remove(&region.location);
+        let ox = (region.location.0 - min_x) as u32 * size;
+        let oy = (region.location.1 - min_y) as u32 * size;
+
+        if let Some(img) = &region.height {
+            expect_format(img, &["RFloat"], 4)?;
+            for y in 0..img.height.min(size) {
+                for x in 0..img.width.min(size) {
+                    let i = ((y * img.width + x) * 4) as usize;
+                    let h = f32::from_le_bytes(img.data[i..i + 4].try_into().unwrap());
+                    heights.put_pixel(ox + x, oy + y, Luma([h]));
+                }
+            }
+        }
+        if let (Some(img), Some(control)) = (&region.control, control.as_mut()) {
+            expect_format(img, &["RFloat"], 4)?;
+            for y in 0..img.height.min(size) {
+                for x in 0..img.width.min(size) {
+                    let i = ((y * img.width + x) * 4) as usize;
+                    let bits = u32::from_le_bytes(img.data[i..i + 4].try_into().unwrap());
+                    control.put_pixel(ox + x, oy + y, Luma([bits]));
+                }
+            }
+        }
+        if let (Some(img), Some(color)) = (&region.color, color.as_mut()) {
+            expect_format(img, &["RGBA8"], 4)?;
+            for y in 0..img.height.min(size) {
+                for x in 0..img.width.min(size) {
+                    let i = ((y * img.width + x) * 4) as usize;
+                    let p = &img.data[i..i + 4];
+                    color.put_pixel(ox + x, oy + y, image::Rgba([p[0], p[1], p[2], p[3]]));
+                }
+            }
+        }
+    }
+
+    Ok(ImportedTerrain {
+        region_size: size,
+        origin: (min_x, min_y),
+        missing,
+        heights,
+        control,
+        color,
+    })
+}
@@ -0,0 +1,325 @@
+use egui::{CollapsingHeader, ComboBox, Context, Ui};
+use image::DynamicImage;
+use image_dds::ddsfile::Dds;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use crate::file_names::{self, FileNames};
+use crate::{frames, normals, paths, project, ProcessingState, TerrainApp};
+
+// Roles stored as a single channel, everything else keeps RGBA
+const SINGLE_CHANNEL_ROLES: [&str; 5] = ["roughness", "opacity", "translucency", "albedo_mask", "macro_variation"];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Container {
+    Keep,
+    Png,
+    Dds,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Operation {
+    Copy,
+    Move,
+}
+
+// One existing output and where it goes
+#[derive(Debug, Clone)]
+pub struct PlannedFile {
+    pub role: String,
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub convert: bool,
+}
+
+fn extension(path: &Path) -> String {
+    path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase()
+}
+
+// Also through another spelling of the path, e.g. a different case on
+// Windows or a link. Copying a file onto itself empties it.
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(paths::long_path(a)), fs::canonicalize(paths::long_path(b))) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// Template tokens: {set} for the set name and {role} for the output name,
+// e.g. "{set}/{set}_{role}". {role} takes the export's map names, e.g. "_AH"
+// for albedo, and the extension follows the container. The existing
+// exports are found by the export's file names, under their map names or
+// the outputs' own.
+pub fn plan(
+    root: &Path,
+    destination: &Path,
+    template: &str,
+    file_names: &FileNames,
+    container: Container,
+) -> Result<Vec<PlannedFile>, String> {
+    if !template.contains("{role}") {
+        return Err("The template needs {role} or every output gets the same name".to_string());
+    }
+    file_names.validate()?;
+    let mut planned = Vec::new();
+    for set in file_names::find_sets(root, file_names)? {
+        for (role, files) in &set.files {
+            for source in files {
+                let ext = if project::is_sidecar(source) { project::SIDECAR_EXTENSION.to_string() } else { extension(source) };
+                let is_texture = ext == "png" || ext == "dds";
+                let target_ext = match container {
+                    Container::Png if is_texture => "png".to_string(),
+                    Container::Dds if is_texture => "dds".to_string(),
+                    _ => ext.clone(),
+                };
+                let name = template.replace("{set}", &set.name).replace("{role}", file_names.map_name(role));
+                let target = destination.join(format!("{}.{}", name, target_ext));
+                // Already where the template puts it, nothing to do
+                if same_file(source, &target) {
+                    continue;
+                }
+                planned.push(PlannedFile {
+                    role: role.clone(),
+                    source: source.clone(),
+                    destination: target,
+                    convert: target_ext != ext,
+                });
+            }
+        }
+    }
+    let mut destinations: Vec<_> = planned.iter().map(|p| &p.destination).collect();
+    destinations.sort();
+    if let Some(pair) = destinations.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(format!("Several outputs would be written to {}", pair[0].display()));
+    }
+    let existing: Vec<_> = destinations.iter().filter(|destination| destination.exists()).collect();
+    if let Some(first) = existing.first() {
+        return Err(match existing.len() {
+            1 => format!("{} already exists", first.display()),
+            count => format!("{} and {} other files already exist", first.display(), count - 1),
+        });
+    }
+    Ok(planned)
+}
+
+pub fn load_texture(path: &Path) -> Result<DynamicImage, String> {
+    if extension(path) == "dds" {
+        let file = File::open(paths::long_path(path)).map_err(|e| e.to_string())?;
+        let dds = Dds::read(BufReader::new(file)).map_err(|e| e.to_string())?;
+        image_dds::image_from_dds(&dds, 0).map(DynamicImage::ImageRgba8).map_err(|e| e.to_string())
+    } else {
+        frames::open_single(&paths::long_path(path))
+    }
+}
+
+// Re-encodes with the format the export would have picked for the role. A
+// normal map is only written as two-channel when its blue doesn't hold Z.
+fn convert(file: &PlannedFile) -> Result<(), String> {
+    let image = load_texture(&file.source)?;
+    let destination = paths::long_path(&file.destination);
+    if extension(&destination) == "png" {
+        return image.save(&destination).map_err(|e| e.to_string());
+    }
+    let format = if SINGLE_CHANNEL_ROLES.contains(&file.role.as_str()) {
+        image_dds::ImageFormat::BC4RUnorm
+    } else if file.role == "normal" {
+        let normal = image.to_rgba8();
+        if normals::holds_z(&normal) {
+            image_dds::ImageFormat::BC3RgbaUnorm
+        } else {
+            normals::two_channel_dds_format(normal.pixels().any(|p| p[2] != 0))
+        }
+    } else {
+        image_dds::ImageFormat::BC3RgbaUnorm
+    };
+    TerrainApp::save_as_dds_format(&image, destination, format, Default::default())
+}
+
+pub fn execute(planned: &[PlannedFile], operation: Operation) -> Result<usize, String> {
+    for file in planned {
+        if let Some(dir) = file.destination.parent() {
+            fs::create_dir_all(paths::long_path(dir)).map_err(|e| e.to_string())?;
+        }
+        let describe = |e: String| format!("{}: {}", file.source.display(), e);
+        // Checked again as files may have appeared since the plan was made
+        if file.destination.exists() {
+            return Err(describe(format!("{} already exists", file.destination.display())));
+        }
+        if file.convert {
+            convert(file).map_err(describe)?;
+            if operation == Operation::Move {
+                fs::remove_file(paths::long_path(&file.source)).map_err(|e| describe(e.to_string()))?;
+            }
+        } else {
+            let (source, destination) = (paths::long_path(&file.source), paths::long_path(&file.destination));
+            match operation {
+                // Rename fails across filesystems, fall back to copy and delete
+                Operation::Move => fs::rename(&source, &destination)
+                    .or_else(|_| fs::copy(&source, &destination).and_then(|_| fs::remove_file(&source)))
+                    .map_err(|e| describe(e.to_string()))?,
+                Operation::Copy => {
+                    fs::copy(&source, &destination).map_err(|e| describe(e.to_string()))?;
+                }
+            }
+        }
+    }
+    Ok(planned.len())
+}
+
+// Renames, moves or re-containers existing exports without reprocessing
+pub struct ExportReorganizer {
+    source_root: Option<PathBuf>,
+    // The editor's, which the existing exports are named by and {role} follows
+    file_names: FileNames,
+    destination: Option<PathBuf>,
+    template: String,
+    container: Container,
+    operation: Operation,
+    planned: Result<Vec<PlannedFile>, String>,
+    state: ProcessingState,
+    receiver: Receiver<Result<usize, String>>,
+    sender: Sender<Result<usize, String>>,
+}
+
+impl Default for ExportReorganizer {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            source_root: None,
+            file_names: FileNames::new(file_names::DEFAULT_TEMPLATE, &BTreeMap::new(), String::new()),
+            destination: None,
+            template: "{set}/{role}".to_string(),
+            container: Container::Keep,
+            operation: Operation::Copy,
+            planned: Ok(Vec::new()),
+            state: ProcessingState::NotStarted,
+            receiver: rx,
+            sender: tx,
+        }
+    }
+}
+
+impl ExportReorganizer {
+    fn update_plan(&mut self) {
+        self.planned = match (&self.source_root, &self.destination) {
+            (Some(root), Some(destination)) => {
+                plan(root, destination, &self.template, &self.file_names, self.container)
+            }
+            _ => Ok(Vec::new()),
+        };
+    }
+
+    pub fn poll(&mut self, ctx: &Context) {
+        if let Ok(result) = self.receiver.try_recv() {
+            self.state = match result {
+                Ok(_) => ProcessingState::Done,
+                Err(e) => ProcessingState::Error(e),
+            };
+            self.update_plan();
+            ctx.request_repaint();
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, file_names: &FileNames) {
+        CollapsingHeader::new("Reorganize Exports")
+            .default_open(false)
+            .show(ui, |ui| {
+                let mut changed = self.file_names != *file_names;
+                if changed {
+                    self.file_names = file_names.clone();
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Select Export Root").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                            self.source_root = Some(path);
+                            changed = true;
+                        }
+                    }
+                    if let Some(path) = &self.source_root {
+                        ui.label(path.to_string_lossy().to_string());
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Select Destination").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                            self.destination = Some(path);
+                            changed = true;
+                        }
+                    }
+                    if let Some(path) = &self.destination {
+                        ui.label(path.to_string_lossy().to_string());
+                    }
+                });
+                changed |= ui.horizontal(|ui| {
+                    ui.label("Name Template");
+                    ui.text_edit_singleline(&mut self.template)
+                        .on_hover_text("{set} is the set name, {role} the output name such as albedo or normal, or the name given to it under Map Names. Use / for subfolders.")
+                        .changed()
+                }).inner;
+                let before = self.container;
+                ComboBox::from_label("Container")
+                    .selected_text(format!("{:?}", self.container))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.container, Container::Keep, "Keep");
+                        ui.selectable_value(&mut self.container, Container::Png, "PNG");
+                        ui.selectable_value(&mut self.container, Container::Dds, "DDS");
+                    });
+                changed |= before != self.container;
+                ComboBox::from_label("Operation")
+                    .selected_text(format!("{:?}", self.operation))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.operation, Operation::Copy, "Copy");
+                        ui.selectable_value(&mut self.operation, Operation::Move, "Move");
+                    });
+                if changed {
+                    self.update_plan();
+                }
+
+                let busy = matches!(self.state, ProcessingState::Processing);
+                match &self.planned {
+                    Ok(planned) => {
+                        ui.label(format!("{} files planned", planned.len()));
+                        egui::ScrollArea::vertical().max_height(160.0).show(ui, |ui| {
+                            for file in planned {
+                                ui.label(format!(
+                                    "{} -> {}{}",
+                                    file.source.display(),
+                                    file.destination.display(),
+                                    if file.convert { " (converted)" } else { "" }
+                                ));
+                            }
+                        });
+                        if ui.add_enabled(!planned.is_empty() && !busy, egui::Button::new("Run")).clicked() {
+                            let planned = planned.clone();
+                            let operation = self.operation;
+                            let tx = self.sender.clone();
+                            self.state = ProcessingState::Processing;
+                            thread::spawn(move || {
+                                tx.send(execute(&planned, operation)).ok();
+                            });
+                        }
+                    }
+                    Err(e) => {
+                        ui.label(format!("Error: {}", e));
+                    }
+                }
+
+                match &self.state {
+                    ProcessingState::Processing => {
+                        ui.spinner();
+                    }
+                    ProcessingState::Done => {
+                        ui.label("Reorganization complete");
+                    }
+                    ProcessingState::Error(e) => {
+                        ui.label(format!("Error: {}", e));
+                    }
+                    _ => {}
+                }
+            });
+    }
+}
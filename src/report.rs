@@ -0,0 +1,125 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+// Exit codes of non-interactive runs. 2 is taken by command line errors.
+pub const EXIT_VALIDATION_FAILED: i32 = 3;
+pub const EXIT_ENCODE_FAILED: i32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportStatus {
+    Ok,
+    // Inputs missing, unreadable or inconsistent, nothing was written
+    ValidationFailed,
+    // Processing, encoding or writing the outputs failed
+    EncodeFailed,
+}
+
+// What was decided about a job that failed in the export queue
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriageDecision {
+    Skip,
+    Retry,
+    EditSettings,
+    AbortAll,
+}
+
+impl TriageDecision {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Skip => "skipped",
+            Self::Retry => "retried",
+            Self::EditSettings => "opened for editing",
+            Self::AbortAll => "aborted the queue",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedOutput {
+    pub file: String,
+    // None for files that aren't images
+    pub dimensions: Option<[u32; 2]>,
+    pub format: String,
+    pub estimated_bytes: u64,
+}
+
+// Outcome of one headless export, printed as JSON with --json
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub status: ExportStatus,
+    pub inputs: BTreeMap<String, PathBuf>,
+    pub outputs: Vec<PathBuf>,
+    // Nothing was written because the outputs already matched the sources
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub up_to_date: bool,
+    // Nothing was written because the textures were already in the output
+    // directory and the project skips existing ones
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub skipped: bool,
+    // Files a dry run would have written
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub planned: Vec<PlannedOutput>,
+    pub warnings: Vec<String>,
+    pub load_seconds: f64,
+    pub export_seconds: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // Decisions taken in the export queue after failed attempts, in order
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub triage: Vec<TriageDecision>,
+}
+
+impl ExportReport {
+    pub fn new(inputs: BTreeMap<String, PathBuf>) -> Self {
+        Self {
+            name: None,
+            status: ExportStatus::Ok,
+            inputs,
+            outputs: Vec::new(),
+            up_to_date: false,
+            skipped: false,
+            planned: Vec::new(),
+            warnings: Vec::new(),
+            load_seconds: 0.0,
+            export_seconds: 0.0,
+            error: None,
+            triage: Vec::new(),
+        }
+    }
+
+    pub fn fail(mut self, status: ExportStatus, error: String) -> Self {
+        self.status = status;
+        self.error = Some(error);
+        self
+    }
+
+    pub fn into_result(self) -> Result<Vec<PathBuf>, String> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.outputs),
+        }
+    }
+}
+
+// Encode failures outrank validation failures, the worst set decides
+pub fn exit_code(reports: &[ExportReport]) -> i32 {
+    if reports.iter().any(|r| r.status == ExportStatus::EncodeFailed) {
+        EXIT_ENCODE_FAILED
+    } else if reports.iter().any(|r| r.status == ExportStatus::ValidationFailed) {
+        EXIT_VALIDATION_FAILED
+    } else {
+        0
+    }
+}
+
+pub fn print_json<T: Serialize>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("Failed to write summary: {}", e),
+    }
+}
@@ -0,0 +1,161 @@
+use image::imageops::{self, FilterType};
+use image::{ImageBuffer, Pixel, Primitive, RgbaImage};
+use num_traits::{NumCast, ToPrimitive};
+use std::f32::consts::PI;
+use rayon::prelude::*;
+
+// Choices for the longest side of a packed output, None keeps the source size
+pub const OUTPUT_SIZES: [u32; 6] = [256, 512, 1024, 2048, 4096, 8192];
+
+pub fn size_label(size: Option<u32>) -> String {
+    size.map_or("Source".to_string(), |size| size.to_string())
+}
+
+// Only ever shrinks, keeping the aspect ratio so rectangular sources stay
+// rectangular
+fn target_size(width: u32, height: u32, max_size: Option<u32>) -> Option<(u32, u32)> {
+    let max_size = max_size?;
+    let longest = width.max(height);
+    if longest <= max_size {
+        return None;
+    }
+    let scale = max_size as f64 / longest as f64;
+    Some((
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    ))
+}
+
+// Size an output is written at
+pub fn output_size(width: u32, height: u32, max_size: Option<u32>) -> (u32, u32) {
+    target_size(width, height, max_size).unwrap_or((width, height))
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        return 1.0;
+    }
+    let a = x * PI;
+    a.sin() / a
+}
+
+fn catmull_rom(x: f32) -> f32 {
+    let a = x.abs();
+    if a < 1.0 {
+        (9.0 * a.powi(3) - 15.0 * a.powi(2) + 6.0) / 6.0
+    } else if a < 2.0 {
+        (-3.0 * a.powi(3) + 15.0 * a.powi(2) - 24.0 * a + 12.0) / 6.0
+    } else {
+        0.0
+    }
+}
+
+// Kernel and support of each filter, the same ones imageops::resize uses
+fn kernel(filter: FilterType) -> (fn(f32) -> f32, f32) {
+    match filter {
+        FilterType::Nearest => (|_| 1.0, 0.0),
+        FilterType::Triangle => (|x| (1.0 - x.abs()).max(0.0), 1.0),
+        FilterType::CatmullRom => (catmull_rom, 2.0),
+        FilterType::Gaussian => (|x| (-2.0 * x * x).exp() / (0.5 * (2.0 * PI).sqrt()), 3.0),
+        FilterType::Lanczos3 => (|x| if x.abs() < 3.0 { sinc(x) * sinc(x / 3.0) } else { 0.0 }, 3.0),
+    }
+}
+
+// First source texel and the weights of every output pixel along one axis,
+// placed like imageops::resize places them. Taps past an edge are read from
+// the opposite one, so only the filter support wraps around.
+fn wrapped_taps(from: u32, to: u32, filter: FilterType) -> Vec<(i64, Vec<f32>)> {
+    let (kernel, support) = kernel(filter);
+    let ratio = from as f32 / to as f32;
+    let scale = ratio.max(1.0);
+    (0..to)
+        .map(|out| {
+            let centre = (out as f32 + 0.5) * ratio;
+            let left = (centre - support * scale).floor() as i64;
+            let right = ((centre + support * scale).ceil() as i64).max(left + 1);
+            let weights: Vec<f32> = (left..right).map(|i| kernel((i as f32 - (centre - 0.5)) / scale)).collect();
+            let sum: f32 = weights.iter().sum();
+            (left, weights.iter().map(|w| w / sum).collect())
+        })
+        .collect()
+}
+
+// Resizes a tileable image as if it repeated in every direction, so the
+// result still tiles instead of fading towards clamped edges
+pub fn resize_wrapped<P>(img: &ImageBuffer<P, Vec<P::Subpixel>>, width: u32, height: u32, filter: FilterType) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + 'static,
+    P::Subpixel: Send + Sync,
+{
+    let (source_width, source_height) = img.dimensions();
+    let channels = P::CHANNEL_COUNT as usize;
+    let stride = source_width as usize * channels;
+    let samples: &[P::Subpixel] = img;
+
+    // Columns first like imageops::resize, kept as floats in between
+    let mut columns = vec![0.0f32; stride * height as usize];
+    columns.par_chunks_mut(stride).zip(wrapped_taps(source_height, height, filter)).for_each(|(row, (top, weights))| {
+        for (i, weight) in weights.iter().enumerate() {
+            let y = (top + i as i64).rem_euclid(source_height as i64) as usize;
+            for (value, sample) in row.iter_mut().zip(&samples[y * stride..(y + 1) * stride]) {
+                *value += sample.to_f32().unwrap_or_default() * weight;
+            }
+        }
+    });
+
+    let taps = wrapped_taps(source_width, width, filter);
+    let min = P::Subpixel::DEFAULT_MIN_VALUE.to_f32().unwrap_or_default();
+    let max = P::Subpixel::DEFAULT_MAX_VALUE.to_f32().unwrap_or(1.0);
+    let mut resized = ImageBuffer::<P, Vec<P::Subpixel>>::new(width, height);
+    resized.par_chunks_mut(width as usize * channels).zip(columns.par_chunks(stride)).for_each(|(row, line)| {
+        for (pixel, (left, weights)) in row.chunks_mut(channels).zip(&taps) {
+            for (c, channel) in pixel.iter_mut().enumerate() {
+                let value: f32 = weights.iter().enumerate()
+                    .map(|(i, weight)| {
+                        let x = (left + i as i64).rem_euclid(source_width as i64) as usize;
+                        line[x * channels + c] * weight
+                    })
+                    .sum();
+                // Integer channels are rounded, float ones have a maximum of 1
+                let value = value.clamp(min, max);
+                let value = if max > 1.0 { value.round() } else { value };
+                *channel = NumCast::from(value).unwrap_or(P::Subpixel::DEFAULT_MIN_VALUE);
+            }
+        }
+    });
+    resized
+}
+
+// With wrap set the source is treated as tileable, see resize_wrapped
+pub fn downsample<P>(img: &ImageBuffer<P, Vec<P::Subpixel>>, max_size: Option<u32>, wrap: bool) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + 'static,
+    P::Subpixel: Send + Sync,
+{
+    let (width, height) = img.dimensions();
+    match target_size(width, height, max_size) {
+        Some((w, h)) if wrap => resize_wrapped(img, w, h, FilterType::Lanczos3),
+        Some((w, h)) => imageops::resize(img, w, h, FilterType::Lanczos3),
+        None => img.clone(),
+    }
+}
+
+// Filtering shortens the averaged vectors, so they are renormalized after the
+// resize. Alpha (roughness) is filtered like any other channel.
+pub fn downsample_normal(normal: &RgbaImage, max_size: Option<u32>, wrap: bool) -> RgbaImage {
+    let (width, height) = normal.dimensions();
+    if target_size(width, height, max_size).is_none() {
+        return normal.clone();
+    }
+    let mut resized = downsample(normal, max_size, wrap);
+    resized.par_chunks_mut(4).for_each(|p| {
+        let v = [0, 1, 2].map(|c| p[c] as f32 / 127.5 - 1.0);
+        let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+        if length > 1e-4 {
+            for c in 0..3 {
+                p[c] = ((v[c] / length * 0.5 + 0.5) * 255.0).round() as u8;
+            }
+        }
+    });
+    resized
+}
@@ -0,0 +1,83 @@
+use egui::{CollapsingHeader, Color32, ComboBox, Ui};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::paths;
+use crate::project::PROJECT_EXTENSION;
+
+// Where a set stands in art review. Stored with the set's project and never
+// affects the exported pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum ReviewStatus {
+    #[default]
+    Draft,
+    Approved,
+    NeedsFix,
+}
+
+impl ReviewStatus {
+    const ALL: [ReviewStatus; 3] = [Self::Draft, Self::Approved, Self::NeedsFix];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Draft => "Draft",
+            Self::Approved => "Approved",
+            Self::NeedsFix => "Needs Fix",
+        }
+    }
+
+    pub fn color(self) -> Color32 {
+        match self {
+            Self::Draft => Color32::GRAY,
+            Self::Approved => Color32::LIGHT_GREEN,
+            Self::NeedsFix => Color32::YELLOW,
+        }
+    }
+
+    // The status as shown next to a set in lists, notes on hover
+    pub fn badge(self, ui: &mut Ui, notes: &str) {
+        let response = ui.colored_label(self.color(), format!("[{}]", self.label()));
+        if !notes.is_empty() {
+            response.on_hover_text(notes);
+        }
+    }
+}
+
+// Status and notes of the set being edited
+pub fn show(ui: &mut Ui, status: &mut ReviewStatus, notes: &mut String) {
+    CollapsingHeader::new(format!("Review ({})", status.label()))
+        .default_open(false)
+        .show(ui, |ui| {
+            ComboBox::from_label("Status")
+                .selected_text(status.label())
+                .show_ui(ui, |ui| {
+                    for option in ReviewStatus::ALL {
+                        ui.selectable_value(status, option, option.label());
+                    }
+                });
+            ui.label("Notes:");
+            ui.add(egui::TextEdit::multiline(notes)
+                .desired_rows(3)
+                .hint_text("What to check or fix, saved with the project"));
+        });
+}
+
+// Status and notes of the first project file in a folder, read without
+// resolving the project's paths so listing a library stays quick
+pub fn folder_review(dir: &Path) -> Option<(ReviewStatus, String)> {
+    let mut projects: Vec<_> = fs::read_dir(paths::long_path(dir)).ok()?
+        .flatten()
+        .map(|entry| dir.join(entry.file_name()))
+        .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(PROJECT_EXTENSION)))
+        .collect();
+    projects.sort();
+    let text = fs::read_to_string(paths::long_path(projects.first()?)).ok()?;
+    let project: Value = serde_json::from_str(&text).ok()?;
+    let status = project.get("review_status")
+        .and_then(|status| serde_json::from_value(status.clone()).ok())
+        .unwrap_or_default();
+    let notes = project.get("notes").and_then(Value::as_str).unwrap_or_default().to_string();
+    Some((status, notes))
+}
@@ -0,0 +1,202 @@
+use egui::{Color32, Pos2, Rect, Sense, Stroke, Ui, Vec2};
+use image::GrayImage;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoughnessAdjust {
+    // Linear remap of the full 0..1 range onto output_min..output_max
+    pub output_min: f32,
+    pub output_max: f32,
+    // Hard limits applied after the remap
+    pub clamp_min: f32,
+    pub clamp_max: f32,
+}
+
+impl Default for RoughnessAdjust {
+    fn default() -> Self {
+        Self {
+            output_min: 0.0,
+            output_max: 1.0,
+            clamp_min: 0.0,
+            clamp_max: 1.0,
+        }
+    }
+}
+
+impl RoughnessAdjust {
+    pub fn apply(&self, roughness: f32) -> f32 {
+        let remapped = self.output_min + roughness * (self.output_max - self.output_min);
+        remapped.clamp(self.clamp_min, self.clamp_max.max(self.clamp_min))
+    }
+}
+
+// Piecewise linear response curve, points sorted by input with the first and
+// last pinned to 0 and 1
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoughnessCurve {
+    pub points: Vec<[f32; 2]>,
+}
+
+impl Default for RoughnessCurve {
+    fn default() -> Self {
+        Self {
+            points: vec![[0.0, 0.0], [1.0, 1.0]],
+        }
+    }
+}
+
+impl RoughnessCurve {
+    pub fn evaluate(&self, x: f32) -> f32 {
+        let points = &self.points;
+        let i = points.partition_point(|p| p[0] < x).clamp(1, points.len() - 1);
+        let (a, b) = (points[i - 1], points[i]);
+        let t = if b[0] > a[0] { ((x - a[0]) / (b[0] - a[0])).clamp(0.0, 1.0) } else { 1.0 };
+        a[1] + (b[1] - a[1]) * t
+    }
+
+    fn insert(&mut self, point: [f32; 2]) {
+        let i = self.points.partition_point(|p| p[0] < point[0]).clamp(1, self.points.len() - 1);
+        self.points.insert(i, point);
+    }
+}
+
+// Every possible 8-bit roughness value mapped once up front. Curve first for
+// the perceptual shaping, then remap and clamp as the final limits.
+pub fn combined_lut(curve: &RoughnessCurve, adjust: &RoughnessAdjust) -> [u8; 256] {
+    std::array::from_fn(|i| {
+        let shaped = curve.evaluate(i as f32 / 255.0);
+        (adjust.apply(shaped).clamp(0.0, 1.0) * 255.0).round() as u8
+    })
+}
+
+// Drag points to shape the curve, double click to add one and right click to
+// remove it
+pub fn curve_editor(ui: &mut Ui, curve: &mut RoughnessCurve) {
+    let side = ui.available_width().min(200.0);
+    let (response, painter) = ui.allocate_painter(Vec2::splat(side), Sense::click());
+    let rect = response.rect;
+    let to_screen = |p: [f32; 2]| Pos2::new(rect.left() + p[0] * rect.width(), rect.bottom() - p[1] * rect.height());
+    let from_screen = |pos: Pos2| [
+        ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0),
+        ((rect.bottom() - pos.y) / rect.height()).clamp(0.0, 1.0),
+    ];
+
+    painter.rect_filled(rect, 0.0, Color32::from_gray(30));
+    for i in 1..4 {
+        let f = i as f32 / 4.0;
+        let grid = Stroke::new(1.0, Color32::from_gray(60));
+        painter.line_segment([to_screen([f, 0.0]), to_screen([f, 1.0])], grid);
+        painter.line_segment([to_screen([0.0, f]), to_screen([1.0, f])], grid);
+    }
+
+    let last = curve.points.len() - 1;
+    let mut remove = None;
+    for i in 0..=last {
+        let id = response.id.with(i);
+        let handle = Rect::from_center_size(to_screen(curve.points[i]), Vec2::splat(12.0));
+        let point_response = ui.interact(handle, id, Sense::click_and_drag());
+        if point_response.dragged() {
+            if let Some(pos) = point_response.interact_pointer_pos() {
+                let [mut x, y] = from_screen(pos);
+                // Endpoints stay at the edges, inner points stay between their neighbors
+                x = if i == 0 {
+                    0.0
+                } else if i == last {
+                    1.0
+                } else {
+                    x.clamp(curve.points[i - 1][0], curve.points[i + 1][0])
+                };
+                curve.points[i] = [x, y];
+            }
+        }
+        if point_response.secondary_clicked() && i != 0 && i != last {
+            remove = Some(i);
+        }
+    }
+    if let Some(i) = remove {
+        curve.points.remove(i);
+    }
+
+    if response.double_clicked() {
+        if let Some(pos) = response.interact_pointer_pos() {
+            let [x, _] = from_screen(pos);
+            curve.insert([x, curve.evaluate(x)]);
+        }
+    }
+
+    let line: Vec<Pos2> = (0..=64)
+        .map(|i| {
+            let x = i as f32 / 64.0;
+            to_screen([x, curve.evaluate(x)])
+        })
+        .collect();
+    painter.add(egui::Shape::line(line, Stroke::new(2.0, Color32::LIGHT_BLUE)));
+    for point in &curve.points {
+        painter.circle_filled(to_screen(*point), 4.0, Color32::WHITE);
+    }
+}
+
+// Spots across the map's range the conversion swatches are taken from
+const SWATCH_PERCENTILES: [f32; 5] = [0.05, 0.25, 0.5, 0.75, 0.95];
+
+pub fn sample_swatches(source: &GrayImage) -> [u8; 5] {
+    let mut histogram = [0usize; 256];
+    for p in source.pixels() {
+        histogram[p[0] as usize] += 1;
+    }
+    let total = source.pixels().len().max(1);
+    SWATCH_PERCENTILES.map(|percentile| {
+        let target = (percentile * total as f32) as usize;
+        let mut seen = 0;
+        histogram.iter()
+            .position(|&count| {
+                seen += count;
+                seen > target
+            })
+            .unwrap_or(255) as u8
+    })
+}
+
+fn convention(smoothness: bool) -> &'static str {
+    if smoothness { "Smoothness" } else { "Roughness" }
+}
+
+// States how the map is read and written, with swatches of sampled input
+// values next to what ends up in the texture, so an inverted map is caught
+// before it ships
+pub fn conversion_indicator(ui: &mut Ui, samples: &[u8], input_smoothness: bool, output_smoothness: bool, lut: &[u8; 256]) {
+    let inverted = input_smoothness != output_smoothness;
+    let summary = format!(
+        "Input interpreted as {}, output stored as {}{}",
+        convention(input_smoothness),
+        convention(output_smoothness),
+        if inverted { " (inverted)" } else { "" },
+    );
+    if inverted {
+        ui.colored_label(Color32::YELLOW, summary);
+    } else {
+        ui.label(summary);
+    }
+    ui.horizontal_wrapped(|ui| {
+        ui.label("Map | Texture");
+        for &input in samples {
+            let roughness = if input_smoothness { 255 - input } else { input };
+            let shaped = lut[roughness as usize];
+            let output = if output_smoothness { 255 - shaped } else { shaped };
+            let (rect, response) = ui.allocate_exact_size(Vec2::new(44.0, 18.0), Sense::hover());
+            let (before, after) = rect.split_left_right_at_fraction(0.5);
+            ui.painter().rect_filled(before, 0.0, Color32::from_gray(input));
+            ui.painter().rect_filled(after, 0.0, Color32::from_gray(output));
+            ui.painter().rect_stroke(rect, 0.0, Stroke::new(1.0, Color32::from_gray(90)));
+            response.on_hover_text(format!(
+                "{} {} in the map, {} {} in the texture",
+                convention(input_smoothness),
+                input,
+                convention(output_smoothness),
+                output,
+            ));
+        }
+    });
+}
@@ -0,0 +1,249 @@
+use image::{DynamicImage, GenericImageView, GrayImage, ImageBuffer, Luma, Rgba};
+use rayon::prelude::*;
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::paths;
+
+// Operations one function call may take, so a runaway loop fails the load
+// instead of hanging it
+const MAX_OPERATIONS: u64 = 100_000;
+
+// A user script run on each map after it's loaded and before anything is
+// packed, so the preview shows what gets exported. Functions are named after
+// the slot they transform and are all optional:
+//
+//   fn albedo(r, g, b, a) { [r * 0.9, g, b, a] }
+//   fn normal(x, y, z, a) { [x, y, z, a] }
+//   fn roughness(v) { v * 0.8 + 0.1 }
+//
+// Values are 0 to 1. albedo and normal run for every pixel and return all
+// four channels; height, ao, roughness, opacity and translucency map a
+// single value and are evaluated once per possible input value.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = fs::read_to_string(paths::long_path(path))
+            .map_err(|e| format!("Failed to read script {}: {}", path.display(), e))?;
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let ast = engine.compile(&source)
+            .map_err(|e| format!("Script {}: {}", path.display(), e))?;
+        Ok(Self { engine, ast })
+    }
+
+    pub fn has_function(&self, name: &str, params: usize) -> bool {
+        self.ast.iter_functions().any(|f| f.name == name && f.params.len() == params)
+    }
+
+    // Whether the script has a function for the slot, so apply changes it
+    pub fn transforms(&self, image_type: &str) -> bool {
+        match image_type {
+            "albedo" | "normal" => self.has_function(image_type, 4),
+            "height" | "ao" | "roughness" | "opacity" | "translucency" => self.has_function(image_type, 1),
+            _ => false,
+        }
+    }
+
+    fn call(&self, name: &str, args: impl rhai::FuncArgs) -> Result<Dynamic, String> {
+        self.call_in(&mut Scope::new(), name, args)
+    }
+
+    // Calls sharing a scope, e.g. along a row, skip setting up a new one each time
+    fn call_in(&self, scope: &mut Scope, name: &str, args: impl rhai::FuncArgs) -> Result<Dynamic, String> {
+        // Only the functions are used, top-level statements never run. The
+        // scope is left as it was, so every call starts out the same.
+        let options = CallFnOptions::new().eval_ast(false).rewind_scope(true);
+        self.engine.call_fn_with_options(options, scope, &self.ast, name, args)
+            .map_err(|e| format!("Script function {}: {}", name, e))
+    }
+
+    fn channels(name: &str, result: Dynamic) -> Result<[f32; 4], String> {
+        let array = result.try_cast::<Array>()
+            .filter(|array| array.len() == 4)
+            .ok_or_else(|| format!("Script function {} must return an array of four values", name))?;
+        let mut channels = [0.0; 4];
+        for (channel, value) in channels.iter_mut().zip(array) {
+            *channel = number(&value).ok_or_else(|| format!("Script function {} returned {} instead of a number", name, value))?;
+        }
+        Ok(channels)
+    }
+
+    fn value(&self, name: &str, value: f32) -> Result<f32, String> {
+        let result = self.call(name, (f64::from(value),))?;
+        number(&result).ok_or_else(|| format!("Script function {} returned {} instead of a number", name, result))
+    }
+
+    // The map with the slot's function applied, unchanged when the script
+    // has none. Sources deeper than 8 bits come back as 16 bits.
+    pub fn apply(&self, img: DynamicImage, image_type: &str) -> Result<DynamicImage, String> {
+        let eight_bit = matches!(
+            img,
+            DynamicImage::ImageLuma8(_) | DynamicImage::ImageLumaA8(_) | DynamicImage::ImageRgb8(_) | DynamicImage::ImageRgba8(_)
+        );
+        if !self.transforms(image_type) {
+            return Ok(img);
+        }
+        match image_type {
+            "albedo" | "normal" => {
+                if eight_bit {
+                    let mut img = img.to_rgba8();
+                    self.map_pixels(&mut img, image_type)?;
+                    Ok(DynamicImage::ImageRgba8(img))
+                } else {
+                    let mut img = img.to_rgba16();
+                    self.map_pixels(&mut img, image_type)?;
+                    Ok(DynamicImage::ImageRgba16(img))
+                }
+            }
+            _ => {
+                if eight_bit {
+                    let lut = self.lut(image_type, u8::MAX)?;
+                    let mut img: GrayImage = img.to_luma8();
+                    img.par_iter_mut().for_each(|value| *value = lut[*value as usize]);
+                    Ok(DynamicImage::ImageLuma8(img))
+                } else {
+                    let lut = self.lut(image_type, u16::MAX)?;
+                    let mut img: ImageBuffer<Luma<u16>, Vec<u16>> = img.to_luma16();
+                    img.par_iter_mut().for_each(|value| *value = lut[*value as usize]);
+                    Ok(DynamicImage::ImageLuma16(img))
+                }
+            }
+        }
+    }
+
+    fn map_pixels<T: Sample>(&self, img: &mut ImageBuffer<Rgba<T>, Vec<T>>, name: &str) -> Result<(), String>
+    where
+        Rgba<T>: image::Pixel<Subpixel = T>,
+    {
+        // A row per task, the engine is entered once per pixel either way
+        let width = img.width() as usize;
+        img.par_chunks_mut(width * 4).try_for_each(|row| {
+            let mut scope = Scope::new();
+            for pixel in row.chunks_mut(4) {
+                let result = self.call_in(&mut scope, name, std::array::from_fn::<f64, 4, _>(|c| pixel[c].to_unit().into()))?;
+                for (channel, value) in pixel.iter_mut().zip(Self::channels(name, result)?) {
+                    *channel = T::from_unit(value);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    // Whole set transform for plugins: fn process(p) gets a map of every
+    // input slot's value at one pixel, arrays for albedo and normal and
+    // numbers for the rest, and returns a map with the output slots
+    pub fn process_set(&self, maps: &BTreeMap<String, DynamicImage>, outputs: &[String]) -> Result<BTreeMap<String, DynamicImage>, String> {
+        let (width, height) = maps.values().next().map(|img| img.dimensions()).ok_or("The plugin got no input maps")?;
+        if maps.values().any(|img| img.dimensions() != (width, height)) {
+            return Err("The plugin's input maps differ in size".to_string());
+        }
+        let inputs: Vec<(&str, Vec<f32>)> = maps.iter().map(|(slot, img)| (slot.as_str(), img.to_rgba32f().into_raw())).collect();
+        let mut values = vec![[0u16; 4]; width as usize * height as usize * outputs.len()];
+        values.par_chunks_mut(outputs.len().max(1)).enumerate().try_for_each(|(i, pixel)| {
+            let mut p = Map::new();
+            for (slot, data) in &inputs {
+                let texel = &data[i * 4..i * 4 + 4];
+                let value = if is_color(slot) {
+                    Dynamic::from_array(texel.iter().map(|&v| Dynamic::from_float(v.into())).collect())
+                } else {
+                    Dynamic::from_float(texel[0].into())
+                };
+                p.insert((*slot).into(), value);
+            }
+            let result = self.call("process", (p,))?.try_cast::<Map>()
+                .ok_or("Script function process must return a map of output slots")?;
+            for (value, slot) in pixel.iter_mut().zip(outputs) {
+                let output = result.get(slot.as_str())
+                    .ok_or_else(|| format!("Script function process didn't return {}", slot))?;
+                let channels = if is_color(slot) {
+                    output.clone().try_cast::<Array>()
+                        .filter(|array| array.len() == 4)
+                        .and_then(|array| {
+                            let channels: Option<Vec<f32>> = array.iter().map(number).collect();
+                            channels.map(|channels| std::array::from_fn(|c| channels[c]))
+                        })
+                } else {
+                    number(output).map(|v| [v; 4])
+                };
+                let channels = channels.ok_or_else(|| format!(
+                    "Script function process returned {} for {}, expected {}",
+                    output, slot, if is_color(slot) { "an array of four numbers" } else { "a number" },
+                ))?;
+                *value = channels.map(u16::from_unit);
+            }
+            Ok::<(), String>(())
+        })?;
+
+        Ok(outputs.iter().enumerate().map(|(o, slot)| {
+            let texels = values.iter().skip(o).step_by(outputs.len());
+            let img = if is_color(slot) {
+                DynamicImage::ImageRgba16(ImageBuffer::from_raw(width, height, texels.flatten().copied().collect()).unwrap())
+            } else {
+                DynamicImage::ImageLuma16(ImageBuffer::from_raw(width, height, texels.map(|v| v[0]).collect()).unwrap())
+            };
+            (slot.clone(), img)
+        }).collect())
+    }
+
+    fn lut<T: Sample + Send>(&self, name: &str, max: T) -> Result<Vec<T>, String> {
+        (0..=max.index()).into_par_iter()
+            .map(|i| self.value(name, i as f32 / max.index() as f32).map(T::from_unit))
+            .collect()
+    }
+}
+
+// Slots with four channels, the others hold one value
+fn is_color(slot: &str) -> bool {
+    matches!(slot, "albedo" | "normal")
+}
+
+fn number(value: &Dynamic) -> Option<f32> {
+    value.as_float().map(|v| v as f32)
+        .or_else(|_| value.as_int().map(|v| v as f32))
+        .ok()
+}
+
+// The integer channel types maps are scripted in
+trait Sample: Copy + Sync + Send + 'static {
+    fn to_unit(self) -> f32;
+    fn from_unit(value: f32) -> Self;
+    fn index(self) -> usize;
+}
+
+impl Sample for u8 {
+    fn to_unit(self) -> f32 {
+        self as f32 / 255.0
+    }
+    fn from_unit(value: f32) -> Self {
+        (value * 255.0).round().clamp(0.0, 255.0) as u8
+    }
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl Sample for u16 {
+    fn to_unit(self) -> f32 {
+        self as f32 / 65535.0
+    }
+    fn from_unit(value: f32) -> Self {
+        (value * 65535.0).round().clamp(0.0, 65535.0) as u16
+    }
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+// Content hash of the script, so cached and up-to-date checks notice edits
+pub fn script_hash(path: &Path) -> String {
+    fs::read(paths::long_path(path))
+        .map(|bytes| format!("{:016x}", xxhash_rust::xxh3::xxh3_64(&bytes)))
+        .unwrap_or_default()
+}
@@ -0,0 +1,178 @@
+use serde_json::{json, Value};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crate::batch;
+use crate::map_names;
+use crate::project::{Project, SLOTS};
+use crate::validate;
+
+// A JSON-RPC 2.0 server for editor plugins, one request per line:
+//
+// {"jsonrpc": "2.0", "id": 1, "token": "...", "method": "load_set", "params": {"folder": "/textures/rock"}}
+// {"jsonrpc": "2.0", "id": 2, "token": "...", "method": "set_options", "params": {"output_directory": "/game/terrain/rock"}}
+// {"jsonrpc": "2.0", "id": 3, "token": "...", "method": "export", "params": {"dry_run": false}}
+//
+// Every connection keeps its own settings, starting from the project given on
+// the command line. Only localhost can connect, and every request carries the
+// token printed at startup, so a web page posting to the port can't drive it.
+// A line that isn't JSON, e.g. an HTTP header, or a wrong token closes the
+// connection.
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+// Options that run programs, only taken from the project file the server was
+// started with
+const PROTECTED_OPTIONS: [&str; 2] = ["post_export_command", "script"];
+
+type RpcError = (i64, String);
+
+// Unguessable per launch, from the randomly seeded hasher keys. Also
+// guards the single instance listener.
+pub fn new_token() -> String {
+    (0..2).map(|_| format!("{:016x}", RandomState::new().hash_one(std::process::id()))).collect()
+}
+
+fn invalid_params(e: impl ToString) -> RpcError {
+    (INVALID_PARAMS, e.to_string())
+}
+
+// Replaces every map, either from a folder sorted by file names or from
+// explicit slots like {"albedo": "...", "normal": "..."}
+fn load_set(project: &mut Project, params: &Value) -> Result<Value, RpcError> {
+    for slot in SLOTS {
+        project.set_map(slot, None).map_err(invalid_params)?;
+    }
+    let mut unrecognized = Vec::new();
+    if let Some(folder) = params.get("folder").and_then(Value::as_str) {
+        let folder = PathBuf::from(folder);
+        if !folder.is_dir() {
+            return Err(invalid_params(format!("{} is not a folder", folder.display())));
+        }
+        let (maps, unmatched) = map_names::assign(&map_names::map_files(&folder));
+        map_names::apply(project, &maps);
+        unrecognized = unmatched;
+    }
+    for slot in SLOTS {
+        if let Some(path) = params.get(slot).and_then(Value::as_str) {
+            project.set_map(slot, Some(PathBuf::from(path))).map_err(invalid_params)?;
+        }
+    }
+    let maps: serde_json::Map<String, Value> = SLOTS.into_iter()
+        .zip(project.input_paths())
+        .filter_map(|(slot, path)| Some((slot.to_string(), json!(path?))))
+        .collect();
+    Ok(json!({ "maps": maps, "unrecognized": unrecognized }))
+}
+
+// Shallow merge like batch defaults, so an option group is replaced whole.
+// Paths are resolved like a project file's, against base.
+fn set_options(project: &mut Project, params: &Value, base: &Path) -> Result<Value, RpcError> {
+    if let Some(option) = PROTECTED_OPTIONS.iter().find(|option| params.get(**option).is_some()) {
+        return Err(invalid_params(format!("{} can't be set over the server", option)));
+    }
+    let current = serde_json::to_value(&*project).map_err(|e| (INVALID_REQUEST, e.to_string()))?;
+    let mut merged: Project = serde_json::from_value(batch::merge(&current, params)).map_err(invalid_params)?;
+    merged.resolve(base).map_err(invalid_params)?;
+    *project = merged;
+    serde_json::to_value(&*project).map_err(|e| (INVALID_REQUEST, e.to_string()))
+}
+
+fn handle(project: &mut Project, method: &str, params: &Value, base: &Path) -> Result<Value, RpcError> {
+    match method {
+        "load_set" => load_set(project, params),
+        "set_options" => set_options(project, params, base),
+        "get_options" => serde_json::to_value(&*project).map_err(|e| (INVALID_REQUEST, e.to_string())),
+        // Failed exports are still a result, the report says what went wrong
+        "export" => {
+            let dry_run = params.get("dry_run").and_then(Value::as_bool).unwrap_or(false);
+            let report = crate::TerrainApp::run_headless(project.clone(), dry_run);
+            serde_json::to_value(report).map_err(|e| (INVALID_REQUEST, e.to_string()))
+        }
+        "validate" => {
+            let folder = params.get("folder").and_then(Value::as_str).ok_or_else(|| invalid_params("validate needs a folder"))?;
+            let rectangular = params.get("rectangular").and_then(Value::as_bool).unwrap_or(project.rectangular_mode);
+            let sets = validate::validate_folder(&PathBuf::from(folder), rectangular).map_err(invalid_params)?;
+            serde_json::to_value(sets).map_err(|e| (INVALID_REQUEST, e.to_string()))
+        }
+        other => Err((METHOD_NOT_FOUND, format!("Unknown method {}", other))),
+    }
+}
+
+// The response to one line, None for notifications that don't get one. Err
+// with a last response when the connection has to be closed.
+fn respond(project: &mut Project, token: &str, line: &str, base: &Path) -> Result<Option<Value>, Value> {
+    let request: Value = serde_json::from_str(line)
+        .map_err(|e| json!({ "jsonrpc": "2.0", "id": null, "error": { "code": PARSE_ERROR, "message": e.to_string() } }))?;
+    if request.get("token").and_then(Value::as_str) != Some(token) {
+        return Err(json!({ "jsonrpc": "2.0", "id": null, "error": { "code": INVALID_REQUEST, "message": "Missing or wrong token" } }));
+    }
+    let id = request.get("id").cloned();
+    let result = match request.get("method").and_then(Value::as_str) {
+        Some(method) => handle(project, method, request.get("params").unwrap_or(&Value::Null), base),
+        None => Err((INVALID_REQUEST, "Request has no method".to_string())),
+    };
+    let Some(id) = id else {
+        return Ok(None);
+    };
+    Ok(Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+    }))
+}
+
+fn connection(stream: TcpStream, mut project: Project, token: &str, base: &Path) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match respond(&mut project, token, &line, base) {
+            Ok(Some(response)) => {
+                writeln!(writer, "{}", response)?;
+                writer.flush()?;
+            }
+            Ok(None) => {}
+            Err(response) => {
+                writeln!(writer, "{}", response)?;
+                writer.flush()?;
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+// Serves until the process is stopped, each connection on its own thread.
+// The token goes to stdout so the plugin starting the server can read it.
+// base is the starting project's folder, relative paths sent later resolve
+// against it.
+pub fn serve(port: u16, project: Project, base: PathBuf) -> Result<(), String> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+    let token = new_token();
+    eprintln!("Listening on 127.0.0.1:{}", port);
+    println!("{}", json!({ "port": port, "token": token }));
+    std::io::stdout().flush().ok();
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let project = project.clone();
+        let token = token.clone();
+        let base = base.clone();
+        thread::spawn(move || {
+            if let Err(e) = connection(stream, project, &token, &base) {
+                eprintln!("Connection closed: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,208 @@
+use egui::{CollapsingHeader, Color32, Context, Ui};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use crate::map_names;
+use crate::project::Project;
+
+pub struct TextureSet {
+    pub name: String,
+    pub folder: PathBuf,
+    pub files: Vec<PathBuf>,
+    pub slots: Vec<&'static str>,
+}
+
+impl TextureSet {
+    fn complete(&self) -> bool {
+        self.slots.contains(&"albedo") && self.slots.contains(&"normal")
+    }
+}
+
+// Groups the maps of every folder under root by the name before their map
+// type, so both one set per folder libraries and folders holding several
+// sets are found. Names are made unique since they become output folders.
+pub fn scan_sets(root: &Path) -> Result<Vec<TextureSet>, String> {
+    let mut sets: Vec<TextureSet> = Vec::new();
+    for folder in map_names::folders_under(root)? {
+        let mut groups: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+        for path in map_names::map_files(&folder) {
+            if let Some(prefix) = map_names::set_prefix(&path) {
+                groups.entry(prefix).or_default().push(path);
+            }
+        }
+        for (prefix, files) in groups {
+            let base = if prefix.is_empty() {
+                folder.file_name().unwrap_or_default().to_string_lossy().to_string()
+            } else {
+                prefix
+            };
+            let mut name = base.clone();
+            let mut copy = 1;
+            while sets.iter().any(|set| set.name == name) {
+                copy += 1;
+                name = format!("{}_{}", base, copy);
+            }
+            let slots = map_names::assign(&files).0.iter().map(|(slot, _)| slot.input_name()).collect();
+            sets.push(TextureSet { name, folder: folder.clone(), files, slots });
+        }
+    }
+    if sets.is_empty() {
+        return Err(format!("No texture sets found in {}", root.display()));
+    }
+    Ok(sets)
+}
+
+// Finds every texture set in a library folder and queues the chosen ones
+// for export with the editor's settings
+pub struct SetScanner {
+    root: Option<PathBuf>,
+    output_root: Option<PathBuf>,
+    // Each set with whether it's selected
+    sets: Vec<(TextureSet, bool)>,
+    scanning: bool,
+    error: Option<String>,
+    queued: Option<usize>,
+    receiver: Receiver<Result<Vec<TextureSet>, String>>,
+    sender: Sender<Result<Vec<TextureSet>, String>>,
+}
+
+impl Default for SetScanner {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        Self {
+            root: None,
+            output_root: None,
+            sets: Vec::new(),
+            scanning: false,
+            error: None,
+            queued: None,
+            receiver: rx,
+            sender: tx,
+        }
+    }
+}
+
+impl SetScanner {
+    fn scan(&mut self, root: PathBuf) {
+        self.root = Some(root.clone());
+        self.scanning = true;
+        self.error = None;
+        self.queued = None;
+        let tx = self.sender.clone();
+        thread::spawn(move || {
+            tx.send(scan_sets(&root)).ok();
+        });
+    }
+
+    pub fn poll(&mut self, ctx: &Context) {
+        while let Ok(result) = self.receiver.try_recv() {
+            self.scanning = false;
+            match result {
+                Ok(sets) => {
+                    self.sets = sets.into_iter().map(|set| {
+                        let complete = set.complete();
+                        (set, complete)
+                    }).collect();
+                }
+                Err(e) => {
+                    self.sets.clear();
+                    self.error = Some(e);
+                }
+            }
+            ctx.request_repaint();
+        }
+    }
+
+    // One export per selected set, each going to output_root/name
+    pub fn jobs(&mut self, settings: &Project) -> Vec<Project> {
+        let Some(output_root) = &self.output_root else {
+            return Vec::new();
+        };
+        let jobs: Vec<Project> = self.sets.iter()
+            .filter(|(_, selected)| *selected)
+            .filter_map(|(set, _)| map_names::set_project(settings, &set.files, output_root.join(&set.name)).ok())
+            .collect();
+        self.queued = Some(jobs.len());
+        jobs
+    }
+
+    // True when the selected sets should be added to the export queue
+    pub fn show(&mut self, ui: &mut Ui) -> bool {
+        let mut queue = false;
+        CollapsingHeader::new("Scan Folder")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(!self.scanning, egui::Button::new("Scan Folder"))
+                        .on_hover_text("Finds every texture set below a folder, grouping maps by the name before their \
+                            type, e.g. Rock023_2K_Color.png and Rock023_2K_NormalGL.png")
+                        .clicked() {
+                        if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                            self.scan(path);
+                        }
+                    }
+                    if let Some(path) = &self.root {
+                        ui.label(path.to_string_lossy().to_string());
+                    }
+                    if self.scanning {
+                        ui.spinner();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Select Output Root").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                            self.output_root = Some(path);
+                        }
+                    }
+                    if let Some(path) = &self.output_root {
+                        ui.label(path.to_string_lossy().to_string());
+                    }
+                });
+                if let Some(e) = &self.error {
+                    ui.label(format!("Error: {}", e));
+                }
+                if self.sets.is_empty() {
+                    return;
+                }
+
+                let selected = self.sets.iter().filter(|(_, selected)| *selected).count();
+                ui.horizontal(|ui| {
+                    if ui.small_button("Select All").clicked() {
+                        for (set, selected) in &mut self.sets {
+                            *selected = set.complete();
+                        }
+                    }
+                    if ui.small_button("Select None").clicked() {
+                        for (_, selected) in &mut self.sets {
+                            *selected = false;
+                        }
+                    }
+                    ui.label(format!("{} of {} sets selected", selected, self.sets.len()));
+                });
+                let can_queue = selected > 0 && self.output_root.is_some();
+                queue = ui.add_enabled(can_queue, egui::Button::new("Add Selected to Queue"))
+                    .on_hover_text("Each set is exported with the current settings into its own folder under the output root")
+                    .clicked();
+                if let Some(count) = self.queued {
+                    ui.label(format!("Added {} sets to the Export Queue", count));
+                }
+
+                let root = self.root.clone().unwrap_or_default();
+                egui::ScrollArea::vertical().id_salt("scanned_sets").max_height(240.0).show(ui, |ui| {
+                    for (set, selected) in &mut self.sets {
+                        let folder = set.folder.strip_prefix(&root).unwrap_or(&set.folder);
+                        let label = format!("{} ({}) {}", set.name, folder.display(), set.slots.join(", "));
+                        if set.complete() {
+                            ui.checkbox(selected, label);
+                        } else {
+                            ui.add_enabled(false, egui::Checkbox::new(selected, label));
+                            ui.colored_label(Color32::YELLOW, "  Needs an albedo and a normal map");
+                        }
+                    }
+                });
+            });
+        queue
+    }
+}
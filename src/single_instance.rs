@@ -0,0 +1,195 @@
+use egui::{Context, ViewportCommand};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::paths;
+use crate::server::new_token;
+
+// Port and token of the running window, in the settings folder
+const INSTANCE_FILE: &str = "instance.json";
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+// How long a launch waits for another one that claimed the instance file
+// at the same moment to start listening
+const CLAIM_WAIT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize)]
+struct InstanceInfo {
+    port: u16,
+    // Tells this app's listener apart from whatever else may have taken
+    // the port after a crash left the file behind
+    token: String,
+}
+
+// Files handed over by a later launch, e.g. from "Open with"
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Forwarded {
+    #[serde(default)]
+    token: String,
+    pub project: Option<PathBuf>,
+    pub inputs: Vec<PathBuf>,
+}
+
+fn instance_file() -> Option<PathBuf> {
+    paths::config_dir().map(|dir| dir.join(INSTANCE_FILE))
+}
+
+fn read_info(path: &Path) -> Option<InstanceInfo> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+// Paths are resolved here, the running window has its own working directory
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn reachable(info: &InstanceInfo) -> bool {
+    TcpStream::connect_timeout(&SocketAddr::from((Ipv4Addr::LOCALHOST, info.port)), CONNECT_TIMEOUT).is_ok()
+}
+
+// Sends the files to the running window, true once it has taken them
+fn send(info: &InstanceInfo, project: Option<&Path>, inputs: &[PathBuf]) -> bool {
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, info.port));
+    let Ok(mut stream) = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) else {
+        return false;
+    };
+    stream.set_read_timeout(Some(CONNECT_TIMEOUT)).ok();
+    let request = Forwarded {
+        token: info.token.clone(),
+        project: project.map(absolute),
+        inputs: inputs.iter().map(|path| absolute(path)).collect(),
+    };
+    let Ok(line) = serde_json::to_string(&request) else {
+        return false;
+    };
+    if writeln!(stream, "{}", line).and_then(|_| stream.flush()).is_err() {
+        return false;
+    }
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply).is_ok() && reply.trim() == "ok"
+}
+
+// The window this launch opens, holding the instance file until it closes
+pub struct Instance {
+    listener: TcpListener,
+    token: String,
+    file: PathBuf,
+}
+
+pub enum Launch {
+    // Another window took the files, this launch is done
+    Forwarded,
+    // This launch opens the window, and takes requests when it got the
+    // instance file
+    Primary(Option<Instance>),
+}
+
+// Forwards the files when a window is already running, otherwise claims the
+// instance file. Two launches at once race for creating the file, the loser
+// waits for the winner to listen and forwards to it.
+pub fn launch(project: Option<&Path>, inputs: &[PathBuf]) -> Launch {
+    let Some(file) = instance_file() else {
+        return Launch::Primary(None);
+    };
+    let forward = project.is_some() || !inputs.is_empty();
+    let started = Instant::now();
+    loop {
+        match read_info(&file) {
+            Some(info) if forward && send(&info, project, inputs) => return Launch::Forwarded,
+            // Without files a launch just opens another window
+            Some(info) if !forward && reachable(&info) => return Launch::Primary(None),
+            // The file is written once the port is open, so nobody answering
+            // means it was left over from a crash
+            Some(info) if !reachable(&info) => {
+                fs::remove_file(&file).ok();
+            }
+            _ => {}
+        }
+        match claim(&file) {
+            Ok(instance) => return Launch::Primary(Some(instance)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists && started.elapsed() < CLAIM_WAIT => {
+                thread::sleep(Duration::from_millis(100));
+            }
+            // A window that answers keeps its file even when it was too slow
+            // to take the files, this one just doesn't get forwarded ones
+            Err(e) if e.kind() == ErrorKind::AlreadyExists && read_info(&file).is_some_and(|info| reachable(&info)) => {
+                return Launch::Primary(None);
+            }
+            // Still unreadable or not this app's, take it over
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                fs::remove_file(&file).ok();
+                return Launch::Primary(claim(&file).ok());
+            }
+            Err(_) => return Launch::Primary(None),
+        }
+    }
+}
+
+fn claim(file: &Path) -> std::io::Result<Instance> {
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut created = OpenOptions::new().write(true).create_new(true).open(file)?;
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0));
+    let listener = match listener {
+        Ok(listener) => listener,
+        Err(e) => {
+            fs::remove_file(file).ok();
+            return Err(e);
+        }
+    };
+    let info = InstanceInfo { port: listener.local_addr()?.port(), token: new_token() };
+    created.write_all(serde_json::to_string(&info).unwrap_or_default().as_bytes())?;
+    Ok(Instance { listener, token: info.token, file: file.to_path_buf() })
+}
+
+impl Instance {
+    // Takes forwarded files until the process ends, bringing the window to
+    // the front for each
+    pub fn listen(&self, ctx: Context, sender: Sender<Forwarded>) {
+        let Ok(listener) = self.listener.try_clone() else {
+            return;
+        };
+        let token = self.token.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                stream.set_read_timeout(Some(CONNECT_TIMEOUT)).ok();
+                let Ok(mut writer) = stream.try_clone() else {
+                    continue;
+                };
+                let mut line = String::new();
+                if BufReader::new(stream).read_line(&mut line).is_err() {
+                    continue;
+                }
+                let Some(request) = serde_json::from_str::<Forwarded>(&line).ok().filter(|request| request.token == token) else {
+                    continue;
+                };
+                if sender.send(request).is_err() {
+                    return;
+                }
+                writeln!(writer, "ok").ok();
+                ctx.send_viewport_cmd(ViewportCommand::Minimized(false));
+                ctx.send_viewport_cmd(ViewportCommand::Focus);
+                ctx.request_repaint();
+            }
+        });
+    }
+}
+
+// Removes the instance file when the window closes, unless a later launch
+// has taken it over since
+impl Drop for Instance {
+    fn drop(&mut self) {
+        if read_info(&self.file).is_some_and(|info| info.token == self.token) {
+            fs::remove_file(&self.file).ok();
+        }
+    }
+}
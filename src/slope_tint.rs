@@ -0,0 +1,95 @@
+use egui::{Color32, Ui};
+use image::{imageops::FilterType, RgbaImage};
+use rayon::prelude::*;
+
+// Half the angle over which the tint fades in, in degrees
+const FADE: f32 = 5.0;
+
+// Tints the albedo preview where the height map makes the surface steep,
+// roughly as an engine mixes in its cliff material by slope, to see whether
+// the set still reads on cliffs. Only affects the preview.
+pub struct SlopeTint {
+    enabled: bool,
+    color: Color32,
+    strength: f32,
+    // Slope in degrees from which the tint shows
+    slope_start: f32,
+    // Height range of the map relative to the texture's width, e.g. 0.05
+    // for 10 cm of relief on a 2 m tile
+    depth: f32,
+    // The height map changed since the preview was built
+    stale: bool,
+}
+
+impl Default for SlopeTint {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: Color32::from_rgb(128, 118, 105),
+            strength: 0.6,
+            slope_start: 35.0,
+            depth: 0.05,
+            stale: false,
+        }
+    }
+}
+
+impl SlopeTint {
+    pub fn invalidate(&mut self) {
+        self.stale = true;
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.stale && self.enabled
+    }
+
+    // The tinted preview, None when the tint is off or there's no height
+    pub fn apply(&mut self, preview: &RgbaImage, height: Option<&RgbaImage>) -> Option<RgbaImage> {
+        self.stale = false;
+        let height = height.filter(|_| self.enabled)?;
+        let (width, rows) = preview.dimensions();
+        let height = image::imageops::resize(height, width, rows, FilterType::Triangle);
+        // Height steps per texel, the preview is the whole texture width
+        let scale = self.depth * width as f32 / 255.0;
+        let sample = |x: i64, y: i64| height.get_pixel(x.rem_euclid(width as i64) as u32, y.rem_euclid(rows as i64) as u32)[0] as f32;
+        let tint = [self.color.r(), self.color.g(), self.color.b()].map(f32::from);
+
+        let mut tinted = preview.clone();
+        tinted.par_chunks_mut(4).enumerate().for_each(|(i, pixel)| {
+            let (x, y) = ((i as u32 % width) as i64, (i as u32 / width) as i64);
+            let dx = (sample(x + 1, y) - sample(x - 1, y)) * 0.5 * scale;
+            let dy = (sample(x, y + 1) - sample(x, y - 1)) * 0.5 * scale;
+            let slope = (dx * dx + dy * dy).sqrt().atan().to_degrees();
+            let weight = ((slope - self.slope_start + FADE) / (2.0 * FADE)).clamp(0.0, 1.0) * self.strength;
+            for (value, tint) in pixel[..3].iter_mut().zip(tint) {
+                *value = (*value as f32 + (tint - *value as f32) * weight).round() as u8;
+            }
+        });
+        Some(tinted)
+    }
+
+    // Returns true when the preview needs rebuilding
+    pub fn show(&mut self, ui: &mut Ui, has_height: bool) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            changed |= ui.checkbox(&mut self.enabled, "Slope Tint")
+                .on_hover_text("Tints steep areas of the height map in the preview, like an engine's cliff material")
+                .changed();
+            if self.enabled {
+                changed |= ui.color_edit_button_srgba(&mut self.color).changed();
+            }
+        });
+        if !self.enabled {
+            return changed;
+        }
+        if !has_height {
+            ui.label("Needs a height map");
+        }
+        changed |= ui.add(egui::Slider::new(&mut self.slope_start, 0.0..=85.0).text("Slope Start").suffix("°")).changed();
+        changed |= ui.add(egui::Slider::new(&mut self.strength, 0.0..=1.0).text("Tint Strength")).changed();
+        changed |= ui.add(egui::Slider::new(&mut self.depth, 0.005..=0.5).logarithmic(true).text("Height Depth"))
+            .on_hover_text("Height range of the map relative to the texture's width")
+            .changed();
+        changed
+    }
+}
@@ -0,0 +1,104 @@
+use egui::{Color32, Ui};
+use image::DynamicImage;
+use rayon::prelude::*;
+
+// What a source file actually holds, as opposed to what it was saved as
+#[derive(Debug, Clone, Copy)]
+pub struct SourceInfo {
+    pub container_bits: u8,
+    // 16-bit files exported from 8-bit data only use every 257th value
+    pub effective_bits: u8,
+    pub channels: u8,
+    pub float: bool,
+    // Alpha that varies across the image, e.g. gloss or height packed in by
+    // another exporter, rather than a flat opaque channel
+    pub alpha_data: bool,
+    // A float source a script ran on, which only sees 0 to 1 at 16 bits
+    pub scripted_float: bool,
+}
+
+// Differences this small are dithering or compression noise
+const ALPHA_TOLERANCE: u8 = 2;
+
+fn alpha_varies(img: &DynamicImage) -> bool {
+    if !img.color().has_alpha() {
+        return false;
+    }
+    let (min, max) = img.to_rgba8().as_raw().par_chunks(4)
+        .map(|p| (p[3], p[3]))
+        .reduce(|| (255, 0), |a, b| (a.0.min(b.0), a.1.max(b.1)));
+    max.saturating_sub(min) > ALPHA_TOLERANCE
+}
+
+pub fn inspect(img: &DynamicImage) -> SourceInfo {
+    let color = img.color();
+    let channels = color.channel_count();
+    let container_bits = color.bytes_per_pixel() / channels * 8;
+    let float = matches!(img, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_));
+    let effective_bits = match img {
+        DynamicImage::ImageLuma16(buffer) => effective_bits_16(buffer.as_raw()),
+        DynamicImage::ImageLumaA16(buffer) => effective_bits_16(buffer.as_raw()),
+        DynamicImage::ImageRgb16(buffer) => effective_bits_16(buffer.as_raw()),
+        DynamicImage::ImageRgba16(buffer) => effective_bits_16(buffer.as_raw()),
+        _ => container_bits,
+    };
+    SourceInfo {
+        container_bits,
+        effective_bits,
+        channels,
+        float,
+        alpha_data: alpha_varies(img),
+        scripted_float: false,
+    }
+}
+
+fn effective_bits_16(samples: &[u16]) -> u8 {
+    if samples.par_iter().all(|&v| v % 257 == 0) { 8 } else { 16 }
+}
+
+impl SourceInfo {
+    pub fn describe(&self) -> String {
+        let layout = match self.channels {
+            1 => "Gray",
+            2 => "Gray + Alpha",
+            3 => "RGB",
+            _ => "RGBA",
+        };
+        let depth = if self.float {
+            format!("{}-bit float", self.container_bits)
+        } else {
+            format!("{}-bit", self.container_bits)
+        };
+        if self.effective_bits < self.container_bits {
+            format!("{} {} ({}-bit data)", depth, layout, self.effective_bits)
+        } else {
+            format!("{} {}", depth, layout)
+        }
+    }
+
+    // Every packed output is 8 bits per channel
+    pub fn warnings(&self, image_type: &str) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.effective_bits > 8 {
+            warnings.push(match image_type {
+                "height" => format!("{}-bit height is quantized to 8 bits in albedo alpha", self.effective_bits),
+                "roughness" => format!("{}-bit roughness is quantized to 8 bits in normal alpha", self.effective_bits),
+                _ => format!("{}-bit data is reduced to 8 bits on export", self.effective_bits),
+            });
+        }
+        if self.scripted_float {
+            warnings.push("Float values are clamped to 0 to 1 at 16 bits before the script runs".to_string());
+        }
+        if self.channels < 3 && matches!(image_type, "albedo" | "normal") {
+            warnings.push(format!("Grayscale {} map, all color channels get the same value", image_type));
+        }
+        warnings
+    }
+}
+
+pub fn show(ui: &mut Ui, info: &SourceInfo, image_type: &str) {
+    ui.label(info.describe());
+    for warning in info.warnings(image_type) {
+        ui.colored_label(Color32::YELLOW, warning);
+    }
+}
@@ -0,0 +1,373 @@
+use egui::{CollapsingHeader, Color32, ComboBox, ColorImage, Context, TextureHandle, Ui, widgets::Image, load::SizedTexture};
+use image::{ImageBuffer, Rgb, RgbaImage};
+use rayon::prelude::*;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use crate::frames;
+use crate::paths;
+use crate::{ImageLoadState, ProcessingState};
+
+// Terrain3D supports up to 32 texture slots (5 bits in the control map)
+pub const MAX_TEXTURE_INDEX: u8 = 31;
+
+const CHANNEL_NAMES: [&str; 4] = ["R", "G", "B", "A"];
+
+// Terrain3D control map layout, stored as the raw bits of a 32-bit float:
+// base id (5 bits) | overlay id (5 bits) | blend (8 bits) | uv angle | uv scale | hole | nav | auto
+pub fn encode_control(base: u8, overlay: u8, blend: u8) -> u32 {
+    ((base as u32 & 0x1F) << 27) | ((overlay as u32 & 0x1F) << 22) | ((blend as u32) << 14)
+}
+
+pub fn decode_control(bits: u32) -> (u8, u8, u8) {
+    (
+        ((bits >> 27) & 0x1F) as u8,
+        ((bits >> 22) & 0x1F) as u8,
+        ((bits >> 14) & 0xFF) as u8,
+    )
+}
+
+pub struct Splatmap {
+    pub path: PathBuf,
+    pub load_state: ImageLoadState,
+    pub image: Option<RgbaImage>,
+    // Texture index each splat channel contributes to, None if unused
+    pub channels: [Option<u8>; 4],
+    // Index the R channel would get, later channels follow it
+    first_index: usize,
+    // Assumed until the image has loaded
+    has_alpha: bool,
+}
+
+impl Splatmap {
+    fn new(path: PathBuf, first_index: usize) -> Self {
+        Self {
+            path,
+            load_state: ImageLoadState::Loading,
+            image: None,
+            channels: default_channels(first_index, true),
+            first_index,
+            has_alpha: true,
+        }
+    }
+
+    // Channels that ran past the last texture index and weren't mapped since
+    fn needs_mapping(&self) -> bool {
+        let count = if self.has_alpha { 4 } else { 3 };
+        (0..count).any(|c| self.channels[c].is_none() && self.first_index + c > MAX_TEXTURE_INDEX as usize)
+    }
+}
+
+// Consecutive texture indices from first_index. Channels past the last index
+// are left for manual mapping instead of piling onto it, and without alpha A
+// would read as full weight everywhere.
+fn default_channels(first_index: usize, has_alpha: bool) -> [Option<u8>; 4] {
+    let mut channels = [None; 4];
+    let count = if has_alpha { 4 } else { 3 };
+    for (i, channel) in channels.iter_mut().enumerate().take(count) {
+        let index = first_index + i;
+        if index <= MAX_TEXTURE_INDEX as usize {
+            *channel = Some(index as u8);
+        }
+    }
+    channels
+}
+
+// Builds control map bits from weighted splat channels. The two strongest
+// texture weights per pixel become base and overlay, blended by their ratio.
+pub fn splats_to_control(splats: &[(&RgbaImage, [Option<u8>; 4])]) -> Result<(u32, u32, Vec<u32>), String> {
+    let (width, height) = match splats.first() {
+        Some((img, _)) => img.dimensions(),
+        None => return Err("No splatmaps loaded".to_string()),
+    };
+    if splats.iter().any(|(img, _)| img.dimensions() != (width, height)) {
+        return Err("All splatmaps must have the same dimensions".to_string());
+    }
+
+    let mut control = vec![0u32; (width * height) as usize];
+    control.par_iter_mut().enumerate().for_each(|(i, value)| {
+        let x = (i % width as usize) as u32;
+        let y = (i / width as usize) as u32;
+        let mut weights = [0u32; MAX_TEXTURE_INDEX as usize + 1];
+        for (img, channels) in splats {
+            let pixel = img.get_pixel(x, y);
+            for (c, index) in channels.iter().enumerate() {
+                if let Some(index) = index {
+                    weights[*index as usize] += pixel[c] as u32;
+                }
+            }
+        }
+
+        let mut base = 0usize;
+        let mut overlay = 0usize;
+        for index in 0..weights.len() {
+            if weights[index] > weights[base] {
+                overlay = base;
+                base = index;
+            } else if index != base && (weights[index] > weights[overlay] || overlay == base) {
+                overlay = index;
+            }
+        }
+
+        let total = weights[base] + weights[overlay];
+        if weights[overlay] == 0 || total == 0 {
+            *value = encode_control(base as u8, base as u8, 0);
+        } else {
+            let blend = (weights[overlay] * 255 + total / 2) / total;
+            *value = encode_control(base as u8, overlay as u8, blend as u8);
+        }
+    });
+
+    Ok((width, height, control))
+}
+
+// Color-coded view of the control map: each texture index gets a distinct hue
+pub fn control_preview(width: u32, height: u32, control: &[u32]) -> RgbaImage {
+    let color_for = |index: u8| -> [f32; 3] {
+        let hue = (index as f32 * 0.618_034).fract();
+        let sector = hue * 6.0;
+        let f = sector.fract();
+        match sector as u32 {
+            0 => [1.0, f, 0.0],
+            1 => [1.0 - f, 1.0, 0.0],
+            2 => [0.0, 1.0, f],
+            3 => [0.0, 1.0 - f, 1.0],
+            4 => [f, 0.0, 1.0],
+            _ => [1.0, 0.0, 1.0 - f],
+        }
+    };
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let (base, overlay, blend) = decode_control(control[(y * width + x) as usize]);
+        let t = blend as f32 / 255.0;
+        let a = color_for(base);
+        let b = color_for(overlay);
+        let mix = |c: usize| ((a[c] * (1.0 - t) + b[c] * t) * 255.0) as u8;
+        image::Rgba([mix(0), mix(1), mix(2), 255])
+    })
+}
+
+// Written as a float EXR with the control bits in the red channel, matching
+// the FORMAT_RF data Terrain3D's importer expects
+pub fn save_control_exr(width: u32, height: u32, control: &[u32], path: PathBuf) -> Result<(), String> {
+    let buffer: ImageBuffer<Rgb<f32>, Vec<f32>> = ImageBuffer::from_fn(width, height, |x, y| {
+        Rgb([f32::from_bits(control[(y * width + x) as usize]), 0.0, 0.0])
+    });
+    buffer.save(path).map_err(|e| e.to_string())
+}
+
+// The image and whether it has an alpha channel of its own
+type LoadedSplat = (RgbaImage, bool);
+
+pub struct SplatmapConverter {
+    splats: Vec<Splatmap>,
+    load_receiver: Receiver<(PathBuf, Result<LoadedSplat, String>)>,
+    load_sender: Sender<(PathBuf, Result<LoadedSplat, String>)>,
+    export_state: ProcessingState,
+    export_receiver: Receiver<Result<RgbaImage, String>>,
+    export_sender: Sender<Result<RgbaImage, String>>,
+    preview_texture: Option<TextureHandle>,
+}
+
+impl Default for SplatmapConverter {
+    fn default() -> Self {
+        let (tx, rx) = channel();
+        let (etx, erx) = channel();
+        Self {
+            splats: Vec::new(),
+            load_receiver: rx,
+            load_sender: tx,
+            export_state: ProcessingState::NotStarted,
+            export_receiver: erx,
+            export_sender: etx,
+            preview_texture: None,
+        }
+    }
+}
+
+impl SplatmapConverter {
+    fn add_splatmap(&mut self, path: PathBuf) {
+        let first_index = self.splats.len() * 4;
+        self.splats.push(Splatmap::new(path.clone(), first_index));
+
+        let tx = self.load_sender.clone();
+        thread::spawn(move || {
+            let result = frames::open_single(&paths::long_path(&path)).map(|img| (img.to_rgba8(), img.color().has_alpha()));
+            tx.send((path, result)).ok();
+        });
+    }
+
+    fn all_loaded(&self) -> bool {
+        !self.splats.is_empty()
+            && self.splats.iter().all(|s| matches!(s.load_state, ImageLoadState::Loaded))
+    }
+
+    fn export(&mut self, output_dir: PathBuf) {
+        let splats: Vec<(RgbaImage, [Option<u8>; 4])> = self.splats.iter()
+            .filter_map(|s| s.image.clone().map(|img| (img, s.channels)))
+            .collect();
+        let tx = self.export_sender.clone();
+
+        self.export_state = ProcessingState::Processing;
+
+        thread::spawn(move || {
+            let result = (move || {
+                let refs: Vec<_> = splats.iter().map(|(img, channels)| (img, *channels)).collect();
+                let (width, height, control) = splats_to_control(&refs)?;
+                save_control_exr(width, height, &control, output_dir.join("control.exr"))?;
+
+                let preview = control_preview(width, height, &control);
+                let preview = image::imageops::resize(&preview, 512, 512, image::imageops::FilterType::Nearest);
+                Ok(preview)
+            })();
+            tx.send(result).ok();
+        });
+    }
+
+    pub fn poll(&mut self, ctx: &Context) {
+        while let Ok((path, result)) = self.load_receiver.try_recv() {
+            if let Some(splat) = self.splats.iter_mut()
+                .find(|s| s.path == path && matches!(s.load_state, ImageLoadState::Loading)) {
+                match result {
+                    Ok((img, has_alpha)) => {
+                        if !has_alpha {
+                            splat.channels[3] = None;
+                        }
+                        splat.has_alpha = has_alpha;
+                        splat.image = Some(img);
+                        splat.load_state = ImageLoadState::Loaded;
+                    }
+                    Err(e) => splat.load_state = ImageLoadState::Error(e),
+                }
+            }
+            ctx.request_repaint();
+        }
+
+        if let Ok(result) = self.export_receiver.try_recv() {
+            self.export_state = match result {
+                Ok(preview) => {
+                    let size = [preview.width() as _, preview.height() as _];
+                    let color_image = ColorImage::from_rgba_unmultiplied(size, preview.as_raw());
+                    self.preview_texture = Some(ctx.load_texture("control_preview", color_image, Default::default()));
+                    ProcessingState::Done
+                }
+                Err(e) => ProcessingState::Error(e),
+            };
+            ctx.request_repaint();
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, output_directory: Option<&PathBuf>) {
+        CollapsingHeader::new("Splatmap to Control Map")
+            .default_open(false)
+            .show(ui, |ui| {
+                if ui.button("Add Splatmap").clicked() {
+                    if let Some(paths) = rfd::FileDialog::new()
+                        .add_filter("Image files", &crate::TerrainApp::SUPPORTED_FORMATS)
+                        .pick_files() {
+                        for path in paths {
+                            self.add_splatmap(path);
+                        }
+                    }
+                }
+
+                let mut remove = None;
+                for (i, splat) in self.splats.iter_mut().enumerate() {
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label(splat.path.file_name().unwrap_or_default().to_string_lossy().to_string());
+                        match &splat.load_state {
+                            ImageLoadState::Loading => { ui.spinner(); }
+                            ImageLoadState::Error(e) => { ui.label(format!("Error: {}", e)); }
+                            _ => {}
+                        }
+                        if ui.button("Remove").clicked() {
+                            remove = Some(i);
+                        }
+                    });
+                    if splat.needs_mapping() {
+                        ui.colored_label(
+                            Color32::YELLOW,
+                            format!("Channels past texture {} have no index, pick one for them", MAX_TEXTURE_INDEX),
+                        );
+                    }
+                    ui.horizontal(|ui| {
+                        for (c, channel) in splat.channels.iter_mut().enumerate() {
+                            ComboBox::from_id_salt(("splat_channel", i, c))
+                                .width(60.0)
+                                .selected_text(match channel {
+                                    Some(index) => format!("{} → {}", CHANNEL_NAMES[c], index),
+                                    None => format!("{} → -", CHANNEL_NAMES[c]),
+                                })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(channel, None, "Unused");
+                                    for index in 0..=MAX_TEXTURE_INDEX {
+                                        ui.selectable_value(channel, Some(index), index.to_string());
+                                    }
+                                });
+                        }
+                    });
+                }
+                if let Some(i) = remove {
+                    self.splats.remove(i);
+                }
+
+                ui.add_space(4.0);
+                let can_export = self.all_loaded()
+                    && output_directory.is_some()
+                    && !matches!(self.export_state, ProcessingState::Processing);
+                let export_button = ui.add_enabled_ui(can_export, |ui| {
+                    ui.button("Export Control Map")
+                }).inner;
+                if export_button.clicked() {
+                    if let Some(dir) = output_directory {
+                        self.export(dir.clone());
+                    }
+                }
+
+                match &self.export_state {
+                    ProcessingState::Processing => {
+                        ui.spinner();
+                    }
+                    ProcessingState::Done => {
+                        ui.label("Wrote control.exr");
+                    }
+                    ProcessingState::Error(e) => {
+                        ui.label(format!("Error: {}", e));
+                    }
+                    _ => {}
+                }
+
+                if let Some(texture) = &self.preview_texture {
+                    ui.add(Image::from_texture(SizedTexture::from_handle(texture)).max_width(ui.available_width()));
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, RgbImage};
+
+    #[test]
+    fn rgb_splat_leaves_alpha_unused() {
+        let rgb = RgbImage::from_fn(2, 1, |x, _| if x == 0 { Rgb([200, 55, 0]) } else { Rgb([0, 0, 255]) });
+        let img = DynamicImage::ImageRgb8(rgb);
+        let channels = default_channels(0, img.color().has_alpha());
+        assert_eq!(channels, [Some(0), Some(1), Some(2), None]);
+
+        let rgba = img.to_rgba8();
+        let (_, _, control) = splats_to_control(&[(&rgba, channels)]).unwrap();
+        assert_eq!(decode_control(control[0]), (0, 1, 55));
+        assert_eq!(decode_control(control[1]), (2, 2, 0));
+    }
+
+    #[test]
+    fn channels_past_the_last_index_are_unmapped() {
+        assert_eq!(default_channels(28, true), [Some(28), Some(29), Some(30), Some(31)]);
+        assert_eq!(default_channels(30, true), [Some(30), Some(31), None, None]);
+        assert_eq!(default_channels(32, true), [None; 4]);
+    }
+}
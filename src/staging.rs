@@ -0,0 +1,54 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::paths;
+
+// Export outputs are written into a hidden folder next to their destination
+// and only moved into place once everything succeeded, so an aborted export
+// never leaves half-written textures for Godot to import. Dropping without
+// committing removes whatever was written.
+pub struct StagedOutput {
+    staging: PathBuf,
+    target: PathBuf,
+}
+
+impl StagedOutput {
+    pub fn new(target: &Path) -> Result<Self, String> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or_default();
+        // Same directory so the final rename never crosses filesystems
+        let staging = paths::long_path(target).join(format!(".export-staging-{}-{}", std::process::id(), nanos));
+        fs::create_dir(&staging).map_err(|e| format!("Failed to create staging directory: {}", e))?;
+        Ok(Self {
+            staging,
+            target: target.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.staging
+    }
+
+    // Renames every staged file over its destination, returning where they
+    // ended up as plain paths for display and file managers
+    pub fn commit(self) -> Result<Vec<PathBuf>, String> {
+        let entries = fs::read_dir(&self.staging).map_err(|e| e.to_string())?;
+        let target = paths::long_path(&self.target);
+        let mut committed = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| e.to_string())?;
+            let destination = self.target.join(entry.file_name());
+            fs::rename(entry.path(), target.join(entry.file_name()))
+                .map_err(|e| format!("Failed to move {} into place: {}", destination.display(), e))?;
+            committed.push(destination);
+        }
+        committed.sort();
+        Ok(committed)
+    }
+}
+
+impl Drop for StagedOutput {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.staging).ok();
+    }
+}
@@ -0,0 +1,86 @@
+use egui::{CollapsingHeader, Color32, Grid, Ui};
+use serde::{Deserialize, Serialize};
+
+// How finely the exported albedo covers the ground. Terrain3D repeats a
+// texture every 1 / uv_scale meters, so the texture's width over that
+// distance is its texel density.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TexelDensitySettings {
+    // Side length of the terrain in meters
+    pub terrain_size: f32,
+    pub uv_scale: f32,
+    // Texels per meter below which the material looks soft up close
+    pub min_texels_per_meter: f32,
+}
+
+impl Default for TexelDensitySettings {
+    fn default() -> Self {
+        Self {
+            terrain_size: 1024.0,
+            uv_scale: 0.1,
+            min_texels_per_meter: 128.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TexelDensity {
+    pub texels_per_meter: f32,
+    // Ground covered by one repeat of the texture
+    pub tile_meters: f32,
+    // Repeats across the terrain's side
+    pub repeats: f32,
+    pub uv_scale: f32,
+    pub terrain_size: f32,
+    pub below_threshold: bool,
+}
+
+impl TexelDensitySettings {
+    pub fn compute(&self, width: u32) -> TexelDensity {
+        let uv_scale = self.uv_scale.max(0.0001);
+        let texels_per_meter = width as f32 * uv_scale;
+        TexelDensity {
+            texels_per_meter,
+            tile_meters: 1.0 / uv_scale,
+            repeats: self.terrain_size * uv_scale,
+            uv_scale,
+            terrain_size: self.terrain_size,
+            below_threshold: texels_per_meter < self.min_texels_per_meter,
+        }
+    }
+
+    // width is the exported albedo width, None until an albedo is loaded
+    pub fn show(&mut self, ui: &mut Ui, width: Option<u32>) {
+        CollapsingHeader::new("Texel Density").show(ui, |ui| {
+            ui.add(egui::DragValue::new(&mut self.terrain_size).range(1.0..=65536.0).speed(8.0).prefix("Terrain Size: ").suffix(" m"));
+            ui.add(egui::DragValue::new(&mut self.uv_scale).range(0.001..=10.0).speed(0.001).prefix("UV Scale: "))
+                .on_hover_text("Terrain3D's texture UV scale, the texture repeats every 1 / UV scale meters");
+            ui.add(egui::DragValue::new(&mut self.min_texels_per_meter).range(1.0..=8192.0).speed(1.0)
+                .prefix("Warn Below: ").suffix(" texels/m"));
+
+            let Some(width) = width else {
+                ui.label("Needs an albedo map");
+                return;
+            };
+            let density = self.compute(width);
+            Grid::new("texel_density").num_columns(2).show(ui, |ui| {
+                ui.label("Texels per Meter");
+                ui.label(format!("{:.1}", density.texels_per_meter));
+                ui.end_row();
+                ui.label("Tile Size");
+                ui.label(format!("{:.2} m", density.tile_meters));
+                ui.end_row();
+                ui.label("Repeats Across Terrain");
+                ui.label(format!("{:.1}", density.repeats));
+                ui.end_row();
+            });
+            if density.below_threshold {
+                ui.colored_label(Color32::YELLOW, format!(
+                    "{} px over {:.2} m is below {} texels per meter",
+                    width, density.tile_meters, self.min_texels_per_meter,
+                ));
+            }
+        });
+    }
+}
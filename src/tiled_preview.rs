@@ -0,0 +1,138 @@
+use egui::{Color32, ColorImage, Context, Rect, TextureHandle, TextureOptions, Ui};
+use image::RgbaImage;
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+const TILE_SIZE: u32 = 512;
+// Uploads per frame, each tile is 1 MB so this keeps frames short
+const TILES_PER_FRAME: usize = 2;
+
+struct Tile {
+    // Position and size in texels
+    rect: [u32; 4],
+    image: Option<ColorImage>,
+    texture: Option<TextureHandle>,
+}
+
+// Full resolution preview of a large image. The pixels are prepared on a
+// worker thread and uploaded a few tiles per frame, so the UI keeps drawing
+// while a big texture comes in.
+pub struct TiledPreview {
+    name: String,
+    size: [u32; 2],
+    options: TextureOptions,
+    // Rows prepared by the worker so far
+    rows_done: Arc<AtomicUsize>,
+    receiver: Option<Receiver<Vec<Tile>>>,
+    tiles: Vec<Tile>,
+    // Set when the worker stopped without delivering, e.g. after a panic in convert
+    error: Option<String>,
+}
+
+impl TiledPreview {
+    // convert runs on every pixel off the UI thread, e.g. to isolate a channel
+    pub fn build(name: &str, image: RgbaImage, options: TextureOptions, convert: impl Fn(&mut [u8]) + Send + Sync + 'static) -> Self {
+        let (width, height) = image.dimensions();
+        let rows_done = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = channel();
+        let progress = rows_done.clone();
+        thread::spawn(move || {
+            let mut image = image;
+            image.par_chunks_mut(width as usize * 4).for_each(|row| {
+                row.chunks_mut(4).for_each(&convert);
+                progress.fetch_add(1, Ordering::Relaxed);
+            });
+            let mut tiles = Vec::new();
+            for y in (0..height).step_by(TILE_SIZE as usize) {
+                for x in (0..width).step_by(TILE_SIZE as usize) {
+                    let (w, h) = (TILE_SIZE.min(width - x), TILE_SIZE.min(height - y));
+                    let tile = image::imageops::crop_imm(&image, x, y, w, h).to_image();
+                    tiles.push(Tile {
+                        rect: [x, y, w, h],
+                        image: Some(ColorImage::from_rgba_unmultiplied([w as usize, h as usize], tile.as_raw())),
+                        texture: None,
+                    });
+                }
+            }
+            tx.send(tiles).ok();
+        });
+        Self {
+            name: name.to_string(),
+            size: [width, height],
+            options,
+            rows_done,
+            receiver: Some(rx),
+            tiles: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn poll(&mut self, ctx: &Context) {
+        if let Some(receiver) = &self.receiver {
+            match receiver.try_recv() {
+                Ok(tiles) => {
+                    self.tiles = tiles;
+                    self.receiver = None;
+                }
+                Err(TryRecvError::Empty) => {
+                    ctx.request_repaint();
+                    return;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    self.receiver = None;
+                    self.error = Some("The preview stopped before it was ready".to_string());
+                    return;
+                }
+            }
+        }
+        let mut uploaded = 0;
+        for (i, tile) in self.tiles.iter_mut().enumerate() {
+            if uploaded == TILES_PER_FRAME {
+                break;
+            }
+            if let Some(image) = tile.image.take() {
+                tile.texture = Some(ctx.load_texture(format!("{}_{}", self.name, i), image, self.options));
+                uploaded += 1;
+            }
+        }
+        if uploaded > 0 {
+            ctx.request_repaint();
+        }
+    }
+
+    // What's still going on and how far along it is
+    pub fn progress(&self) -> Option<(&'static str, f32)> {
+        if self.receiver.is_some() {
+            let rows = self.rows_done.load(Ordering::Relaxed);
+            return Some(("Preparing preview", rows as f32 / self.size[1].max(1) as f32));
+        }
+        let pending = self.tiles.iter().filter(|tile| tile.texture.is_none()).count();
+        (pending > 0).then(|| ("Uploading preview", 1.0 - pending as f32 / self.tiles.len() as f32))
+    }
+
+    // Draws the tiles uploaded so far scaled to width, gaps stay dark
+    pub fn show(&self, ui: &mut Ui, width: f32) {
+        let [image_width, image_height] = self.size.map(|v| v.max(1) as f32);
+        let scale = width / image_width;
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(width, image_height * scale), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, Color32::from_gray(20));
+        let uv = Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+        for tile in &self.tiles {
+            let Some(texture) = &tile.texture else {
+                continue;
+            };
+            let [x, y, w, h] = tile.rect.map(|v| v as f32 * scale);
+            let tile_rect = Rect::from_min_size(rect.min + egui::vec2(x, y), egui::vec2(w, h));
+            painter.image(texture.id(), tile_rect, uv, Color32::WHITE);
+        }
+        if let Some(e) = &self.error {
+            ui.colored_label(Color32::LIGHT_RED, e);
+        } else if let Some((label, fraction)) = self.progress() {
+            ui.add(egui::ProgressBar::new(fraction).text(label));
+        }
+    }
+}
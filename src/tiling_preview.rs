@@ -0,0 +1,178 @@
+use egui::{CollapsingHeader, ColorImage, ComboBox, TextureHandle, Ui, Vec2, widgets::Image, load::SizedTexture};
+use image::RgbaImage;
+use rayon::prelude::*;
+
+const VIEW_SIZE: u32 = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TilingMode {
+    Repeat,
+    // Hex-tiling as in Mikkelsen's "Practical Real-Time Hex-Tiling": every
+    // cell of a triangle grid samples the texture at its own random offset
+    // and the three nearest cells are blended
+    HexTiling,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TilingSettings {
+    pub mode: TilingMode,
+    // Texture repeats across the view
+    pub repeats: u32,
+    // Hex cells per texture repeat
+    pub cell_rate: f32,
+    // Higher values narrow the blend between cells towards the brighter sample
+    pub contrast: f32,
+    pub rotate: bool,
+}
+
+impl Default for TilingSettings {
+    fn default() -> Self {
+        Self {
+            mode: TilingMode::HexTiling,
+            repeats: 4,
+            cell_rate: 1.0,
+            contrast: 0.6,
+            rotate: false,
+        }
+    }
+}
+
+fn hash(x: i32, y: i32) -> [f32; 2] {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343) ^ (y as u32).wrapping_mul(0xd816_3841);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    [(h & 0xffff) as f32 / 65535.0, (h >> 16) as f32 / 65535.0]
+}
+
+// Bilinear lookup wrapping at the edges, uv in texture repeats
+fn sample(texture: &RgbaImage, u: f32, v: f32) -> [f32; 4] {
+    let (width, height) = texture.dimensions();
+    let x = u.rem_euclid(1.0) * width as f32 - 0.5;
+    let y = v.rem_euclid(1.0) * height as f32 - 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let texel = |dx: i64, dy: i64| {
+        let px = (x0 as i64 + dx).rem_euclid(width as i64) as u32;
+        let py = (y0 as i64 + dy).rem_euclid(height as i64) as u32;
+        texture.get_pixel(px, py).0.map(f32::from)
+    };
+    let (a, b, c, d) = (texel(0, 0), texel(1, 0), texel(0, 1), texel(1, 1));
+    std::array::from_fn(|i| {
+        let top = a[i] + (b[i] - a[i]) * fx;
+        let bottom = c[i] + (d[i] - c[i]) * fx;
+        top + (bottom - top) * fy
+    })
+}
+
+// The three triangle grid vertices around uv with their barycentric weights
+fn triangle_grid(u: f32, v: f32) -> [([i32; 2], f32); 3] {
+    let (u, v) = (u * 2.0 * 3f32.sqrt(), v * 2.0 * 3f32.sqrt());
+    // Skewed so the triangles become half squares
+    let (sx, sy) = (u, -0.577_350_3 * u + 1.154_700_5 * v);
+    let (bx, by) = (sx.floor() as i32, sy.floor() as i32);
+    let (fx, fy) = (sx - sx.floor(), sy - sy.floor());
+    let z = 1.0 - fx - fy;
+    if z > 0.0 {
+        [([bx, by], z), ([bx, by + 1], fy), ([bx + 1, by], fx)]
+    } else {
+        [([bx + 1, by + 1], -z), ([bx + 1, by], 1.0 - fy), ([bx, by + 1], 1.0 - fx)]
+    }
+}
+
+fn hex_sample(texture: &RgbaImage, u: f32, v: f32, settings: &TilingSettings) -> [f32; 4] {
+    let rate = settings.cell_rate.max(0.1);
+    let vertices = triangle_grid(u * rate, v * rate);
+    let samples = vertices.map(|(vertex, weight)| {
+        let [ox, oy] = hash(vertex[0], vertex[1]);
+        let (mut su, mut sv) = (u, v);
+        if settings.rotate {
+            let angle = hash(vertex[1], vertex[0])[0] * std::f32::consts::TAU;
+            let (sin, cos) = angle.sin_cos();
+            (su, sv) = (su * cos - sv * sin, su * sin + sv * cos);
+        }
+        (sample(texture, su + ox, sv + oy), weight)
+    });
+    // Brighter samples win the blend, which keeps the result from washing out
+    let weights = samples.map(|(color, weight)| {
+        let luma = (0.299 * color[0] + 0.587 * color[1] + 0.114 * color[2]) / 255.0;
+        (1.0 + settings.contrast * (luma - 1.0)) * weight.powi(7)
+    });
+    let total = weights.iter().sum::<f32>().max(f32::EPSILON);
+    std::array::from_fn(|c| samples.iter().zip(weights).map(|((color, _), w)| color[c] * w).sum::<f32>() / total)
+}
+
+pub fn render(texture: &RgbaImage, settings: &TilingSettings) -> RgbaImage {
+    let scale = settings.repeats.max(1) as f32 / VIEW_SIZE as f32;
+    let mut view = RgbaImage::new(VIEW_SIZE, VIEW_SIZE);
+    view.par_chunks_mut(4).enumerate().for_each(|(i, pixel)| {
+        let u = (i as u32 % VIEW_SIZE) as f32 * scale;
+        let v = (i as u32 / VIEW_SIZE) as f32 * scale;
+        let color = match settings.mode {
+            TilingMode::Repeat => sample(texture, u, v),
+            TilingMode::HexTiling => hex_sample(texture, u, v, settings),
+        };
+        for c in 0..3 {
+            pixel[c] = color[c].round().clamp(0.0, 255.0) as u8;
+        }
+        pixel[3] = 255;
+    });
+    view
+}
+
+// The albedo repeated over a larger area, plainly or with hex-tiling like
+// detiling terrain shaders, to judge how the material holds up
+#[derive(Default)]
+pub struct TilingPreview {
+    settings: TilingSettings,
+    // Settings the texture was rendered with, None after the albedo changed
+    rendered: Option<TilingSettings>,
+    texture: Option<TextureHandle>,
+}
+
+impl TilingPreview {
+    pub fn invalidate(&mut self) {
+        self.rendered = None;
+    }
+
+    pub fn show(&mut self, ui: &mut Ui, albedo: Option<&RgbaImage>) {
+        CollapsingHeader::new("Tiling Preview")
+            .default_open(false)
+            .show(ui, |ui| {
+                let Some(albedo) = albedo else {
+                    ui.label("Load an albedo map to see it tiled");
+                    return;
+                };
+                let settings = &mut self.settings;
+                ComboBox::from_label("Tiling")
+                    .selected_text(match settings.mode {
+                        TilingMode::Repeat => "Repeat",
+                        TilingMode::HexTiling => "Hex-Tiling",
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut settings.mode, TilingMode::Repeat, "Repeat");
+                        ui.selectable_value(&mut settings.mode, TilingMode::HexTiling, "Hex-Tiling");
+                    });
+                ui.add(egui::Slider::new(&mut settings.repeats, 1..=16).text("Repeats"));
+                if settings.mode == TilingMode::HexTiling {
+                    ui.add(egui::Slider::new(&mut settings.cell_rate, 0.25..=4.0).text("Cells per Repeat"));
+                    ui.add(egui::Slider::new(&mut settings.contrast, 0.0..=1.0).text("Blend Contrast"))
+                        .on_hover_text("How strongly brighter samples win where cells meet");
+                    ui.checkbox(&mut settings.rotate, "Random Rotation")
+                        .on_hover_text("Only for materials without a direction, like sand or gravel");
+                }
+
+                if self.rendered != Some(self.settings) {
+                    let view = render(albedo, &self.settings);
+                    let size = [view.width() as _, view.height() as _];
+                    let color_image = ColorImage::from_rgba_unmultiplied(size, view.as_raw());
+                    self.texture = Some(ui.ctx().load_texture("tiling_preview", color_image, Default::default()));
+                    self.rendered = Some(self.settings);
+                }
+                if let Some(texture) = &self.texture {
+                    let width = ui.available_width();
+                    ui.add(Image::from_texture(SizedTexture::from_handle(texture)).max_size(Vec2::splat(width)));
+                }
+            });
+    }
+}
@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::project::Project;
+use crate::{file_names, layouts, normals, paths, resize, OutputFormat};
+
+const TIMING_FILE: &str = "timings.json";
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+struct TimingEntry {
+    runs: u32,
+    total_seconds: f64,
+}
+
+// Durations of completed exports keyed by output resolution and format, kept
+// between sessions so estimates improve with use
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TimingStats {
+    entries: BTreeMap<String, TimingEntry>,
+}
+
+fn key(resolution: u32, format: &str) -> String {
+    format!("{}:{}", resolution, format)
+}
+
+fn stats_path() -> Option<PathBuf> {
+    paths::config_dir().map(|dir| dir.join(TIMING_FILE))
+}
+
+// Size of the map the other maps are sampled against, read from the file
+// header for sets that haven't been loaded
+pub fn source_size(project: &Project) -> Option<(u32, u32)> {
+    let reference = project.albedo_map.as_ref().or(project.normal_map.as_ref())?;
+    image::image_dimensions(paths::long_path(reference)).ok()
+}
+
+// What an export's duration depends on: the largest side it writes and its
+// formats, DDS by block format since BC7 takes far longer than BC1
+pub fn export_key(project: &Project, (width, height): (u32, u32)) -> (u32, String) {
+    let layout = layouts::find(&project.packing_layout).ok();
+    let terrain3d = layout.is_some_and(|layout| layout.is_terrain3d());
+    let mut resolution = 0;
+    let mut block_formats = Vec::new();
+    for texture in layout.map_or(&[][..], |layout| layout.textures) {
+        let max_size = match texture.size {
+            layouts::SizeGroup::Albedo if project.export_albedo => project.albedo_output_size,
+            layouts::SizeGroup::Normal if project.export_normal => project.normal_output_size,
+            _ => continue,
+        };
+        let size = resize::output_size(width, height, max_size);
+        resolution = resolution.max(size.0.max(size.1));
+        let formats = match texture.name {
+            "albedo" if terrain3d && project.punch_through_alpha => vec![image_dds::ImageFormat::BC1RgbaUnorm],
+            "normal" if terrain3d && project.two_channel_normals => {
+                let translucency_in_blue = project.pack_translucency && project.translucency_map.is_some();
+                vec![normals::two_channel_dds_format(translucency_in_blue), image_dds::ImageFormat::BC4RUnorm]
+            }
+            _ => vec![texture.dds_format()],
+        };
+        // BC7RgbaUnorm and the like, the block format is the first three letters
+        block_formats.extend(formats.into_iter().map(|format| format!("{:?}", format)[..3].to_string()));
+    }
+    block_formats.sort();
+    block_formats.dedup();
+    let mut formats = vec![project.output_format];
+    formats.extend(project.additional_formats.iter().filter(|format| **format != project.output_format));
+    let label = formats.iter()
+        .map(|format| match format {
+            OutputFormat::PNG if terrain3d && project.sixteen_bit_png => "PNG16".to_string(),
+            OutputFormat::PNG => "PNG".to_string(),
+            OutputFormat::DDS => block_formats.join("+"),
+        })
+        .collect::<Vec<_>>()
+        .join("+");
+    (resolution, label)
+}
+
+impl TimingStats {
+    pub fn load() -> Self {
+        stats_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let path = stats_path().ok_or("No configuration directory")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    pub fn record(&mut self, resolution: u32, format: &str, seconds: f64) {
+        // Queued and batch exports record from threads of their own, so
+        // the file holds runs this copy hasn't seen
+        if let Some(stored) = stats_path().filter(|path| path.exists()).map(|_| Self::load()) {
+            *self = stored;
+        }
+        let entry = self.entries.entry(key(resolution, format)).or_default();
+        entry.runs += 1;
+        entry.total_seconds += seconds;
+        // Statistics are a convenience, failing to persist them isn't an error
+        self.save().ok();
+    }
+
+    // Average for an exact match, otherwise scaled by pixel count from the
+    // closest resolution measured with the same format
+    pub fn estimate(&self, resolution: u32, format: &str) -> Option<f64> {
+        if let Some(entry) = self.entries.get(&key(resolution, format)) {
+            return Some(entry.total_seconds / entry.runs as f64);
+        }
+        let suffix = format!(":{}", format);
+        self.entries.iter()
+            .filter_map(|(k, entry)| {
+                let measured: u32 = k.strip_suffix(&suffix)?.parse().ok()?;
+                Some((measured, entry))
+            })
+            .min_by_key(|(measured, _)| measured.abs_diff(resolution))
+            .map(|(measured, entry)| {
+                let scale = (resolution as f64 / measured as f64).powi(2);
+                entry.total_seconds / entry.runs as f64 * scale
+            })
+    }
+
+    // Estimate for several sets at once, e.g. "~14 min for 23 sets at 2K BC7"
+    pub fn describe(&self, sets: &[(u32, String)]) -> Option<String> {
+        let total: f64 = sets.iter()
+            .map(|(resolution, format)| self.estimate(*resolution, format))
+            .sum::<Option<f64>>()?;
+        let duration = if total >= 90.0 {
+            format!("~{:.0} min", total / 60.0)
+        } else {
+            format!("~{:.0} s", total.max(1.0))
+        };
+        let (resolution, format) = sets.first()?;
+        let at = format!("at {} {}", file_names::resolution_label((*resolution, *resolution)), format);
+        Some(match sets.len() {
+            1 => format!("{} {}", duration, at),
+            count if sets.iter().all(|set| set == &sets[0]) => format!("{} for {} sets {}", duration, count, at),
+            count => format!("{} for {} sets", duration, count),
+        })
+    }
+}
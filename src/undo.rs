@@ -0,0 +1,137 @@
+use egui::{Key, KeyboardShortcut, Modifiers, Ui};
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+use crate::project::Project;
+
+const MAX_STEPS: usize = 100;
+
+const UNDO: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+const REDO: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z);
+const REDO_ALT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Y);
+
+fn snapshot(project: &Project) -> String {
+    serde_json::to_string(project).unwrap_or_default()
+}
+
+fn restore(snapshot: &str) -> Option<Project> {
+    serde_json::from_str(snapshot).ok()
+}
+
+// Settings that differ between two snapshots, named for the button tooltips
+fn changes(from: &str, to: &str) -> String {
+    let (Ok(Value::Object(from)), Ok(Value::Object(to))) = (serde_json::from_str(from), serde_json::from_str(to)) else {
+        return String::new();
+    };
+    let keys: BTreeSet<&String> = from.keys().chain(to.keys()).collect();
+    keys.into_iter()
+        .filter(|key| from.get(*key) != to.get(*key))
+        .map(|key| key.replace('_', " "))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Slot assignments and options as they were before each change, stored the
+// way a project file stores them. A change becomes one step once it has
+// settled, so a slider drag or a typed path is undone as a whole.
+#[derive(Default)]
+pub struct UndoStack {
+    undo: Vec<String>,
+    redo: Vec<String>,
+    // Settings as of the last step
+    current: Option<String>,
+    // Settings as last tracked, also used by the buttons and shortcuts
+    latest: String,
+}
+
+impl UndoStack {
+    // Called with the editor's settings on frames that may have changed them.
+    // Nothing is settled while the pointer is held or a text field has focus.
+    pub fn track(&mut self, project: &Project, settled: bool) {
+        self.latest = snapshot(project);
+        let Some(current) = &mut self.current else {
+            self.current = Some(self.latest.clone());
+            return;
+        };
+        if settled && *current != self.latest {
+            self.undo.push(std::mem::replace(current, self.latest.clone()));
+            if self.undo.len() > MAX_STEPS {
+                self.undo.remove(0);
+            }
+            self.redo.clear();
+        }
+    }
+
+    // Takes the settings as the editor shows them after a restored step was
+    // applied, so the round trip doesn't count as a new change
+    pub fn sync(&mut self, project: &Project) {
+        self.latest = snapshot(project);
+        self.current = Some(self.latest.clone());
+    }
+
+    // A change made since the last step that hasn't settled yet
+    fn pending(&self) -> bool {
+        self.current.as_ref().is_some_and(|current| *current != self.latest)
+    }
+
+    fn undo(&mut self) -> Option<Project> {
+        let current = self.current.as_mut()?;
+        // A change that hasn't settled yet is the first thing undone
+        if *current != self.latest {
+            self.redo.push(self.latest.clone());
+            return restore(current);
+        }
+        let previous = self.undo.pop()?;
+        self.redo.push(std::mem::replace(current, previous));
+        restore(current)
+    }
+
+    fn redo(&mut self) -> Option<Project> {
+        // Redoing over a fresh change would lose it, and once it settles the
+        // steps to redo are gone anyway
+        if self.pending() {
+            return None;
+        }
+        let current = self.current.as_mut()?;
+        let next = self.redo.pop()?;
+        self.undo.push(std::mem::replace(current, next));
+        restore(current)
+    }
+
+    // Undo and Redo buttons plus their shortcuts. Returns settings to apply
+    // to the editor.
+    pub fn show(&mut self, ui: &mut Ui) -> Option<Project> {
+        let mut restored = None;
+        let current = self.current.clone().unwrap_or_default();
+        let undo_button = ui.add_enabled(!self.undo.is_empty(), egui::Button::new("Undo"))
+            .on_hover_ui(|ui| {
+                let previous = self.undo.last().map_or("", String::as_str);
+                ui.label(format!("Reverts {} ({})", changes(previous, &current), ui.ctx().format_shortcut(&UNDO)));
+            });
+        if undo_button.clicked() {
+            restored = self.undo();
+        }
+        let mut redo_button = ui.add_enabled(!self.redo.is_empty() && !self.pending(), egui::Button::new("Redo"))
+            .on_hover_ui(|ui| {
+                let next = self.redo.last().map_or("", String::as_str);
+                ui.label(format!("Reapplies {} ({})", changes(&current, next), ui.ctx().format_shortcut(&REDO)));
+            });
+        if self.pending() && !self.redo.is_empty() {
+            redo_button = redo_button.on_disabled_hover_text("A new change is being made, redoing would lose it");
+        }
+        if redo_button.clicked() {
+            restored = self.redo();
+        }
+
+        // Text fields keep the shortcuts for their own undo
+        if ui.ctx().memory(|memory| memory.focused().is_none()) {
+            // Checked first, as the plain shortcut also matches with shift held
+            if ui.ctx().input_mut(|input| input.consume_shortcut(&REDO) || input.consume_shortcut(&REDO_ALT)) {
+                restored = self.redo();
+            } else if ui.ctx().input_mut(|input| input.consume_shortcut(&UNDO)) {
+                restored = self.undo();
+            }
+        }
+        restored
+    }
+}
@@ -0,0 +1,148 @@
+use image::GenericImageView;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::map_names::{self, Slot};
+use crate::{frames, paths, TerrainApp};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileCheck {
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slot: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<[u32; 2]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// One folder of maps checked the way an export would check them as a set
+#[derive(Debug, Clone, Serialize)]
+pub struct SetCheck {
+    pub folder: PathBuf,
+    pub files: Vec<FileCheck>,
+    // Problems between the maps, e.g. mismatched sizes
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl SetCheck {
+    pub fn passed(&self) -> bool {
+        self.errors.is_empty() && self.files.iter().all(|file| file.error.is_none())
+    }
+}
+
+fn slot_name(slot: Slot) -> &'static str {
+    match slot {
+        Slot::Albedo => "albedo",
+        Slot::Height => "height",
+        Slot::AmbientOcclusion => "ao",
+        Slot::Normal => "normal",
+        Slot::Roughness => "roughness",
+        Slot::Smoothness => "smoothness",
+        Slot::Translucency => "translucency",
+        Slot::Opacity => "opacity",
+    }
+}
+
+fn check_file(path: &Path, rectangular: bool) -> FileCheck {
+    let mut check = FileCheck {
+        file: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+        slot: map_names::guess_slot(path).map(slot_name),
+        dimensions: None,
+        error: None,
+    };
+    match frames::open_frame(&paths::long_path(path), 0) {
+        Ok((img, _)) => {
+            let (width, height) = img.dimensions();
+            check.dimensions = Some([width, height]);
+            check.error = TerrainApp::validate_image(&img, rectangular).err().map(|e| e.to_string());
+        }
+        Err(e) => check.error = Some(e),
+    }
+    check
+}
+
+// The same rules an export applies across a set's maps
+fn check_set(folder: &Path, files: Vec<FileCheck>) -> SetCheck {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    for file in files.iter().filter(|file| file.slot.is_none()) {
+        warnings.push(format!("{} doesn't match any map name and was only checked on its own", file.file));
+    }
+    let mut slots: Vec<&'static str> = files.iter().filter_map(|file| file.slot).collect();
+    slots.sort();
+    slots.dedup();
+    for slot in &slots {
+        let named: Vec<&str> = files.iter().filter(|file| file.slot == Some(*slot)).map(|file| file.file.as_str()).collect();
+        if named.len() > 1 {
+            warnings.push(format!("Several {} maps: {}, the first one would be used", slot, named.join(", ")));
+        }
+    }
+    if !slots.is_empty() {
+        for required in ["albedo", "normal"] {
+            if !slots.contains(&required) {
+                errors.push(format!("No {} map", required));
+            }
+        }
+    }
+
+    // Sizes are compared against the albedo like check_matching_sizes does,
+    // or against the first map when there is none
+    let reference = files.iter().filter(|file| file.slot.is_some() && file.dimensions.is_some())
+        .min_by_key(|file| file.slot != Some("albedo"));
+    if let Some(reference) = reference {
+        let [width, height] = reference.dimensions.unwrap_or_default();
+        for file in files.iter().filter(|file| file.slot.is_some()) {
+            if let Some([w, h]) = file.dimensions.filter(|d| *d != [width, height]) {
+                errors.push(format!(
+                    "{} is {}x{} but {} is {}x{}",
+                    file.file, w, h, reference.file, width, height
+                ));
+            }
+        }
+    }
+    SetCheck { folder: folder.to_path_buf(), files, errors, warnings }
+}
+
+// Checks every folder under root that holds textures, each as its own set
+pub fn validate_folder(root: &Path, rectangular: bool) -> Result<Vec<SetCheck>, String> {
+    let sets: Vec<SetCheck> = map_names::folders_under(root)?.into_iter()
+        .filter_map(|folder| {
+            let files = map_names::map_files(&folder);
+            if files.is_empty() {
+                return None;
+            }
+            let checks = files.par_iter().map(|path| check_file(path, rectangular)).collect();
+            Some(check_set(&folder, checks))
+        })
+        .collect();
+    if sets.is_empty() {
+        return Err(format!("No textures found in {}", root.display()));
+    }
+    Ok(sets)
+}
+
+pub fn print_report(root: &Path, sets: &[SetCheck]) {
+    for set in sets {
+        let name = set.folder.strip_prefix(root).ok().filter(|p| !p.as_os_str().is_empty()).unwrap_or(&set.folder);
+        println!("{}", name.display());
+        for file in &set.files {
+            let size = file.dimensions.map(|[w, h]| format!("{}x{}", w, h)).unwrap_or_default();
+            let slot = file.slot.unwrap_or("-");
+            match &file.error {
+                None => println!("  OK    {:<40} {:>11} {}", file.file, size, slot),
+                Some(e) => println!("  FAIL  {:<40} {:>11} {}: {}", file.file, size, slot, e),
+            }
+        }
+        for error in &set.errors {
+            println!("  FAIL  {}", error);
+        }
+        for warning in &set.warnings {
+            println!("  WARN  {}", warning);
+        }
+    }
+    let failed = sets.iter().filter(|set| !set.passed()).count();
+    println!("{} of {} folders passed", sets.len() - failed, sets.len());
+}